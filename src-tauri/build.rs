@@ -1,3 +1,12 @@
 fn main() {
+    // Finder's "Send with Vegam" service (see src/launch.rs)
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        println!("cargo:rerun-if-changed=macos/services.m");
+        println!("cargo:rustc-link-lib=framework=AppKit");
+        cc::Build::new()
+            .file("macos/services.m")
+            .flag("-fobjc-arc")
+            .compile("vegam_services");
+    }
     tauri_build::build()
 }
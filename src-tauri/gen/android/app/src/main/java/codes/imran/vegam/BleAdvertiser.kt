@@ -0,0 +1,113 @@
+package codes.imran.vegam
+
+import android.Manifest
+import android.bluetooth.BluetoothDevice
+import android.bluetooth.BluetoothGatt
+import android.bluetooth.BluetoothGattCharacteristic
+import android.bluetooth.BluetoothGattServer
+import android.bluetooth.BluetoothGattServerCallback
+import android.bluetooth.BluetoothGattService
+import android.bluetooth.BluetoothManager
+import android.bluetooth.le.AdvertiseCallback
+import android.bluetooth.le.AdvertiseData
+import android.bluetooth.le.AdvertiseSettings
+import android.content.Context
+import android.content.pm.PackageManager
+import android.os.Build
+import android.os.ParcelUuid
+import androidx.core.content.ContextCompat
+import java.util.UUID
+
+// Peripheral side of BLE discovery: advertises the vegam service and serves
+// our node ID from its characteristic, so nearby scanners (src/iroh/ble.rs)
+// can read it. Started and stopped from Rust; the UUIDs must match ble.rs.
+object BleAdvertiser {
+  private val SERVICE_UUID = UUID.fromString("76656761-6d00-4000-8000-00805f9b34fb")
+  private val NODE_ID_CHAR_UUID = UUID.fromString("76656761-6d01-4000-8000-00805f9b34fb")
+
+  private var server: BluetoothGattServer? = null
+  private var callback: AdvertiseCallback? = null
+
+  /** Advertise `nodeId` (32 bytes); throws if Bluetooth is off or permissions are missing */
+  @JvmStatic
+  @Synchronized
+  fun start(context: Context, nodeId: ByteArray) {
+    stop(context)
+    if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.S) {
+      for (permission in listOf(
+        Manifest.permission.BLUETOOTH_ADVERTISE, Manifest.permission.BLUETOOTH_CONNECT
+      )) {
+        if (ContextCompat.checkSelfPermission(context, permission) != PackageManager.PERMISSION_GRANTED) {
+          throw IllegalStateException("Missing permission $permission")
+        }
+      }
+    }
+    val manager = context.getSystemService(BluetoothManager::class.java)
+    val adapter = manager?.adapter ?: throw IllegalStateException("No Bluetooth adapter")
+    if (!adapter.isEnabled) throw IllegalStateException("Bluetooth is off")
+    val advertiser = adapter.bluetoothLeAdvertiser
+      ?: throw IllegalStateException("This device can't advertise over BLE")
+
+    val characteristic = BluetoothGattCharacteristic(
+      NODE_ID_CHAR_UUID,
+      BluetoothGattCharacteristic.PROPERTY_READ,
+      BluetoothGattCharacteristic.PERMISSION_READ
+    )
+    val service = BluetoothGattService(SERVICE_UUID, BluetoothGattService.SERVICE_TYPE_PRIMARY)
+    service.addCharacteristic(characteristic)
+
+    val gatt = manager.openGattServer(context, object : BluetoothGattServerCallback() {
+      override fun onCharacteristicReadRequest(
+        device: BluetoothDevice,
+        requestId: Int,
+        offset: Int,
+        characteristic: BluetoothGattCharacteristic
+      ) {
+        val srv = server ?: return
+        if (characteristic.uuid != NODE_ID_CHAR_UUID || offset > nodeId.size) {
+          srv.sendResponse(device, requestId, BluetoothGatt.GATT_FAILURE, offset, null)
+          return
+        }
+        srv.sendResponse(
+          device, requestId, BluetoothGatt.GATT_SUCCESS, offset,
+          nodeId.copyOfRange(offset, nodeId.size)
+        )
+      }
+    }) ?: throw IllegalStateException("Failed to open the GATT server")
+    gatt.addService(service)
+    server = gatt
+
+    val settings = AdvertiseSettings.Builder()
+      .setAdvertiseMode(AdvertiseSettings.ADVERTISE_MODE_BALANCED)
+      .setTxPowerLevel(AdvertiseSettings.ADVERTISE_TX_POWER_MEDIUM)
+      .setConnectable(true)
+      .build()
+    val data = AdvertiseData.Builder()
+      .addServiceUuid(ParcelUuid(SERVICE_UUID))
+      .setIncludeDeviceName(false)
+      .build()
+    val cb = object : AdvertiseCallback() {
+      override fun onStartFailure(errorCode: Int) {
+        android.util.Log.w("vegam", "BLE advertising failed: $errorCode")
+      }
+    }
+    advertiser.startAdvertising(settings, data, cb)
+    callback = cb
+  }
+
+  @JvmStatic
+  @Synchronized
+  fun stop(context: Context) {
+    val manager = context.getSystemService(BluetoothManager::class.java)
+    callback?.let { cb ->
+      try {
+        manager?.adapter?.bluetoothLeAdvertiser?.stopAdvertising(cb)
+      } catch (e: SecurityException) {
+        android.util.Log.w("vegam", "Failed to stop BLE advertising", e)
+      }
+    }
+    callback = null
+    server?.close()
+    server = null
+  }
+}
@@ -0,0 +1,34 @@
+package codes.imran.vegam
+
+import android.content.Context
+import android.net.Uri
+import android.provider.OpenableColumns
+import org.json.JSONObject
+
+// What the content resolver knows about a content:// URI, without opening
+// it; called from Rust (platform::file_metadata) and for share intents
+object ContentInfo {
+  /** `{ name, size, mime_type }` as JSON; fields the provider doesn't report are left out */
+  @JvmStatic
+  fun query(context: Context, uri: String): String = query(context, Uri.parse(uri)).toString()
+
+  fun query(context: Context, uri: Uri): JSONObject {
+    val info = JSONObject()
+    val resolver = context.contentResolver
+    val columns = arrayOf(OpenableColumns.DISPLAY_NAME, OpenableColumns.SIZE)
+    resolver.query(uri, columns, null, null, null)?.use { cursor ->
+      if (cursor.moveToFirst()) {
+        val nameIndex = cursor.getColumnIndex(OpenableColumns.DISPLAY_NAME)
+        if (nameIndex >= 0 && !cursor.isNull(nameIndex)) {
+          info.put("name", cursor.getString(nameIndex))
+        }
+        val sizeIndex = cursor.getColumnIndex(OpenableColumns.SIZE)
+        if (sizeIndex >= 0 && !cursor.isNull(sizeIndex)) {
+          info.put("size", cursor.getLong(sizeIndex))
+        }
+      }
+    }
+    resolver.getType(uri)?.let { info.put("mime_type", it) }
+    return info
+  }
+}
@@ -0,0 +1,36 @@
+package codes.imran.vegam
+
+import android.app.ActivityManager
+import android.app.usage.UsageStatsManager
+import android.content.Context
+import android.content.Intent
+import android.os.PowerManager
+import android.provider.Settings
+import org.json.JSONObject
+
+// Doze, battery saver and background restrictions; polled from Rust
+// (doze::detect) so transfers wait out Doze instead of stalling
+object DeviceState {
+  /** `{ device_idle, power_save, ignoring_battery_optimizations, background_restricted, standby_bucket }` as JSON */
+  @JvmStatic
+  fun query(context: Context): String {
+    val info = JSONObject()
+    val power = context.getSystemService(Context.POWER_SERVICE) as PowerManager
+    info.put("device_idle", power.isDeviceIdleMode)
+    info.put("power_save", power.isPowerSaveMode)
+    info.put("ignoring_battery_optimizations", power.isIgnoringBatteryOptimizations(context.packageName))
+    val activity = context.getSystemService(Context.ACTIVITY_SERVICE) as ActivityManager
+    info.put("background_restricted", activity.isBackgroundRestricted)
+    val usage = context.getSystemService(Context.USAGE_STATS_SERVICE) as UsageStatsManager
+    info.put("standby_bucket", usage.appStandbyBucket)
+    return info.toString()
+  }
+
+  /** Battery optimization list, where the user can exempt the app */
+  @JvmStatic
+  fun openBatterySettings(context: Context) {
+    val intent = Intent(Settings.ACTION_IGNORE_BATTERY_OPTIMIZATION_SETTINGS)
+      .addFlags(Intent.FLAG_ACTIVITY_NEW_TASK)
+    context.startActivity(intent)
+  }
+}
@@ -1,11 +1,53 @@
 package codes.imran.vegam
 
+import android.content.Intent
+import android.net.Uri
 import android.os.Bundle
 import androidx.activity.enableEdgeToEdge
+import androidx.core.content.IntentCompat
+import org.json.JSONArray
+import org.json.JSONObject
 
 class MainActivity : TauriActivity() {
   override fun onCreate(savedInstanceState: Bundle?) {
     enableEdgeToEdge()
     super.onCreate(savedInstanceState)
+    handleShareIntent(intent)
   }
+
+  override fun onNewIntent(intent: Intent) {
+    super.onNewIntent(intent)
+    handleShareIntent(intent)
+  }
+
+  // "Share → vegam" from other apps: pass the shared files to Rust (src/share.rs)
+  private fun handleShareIntent(intent: Intent?) {
+    val uris = when (intent?.action) {
+      Intent.ACTION_SEND ->
+        listOfNotNull(IntentCompat.getParcelableExtra(intent, Intent.EXTRA_STREAM, Uri::class.java))
+      Intent.ACTION_SEND_MULTIPLE ->
+        IntentCompat.getParcelableArrayListExtra(intent, Intent.EXTRA_STREAM, Uri::class.java)
+          ?: emptyList()
+      else -> return
+    }
+    // Handled once, not again when the activity is recreated
+    intent.action = Intent.ACTION_MAIN
+
+    val files = JSONArray()
+    for (uri in uris) {
+      val info = ContentInfo.query(this, uri)
+      val file = JSONObject()
+        .put("uri", uri.toString())
+        .put("name", info.optString("name", uri.lastPathSegment ?: "shared_file"))
+      if (info.has("size")) {
+        file.put("size", info.getLong("size"))
+      }
+      files.put(file)
+    }
+    if (files.length() > 0) {
+      onShareReceived(files.toString())
+    }
+  }
+
+  private external fun onShareReceived(filesJson: String)
 }
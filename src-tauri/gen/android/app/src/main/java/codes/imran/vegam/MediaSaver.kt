@@ -0,0 +1,71 @@
+package codes.imran.vegam
+
+import android.content.ContentValues
+import android.content.Context
+import android.media.MediaScannerConnection
+import android.net.Uri
+import android.os.Build
+import android.os.Environment
+import android.provider.MediaStore
+import java.io.File
+import java.io.IOException
+
+// Received photos and videos into the shared gallery; called from Rust
+// (src/gallery.rs)
+object MediaSaver {
+  private const val ALBUM = "vegam"
+
+  /** Copy `path` into Pictures/vegam or Movies/vegam and return its content URI */
+  @JvmStatic
+  fun save(context: Context, path: String, name: String, mimeType: String, isVideo: Boolean): String {
+    val source = File(path)
+    val directory = if (isVideo) Environment.DIRECTORY_MOVIES else Environment.DIRECTORY_PICTURES
+
+    if (Build.VERSION.SDK_INT < Build.VERSION_CODES.Q) {
+      // No MediaStore.RELATIVE_PATH before Android 10: copy into the public
+      // folder and have the media scanner index it
+      @Suppress("DEPRECATION")
+      val album = File(Environment.getExternalStoragePublicDirectory(directory), ALBUM)
+      album.mkdirs()
+      val target = unusedFile(album, name)
+      source.copyTo(target)
+      MediaScannerConnection.scanFile(context, arrayOf(target.path), arrayOf(mimeType), null)
+      return Uri.fromFile(target).toString()
+    }
+
+    val collection = if (isVideo) {
+      MediaStore.Video.Media.getContentUri(MediaStore.VOLUME_EXTERNAL_PRIMARY)
+    } else {
+      MediaStore.Images.Media.getContentUri(MediaStore.VOLUME_EXTERNAL_PRIMARY)
+    }
+    val values = ContentValues().apply {
+      put(MediaStore.MediaColumns.DISPLAY_NAME, name)
+      put(MediaStore.MediaColumns.MIME_TYPE, mimeType)
+      put(MediaStore.MediaColumns.RELATIVE_PATH, "$directory/$ALBUM")
+      put(MediaStore.MediaColumns.IS_PENDING, 1)
+    }
+    val resolver = context.contentResolver
+    val uri = resolver.insert(collection, values) ?: throw IOException("MediaStore refused $name")
+    try {
+      val output = resolver.openOutputStream(uri) ?: throw IOException("Can't write $uri")
+      output.use { out -> source.inputStream().use { it.copyTo(out) } }
+      // Published once complete, so the gallery never shows a partial file;
+      // date taken and location are then read from the file's metadata
+      resolver.update(uri, ContentValues().apply { put(MediaStore.MediaColumns.IS_PENDING, 0) }, null, null)
+    } catch (e: Exception) {
+      resolver.delete(uri, null, null)
+      throw e
+    }
+    return uri.toString()
+  }
+
+  private fun unusedFile(dir: File, name: String): File {
+    var file = File(dir, name)
+    var n = 1
+    while (file.exists()) {
+      file = File(dir, "${name.substringBeforeLast('.')} ($n).${name.substringAfterLast('.', "")}".removeSuffix("."))
+      n++
+    }
+    return file
+  }
+}
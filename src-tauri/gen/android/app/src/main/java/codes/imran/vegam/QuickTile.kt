@@ -0,0 +1,81 @@
+package codes.imran.vegam
+
+import android.app.PendingIntent
+import android.content.Intent
+import android.os.Build
+import android.os.Handler
+import android.os.Looper
+import android.service.quicksettings.Tile
+import android.service.quicksettings.TileService
+import org.json.JSONObject
+
+// Quick Settings tile toggling discoverability, with peers online or transfer
+// progress underneath. Status comes straight from Rust (src/quick.rs), so the
+// webview doesn't have to run; if the app isn't up, a tap opens it.
+class QuickTile : TileService() {
+  companion object {
+    init {
+      // Loaded already when the app runs; a process woken for the tile only
+      // gets the library, so `status` reports running = false
+      System.loadLibrary("vegam_lib")
+    }
+
+    /** `QuickStatus` as JSON */
+    @JvmStatic external fun status(): String
+
+    /** Flip discoverability, returning the new `QuickStatus` as JSON */
+    @JvmStatic external fun toggleDiscoverable(): String
+  }
+
+  private val main = Handler(Looper.getMainLooper())
+
+  override fun onStartListening() {
+    super.onStartListening()
+    refresh { status() }
+  }
+
+  override fun onClick() {
+    super.onClick()
+    if (!JSONObject(status()).optBoolean("running")) {
+      openApp()
+      return
+    }
+    refresh { toggleDiscoverable() }
+  }
+
+  /** Call into Rust off the main thread (toggling may say goodbye over the network) */
+  private fun refresh(query: () -> String) {
+    Thread {
+      val status = JSONObject(query())
+      main.post { show(status) }
+    }.start()
+  }
+
+  private fun show(status: JSONObject) {
+    val tile = qsTile ?: return
+    val running = status.optBoolean("running")
+    tile.state = if (running && status.optBoolean("discoverable")) Tile.STATE_ACTIVE else Tile.STATE_INACTIVE
+    if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.Q) {
+      val transfers = status.optInt("active_transfers")
+      tile.subtitle = when {
+        !running -> "Tap to open"
+        transfers > 0 && !status.isNull("progress") -> "$transfers transferring · ${status.optInt("progress")}%"
+        transfers > 0 -> "$transfers transferring"
+        else -> "${status.optInt("peers_online")} nearby"
+      }
+    }
+    tile.updateTile()
+  }
+
+  private fun openApp() {
+    val intent = Intent(this, MainActivity::class.java).addFlags(Intent.FLAG_ACTIVITY_NEW_TASK)
+    if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.UPSIDE_DOWN_CAKE) {
+      startActivityAndCollapse(
+        PendingIntent.getActivity(this, 0, intent, PendingIntent.FLAG_IMMUTABLE)
+      )
+    } else {
+      @Suppress("DEPRECATION")
+      startActivityAndCollapse(intent)
+    }
+  }
+}
@@ -0,0 +1,72 @@
+package codes.imran.vegam
+
+import android.content.Context
+import android.security.keystore.KeyGenParameterSpec
+import android.security.keystore.KeyProperties
+import android.util.Base64
+import java.security.KeyStore
+import javax.crypto.Cipher
+import javax.crypto.KeyGenerator
+import javax.crypto.SecretKey
+import javax.crypto.spec.GCMParameterSpec
+
+// Secrets kept under the Android Keystore; called from Rust
+// (src/iroh/keystore.rs). Android has no general secret store, so each secret
+// is encrypted with an AES-GCM key that lives in the Keystore (hardware-backed
+// where the device has it, never exported) and the ciphertext is kept in
+// private shared preferences.
+object SecretStore {
+  private const val KEYSTORE = "AndroidKeyStore"
+  private const val WRAPPING_KEY = "vegam-secrets"
+  private const val PREFS = "vegam-secrets"
+  private const val TRANSFORMATION = "AES/GCM/NoPadding"
+  private const val TAG_BITS = 128
+
+  /** The secret saved under `account`, or null if there is none */
+  @JvmStatic
+  @Synchronized
+  fun load(context: Context, account: String): ByteArray? {
+    val stored = prefs(context).getString(account, null) ?: return null
+    val bytes = Base64.decode(stored, Base64.NO_WRAP)
+    val ivLength = bytes[0].toInt()
+    val cipher = Cipher.getInstance(TRANSFORMATION)
+    cipher.init(
+      Cipher.DECRYPT_MODE, wrappingKey(), GCMParameterSpec(TAG_BITS, bytes, 1, ivLength)
+    )
+    return cipher.doFinal(bytes, 1 + ivLength, bytes.size - 1 - ivLength)
+  }
+
+  /** Save `secret` under `account`, replacing any previous one */
+  @JvmStatic
+  @Synchronized
+  fun store(context: Context, account: String, secret: ByteArray) {
+    val cipher = Cipher.getInstance(TRANSFORMATION)
+    cipher.init(Cipher.ENCRYPT_MODE, wrappingKey())
+    val iv = cipher.iv
+    val sealed = cipher.doFinal(secret)
+    val bytes = byteArrayOf(iv.size.toByte()) + iv + sealed
+    val saved = prefs(context).edit()
+      .putString(account, Base64.encodeToString(bytes, Base64.NO_WRAP))
+      .commit()
+    if (!saved) throw IllegalStateException("Failed to save secret")
+  }
+
+  private fun prefs(context: Context) =
+    context.getSharedPreferences(PREFS, Context.MODE_PRIVATE)
+
+  private fun wrappingKey(): SecretKey {
+    val keyStore = KeyStore.getInstance(KEYSTORE).apply { load(null) }
+    (keyStore.getKey(WRAPPING_KEY, null) as? SecretKey)?.let { return it }
+    val generator = KeyGenerator.getInstance(KeyProperties.KEY_ALGORITHM_AES, KEYSTORE)
+    generator.init(
+      KeyGenParameterSpec.Builder(
+        WRAPPING_KEY, KeyProperties.PURPOSE_ENCRYPT or KeyProperties.PURPOSE_DECRYPT
+      )
+        .setBlockModes(KeyProperties.BLOCK_MODE_GCM)
+        .setEncryptionPaddings(KeyProperties.ENCRYPTION_PADDING_NONE)
+        .setKeySize(256)
+        .build()
+    )
+    return generator.generateKey()
+  }
+}
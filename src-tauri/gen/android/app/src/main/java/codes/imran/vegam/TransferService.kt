@@ -0,0 +1,93 @@
+package codes.imran.vegam
+
+import android.app.NotificationChannel
+import android.app.NotificationManager
+import android.app.PendingIntent
+import android.app.Service
+import android.content.Context
+import android.content.Intent
+import android.content.pm.ServiceInfo
+import android.net.wifi.WifiManager
+import android.os.Build
+import android.os.IBinder
+import android.os.PowerManager
+import androidx.core.app.NotificationCompat
+import androidx.core.app.ServiceCompat
+import androidx.core.content.ContextCompat
+
+// Foreground service keeping the process, CPU and Wi-Fi awake while transfers
+// run; started, updated and stopped from Rust (src/foreground.rs)
+class TransferService : Service() {
+  private var wakeLock: PowerManager.WakeLock? = null
+  private var wifiLock: WifiManager.WifiLock? = null
+
+  override fun onCreate() {
+    super.onCreate()
+    val channel = NotificationChannel(
+      CHANNEL_ID, "Transfers", NotificationManager.IMPORTANCE_LOW
+    )
+    getSystemService(NotificationManager::class.java).createNotificationChannel(channel)
+
+    wakeLock = getSystemService(PowerManager::class.java)
+      .newWakeLock(PowerManager.PARTIAL_WAKE_LOCK, "vegam:transfer")
+      .apply { acquire() }
+    @Suppress("DEPRECATION")
+    val wifiMode = if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.Q) {
+      WifiManager.WIFI_MODE_FULL_HIGH_PERF
+    } else {
+      WifiManager.WIFI_MODE_FULL
+    }
+    wifiLock = applicationContext.getSystemService(WifiManager::class.java)
+      .createWifiLock(wifiMode, "vegam:transfer")
+      .apply { acquire() }
+  }
+
+  override fun onStartCommand(intent: Intent?, flags: Int, startId: Int): Int {
+    val title = intent?.getStringExtra(EXTRA_TITLE) ?: "Transferring files"
+    val progress = intent?.getIntExtra(EXTRA_PROGRESS, -1) ?: -1
+    val open = PendingIntent.getActivity(
+      this, 0, Intent(this, MainActivity::class.java), PendingIntent.FLAG_IMMUTABLE
+    )
+    val notification = NotificationCompat.Builder(this, CHANNEL_ID)
+      .setSmallIcon(android.R.drawable.stat_sys_download)
+      .setContentTitle(title)
+      .setContentIntent(open)
+      .setOngoing(true)
+      .setOnlyAlertOnce(true)
+      .setProgress(100, progress.coerceAtLeast(0), progress < 0)
+      .build()
+    ServiceCompat.startForeground(
+      this, NOTIFICATION_ID, notification, ServiceInfo.FOREGROUND_SERVICE_TYPE_DATA_SYNC
+    )
+    return START_NOT_STICKY
+  }
+
+  override fun onDestroy() {
+    wakeLock?.takeIf { it.isHeld }?.release()
+    wifiLock?.takeIf { it.isHeld }?.release()
+    super.onDestroy()
+  }
+
+  override fun onBind(intent: Intent?): IBinder? = null
+
+  companion object {
+    private const val CHANNEL_ID = "transfers"
+    private const val NOTIFICATION_ID = 1
+    private const val EXTRA_TITLE = "title"
+    private const val EXTRA_PROGRESS = "progress"
+
+    /** Start the service or update its notification; `progress` is a percentage, -1 if unknown */
+    @JvmStatic
+    fun update(context: Context, title: String, progress: Int) {
+      val intent = Intent(context, TransferService::class.java)
+        .putExtra(EXTRA_TITLE, title)
+        .putExtra(EXTRA_PROGRESS, progress)
+      ContextCompat.startForegroundService(context, intent)
+    }
+
+    @JvmStatic
+    fun stop(context: Context) {
+      context.stopService(Intent(context, TransferService::class.java))
+    }
+  }
+}
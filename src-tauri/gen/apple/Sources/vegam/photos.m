@@ -0,0 +1,25 @@
+#import <Foundation/Foundation.h>
+#import <Photos/Photos.h>
+#include <stdbool.h>
+
+// Add a received photo or video to the photo library; called from Rust
+// (src/gallery.rs). Blocks until Photos has the asset. Photos reads date
+// taken and location from the file's own metadata.
+bool vegam_save_to_photos(const char *path, bool is_video) {
+	@autoreleasepool {
+		NSURL *url = [NSURL fileURLWithPath:[NSString stringWithUTF8String:path]];
+		NSError *error = nil;
+		BOOL saved = [[PHPhotoLibrary sharedPhotoLibrary] performChangesAndWait:^{
+			PHAssetCreationRequest *request = [PHAssetCreationRequest creationRequestForAsset];
+			PHAssetResourceCreationOptions *options = [PHAssetResourceCreationOptions new];
+			options.originalFilename = url.lastPathComponent;
+			[request addResourceWithType:(is_video ? PHAssetResourceTypeVideo : PHAssetResourceTypePhoto)
+			                     fileURL:url
+			                     options:options];
+		} error:&error];
+		if (!saved) {
+			NSLog(@"Saving %@ to Photos failed: %@", url.lastPathComponent, error);
+		}
+		return saved;
+	}
+}
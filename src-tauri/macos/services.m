@@ -0,0 +1,53 @@
+#import <AppKit/AppKit.h>
+#import <CoreServices/CoreServices.h>
+
+// Finder's "Send with Vegam" service, declared under NSServices in
+// Info.plist. macOS delivers the selected files to the running app (starting
+// it first if needed), which hands their paths to Rust (src/launch.rs) as a
+// JSON array.
+extern void vegam_services_send(const char *paths_json);
+// `vegam://` links, taken here rather than as `RunEvent::Opened` because
+// tickets don't parse as `Url`s (src/launch.rs)
+extern void vegam_link_opened(const char *link);
+
+@interface VegamServices : NSObject
+@end
+
+@implementation VegamServices
+- (void)sendFiles:(NSPasteboard *)pboard userData:(NSString *)userData error:(NSString **)error {
+	NSArray<NSURL *> *urls = [pboard readObjectsForClasses:@[[NSURL class]]
+	                                               options:@{NSPasteboardURLReadingFileURLsOnlyKey: @YES}];
+	NSMutableArray<NSString *> *paths = [NSMutableArray array];
+	for (NSURL *url in urls) {
+		[paths addObject:url.path];
+	}
+	if (paths.count == 0) {
+		*error = @"No files to send";
+		return;
+	}
+	NSData *json = [NSJSONSerialization dataWithJSONObject:paths options:0 error:nil];
+	NSString *string = [[NSString alloc] initWithData:json encoding:NSUTF8StringEncoding];
+	vegam_services_send(string.UTF8String);
+}
+
+- (void)handleGetURL:(NSAppleEventDescriptor *)event withReplyEvent:(NSAppleEventDescriptor *)reply {
+	NSString *link = [[event paramDescriptorForKeyword:keyDirectObject] stringValue];
+	if (link) {
+		vegam_link_opened(link.UTF8String);
+	}
+}
+@end
+
+// Start taking service requests and links; called once the app is set up
+void vegam_register_services(void) {
+	static VegamServices *provider;
+	dispatch_async(dispatch_get_main_queue(), ^{
+		provider = [VegamServices new];
+		[NSApp setServicesProvider:provider];
+		NSUpdateDynamicServices();
+		[[NSAppleEventManager sharedAppleEventManager] setEventHandler:provider
+		                                                   andSelector:@selector(handleGetURL:withReplyEvent:)
+		                                                 forEventClass:kInternetEventClass
+		                                                    andEventID:kAEGetURL];
+	});
+}
@@ -0,0 +1,219 @@
+// Connection and transfer audit log
+//
+// For devices shared by several people, an append-only `audit.jsonl` in the
+// app data dir records who connected to the blob provider, which blobs they
+// requested and every transfer that finished. Unlike the transfer history it
+// is never pruned or edited by the app; it can only be read and exported. It
+// is off by default (`audit_log` setting).
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, RwLock};
+use tracing::warn;
+
+use crate::history::{now_secs, HistoryStore};
+use crate::state::{TransferDirection, TransferInfo, TransferStatus};
+
+pub const AUDIT_FILE: &str = "audit.jsonl";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    /// A node connected to the blob provider
+    Connected,
+    /// A connection refused by the access policy
+    Refused,
+    BlobRequested,
+    TransferFinished,
+}
+
+/// One line of the audit log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub kind: AuditKind,
+    /// Remote node, when known
+    pub node_id: Option<String>,
+    pub hash: Option<String>,
+    pub file_name: Option<String>,
+    /// Size of the requested blob, or bytes moved by a finished transfer
+    pub bytes: Option<u64>,
+    pub transfer_id: Option<String>,
+    pub direction: Option<TransferDirection>,
+    /// Final status of a finished transfer
+    pub status: Option<TransferStatus>,
+}
+
+impl AuditEntry {
+    pub fn new(kind: AuditKind, node_id: Option<String>) -> Self {
+        Self {
+            timestamp: now_secs(),
+            kind,
+            node_id,
+            hash: None,
+            file_name: None,
+            bytes: None,
+            transfer_id: None,
+            direction: None,
+            status: None,
+        }
+    }
+}
+
+/// Handle to the audit log, shared with the blob provider
+#[derive(Clone)]
+pub struct AuditLog {
+    path: Arc<RwLock<Option<PathBuf>>>,
+    enabled: Arc<AtomicBool>,
+    // Looks up names and sizes of requested blobs
+    history: Arc<RwLock<Option<HistoryStore>>>,
+    // Serializes appends so lines never interleave
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl AuditLog {
+    pub fn new(history: Arc<RwLock<Option<HistoryStore>>>) -> Self {
+        Self {
+            path: Arc::new(RwLock::new(None)),
+            enabled: Arc::new(AtomicBool::new(false)),
+            history,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub async fn set_path(&self, path: PathBuf) {
+        *self.path.write().await = Some(path);
+    }
+
+    /// Forget the log file, so nothing is written or read until a new path is set
+    pub async fn clear_path(&self) {
+        *self.path.write().await = None;
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Append an entry; failures are logged, never surfaced to the caller
+    pub async fn record(&self, entry: AuditEntry) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(path) = self.path.read().await.clone() else {
+            return;
+        };
+        let _guard = self.write_lock.lock().await;
+        if let Err(e) = append(&path, &entry).await {
+            warn!("Failed to write audit log: {}", e);
+        }
+    }
+
+    pub async fn blob_requested(&self, node_id: Option<String>, hash: String) {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut entry = AuditEntry::new(AuditKind::BlobRequested, node_id);
+        let history = self.history.read().await.clone();
+        if let Some(history) = history {
+            if let Ok(Some(blob)) = history.get_blob(hash.clone()).await {
+                entry.file_name = Some(blob.file_name);
+                entry.bytes = Some(blob.size);
+            }
+        }
+        entry.hash = Some(hash);
+        self.record(entry).await;
+    }
+
+    pub async fn transfer_finished(&self, transfer: &TransferInfo) {
+        let mut entry = AuditEntry::new(AuditKind::TransferFinished, transfer.peer_node_id.clone());
+        entry.hash = transfer.ticket_hash.clone();
+        entry.file_name = Some(transfer.file_name.clone());
+        entry.bytes = Some(transfer.bytes_transferred);
+        entry.transfer_id = Some(transfer.id.clone());
+        entry.direction = Some(transfer.direction.clone());
+        entry.status = Some(transfer.status.clone());
+        self.record(entry).await;
+    }
+
+    /// The newest `limit` entries, newest first
+    pub async fn recent(&self, limit: usize) -> Result<Vec<AuditEntry>> {
+        let Some(path) = self.path.read().await.clone() else {
+            return Ok(Vec::new());
+        };
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(parse_recent(&contents, limit))
+    }
+
+    /// Copy the log as JSONL to `dest`
+    pub async fn export(&self, dest: &Path) -> Result<u64> {
+        let path = self
+            .path
+            .read()
+            .await
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Audit log is not available"))?;
+        let _guard = self.write_lock.lock().await;
+        match tokio::fs::copy(&path, dest).await {
+            Ok(bytes) => Ok(bytes),
+            // Nothing logged yet: export an empty file
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tokio::fs::write(dest, b"").await?;
+                Ok(0)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+async fn append(path: &Path, entry: &AuditEntry) -> Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(&line).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+/// Parse the last `limit` lines, skipping any that are damaged
+fn parse_recent(contents: &str, limit: usize) -> Vec<AuditEntry> {
+    contents
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_recent_newest_first() {
+        let entries: Vec<String> = (0..3)
+            .map(|i| {
+                let mut entry = AuditEntry::new(AuditKind::Connected, Some(format!("node{}", i)));
+                entry.timestamp = i;
+                serde_json::to_string(&entry).unwrap()
+            })
+            .collect();
+        let contents = format!("{}\n{}\nnot json\n{}\n", entries[0], entries[1], entries[2]);
+
+        let recent = parse_recent(&contents, 2);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].node_id.as_deref(), Some("node2"));
+        assert_eq!(recent[1].node_id.as_deref(), Some("node1"));
+    }
+}
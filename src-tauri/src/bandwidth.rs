@@ -0,0 +1,117 @@
+// Global bandwidth caps
+//
+// One token bucket for uploads and one for downloads, shared by every
+// transfer, so the total stays under the configured rate however many
+// transfers run at once. Uploads are paced by the blob provider's throttle
+// events, which hold each outgoing chunk until the bucket allows it.
+// Downloads are paced by holding back the downloader's progress stream; its
+// channel is bounded, so the download stalls with it. That keeps the average
+// rate right, with bursts of up to one second's worth of data.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upload and download caps in bytes per second; unlimited when unset
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthLimits {
+    pub upload_bps: Option<u64>,
+    pub download_bps: Option<u64>,
+}
+
+/// Limiters shared by all transfers
+#[derive(Clone, Debug, Default)]
+pub struct Bandwidth {
+    pub upload: RateLimiter,
+    pub download: RateLimiter,
+}
+
+impl Bandwidth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&self, limits: BandwidthLimits) {
+        self.upload.set_rate(limits.upload_bps);
+        self.download.set_rate(limits.download_bps);
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn set_rate(&self, rate: Option<u64>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        *bucket = Bucket::new(rate.filter(|r| *r > 0), Instant::now());
+    }
+
+    /// Wait until `bytes` may pass
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = self.bucket.lock().unwrap().reserve(bytes, Instant::now());
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes per second, also the burst size
+    rate: Option<u64>,
+    /// Negative while callers wait for bytes they already reserved
+    tokens: f64,
+    last: Instant,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self::new(None, Instant::now())
+    }
+}
+
+impl Bucket {
+    fn new(rate: Option<u64>, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate.unwrap_or(0) as f64,
+            last: now,
+        }
+    }
+
+    /// Take `bytes` from the bucket, returning how long to wait before using them
+    fn reserve(&mut self, bytes: u64, now: Instant) -> Option<Duration> {
+        let rate = self.rate? as f64;
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.last = now;
+
+        self.tokens -= bytes as f64;
+        (self.tokens < 0.0).then(|| Duration::from_secs_f64(-self.tokens / rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_paces_shared_bytes() {
+        let start = Instant::now();
+        let mut unlimited = Bucket::new(None, start);
+        assert_eq!(unlimited.reserve(u64::MAX, start), None);
+
+        let mut bucket = Bucket::new(Some(1000), start);
+        // A full bucket lets one second's worth through at once
+        assert_eq!(bucket.reserve(1000, start), None);
+        // Two more callers queue up behind each other
+        assert_eq!(bucket.reserve(500, start), Some(Duration::from_millis(500)));
+        assert_eq!(bucket.reserve(500, start), Some(Duration::from_secs(1)));
+        // Refills at the rate, paying off the debt first
+        let later = start + Duration::from_secs(1);
+        assert_eq!(bucket.reserve(1000, later), Some(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    vegam_lib::cli::run()
+}
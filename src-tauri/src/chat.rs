@@ -0,0 +1,128 @@
+// Peer chat
+//
+// Short text messages between paired devices, for coordinating a transfer
+// without another app. A message travels as a `chat` control message, so it
+// goes only to the addressed peer and delivery is confirmed. Both sides keep
+// it in the `messages` history table; the receiver also emits
+// `message-received` and raises a notification. While the app is locked the
+// notification leaves the text out and no event is emitted. Only trusted peers
+// can be messaged, and messages from anyone else are dropped.
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::events::AppEvent;
+use crate::history::now_secs;
+use crate::iroh::control::ControlMessage;
+use crate::notify::{self, Notice};
+use crate::state::AppState;
+
+/// Longest message, in characters
+pub const MAX_TEXT_LEN: usize = 4000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub id: String,
+    /// The other device
+    pub peer_node_id: String,
+    /// We sent it
+    pub outgoing: bool,
+    pub text: String,
+    /// Unix seconds, by the sender's clock
+    pub sent_at: u64,
+}
+
+/// Trimmed text, or why it can't be sent
+pub fn validate_text(text: &str) -> Result<&str, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Message is empty".to_string());
+    }
+    if text.chars().count() > MAX_TEXT_LEN {
+        return Err(format!(
+            "Message is longer than {} characters",
+            MAX_TEXT_LEN
+        ));
+    }
+    Ok(text)
+}
+
+/// Deliver `text` to a paired peer and record it
+pub async fn send(state: &AppState, node_id: &str, text: &str) -> Result<ChatMessage, String> {
+    if !state.is_trusted(node_id).await {
+        return Err("Pair with this device before messaging it".to_string());
+    }
+    let message = ChatMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        peer_node_id: node_id.to_string(),
+        outgoing: true,
+        text: validate_text(text)?.to_string(),
+        sent_at: now_secs(),
+    };
+    let chat = ControlMessage::Chat {
+        id: message.id.clone(),
+        text: message.text.clone(),
+        sent_at: message.sent_at,
+    };
+    crate::deliver_control_message(state, node_id, &chat).await?;
+
+    if let Err(e) = state.record_message(message.clone()).await {
+        warn!("Failed to record message {}: {}", message.id, e);
+    }
+    Ok(message)
+}
+
+/// Handle a `chat` control message from `from`
+pub async fn received(handle: &AppHandle, from: String, id: String, text: String, sent_at: u64) {
+    let state = handle.state::<AppState>();
+    if !state.is_trusted(&from).await {
+        warn!("Dropped message from unpaired peer {}", from);
+        return;
+    }
+    let text = match validate_text(&text) {
+        Ok(text) => text.to_string(),
+        Err(e) => {
+            warn!("Dropped message from {}: {}", from, e);
+            return;
+        }
+    };
+    let message = ChatMessage {
+        id,
+        peer_node_id: from,
+        outgoing: false,
+        text,
+        sent_at,
+    };
+    if let Err(e) = state.record_message(message.clone()).await {
+        warn!("Failed to record message {}: {}", message.id, e);
+    }
+
+    let name = match state.peers.read().await.get(&message.peer_node_id) {
+        Some(peer) => peer.device_name.clone(),
+        None => message.peer_node_id.chars().take(8).collect(),
+    };
+    // While locked the message is only stored; it shows up after unlocking
+    let locked = state.is_locked().await;
+    let notice = Notice::Message {
+        from: name,
+        text: (!locked).then(|| message.text.clone()),
+    };
+    notify::notify(handle, notice).await;
+    if !locked {
+        let _ = AppEvent::MessageReceived(message).emit(handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_text() {
+        assert_eq!(validate_text("  sending it now\n"), Ok("sending it now"));
+        assert!(validate_text(" \n\t").is_err());
+        assert!(validate_text(&"é".repeat(MAX_TEXT_LEN)).is_ok());
+        assert!(validate_text(&"a".repeat(MAX_TEXT_LEN + 1)).is_err());
+    }
+}
@@ -0,0 +1,363 @@
+// Headless command line (`vegam-cli`)
+//
+// `vegam-cli send <path>` imports a file and prints its ticket and a QR code,
+// then serves it until interrupted; `vegam-cli receive <ticket> [-o dir]`
+// downloads with a progress bar. Both start the same node as the app
+// (`Iroh::new` with an `AppState` of its own) and go through
+// `iroh::transfer`, minus the pairing and control protocols, which need the
+// app. The node lives in its own data directory (`--data-dir`, else
+// `$VEGAM_CLI_DIR` or `~/.vegam-cli`), so it keeps its identity between runs
+// and a cut-off receive picks up where it stopped.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::iroh::{transfer, BlobStoreKind, Iroh, NodeOptions};
+use crate::settings::Settings;
+use crate::state::AppState;
+
+const USAGE: &str = "\
+Usage:
+  vegam-cli send <path>                 share a file and print its ticket
+  vegam-cli receive <ticket> [-o dir]   download a file (default: current directory)
+
+Options:
+  --data-dir <dir>   node directory (default: $VEGAM_CLI_DIR or ~/.vegam-cli)
+  --no-qr            don't print the ticket as a QR code";
+
+/// Progress line redraws at most this often
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Send {
+        path: PathBuf,
+    },
+    Receive {
+        ticket: String,
+        output_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    command: Command,
+    data_dir: Option<PathBuf>,
+    qr: bool,
+}
+
+/// Arguments after the program name
+fn parse_args(args: &[String]) -> Result<Args> {
+    let mut positional = Vec::new();
+    let mut data_dir = None;
+    let mut output_dir = None;
+    let mut qr = true;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data-dir" => {
+                let dir = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--data-dir needs a directory"))?;
+                data_dir = Some(PathBuf::from(dir));
+            }
+            "-o" | "--output" => {
+                let dir = args
+                    .next()
+                    .ok_or_else(|| anyhow!("{} needs a directory", arg))?;
+                output_dir = Some(PathBuf::from(dir));
+            }
+            "--no-qr" => qr = false,
+            _ if arg.starts_with('-') && arg.len() > 1 => bail!("Unknown option {}", arg),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let command = match positional.as_slice() {
+        [command, path] if command == "send" && output_dir.is_none() => Command::Send {
+            path: PathBuf::from(path),
+        },
+        [command, ticket] if command == "receive" => Command::Receive {
+            ticket: ticket.clone(),
+            output_dir,
+        },
+        _ => bail!("{}", USAGE),
+    };
+    Ok(Args {
+        command,
+        data_dir,
+        qr,
+    })
+}
+
+/// Entry point of `vegam-cli`
+pub fn run() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_env("VEGAM_LOG")
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    if argv.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let result = parse_args(&argv).and_then(|args| {
+        tokio::runtime::Runtime::new()
+            .context("Failed to start the runtime")?
+            .block_on(execute(args))
+    });
+    if let Err(e) = result {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn execute(args: Args) -> Result<()> {
+    let data_dir = match args.data_dir {
+        Some(dir) => dir,
+        None => default_data_dir()?,
+    };
+    let iroh = start_node(data_dir).await?;
+    let result = match args.command {
+        Command::Send { path } => send(&iroh, &path, args.qr).await,
+        Command::Receive { ticket, output_dir } => {
+            let dir = match output_dir {
+                Some(dir) => dir,
+                None => std::env::current_dir()?,
+            };
+            receive(&iroh, ticket, &dir).await
+        }
+    };
+    let _ = iroh.shutdown().await;
+    result
+}
+
+fn default_data_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("VEGAM_CLI_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".vegam-cli"))
+        .ok_or_else(|| anyhow!("No home directory; pass --data-dir"))
+}
+
+/// Start a node the way `init_node` does, without the app's protocols
+async fn start_node(data_dir: PathBuf) -> Result<Iroh> {
+    let state = AppState::new();
+    let settings = Settings::default();
+    let options = NodeOptions {
+        access_policy: state.access_policy.clone(),
+        peers: state.peers.clone(),
+        blobs_served: state.blobs_served.clone(),
+        audit: state.audit.clone(),
+        ephemeral: false,
+        network: settings.network,
+        bandwidth: state.bandwidth.clone(),
+        low_power: false,
+        serving: state.serving.clone(),
+    };
+    Iroh::new(data_dir, None, None, BlobStoreKind::Filesystem, options)
+        .await
+        .context("Failed to start the node")
+}
+
+async fn send(iroh: &Iroh, path: &Path, qr: bool) -> Result<()> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Can't open {}", path.display()))?;
+    let size = file.metadata().await?.len();
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("file")
+        .to_string();
+
+    let progress = Progress::new("Importing", size);
+    let ticket = transfer::create_send_ticket(
+        iroh,
+        file,
+        file_name,
+        transfer::SendEncryption::None,
+        move |read| progress.update(read),
+    )
+    .await
+    .context("Failed to create ticket")?;
+    eprintln!();
+
+    if qr {
+        match qrcode::QrCode::new(ticket.ticket.as_bytes()) {
+            Ok(code) => println!(
+                "{}",
+                code.render::<qrcode::render::unicode::Dense1x2>()
+                    .quiet_zone(true)
+                    .build()
+            ),
+            Err(e) => eprintln!("Ticket too long for a QR code: {}", e),
+        }
+    }
+    println!("{}", ticket.ticket);
+    eprintln!(
+        "Sharing {} ({}); press Ctrl-C to stop",
+        ticket.file_name,
+        format_bytes(ticket.file_size)
+    );
+
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+async fn receive(iroh: &Iroh, ticket: String, output_dir: &Path) -> Result<()> {
+    let node_id = iroh.node_addr.id.to_string();
+    let (file_name, size, _, _) =
+        transfer::parse_enhanced_ticket(&ticket, &node_id).context("Invalid ticket")?;
+    let name = Path::new(&file_name)
+        .file_name()
+        .ok_or_else(|| anyhow!("Ticket has no usable file name"))?;
+    tokio::fs::create_dir_all(output_dir).await?;
+    let path = output_dir.join(name);
+    let partial = transfer::partial_path(&path);
+
+    let progress = Progress::new("Receiving", size);
+    let received = transfer::receive_file(iroh, ticket, partial.clone(), move |_, bytes, _| {
+        progress.update(bytes)
+    })
+    .await;
+    eprintln!();
+    if let Err(e) = received {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e.context("Receive failed"));
+    }
+    tokio::fs::rename(&partial, &path).await?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Progress bar on stderr
+struct Progress {
+    label: &'static str,
+    total: u64,
+    started: Instant,
+    last_draw: std::sync::Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    fn new(label: &'static str, total: u64) -> Self {
+        Self {
+            label,
+            total,
+            started: Instant::now(),
+            last_draw: std::sync::Mutex::new(None),
+        }
+    }
+
+    fn update(&self, bytes: u64) {
+        let mut last_draw = self.last_draw.lock().unwrap();
+        let now = Instant::now();
+        let done = self.total > 0 && bytes >= self.total;
+        if !done && last_draw.is_some_and(|at| now.duration_since(at) < PROGRESS_INTERVAL) {
+            return;
+        }
+        *last_draw = Some(now);
+
+        let elapsed = now.duration_since(self.started).as_secs_f64();
+        let speed = if elapsed > 0.0 {
+            (bytes as f64 / elapsed) as u64
+        } else {
+            0
+        };
+        let mut stderr = std::io::stderr();
+        let _ = write!(
+            stderr,
+            "\r{} {} {}/s   ",
+            self.label,
+            progress_bar(bytes, self.total),
+            format_bytes(speed)
+        );
+        let _ = stderr.flush();
+    }
+}
+
+const BAR_WIDTH: u64 = 30;
+
+/// `[#####-----]  50% 5.0 MiB/10.0 MiB`, or just the bytes when the total
+/// isn't known
+fn progress_bar(bytes: u64, total: u64) -> String {
+    if total == 0 {
+        return format_bytes(bytes);
+    }
+    let bytes = bytes.min(total);
+    let filled = bytes * BAR_WIDTH / total;
+    format!(
+        "[{}{}] {:>3}% {}/{}",
+        "#".repeat(filled as usize),
+        "-".repeat((BAR_WIDTH - filled) as usize),
+        bytes * 100 / total,
+        format_bytes(bytes),
+        format_bytes(total)
+    )
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_args() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            parse_args(&args(&["send", "a.jpg", "--no-qr"])).unwrap(),
+            Args {
+                command: Command::Send {
+                    path: PathBuf::from("a.jpg")
+                },
+                data_dir: None,
+                qr: false,
+            }
+        );
+        assert_eq!(
+            parse_args(&args(&["receive", "vegam://x:y", "-o", "out"]))
+                .unwrap()
+                .command,
+            Command::Receive {
+                ticket: "vegam://x:y".to_string(),
+                output_dir: Some(PathBuf::from("out")),
+            }
+        );
+        assert!(parse_args(&args(&["send"])).is_err());
+        assert!(parse_args(&args(&["send", "a.jpg", "-o", "out"])).is_err());
+        assert!(parse_args(&args(&["receive", "t", "--bogus"])).is_err());
+
+        assert_eq!(
+            progress_bar(5 * 1024 * 1024, 10 * 1024 * 1024),
+            format!(
+                "[{}{}]  50% 5.0 MiB/10.0 MiB",
+                "#".repeat(15),
+                "-".repeat(15)
+            )
+        );
+        assert_eq!(progress_bar(512, 0), "512 B");
+    }
+}
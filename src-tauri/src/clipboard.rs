@@ -0,0 +1,278 @@
+// Clipboard sync
+//
+// Opt-in sharing of clipboard text and small images between the user's own
+// devices. Each side lists the trusted peers it syncs with, and only accepts
+// clipboards from peers on its own list, so sync runs once both devices have
+// turned it on for each other. A poll of the local clipboard pushes every
+// change to the listed peers that are online as a `clipboard` control
+// message; a received clipboard is written locally and remembered, so the
+// poll doesn't send it back. Content over the size caps is skipped, in both
+// directions, and nothing syncs while the app is locked. Images travel as raw
+// RGBA, which is why their cap is small; mobile clipboards only carry text.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tauri::image::Image;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration};
+use tracing::{debug, warn};
+
+use crate::iroh::control::ControlMessage;
+use crate::state::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardSyncSettings {
+    pub enabled: bool,
+    /// Trusted peers to sync with
+    pub devices: BTreeSet<String>,
+    /// Also sync images
+    pub images: bool,
+    /// Largest text synced, in bytes
+    pub max_text_bytes: usize,
+    /// Largest image synced, in bytes of RGBA pixels
+    pub max_image_bytes: usize,
+}
+
+impl Default for ClipboardSyncSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            devices: BTreeSet::new(),
+            images: true,
+            max_text_bytes: 64 * 1024,
+            max_image_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl ClipboardSyncSettings {
+    /// Whether `content` is within the caps
+    pub fn allows(&self, content: &ClipboardContent) -> bool {
+        match content {
+            ClipboardContent::Text { text } => {
+                !text.is_empty() && text.len() <= self.max_text_bytes
+            }
+            ClipboardContent::Image {
+                width,
+                height,
+                rgba,
+            } => {
+                let size = (*width as usize)
+                    .checked_mul(*height as usize)
+                    .and_then(|pixels| pixels.checked_mul(4))
+                    .unwrap_or(usize::MAX);
+                self.images
+                    && size > 0
+                    && size <= self.max_image_bytes
+                    && rgba.len() == base64::encoded_len(size, true).unwrap_or(0)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ClipboardContent {
+    Text {
+        text: String,
+    },
+    Image {
+        width: u32,
+        height: u32,
+        /// Base64 RGBA pixels
+        rgba: String,
+    },
+}
+
+impl ClipboardContent {
+    fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        match self {
+            Self::Text { text } => {
+                hasher.update(b"text");
+                hasher.update(text.as_bytes());
+            }
+            Self::Image {
+                width,
+                height,
+                rgba,
+            } => {
+                hasher.update(b"image");
+                hasher.update(width.to_le_bytes());
+                hasher.update(height.to_le_bytes());
+                hasher.update(rgba.as_bytes());
+            }
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// Digest of the clipboard content last seen or written
+#[derive(Clone, Default)]
+pub struct ClipboardSync {
+    last: Arc<Mutex<Option<[u8; 32]>>>,
+}
+
+impl ClipboardSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Read the local clipboard, images only if `images`
+fn read(handle: &AppHandle, images: bool) -> Option<ClipboardContent> {
+    let clipboard = handle.clipboard();
+    if let Ok(text) = clipboard.read_text() {
+        return Some(ClipboardContent::Text { text });
+    }
+    if !images {
+        return None;
+    }
+    let image = clipboard.read_image().ok()?;
+    Some(ClipboardContent::Image {
+        width: image.width(),
+        height: image.height(),
+        rgba: STANDARD.encode(image.rgba()),
+    })
+}
+
+fn write(handle: &AppHandle, content: &ClipboardContent) -> anyhow::Result<()> {
+    let clipboard = handle.clipboard();
+    match content {
+        ClipboardContent::Text { text } => clipboard.write_text(text.clone())?,
+        ClipboardContent::Image {
+            width,
+            height,
+            rgba,
+        } => {
+            let rgba = STANDARD.decode(rgba)?;
+            clipboard.write_image(&Image::new_owned(rgba, *width, *height))?;
+        }
+    }
+    Ok(())
+}
+
+/// Spawn the task that pushes local clipboard changes to the synced devices
+pub fn spawn_clipboard_sync(handle: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut timer = interval(POLL_INTERVAL);
+        loop {
+            timer.tick().await;
+            let state = handle.state::<AppState>();
+            let settings = state.get_settings().await.clipboard_sync;
+            if !settings.enabled || settings.devices.is_empty() || state.is_locked().await {
+                continue;
+            }
+
+            let read_handle = handle.clone();
+            let images = settings.images;
+            let content =
+                match tokio::task::spawn_blocking(move || read(&read_handle, images)).await {
+                    Ok(Some(content)) => content,
+                    _ => continue,
+                };
+            let digest = content.digest();
+            let previous = state.clipboard.last.lock().await.replace(digest);
+            // The first read only records what was already there
+            if previous.is_none() || previous == Some(digest) || !settings.allows(&content) {
+                continue;
+            }
+
+            let message = ControlMessage::Clipboard { content };
+            for node_id in &settings.devices {
+                let online = state.peers.read().await.contains_key(node_id);
+                if !online || !state.is_trusted(node_id).await {
+                    continue;
+                }
+                let handle = handle.clone();
+                let node_id = node_id.clone();
+                let message = message.clone();
+                tauri::async_runtime::spawn(async move {
+                    let state = handle.state::<AppState>();
+                    if let Err(e) = crate::deliver_control_message(&state, &node_id, &message).await
+                    {
+                        warn!("Failed to sync clipboard to {}: {}", node_id, e);
+                    }
+                });
+            }
+        }
+    })
+}
+
+/// Handle a `clipboard` control message from `from`
+pub async fn received(handle: &AppHandle, from: &str, content: ClipboardContent) {
+    let state = handle.state::<AppState>();
+    let settings = state.get_settings().await.clipboard_sync;
+    if !settings.enabled
+        || !settings.devices.contains(from)
+        || !state.is_trusted(from).await
+        || state.is_locked().await
+    {
+        debug!("Ignored clipboard from {}", from);
+        return;
+    }
+    if !settings.allows(&content) {
+        warn!("Ignored clipboard from {}: over the size cap", from);
+        return;
+    }
+
+    *state.clipboard.last.lock().await = Some(content.digest());
+    let write_handle = handle.clone();
+    match tokio::task::spawn_blocking(move || write(&write_handle, &content)).await {
+        Ok(Ok(())) => debug!("Clipboard synced from {}", from),
+        Ok(Err(e)) => warn!("Failed to write clipboard from {}: {}", from, e),
+        Err(e) => warn!("Failed to write clipboard from {}: {}", from, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clipboard_caps() {
+        let settings = ClipboardSyncSettings {
+            max_text_bytes: 8,
+            max_image_bytes: 16,
+            ..ClipboardSyncSettings::default()
+        };
+        let text = |text: &str| ClipboardContent::Text {
+            text: text.to_string(),
+        };
+        assert!(settings.allows(&text("hello")));
+        assert!(!settings.allows(&text("")));
+        assert!(!settings.allows(&text("too long!")));
+
+        let image = |width: u32, height: u32| ClipboardContent::Image {
+            width,
+            height,
+            rgba: STANDARD.encode(vec![0u8; (width * height * 4) as usize]),
+        };
+        assert!(settings.allows(&image(2, 2)));
+        assert!(!settings.allows(&image(3, 2)));
+        let no_images = ClipboardSyncSettings {
+            images: false,
+            ..settings.clone()
+        };
+        assert!(!no_images.allows(&image(2, 2)));
+        // Pixel data must match the dimensions
+        let lying = ClipboardContent::Image {
+            width: 2,
+            height: 2,
+            rgba: STANDARD.encode([0u8; 4]),
+        };
+        assert!(!settings.allows(&lying));
+
+        assert_ne!(text("a").digest(), text("b").digest());
+        assert_eq!(image(1, 1).digest(), image(1, 1).digest());
+    }
+}
@@ -0,0 +1,97 @@
+// Where received files go
+//
+// `download_dir` in settings picks the directory for received files; unset,
+// a per-platform default is used: the OS Downloads folder on desktop (or
+// ~/Downloads when the OS doesn't report one), Documents/Downloads on iOS so
+// files show up in the Files app, and the app's own storage on Android, where
+// the public Downloads folder can't be written with `std::fs` (pick a SAF
+// folder for that). Relative paths from the frontend are resolved against
+// this directory, `~/` paths against home.
+
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+use crate::state::AppState;
+
+/// Resolve a user-supplied path: absolute as is, `~/` under `home`, anything
+/// else under `downloads`
+pub fn resolve(path: &str, home: &Path, downloads: &Path) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        return home.join(rest);
+    }
+    if path == "~" {
+        return home.to_path_buf();
+    }
+    let path = Path::new(path);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        downloads.join(path)
+    }
+}
+
+/// Default download directory for this platform
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub fn platform_default(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .download_dir()
+        .or_else(|_| app.path().home_dir().map(|home| home.join("Downloads")))
+        .map_err(|e| format!("Failed to get download directory: {}", e))
+}
+
+#[cfg(target_os = "ios")]
+pub fn platform_default(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .document_dir()
+        .map(|documents| documents.join("Downloads"))
+        .map_err(|e| format!("Failed to get documents directory: {}", e))
+}
+
+#[cfg(target_os = "android")]
+pub fn platform_default(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_local_data_dir()
+        .map(|data| data.join("downloads"))
+        .map_err(|e| format!("Failed to get data directory: {}", e))
+}
+
+/// Directory received files go to when nothing more specific is configured
+pub async fn download_dir(state: &AppState, app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    match state.get_settings().await.download_dir {
+        // A SAF folder is written by `platform::save_to_tree`, not here
+        Some(dir) if !crate::platform::is_content_uri(&dir) => {
+            let home = home_dir(app)?;
+            // Always absolute or `~/` (see `set_download_dir`)
+            Ok(resolve(&dir, &home, &home))
+        }
+        _ => platform_default(app),
+    }
+}
+
+/// Home directory; mobile has none, so the default download directory stands in
+pub fn home_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path().home_dir().or_else(|_| platform_default(app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let home = Path::new("/home/me");
+        let downloads = Path::new("/home/me/Downloads");
+        assert_eq!(
+            resolve("/tmp/a.txt", home, downloads),
+            PathBuf::from("/tmp/a.txt")
+        );
+        assert_eq!(
+            resolve("~/Desktop/a.txt", home, downloads),
+            PathBuf::from("/home/me/Desktop/a.txt")
+        );
+        assert_eq!(
+            resolve("photos/a.jpg", home, downloads),
+            PathBuf::from("/home/me/Downloads/photos/a.jpg")
+        );
+    }
+}
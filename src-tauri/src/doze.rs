@@ -0,0 +1,213 @@
+// Doze and background restrictions on Android
+//
+// In Doze the system cuts network access for apps that aren't exempt from
+// battery optimization, so a receive started or running then would stall
+// until its retries give up. A monitor polls `DeviceState.kt` for Doze,
+// battery saver, the optimization exemption, background restriction and the
+// standby bucket, and emits `background-restrictions-changed` when they
+// change. Receives that would start while transfers are blocked are deferred
+// (`transfer-deferred`), and ones that fail while blocked wait for the device
+// to wake and then resume, since the blob store keeps what already arrived.
+// Elsewhere nothing is ever restricted.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration};
+use tracing::info;
+
+use crate::events::AppEvent;
+use crate::state::AppState;
+
+/// How often the restrictions are polled, and how often a waiting transfer
+/// re-checks them in case a change was missed
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackgroundRestrictions {
+    /// Device is in Doze
+    pub device_idle: bool,
+    /// Battery saver is on
+    pub power_save: bool,
+    /// The user exempted vegam from battery optimization, so Doze leaves its
+    /// network access alone
+    pub ignoring_battery_optimizations: bool,
+    /// Background activity is restricted in the app's battery settings
+    pub background_restricted: bool,
+    /// App standby bucket (10 active up to 45 restricted), when known
+    pub standby_bucket: Option<i32>,
+}
+
+impl BackgroundRestrictions {
+    /// Whether transfers can't reach the network right now
+    pub fn blocks_transfers(&self) -> bool {
+        self.device_idle && !self.ignoring_battery_optimizations
+    }
+}
+
+/// Latest restrictions and the transfers waiting for them to lift
+#[derive(Clone, Default)]
+pub struct DozeGate {
+    current: Arc<RwLock<BackgroundRestrictions>>,
+    lifted: Arc<Notify>,
+}
+
+impl DozeGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn current(&self) -> BackgroundRestrictions {
+        *self.current.read().await
+    }
+
+    pub async fn is_blocked(&self) -> bool {
+        self.current.read().await.blocks_transfers()
+    }
+
+    /// Record the latest restrictions, returning whether they changed
+    pub async fn update(&self, restrictions: BackgroundRestrictions) -> bool {
+        let changed = {
+            let mut current = self.current.write().await;
+            let changed = *current != restrictions;
+            *current = restrictions;
+            changed
+        };
+        if !restrictions.blocks_transfers() {
+            self.lifted.notify_waiters();
+        }
+        changed
+    }
+
+    /// Wait until transfers can reach the network
+    ///
+    /// Calls `on_deferred` once if the transfer has to wait.
+    pub async fn wait(&self, on_deferred: impl FnOnce()) {
+        if !self.is_blocked().await {
+            return;
+        }
+        on_deferred();
+        while self.is_blocked().await {
+            tokio::select! {
+                _ = self.lifted.notified() => {}
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+            }
+        }
+    }
+}
+
+/// Spawn a task that tracks the restrictions, emitting
+/// `background-restrictions-changed`
+pub fn spawn_doze_monitor(handle: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut timer = interval(CHECK_INTERVAL);
+        loop {
+            timer.tick().await;
+            let Some(restrictions) = detect().await else {
+                continue;
+            };
+            let state = handle.state::<AppState>();
+            if state.doze.update(restrictions).await {
+                info!("Background restrictions changed: {:?}", restrictions);
+                let _ = AppEvent::BackgroundRestrictionsChanged(restrictions).emit(&handle);
+            }
+        }
+    })
+}
+
+#[cfg(target_os = "android")]
+pub async fn detect() -> Option<BackgroundRestrictions> {
+    use jni::objects::{JString, JValue};
+
+    let json = tokio::task::spawn_blocking(|| {
+        crate::platform::with_java_class("codes.imran.vegam.DeviceState", |env, class, context| {
+            let json = env
+                .call_static_method(
+                    class,
+                    "query",
+                    "(Landroid/content/Context;)Ljava/lang/String;",
+                    &[JValue::Object(context)],
+                )?
+                .l()?;
+            let json: String = env.get_string(&JString::from(json))?.into();
+            Ok(json)
+        })
+    })
+    .await
+    .ok()?;
+    match json {
+        Ok(json) => serde_json::from_str(&json).ok(),
+        Err(e) => {
+            tracing::warn!("Failed to read background restrictions: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+pub async fn detect() -> Option<BackgroundRestrictions> {
+    None
+}
+
+/// Open the system's battery optimization settings so the user can exempt
+/// vegam
+#[cfg(target_os = "android")]
+pub async fn open_battery_settings() -> Result<(), String> {
+    tokio::task::spawn_blocking(|| {
+        crate::platform::with_java_class("codes.imran.vegam.DeviceState", |env, class, context| {
+            env.call_static_method(
+                class,
+                "openBatterySettings",
+                "(Landroid/content/Context;)V",
+                &[jni::objects::JValue::Object(context)],
+            )?;
+            Ok(())
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("Failed to open battery settings: {}", e))
+}
+
+#[cfg(not(target_os = "android"))]
+pub async fn open_battery_settings() -> Result<(), String> {
+    Err("Battery optimization settings are only available on Android".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_resumes_when_idle_ends() {
+        let restrictions: BackgroundRestrictions =
+            serde_json::from_str(r#"{"device_idle":true,"standby_bucket":40}"#).unwrap();
+        assert!(restrictions.blocks_transfers());
+        assert!(!BackgroundRestrictions {
+            ignoring_battery_optimizations: true,
+            ..restrictions
+        }
+        .blocks_transfers());
+
+        let gate = DozeGate::new();
+        assert!(gate.update(restrictions).await);
+        assert!(!gate.update(restrictions).await);
+
+        let waiting = tokio::spawn({
+            let gate = gate.clone();
+            async move {
+                let mut deferred = false;
+                gate.wait(|| deferred = true).await;
+                deferred
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        gate.update(BackgroundRestrictions::default()).await;
+        assert!(waiting.await.unwrap());
+    }
+}
@@ -0,0 +1,129 @@
+// Files dragged onto the window on desktop
+//
+// Tauri reports the dropped paths as a window event. Each path is checked
+// (it must be a readable regular file; folders aren't sent), and the valid
+// ones are either staged until the frontend takes them with
+// `take_dropped_files`, or, when a peer was picked with `set_drop_target`,
+// imported right away and offered to that peer over the control channel.
+// Either way `files-dropped` reports what was accepted, with tickets for
+// imported files, and what was refused and why.
+
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DroppedFile {
+    pub path: String,
+    pub name: String,
+    pub size: u64,
+    /// Set once imported for the drop target
+    pub transfer_id: Option<String>,
+    pub ticket: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RejectedDrop {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Payload of `files-dropped`
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FilesDropped {
+    pub files: Vec<DroppedFile>,
+    pub rejected: Vec<RejectedDrop>,
+    /// Peer the files were offered to; staged for `take_dropped_files` if none
+    pub target: Option<String>,
+}
+
+/// Dropped files waiting for the frontend and the pending drop target
+#[derive(Clone, Default)]
+pub struct DropZone {
+    staged: Arc<RwLock<Vec<DroppedFile>>>,
+    target: Arc<RwLock<Option<String>>>,
+}
+
+impl DropZone {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Peer that files dropped from now on are sent to, or `None` to stage them
+    pub async fn set_target(&self, node_id: Option<String>) {
+        *self.target.write().await = node_id;
+    }
+
+    pub async fn target(&self) -> Option<String> {
+        self.target.read().await.clone()
+    }
+
+    pub async fn stage(&self, files: &[DroppedFile]) {
+        self.staged.write().await.extend(files.iter().cloned());
+    }
+
+    /// Take the staged files, leaving none
+    pub async fn take(&self) -> Vec<DroppedFile> {
+        std::mem::take(&mut *self.staged.write().await)
+    }
+}
+
+/// Check a dropped path, returning the file or why it can't be sent
+pub async fn check(path: &Path) -> Result<DroppedFile, RejectedDrop> {
+    let reject = |reason: String| RejectedDrop {
+        path: path.display().to_string(),
+        reason,
+    };
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| reject(format!("Can't read file: {}", e)))?;
+    if metadata.is_dir() {
+        return Err(reject("Folders can't be sent".to_string()));
+    }
+    if !metadata.is_file() {
+        return Err(reject("Not a regular file".to_string()));
+    }
+    // Fail now rather than halfway through the import
+    tokio::fs::File::open(path)
+        .await
+        .map_err(|e| reject(format!("Can't read file: {}", e)))?;
+
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| reject("File name isn't valid UTF-8".to_string()))?;
+    let path = path
+        .to_str()
+        .ok_or_else(|| reject("Path isn't valid UTF-8".to_string()))?;
+    Ok(DroppedFile {
+        path: path.to_string(),
+        name: name.to_string(),
+        size: metadata.len(),
+        transfer_id: None,
+        ticket: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_dropped_paths() {
+        let dir = std::env::temp_dir().join(format!("vegam-drop-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir(&dir).await.unwrap();
+        let file = dir.join("notes.txt");
+        tokio::fs::write(&file, b"hello").await.unwrap();
+
+        let dropped = check(&file).await.unwrap();
+        assert_eq!(dropped.name, "notes.txt");
+        assert_eq!(dropped.size, 5);
+
+        let rejected = check(&dir).await.unwrap_err();
+        assert_eq!(rejected.reason, "Folders can't be sent");
+        assert!(check(&dir.join("missing")).await.is_err());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
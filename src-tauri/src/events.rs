@@ -0,0 +1,149 @@
+// Events pushed to the frontend
+//
+// Every event goes through `AppEvent` so its name and payload shape are
+// defined in one place. Variants serialize as their payload alone, which is
+// what the `listen<T>()` wrappers in `src/lib/api.ts` expect.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::chat::ChatMessage;
+use crate::doze::BackgroundRestrictions;
+use crate::drag_drop::FilesDropped;
+use crate::fanout::TransferGroup;
+use crate::history::BlobRecord;
+use crate::iroh::connection::{ConnectionChange, ConnectionUpgrade};
+use crate::iroh::control::ControlReceived;
+use crate::iroh::network::RelayChange;
+use crate::iroh::spaces::SpaceUpdated;
+use crate::launch::OpenedTicket;
+use crate::power::DeferredSend;
+use crate::settings::TrustedPeer;
+use crate::share::SharedFile;
+use crate::state::{PeerInfo, TransferInfo};
+use crate::watch::WatchFileSent;
+
+/// Bumped whenever an event name or payload changes incompatibly
+pub const EVENT_VERSION: u32 = 1;
+
+/// Payload of `gossip-neighbor-up` / `gossip-neighbor-down`
+#[derive(Clone, Debug, Serialize)]
+pub struct NeighborEvent {
+    pub group_id: String,
+    pub node_id: String,
+    /// Direct gossip neighbors on this topic after the change
+    pub neighbors: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum AppEvent {
+    /// A transfer changed status
+    TransferUpdate(TransferInfo),
+    /// Bytes moved on a running transfer
+    TransferProgress(TransferInfo),
+    /// Download waiting for an unmetered connection
+    TransferDeferred(TransferInfo),
+    /// Large send waiting for the battery to charge or recover
+    SendDeferred(DeferredSend),
+    PeerDiscovered(PeerInfo),
+    /// Node ID of a peer that left or timed out
+    PeerLost(String),
+    PeerListUpdated(Vec<PeerInfo>),
+    PeerPaired(TrustedPeer),
+    PeerConnectionUpgraded(ConnectionUpgrade),
+    PeerConnectionChanged(ConnectionChange),
+    /// Offer, answer, cancellation or receipt sent to us by a peer
+    ControlMessage(ControlReceived),
+    GossipNeighborUp(NeighborEvent),
+    GossipNeighborDown(NeighborEvent),
+    /// Group ID whose discovery loop was restarted
+    DiscoveryRestarted(String),
+    /// Relay URLs after a network change
+    NetworkChanged(Vec<String>),
+    /// URL of the home relay, once connected
+    RelayConnected(String),
+    RelayChanged(RelayChange),
+    /// Whether low-power mode is now on
+    LowPowerChanged(bool),
+    /// Receives interrupted last session that `resume_receive` can restart
+    ReceivesResumable(Vec<TransferInfo>),
+    /// Discoverability changed, possibly from a Quick Settings tile
+    DiscoverableChanged(bool),
+    /// Doze, battery saver or background restrictions changed (Android)
+    BackgroundRestrictionsChanged(BackgroundRestrictions),
+    /// Files shared into the app from another app
+    ShareReceived(Vec<SharedFile>),
+    /// Files dropped on the window, staged or offered to the drop target
+    FilesDropped(FilesDropped),
+    /// A `vegam://` link or ticket file was opened with the app
+    TicketOpened(OpenedTicket),
+    /// A recipient of a fan-out send answered, or the send started
+    TransferGroupUpdated(TransferGroup),
+    /// A paired peer sent a chat message
+    MessageReceived(ChatMessage),
+    /// A shared space's entries changed or file content arrived
+    SpaceUpdated(SpaceUpdated),
+    /// A file from a watch folder was imported and offered
+    WatchFileSent(WatchFileSent),
+    /// Blobs deleted by garbage collection or the storage quota
+    BlobsRemoved(Vec<BlobRecord>),
+}
+
+impl AppEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::TransferUpdate(_) => "transfer-update",
+            Self::TransferProgress(_) => "transfer-progress",
+            Self::TransferDeferred(_) => "transfer-deferred",
+            Self::SendDeferred(_) => "send-deferred",
+            Self::PeerDiscovered(_) => "peer-discovered",
+            Self::PeerLost(_) => "peer-lost",
+            Self::PeerListUpdated(_) => "peer-list-updated",
+            Self::PeerPaired(_) => "peer-paired",
+            Self::PeerConnectionUpgraded(_) => "peer-connection-upgraded",
+            Self::PeerConnectionChanged(_) => "peer-connection-changed",
+            Self::ControlMessage(_) => "control-message",
+            Self::GossipNeighborUp(_) => "gossip-neighbor-up",
+            Self::GossipNeighborDown(_) => "gossip-neighbor-down",
+            Self::DiscoveryRestarted(_) => "discovery-restarted",
+            Self::NetworkChanged(_) => "network-changed",
+            Self::RelayConnected(_) => "relay-connected",
+            Self::RelayChanged(_) => "relay-changed",
+            Self::LowPowerChanged(_) => "low-power-changed",
+            Self::DiscoverableChanged(_) => "discoverable-changed",
+            Self::ReceivesResumable(_) => "receives-resumable",
+            Self::BackgroundRestrictionsChanged(_) => "background-restrictions-changed",
+            Self::ShareReceived(_) => "share-received",
+            Self::FilesDropped(_) => "files-dropped",
+            Self::TicketOpened(_) => "ticket-opened",
+            Self::WatchFileSent(_) => "watch-file-sent",
+            Self::SpaceUpdated(_) => "space-updated",
+            Self::MessageReceived(_) => "message-received",
+            Self::TransferGroupUpdated(_) => "transfer-group-updated",
+            Self::BlobsRemoved(_) => "blobs-removed",
+        }
+    }
+
+    /// Send the event to all windows
+    pub fn emit(&self, handle: &AppHandle) -> tauri::Result<()> {
+        handle.emit(self.name(), self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_serialize_as_payload() {
+        let event = AppEvent::PeerLost("abc".to_string());
+        assert_eq!(serde_json::to_string(&event).unwrap(), "\"abc\"");
+
+        let event = AppEvent::NetworkChanged(vec!["https://relay".to_string()]);
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            "[\"https://relay\"]"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+// Fan-out sends
+//
+// `send_to_peers` imports a file once and offers the same ticket to several
+// peers over the control channel. The send is a single transfer; a
+// `TransferGroup` in `AppState.transfer_groups` tracks it per recipient.
+// Recipients start as `offered` (or `failed` if the offer couldn't be
+// delivered) and move on as their `accept`, `reject`, `cancel` and `receipt`
+// control messages for the transfer arrive. Every change emits
+// `transfer-group-updated`. Groups live for the session.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+
+use crate::events::AppEvent;
+use crate::history::now_secs;
+use crate::iroh::control::ControlMessage;
+use crate::state::AppState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecipientStatus {
+    /// Offer delivered, no answer yet
+    Offered,
+    Accepted,
+    Rejected,
+    Cancelled,
+    /// The recipient confirmed it has the whole file
+    Completed,
+    /// The offer couldn't be delivered
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Recipient {
+    pub node_id: String,
+    pub status: RecipientStatus,
+    /// Why the offer failed or the recipient rejected it
+    pub error: Option<String>,
+    pub bytes_received: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TransferGroup {
+    pub id: String,
+    /// The shared send every recipient downloads
+    pub transfer_id: String,
+    pub file_name: String,
+    pub file_size: u64,
+    pub created_at: u64,
+    pub recipients: Vec<Recipient>,
+}
+
+impl TransferGroup {
+    /// Apply a control message from `from`; returns whether anything changed
+    pub fn apply(&mut self, from: &str, message: &ControlMessage) -> bool {
+        let (transfer_id, status) = match message {
+            ControlMessage::Accept { transfer_id } => (transfer_id, RecipientStatus::Accepted),
+            ControlMessage::Reject { transfer_id, .. } => (transfer_id, RecipientStatus::Rejected),
+            ControlMessage::Cancel { transfer_id } => (transfer_id, RecipientStatus::Cancelled),
+            ControlMessage::Receipt { transfer_id, .. } => {
+                (transfer_id, RecipientStatus::Completed)
+            }
+            _ => return false,
+        };
+        if *transfer_id != self.transfer_id {
+            return false;
+        }
+        let Some(recipient) = self.recipients.iter_mut().find(|r| r.node_id == from) else {
+            return false;
+        };
+        if recipient.status == status {
+            return false;
+        }
+        match message {
+            ControlMessage::Reject { reason, .. } => recipient.error = reason.clone(),
+            ControlMessage::Receipt { bytes_received, .. } => {
+                recipient.bytes_received = Some(*bytes_received)
+            }
+            _ => {}
+        }
+        recipient.status = status;
+        true
+    }
+}
+
+/// Fan-out sends by group ID
+#[derive(Clone, Default)]
+pub struct TransferGroups {
+    groups: Arc<RwLock<HashMap<String, TransferGroup>>>,
+}
+
+impl TransferGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn list(&self) -> Vec<TransferGroup> {
+        let mut groups: Vec<_> = self.groups.read().await.values().cloned().collect();
+        groups.sort_by_key(|g| std::cmp::Reverse(g.created_at));
+        groups
+    }
+
+    pub async fn get(&self, id: &str) -> Option<TransferGroup> {
+        self.groups.read().await.get(id).cloned()
+    }
+
+    async fn insert(&self, group: TransferGroup) {
+        self.groups.write().await.insert(group.id.clone(), group);
+    }
+
+    /// Mark a recipient whose offer couldn't be delivered
+    async fn fail(&self, id: &str, node_id: &str, error: String) {
+        let mut groups = self.groups.write().await;
+        let recipient = groups
+            .get_mut(id)
+            .and_then(|group| group.recipients.iter_mut().find(|r| r.node_id == node_id));
+        if let Some(recipient) = recipient {
+            recipient.status = RecipientStatus::Failed;
+            recipient.error = Some(error);
+        }
+    }
+
+    /// Update the group whose send `message` refers to, returning it if it changed
+    pub async fn apply(&self, from: &str, message: &ControlMessage) -> Option<TransferGroup> {
+        let mut groups = self.groups.write().await;
+        groups
+            .values_mut()
+            .find_map(|group| group.apply(from, message).then(|| group.clone()))
+    }
+}
+
+/// Import `file_path` once and offer it to every peer in `node_ids`
+pub async fn send_to_peers(
+    handle: &AppHandle,
+    mut node_ids: Vec<String>,
+    file_path: String,
+) -> Result<TransferGroup, String> {
+    let state = handle.state::<AppState>();
+    node_ids.sort();
+    node_ids.dedup();
+    if node_ids.is_empty() {
+        return Err("No peers selected".to_string());
+    }
+    for node_id in &node_ids {
+        crate::validate_node_id(node_id)?;
+    }
+
+    let info = crate::share_file(&state, handle.clone(), file_path, false).await?;
+    let offer = ControlMessage::Offer {
+        transfer_id: info.transfer_id.clone(),
+        ticket: info.ticket,
+        file_name: info.file_name.clone(),
+        file_size: info.file_size,
+    };
+
+    // Tracked before the offers go out, since answers can follow right away
+    let group = TransferGroup {
+        id: uuid::Uuid::new_v4().to_string(),
+        transfer_id: info.transfer_id,
+        file_name: info.file_name,
+        file_size: info.file_size,
+        created_at: now_secs(),
+        recipients: node_ids
+            .iter()
+            .map(|node_id| Recipient {
+                node_id: node_id.clone(),
+                status: RecipientStatus::Offered,
+                error: None,
+                bytes_received: None,
+            })
+            .collect(),
+    };
+    state.transfer_groups.insert(group.clone()).await;
+
+    let mut deliveries = JoinSet::new();
+    for node_id in node_ids {
+        let handle = handle.clone();
+        let offer = offer.clone();
+        deliveries.spawn(async move {
+            let state = handle.state::<AppState>();
+            let result = crate::deliver_control_message(&state, &node_id, &offer).await;
+            (node_id, result)
+        });
+    }
+    while let Some(delivery) = deliveries.join_next().await {
+        if let Ok((node_id, Err(e))) = delivery {
+            state.transfer_groups.fail(&group.id, &node_id, e).await;
+        }
+    }
+
+    let group = state.transfer_groups.get(&group.id).await.unwrap_or(group);
+    let _ = AppEvent::TransferGroupUpdated(group.clone()).emit(handle);
+    Ok(group)
+}
+
+/// Track a recipient's answer to a fan-out offer
+pub async fn observe(handle: &AppHandle, from: &str, message: &ControlMessage) {
+    let state = handle.state::<AppState>();
+    if let Some(group) = state.transfer_groups.apply(from, message).await {
+        let _ = AppEvent::TransferGroupUpdated(group).emit(handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipient_updates() {
+        let recipient = |node_id: &str| Recipient {
+            node_id: node_id.to_string(),
+            status: RecipientStatus::Offered,
+            error: None,
+            bytes_received: None,
+        };
+        let mut group = TransferGroup {
+            id: "g1".to_string(),
+            transfer_id: "t1".to_string(),
+            file_name: "a.mp4".to_string(),
+            file_size: 10,
+            created_at: 0,
+            recipients: vec![recipient("a"), recipient("b")],
+        };
+
+        let accept = ControlMessage::Accept {
+            transfer_id: "t1".to_string(),
+        };
+        assert!(group.apply("a", &accept));
+        assert!(!group.apply("a", &accept));
+        assert!(!group.apply("c", &accept));
+        let other = ControlMessage::Accept {
+            transfer_id: "t2".to_string(),
+        };
+        assert!(!group.apply("b", &other));
+
+        let reject = ControlMessage::Reject {
+            transfer_id: "t1".to_string(),
+            reason: Some("No space".to_string()),
+        };
+        assert!(group.apply("b", &reject));
+        let receipt = ControlMessage::Receipt {
+            transfer_id: "t1".to_string(),
+            bytes_received: 10,
+        };
+        assert!(group.apply("a", &receipt));
+
+        assert_eq!(group.recipients[0].status, RecipientStatus::Completed);
+        assert_eq!(group.recipients[0].bytes_received, Some(10));
+        assert_eq!(group.recipients[1].status, RecipientStatus::Rejected);
+        assert_eq!(group.recipients[1].error.as_deref(), Some("No space"));
+    }
+}
@@ -0,0 +1,189 @@
+// Keeping transfers alive in the background
+//
+// Android freezes a backgrounded app shortly after the screen locks, which
+// kills large transfers halfway. While a receive is in progress or a peer is
+// downloading from us, `TransferService` (a dataSync foreground service in
+// the Android project) runs with a progress notification and holds a partial
+// wake lock and a Wi-Fi lock; it stops once everything has finished. The
+// notification follows the transfer table, so it is driven from here rather
+// than from the frontend, which may be suspended itself.
+
+use crate::state::{TransferInfo, TransferStatus};
+
+#[cfg(target_os = "android")]
+const REFRESH_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(1);
+
+/// What the notification shows
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransferSummary {
+    /// Transfers in progress
+    pub running: usize,
+    /// File name, when exactly one transfer is running
+    pub file_name: Option<String>,
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    /// Blob connections open to peers downloading from us
+    pub serving: usize,
+}
+
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+impl TransferSummary {
+    /// Summarise running transfers, or `None` when there's nothing to keep alive
+    pub fn of<'a>(
+        transfers: impl IntoIterator<Item = &'a TransferInfo>,
+        serving: usize,
+    ) -> Option<Self> {
+        let mut summary = Self {
+            serving,
+            ..Self::default()
+        };
+        for transfer in transfers {
+            if transfer.status != TransferStatus::InProgress {
+                continue;
+            }
+            summary.running += 1;
+            summary.file_name = (summary.running == 1).then(|| transfer.file_name.clone());
+            summary.bytes_transferred += transfer.bytes_transferred;
+            summary.total_bytes += transfer.file_size;
+        }
+        (summary.running > 0 || summary.serving > 0).then_some(summary)
+    }
+
+    pub fn title(&self) -> String {
+        match (self.running, &self.file_name) {
+            (0, _) => format!("Sending to {} device(s)", self.serving),
+            (1, Some(name)) => format!("Transferring {}", name),
+            (n, _) => format!("Transferring {} files", n),
+        }
+    }
+
+    /// Overall progress in percent, or -1 when unknown
+    pub fn percent(&self) -> i32 {
+        if self.total_bytes == 0 {
+            return -1;
+        }
+        (self.bytes_transferred.min(self.total_bytes) * 100 / self.total_bytes) as i32
+    }
+}
+
+/// Spawn a task running the foreground service while transfers are active
+#[cfg(target_os = "android")]
+pub fn spawn_transfer_service(handle: tauri::AppHandle) -> tokio::task::JoinHandle<()> {
+    use crate::state::AppState;
+    use std::sync::atomic::Ordering;
+    use tauri::Manager;
+
+    tauri::async_runtime::spawn(async move {
+        let mut timer = tokio::time::interval(REFRESH_INTERVAL);
+        // Last notification shown, to only call into Java on changes
+        let mut shown: Option<(String, i32)> = None;
+        loop {
+            timer.tick().await;
+            let state = handle.state::<AppState>();
+            let serving = state.serving.load(Ordering::Relaxed);
+            let current = TransferSummary::of(state.transfers.read().await.values(), serving)
+                .map(|summary| (summary.title(), summary.percent()));
+            if current == shown {
+                continue;
+            }
+            let res = match &current {
+                Some((title, percent)) => service::update(title, *percent),
+                None => service::stop(),
+            };
+            if let Err(e) = res {
+                tracing::warn!("Failed to update transfer service: {}", e);
+            }
+            shown = current;
+        }
+    })
+}
+
+/// Calls into `codes.imran.vegam.TransferService`
+#[cfg(target_os = "android")]
+mod service {
+    use anyhow::Result;
+    use jni::objects::JValue;
+
+    use crate::platform::with_java_class;
+
+    const CLASS: &str = "codes.imran.vegam.TransferService";
+
+    /// Start the service or update its notification
+    pub fn update(title: &str, percent: i32) -> Result<()> {
+        with_java_class(CLASS, |env, class, context| {
+            let title = env.new_string(title)?;
+            env.call_static_method(
+                class,
+                "update",
+                "(Landroid/content/Context;Ljava/lang/String;I)V",
+                &[
+                    JValue::Object(context),
+                    JValue::Object(&title),
+                    JValue::Int(percent),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn stop() -> Result<()> {
+        with_java_class(CLASS, |env, class, context| {
+            env.call_static_method(
+                class,
+                "stop",
+                "(Landroid/content/Context;)V",
+                &[JValue::Object(context)],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TransferDirection;
+
+    fn transfer(name: &str, size: u64, done: u64, status: TransferStatus) -> TransferInfo {
+        TransferInfo {
+            id: name.to_string(),
+            file_name: name.to_string(),
+            file_size: size,
+            bytes_transferred: done,
+            status,
+            error: None,
+            direction: TransferDirection::Receive,
+            speed_bps: 0,
+            peer_node_id: None,
+            started_at: None,
+            finished_at: None,
+            ticket_hash: None,
+            note: None,
+            pinned: false,
+            actions: Vec::new(),
+            saved_path: None,
+        }
+    }
+
+    #[test]
+    fn test_transfer_summary() {
+        assert_eq!(TransferSummary::of(&[], 0), None);
+
+        let finished = transfer("old.zip", 10, 10, TransferStatus::Completed);
+        assert_eq!(TransferSummary::of([&finished], 0), None);
+        let serving = TransferSummary::of([&finished], 2).unwrap();
+        assert_eq!(serving.title(), "Sending to 2 device(s)");
+        assert_eq!(serving.percent(), -1);
+
+        let a = transfer("a.mp4", 300, 150, TransferStatus::InProgress);
+        let single = TransferSummary::of([&a, &finished], 0).unwrap();
+        assert_eq!(single.title(), "Transferring a.mp4");
+        assert_eq!(single.percent(), 50);
+
+        let b = transfer("b.jpg", 100, 100, TransferStatus::InProgress);
+        let both = TransferSummary::of([&a, &b], 0).unwrap();
+        assert_eq!(both.title(), "Transferring 2 files");
+        assert_eq!(both.percent(), 62);
+    }
+}
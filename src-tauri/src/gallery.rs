@@ -0,0 +1,136 @@
+// Saving received photos and videos to the gallery
+//
+// With `save_media_to_gallery`, images and videos received on a phone end up
+// in the system media library instead of the download directory, where
+// nobody browses: on Android `MediaSaver.kt` inserts them into MediaStore
+// under Pictures/vegam or Movies/vegam, on iOS `vegam_save_to_photos`
+// (Sources/vegam/photos.m) adds them to Photos. Both keep the original file
+// name and read date taken and location from the file's own EXIF/QuickTime
+// metadata. The downloaded copy is removed once saved and kept when saving
+// fails. Desktop has no such library, so the setting does nothing there.
+
+use std::io;
+use std::path::Path;
+
+/// Whether this platform has a gallery to save to
+pub const AVAILABLE: bool = cfg!(any(target_os = "android", target_os = "ios"));
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaKind {
+    Image,
+    Video,
+}
+
+/// Kind and MIME type of a file the gallery can take, by extension
+pub fn media_type(path: &Path) -> Option<(MediaKind, &'static str)> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let media = match ext.as_str() {
+        "jpg" | "jpeg" => (MediaKind::Image, "image/jpeg"),
+        "png" => (MediaKind::Image, "image/png"),
+        "gif" => (MediaKind::Image, "image/gif"),
+        "webp" => (MediaKind::Image, "image/webp"),
+        "heic" => (MediaKind::Image, "image/heic"),
+        "heif" => (MediaKind::Image, "image/heif"),
+        "dng" => (MediaKind::Image, "image/x-adobe-dng"),
+        "mp4" => (MediaKind::Video, "video/mp4"),
+        "m4v" => (MediaKind::Video, "video/x-m4v"),
+        "mov" => (MediaKind::Video, "video/quicktime"),
+        "3gp" => (MediaKind::Video, "video/3gpp"),
+        "webm" => (MediaKind::Video, "video/webm"),
+        "mkv" => (MediaKind::Video, "video/x-matroska"),
+        _ => return None,
+    };
+    Some(media)
+}
+
+/// Add a received file to the gallery under its own name
+#[cfg(target_os = "android")]
+pub async fn save(path: &Path) -> io::Result<()> {
+    use jni::objects::{JString, JValue};
+
+    let (kind, mime) = media_type(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a photo or video"))?;
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        crate::platform::with_java_class("codes.imran.vegam.MediaSaver", |env, class, context| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("media");
+            let path = env.new_string(path.to_string_lossy())?;
+            let name = env.new_string(name)?;
+            let mime = env.new_string(mime)?;
+            let uri = env
+                .call_static_method(
+                    class,
+                    "save",
+                    "(Landroid/content/Context;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Z)Ljava/lang/String;",
+                    &[
+                        JValue::Object(context),
+                        JValue::Object(&path),
+                        JValue::Object(&name),
+                        JValue::Object(&mime),
+                        JValue::Bool((kind == MediaKind::Video).into()),
+                    ],
+                )?
+                .l()?;
+            let uri: String = env.get_string(&JString::from(uri))?.into();
+            log::info!("Saved to gallery: {}", uri);
+            Ok(())
+        })
+    })
+    .await
+    .map_err(io::Error::other)?
+    .map_err(|e| io::Error::other(format!("MediaStore insert failed: {}", e)))
+}
+
+#[cfg(target_os = "ios")]
+pub async fn save(path: &Path) -> io::Result<()> {
+    use std::ffi::{c_char, CString};
+
+    extern "C" {
+        fn vegam_save_to_photos(path: *const c_char, is_video: bool) -> bool;
+    }
+
+    let (kind, _) = media_type(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a photo or video"))?;
+    let c_path = CString::new(path.to_string_lossy().into_owned())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // Blocks until Photos has the asset, or the user denied access
+    let saved = tokio::task::spawn_blocking(move || unsafe {
+        vegam_save_to_photos(c_path.as_ptr(), kind == MediaKind::Video)
+    })
+    .await
+    .map_err(io::Error::other)?;
+    if saved {
+        Ok(())
+    } else {
+        Err(io::Error::other(
+            "Photos refused the file or access was denied",
+        ))
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub async fn save(path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Can't save {} to a gallery on desktop", path.display()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_type() {
+        assert_eq!(
+            media_type(Path::new("IMG_0001.JPG")),
+            Some((MediaKind::Image, "image/jpeg"))
+        );
+        assert_eq!(
+            media_type(Path::new("/tmp/clip.mov")),
+            Some((MediaKind::Video, "video/quicktime"))
+        );
+        assert_eq!(media_type(Path::new("notes.txt")), None);
+        assert_eq!(media_type(Path::new("no_extension")), None);
+    }
+}
@@ -0,0 +1,1359 @@
+// Persistent transfer history
+//
+// Every transfer update is upserted into a small SQLite database in the app's
+// local data directory. `AppState.transfers` only caches transfers that are
+// still running; finished ones are read back from here.
+//
+// The same database records the blobs we share, so the blob store can be
+// garbage collected once nobody has downloaded them for a while.
+//
+// Received files are written to a temporary path and renamed into place. A
+// `file_journal` row covers each write, so after an abrupt kill the next start
+// can finish or undo it and keep history consistent with the files on disk.
+//
+// `peer_addrs` keeps the last address each transfer peer was reached at, so
+// the next session can dial it without waiting for discovery.
+//
+// `resumable_receives` holds the ticket and requested location of each
+// receive while it runs. If the process dies (Android kills it in the
+// background) the transfer is marked interrupted on the next start, and the
+// row lets it be restarted; the blob store keeps what already arrived.
+//
+// `messages` is the chat history with each paired peer (see `chat.rs`).
+
+use anyhow::Result;
+use rusqlite::{params, types::Type, Connection, OptionalExtension, Row, ToSql};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::chat::ChatMessage;
+use crate::post_receive::ActionResult;
+use crate::state::{AppState, TransferDirection, TransferInfo, TransferStatus};
+
+pub const HISTORY_FILE: &str = "history.db";
+
+/// Largest page `list` will return
+pub const MAX_PAGE_SIZE: u32 = 200;
+
+/// Candidates fetched from the database before ranking search results
+const MAX_SEARCH_CANDIDATES: u32 = 500;
+
+/// How often the retention policy is applied
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const FINISHED: &str = "status NOT IN ('pending', 'inprogress')";
+
+/// Only finished, unpinned transfers are ever deleted
+const REMOVABLE: &str = "status NOT IN ('pending', 'inprogress') AND pinned = 0";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS transfers (
+        id TEXT PRIMARY KEY,
+        file_name TEXT NOT NULL,
+        file_size INTEGER NOT NULL,
+        bytes_transferred INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        error TEXT,
+        direction TEXT NOT NULL,
+        peer_node_id TEXT,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS transfers_peer ON transfers (peer_node_id);
+    CREATE INDEX IF NOT EXISTS transfers_created ON transfers (created_at);
+    CREATE TABLE IF NOT EXISTS blobs (
+        hash TEXT PRIMARY KEY,
+        tag TEXT NOT NULL,
+        file_name TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        last_served_at INTEGER,
+        pinned INTEGER NOT NULL DEFAULT 0
+    );
+";
+
+/// Schema changes applied in order on top of `SCHEMA`, tracked by `user_version`
+const MIGRATIONS: &[&str] = &[
+    "
+    ALTER TABLE transfers ADD COLUMN started_at INTEGER;
+    ALTER TABLE transfers ADD COLUMN finished_at INTEGER;
+    ALTER TABLE transfers ADD COLUMN ticket_hash TEXT;
+",
+    "
+    ALTER TABLE transfers ADD COLUMN note TEXT;
+",
+    "
+    CREATE TABLE file_journal (
+        transfer_id TEXT PRIMARY KEY,
+        temp_path TEXT NOT NULL,
+        final_path TEXT NOT NULL,
+        written INTEGER NOT NULL DEFAULT 0,
+        created_at INTEGER NOT NULL
+    );
+",
+    "
+    ALTER TABLE transfers ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+",
+    "
+    CREATE TABLE peer_addrs (
+        node_id TEXT PRIMARY KEY,
+        addr TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+",
+    "
+    CREATE TABLE resumable_receives (
+        transfer_id TEXT PRIMARY KEY,
+        ticket TEXT NOT NULL,
+        output_path TEXT,
+        allow_blocked_type INTEGER NOT NULL DEFAULT 0,
+        created_at INTEGER NOT NULL
+    );
+",
+    "
+    CREATE TABLE messages (
+        id TEXT PRIMARY KEY,
+        peer_node_id TEXT NOT NULL,
+        outgoing INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        sent_at INTEGER NOT NULL
+    );
+    CREATE INDEX messages_peer ON messages (peer_node_id, sent_at);
+",
+    "
+    ALTER TABLE transfers ADD COLUMN actions TEXT;
+",
+    "
+    ALTER TABLE transfers ADD COLUMN saved_path TEXT;
+",
+];
+
+const COLUMNS: &str = "id, file_name, file_size, bytes_transferred, status, error, direction, \
+     peer_node_id, started_at, finished_at, ticket_hash, note, pinned, actions, saved_path";
+
+#[derive(Clone, Debug)]
+pub struct HistoryStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl HistoryStore {
+    /// Open (or create) the history database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        // WAL keeps the database intact if the process is killed mid-write
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute_batch(SCHEMA)?;
+        migrate(&conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run a query on the blocking pool so callers don't stall the runtime
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| anyhow::anyhow!("History database lock poisoned"))?;
+            f(&conn)
+        })
+        .await?
+    }
+
+    /// Insert a transfer or update the stored copy
+    pub async fn upsert(&self, transfer: TransferInfo) -> Result<()> {
+        self.run(move |conn| {
+            let now = now_secs();
+            conn.execute(
+                "INSERT INTO transfers (
+                    id, file_name, file_size, bytes_transferred, status, error,
+                    direction, peer_node_id, created_at, updated_at,
+                    started_at, finished_at, ticket_hash, saved_path
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9, ?10, ?11, ?12, ?13)
+                ON CONFLICT(id) DO UPDATE SET
+                    file_name = excluded.file_name,
+                    file_size = excluded.file_size,
+                    bytes_transferred = excluded.bytes_transferred,
+                    status = excluded.status,
+                    error = excluded.error,
+                    peer_node_id = excluded.peer_node_id,
+                    updated_at = excluded.updated_at,
+                    started_at = COALESCE(started_at, excluded.started_at),
+                    finished_at = excluded.finished_at,
+                    ticket_hash = COALESCE(excluded.ticket_hash, ticket_hash),
+                    saved_path = COALESCE(excluded.saved_path, saved_path)",
+                params![
+                    transfer.id,
+                    transfer.file_name,
+                    transfer.file_size as i64,
+                    transfer.bytes_transferred as i64,
+                    enum_to_str(&transfer.status)?,
+                    transfer.error,
+                    enum_to_str(&transfer.direction)?,
+                    transfer.peer_node_id,
+                    now as i64,
+                    transfer.started_at.map(|t| t as i64),
+                    transfer.finished_at.map(|t| t as i64),
+                    transfer.ticket_hash,
+                    transfer.saved_path,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get(&self, id: String) -> Result<Option<TransferInfo>> {
+        self.run(move |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM transfers WHERE id = ?1", COLUMNS),
+                params![id],
+                read_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Most recent transfers first
+    pub async fn recent(&self, limit: u32) -> Result<Vec<TransferInfo>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM transfers ORDER BY created_at DESC, rowid DESC LIMIT ?1",
+                COLUMNS
+            ))?;
+            let rows = stmt.query_map(params![limit], read_row)?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Delete finished transfers created before `before` (unix seconds), or
+    /// all finished transfers when `None`; returns how many were removed
+    pub async fn clear(&self, before: Option<u64>) -> Result<usize> {
+        self.run(move |conn| {
+            let removed = match before {
+                Some(before) => conn.execute(
+                    &format!(
+                        "DELETE FROM transfers WHERE {} AND created_at < ?1",
+                        REMOVABLE
+                    ),
+                    params![before as i64],
+                )?,
+                None => conn.execute(&format!("DELETE FROM transfers WHERE {}", REMOVABLE), [])?,
+            };
+            Ok(removed)
+        })
+        .await
+    }
+
+    /// Delete one transfer and scrub it from the database files; returns false
+    /// if it wasn't stored
+    pub async fn purge(&self, id: String) -> Result<bool> {
+        self.run(move |conn| {
+            // Zero the freed pages instead of leaving the row readable in the file
+            conn.pragma_update(None, "secure_delete", true)?;
+            let removed = conn.execute("DELETE FROM transfers WHERE id = ?1", params![id]);
+            conn.pragma_update(None, "secure_delete", false)?;
+            let removed = removed?;
+            conn.execute(
+                "DELETE FROM file_journal WHERE transfer_id = ?1",
+                params![id],
+            )?;
+            // Older copies of the page stay in the WAL until it is checkpointed
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+            Ok(removed > 0)
+        })
+        .await
+    }
+
+    /// Drop finished transfers beyond the newest `max_entries`; pinned ones don't count
+    pub async fn keep_newest(&self, max_entries: u32) -> Result<usize> {
+        self.run(move |conn| {
+            let removed = conn.execute(
+                &format!(
+                    "DELETE FROM transfers WHERE {} AND id NOT IN (
+                        SELECT id FROM transfers WHERE pinned = 0
+                        ORDER BY created_at DESC, rowid DESC LIMIT ?1
+                    )",
+                    REMOVABLE
+                ),
+                params![max_entries],
+            )?;
+            Ok(removed)
+        })
+        .await
+    }
+
+    /// Apply a retention policy, returning how many transfers were removed
+    pub async fn enforce(&self, retention: HistoryRetention) -> Result<usize> {
+        let mut removed = 0;
+        if let Some(days) = retention.max_age_days {
+            let cutoff = now_secs().saturating_sub(u64::from(days) * 24 * 60 * 60);
+            removed += self.clear(Some(cutoff)).await?;
+        }
+        if let Some(max_entries) = retention.max_entries {
+            removed += self.keep_newest(max_entries).await?;
+        }
+        Ok(removed)
+    }
+
+    /// One page of transfers matching `filter`, plus the total match count
+    pub async fn list(
+        &self,
+        filter: TransferFilter,
+        page: u32,
+        page_size: u32,
+    ) -> Result<TransferPage> {
+        self.run(move |conn| {
+            let mut clauses: Vec<String> = Vec::new();
+            let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+
+            if !filter.status.is_empty() {
+                let placeholders = vec!["?"; filter.status.len()].join(", ");
+                clauses.push(format!("status IN ({})", placeholders));
+                for status in &filter.status {
+                    values.push(Box::new(enum_to_str(status)?));
+                }
+            }
+            if let Some(direction) = &filter.direction {
+                clauses.push("direction = ?".to_string());
+                values.push(Box::new(enum_to_str(direction)?));
+            }
+            if let Some(peer) = &filter.peer_node_id {
+                clauses.push("peer_node_id = ?".to_string());
+                values.push(Box::new(peer.clone()));
+            }
+            if let Some(from) = filter.from {
+                clauses.push("created_at >= ?".to_string());
+                values.push(Box::new(from as i64));
+            }
+            if let Some(to) = filter.to {
+                clauses.push("created_at < ?".to_string());
+                values.push(Box::new(to as i64));
+            }
+
+            let where_clause = if clauses.is_empty() {
+                String::new()
+            } else {
+                format!("WHERE {}", clauses.join(" AND "))
+            };
+            let params: Vec<&dyn ToSql> = values.iter().map(|v| v.as_ref()).collect();
+
+            let total: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM transfers {}", where_clause),
+                params.as_slice(),
+                |row| row.get(0),
+            )?;
+
+            let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+            let offset = i64::from(page) * i64::from(page_size);
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM transfers {} ORDER BY {} LIMIT {} OFFSET {}",
+                COLUMNS,
+                where_clause,
+                filter.sort.order_by(),
+                page_size,
+                offset
+            ))?;
+            let transfers: Vec<TransferInfo> = stmt
+                .query_map(params.as_slice(), read_row)?
+                .collect::<rusqlite::Result<_>>()?;
+
+            Ok(TransferPage {
+                transfers,
+                total: total as u64,
+                page,
+                page_size,
+            })
+        })
+        .await
+    }
+
+    /// Transfers stored as pending or in progress
+    pub async fn unfinished(&self) -> Result<Vec<TransferInfo>> {
+        self.run(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM transfers WHERE NOT ({})",
+                COLUMNS, FINISHED
+            ))?;
+            let rows = stmt.query_map([], read_row)?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Remember a blob we shared and the store tag protecting it
+    pub async fn record_blob(&self, blob: BlobRecord) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO blobs (hash, tag, file_name, size, created_at, last_served_at, pinned)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(hash) DO UPDATE SET
+                    tag = excluded.tag,
+                    file_name = excluded.file_name,
+                    created_at = excluded.created_at",
+                params![
+                    blob.hash,
+                    blob.tag,
+                    blob.file_name,
+                    blob.size as i64,
+                    blob.created_at as i64,
+                    blob.last_served_at.map(|t| t as i64),
+                    blob.pinned,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn list_blobs(&self) -> Result<Vec<BlobRecord>> {
+        self.run(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM blobs ORDER BY created_at DESC",
+                BLOB_COLUMNS
+            ))?;
+            let rows = stmt.query_map([], read_blob_row)?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    pub async fn get_blob(&self, hash: String) -> Result<Option<BlobRecord>> {
+        self.run(move |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM blobs WHERE hash = ?1", BLOB_COLUMNS),
+                params![hash],
+                read_blob_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Returns false if the blob isn't known
+    pub async fn set_blob_pinned(&self, hash: String, pinned: bool) -> Result<bool> {
+        self.run(move |conn| {
+            let updated = conn.execute(
+                "UPDATE blobs SET pinned = ?2 WHERE hash = ?1",
+                params![hash, pinned],
+            )?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    /// Record when blobs were last requested by a peer
+    pub async fn mark_blobs_served(&self, served: Vec<(String, u64)>) -> Result<()> {
+        self.run(move |conn| {
+            for (hash, at) in served {
+                conn.execute(
+                    "UPDATE blobs SET last_served_at = MAX(COALESCE(last_served_at, 0), ?2)
+                     WHERE hash = ?1",
+                    params![hash, at as i64],
+                )?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn delete_blob(&self, hash: String) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM blobs WHERE hash = ?1", params![hash])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Unpinned blobs not served (or, if never served, not created) since `cutoff`
+    pub async fn idle_blobs(&self, cutoff: u64) -> Result<Vec<BlobRecord>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM blobs
+                 WHERE pinned = 0 AND COALESCE(last_served_at, created_at) < ?1",
+                BLOB_COLUMNS
+            ))?;
+            let rows = stmt.query_map(params![cutoff as i64], read_blob_row)?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Unpinned blobs, least recently shared or downloaded first
+    pub async fn evictable_blobs(&self) -> Result<Vec<BlobRecord>> {
+        self.run(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM blobs WHERE pinned = 0
+                 ORDER BY COALESCE(last_served_at, created_at) ASC",
+                BLOB_COLUMNS
+            ))?;
+            let rows = stmt.query_map([], read_blob_row)?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Combined size of all tracked blobs, pinned or not
+    pub async fn blobs_size(&self) -> Result<u64> {
+        self.run(|conn| {
+            let size: i64 =
+                conn.query_row("SELECT COALESCE(SUM(size), 0) FROM blobs", [], |row| {
+                    row.get(0)
+                })?;
+            Ok(size as u64)
+        })
+        .await
+    }
+
+    /// Record that a received file is about to be written to `temp_path`
+    pub async fn journal_begin(&self, entry: JournalEntry) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO file_journal
+                    (transfer_id, temp_path, final_path, written, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    entry.transfer_id,
+                    entry.temp_path,
+                    entry.final_path,
+                    entry.written,
+                    now_secs() as i64,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// The temporary file is complete and synced; only the rename is left
+    pub async fn journal_written(&self, transfer_id: String) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "UPDATE file_journal SET written = 1 WHERE transfer_id = ?1",
+                params![transfer_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// The write is finished (or abandoned) and history reflects it
+    pub async fn journal_end(&self, transfer_id: String) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "DELETE FROM file_journal WHERE transfer_id = ?1",
+                params![transfer_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn journal_entries(&self) -> Result<Vec<JournalEntry>> {
+        self.run(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT transfer_id, temp_path, final_path, written FROM file_journal")?;
+            let rows = stmt.query_map([], |row| {
+                Ok(JournalEntry {
+                    transfer_id: row.get(0)?,
+                    temp_path: row.get(1)?,
+                    final_path: row.get(2)?,
+                    written: row.get(3)?,
+                })
+            })?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Record how a receive was started, until it ends
+    pub async fn resumable_put(&self, receive: ResumableReceive) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO resumable_receives
+                    (transfer_id, ticket, output_path, allow_blocked_type, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    receive.transfer_id,
+                    receive.ticket,
+                    receive.output_path,
+                    receive.allow_blocked_type,
+                    now_secs() as i64,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn resumable_remove(&self, transfer_id: String) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "DELETE FROM resumable_receives WHERE transfer_id = ?1",
+                params![transfer_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn resumable_receives(&self) -> Result<Vec<ResumableReceive>> {
+        self.run(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT transfer_id, ticket, output_path, allow_blocked_type
+                 FROM resumable_receives ORDER BY created_at",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok(ResumableReceive {
+                    transfer_id: row.get(0)?,
+                    ticket: row.get(1)?,
+                    output_path: row.get(2)?,
+                    allow_blocked_type: row.get(3)?,
+                })
+            })?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Store a chat message; a redelivered one is ignored
+    pub async fn save_message(&self, message: ChatMessage) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO messages (id, peer_node_id, outgoing, text, sent_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    message.id,
+                    message.peer_node_id,
+                    message.outgoing,
+                    message.text,
+                    message.sent_at as i64,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Up to `limit` messages with a peer sent before `before`, oldest first
+    pub async fn messages(
+        &self,
+        node_id: String,
+        before: Option<u64>,
+        limit: u32,
+    ) -> Result<Vec<ChatMessage>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, peer_node_id, outgoing, text, sent_at FROM messages
+                 WHERE peer_node_id = ?1 AND sent_at < ?2
+                 ORDER BY sent_at DESC, rowid DESC LIMIT ?3",
+            )?;
+            let before = before.map_or(i64::MAX, |before| before as i64);
+            let rows = stmt.query_map(params![node_id, before, limit], |row| {
+                Ok(ChatMessage {
+                    id: row.get(0)?,
+                    peer_node_id: row.get(1)?,
+                    outgoing: row.get(2)?,
+                    text: row.get(3)?,
+                    sent_at: row.get::<_, i64>(4)? as u64,
+                })
+            })?;
+            let mut messages = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+            messages.reverse();
+            Ok(messages)
+        })
+        .await
+    }
+
+    /// Delete the conversation with a peer, returning how many messages went
+    pub async fn clear_messages(&self, node_id: String) -> Result<usize> {
+        self.run(move |conn| {
+            Ok(conn.execute(
+                "DELETE FROM messages WHERE peer_node_id = ?1",
+                params![node_id],
+            )?)
+        })
+        .await
+    }
+
+    /// Pinned transfers are skipped by `clear` and retention; returns false if not stored
+    pub async fn set_pinned(&self, id: String, pinned: bool) -> Result<bool> {
+        self.run(move |conn| {
+            let updated = conn.execute(
+                "UPDATE transfers SET pinned = ?2 WHERE id = ?1",
+                params![id, pinned],
+            )?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    /// Set or clear a transfer's note; returns false if the transfer isn't stored
+    pub async fn set_note(&self, id: String, note: Option<String>) -> Result<bool> {
+        self.run(move |conn| {
+            let updated = conn.execute(
+                "UPDATE transfers SET note = ?2 WHERE id = ?1",
+                params![id, note],
+            )?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    /// Attach post-receive action results; returns false if the transfer isn't stored
+    pub async fn set_actions(&self, id: String, actions: Vec<ActionResult>) -> Result<bool> {
+        self.run(move |conn| {
+            let updated = conn.execute(
+                "UPDATE transfers SET actions = ?2 WHERE id = ?1",
+                params![id, serde_json::to_string(&actions)?],
+            )?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    /// Update where a received file is; returns false if the transfer isn't stored
+    pub async fn set_saved_path(&self, id: String, path: String) -> Result<bool> {
+        self.run(move |conn| {
+            let updated = conn.execute(
+                "UPDATE transfers SET saved_path = ?2 WHERE id = ?1",
+                params![id, path],
+            )?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    /// Case-insensitive search over file names, notes and peer names, best match first
+    ///
+    /// `peer_names` maps node IDs to the names the user knows them by.
+    pub async fn search(
+        &self,
+        query: String,
+        peer_names: HashMap<String, String>,
+        limit: u32,
+    ) -> Result<Vec<TransferInfo>> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let peer_ids: Vec<String> = peer_names
+            .iter()
+            .filter(|(_, name)| name.to_lowercase().contains(&query))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let pattern = format!(
+            "%{}%",
+            query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let candidates = self
+            .run(move |conn| {
+                let peers = vec!["?"; peer_ids.len()].join(", ");
+                let sql = format!(
+                    "SELECT {} FROM transfers
+                     WHERE lower(file_name) LIKE ?1 ESCAPE '\\'
+                        OR lower(note) LIKE ?1 ESCAPE '\\'
+                        OR peer_node_id IN ({})
+                     ORDER BY created_at DESC, rowid DESC LIMIT {}",
+                    COLUMNS, peers, MAX_SEARCH_CANDIDATES
+                );
+                let mut args: Vec<&dyn ToSql> = vec![&pattern];
+                args.extend(peer_ids.iter().map(|id| id as &dyn ToSql));
+                let mut stmt = conn.prepare(&sql)?;
+                let rows = stmt.query_map(args.as_slice(), read_row)?;
+                rows.collect::<rusqlite::Result<Vec<_>>>()
+                    .map_err(Into::into)
+            })
+            .await?;
+
+        let mut ranked: Vec<_> = candidates
+            .into_iter()
+            .map(|t| (search_score(&t, &query, &peer_names), t))
+            .filter(|(score, _)| *score > 0)
+            .collect();
+        // Stable, so equal scores stay most recent first
+        ranked.sort_by(|a, b| b.0.cmp(&a.0));
+        Ok(ranked
+            .into_iter()
+            .take(limit as usize)
+            .map(|(_, t)| t)
+            .collect())
+    }
+
+    /// Transfers exchanged with a peer, most recent first
+    pub async fn for_peer(&self, node_id: String) -> Result<Vec<TransferInfo>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM transfers WHERE peer_node_id = ?1
+                 ORDER BY created_at DESC, rowid DESC",
+                COLUMNS
+            ))?;
+            let rows = stmt.query_map(params![node_id], read_row)?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+
+    /// Store the JSON-encoded address a peer was last reached at
+    pub async fn save_peer_addr(&self, node_id: String, addr: String) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO peer_addrs (node_id, addr, updated_at)
+                 VALUES (?1, ?2, ?3)",
+                params![node_id, addr, now_secs() as i64],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Addresses saved since `cutoff`; older ones are deleted
+    pub async fn peer_addrs(&self, cutoff: u64) -> Result<Vec<String>> {
+        self.run(move |conn| {
+            conn.execute(
+                "DELETE FROM peer_addrs WHERE updated_at < ?1",
+                params![cutoff as i64],
+            )?;
+            let mut stmt = conn.prepare("SELECT addr FROM peer_addrs")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+        })
+        .await
+    }
+}
+
+/// A received file write in progress
+#[derive(Clone, Debug)]
+pub struct JournalEntry {
+    pub transfer_id: String,
+    pub temp_path: String,
+    pub final_path: String,
+    /// Set once the temporary file is complete and synced
+    pub written: bool,
+}
+
+/// What's needed to start a receive again
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResumableReceive {
+    pub transfer_id: String,
+    pub ticket: String,
+    /// Location asked for, before resolving; none for the default
+    pub output_path: Option<String>,
+    pub allow_blocked_type: bool,
+}
+
+/// A blob we shared, as tracked for garbage collection
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlobRecord {
+    pub hash: String,
+    /// Store tag keeping the blob from being collected
+    pub tag: String,
+    pub file_name: String,
+    pub size: u64,
+    pub created_at: u64,
+    pub last_served_at: Option<u64>,
+    /// Pinned blobs are never collected
+    pub pinned: bool,
+}
+
+impl BlobRecord {
+    pub fn new(hash: String, tag: String, file_name: String, size: u64) -> Self {
+        Self {
+            hash,
+            tag,
+            file_name,
+            size,
+            created_at: now_secs(),
+            last_served_at: None,
+            pinned: false,
+        }
+    }
+}
+
+const BLOB_COLUMNS: &str = "hash, tag, file_name, size, created_at, last_served_at, pinned";
+
+fn read_blob_row(row: &Row) -> rusqlite::Result<BlobRecord> {
+    Ok(BlobRecord {
+        hash: row.get(0)?,
+        tag: row.get(1)?,
+        file_name: row.get(2)?,
+        size: row.get::<_, i64>(3)? as u64,
+        created_at: row.get::<_, i64>(4)? as u64,
+        last_served_at: row.get::<_, Option<i64>>(5)?.map(|t| t as u64),
+        pinned: row.get(6)?,
+    })
+}
+
+/// Criteria for `list_transfers`; unset fields match everything
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferFilter {
+    /// Any of these statuses
+    pub status: Vec<TransferStatus>,
+    pub direction: Option<TransferDirection>,
+    pub peer_node_id: Option<String>,
+    /// Created at or after (unix seconds)
+    pub from: Option<u64>,
+    /// Created before (unix seconds)
+    pub to: Option<u64>,
+    pub sort: TransferSort,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferSort {
+    #[default]
+    Newest,
+    Oldest,
+    Largest,
+    Smallest,
+    Name,
+}
+
+impl TransferSort {
+    fn order_by(self) -> &'static str {
+        match self {
+            Self::Newest => "created_at DESC, rowid DESC",
+            Self::Oldest => "created_at ASC, rowid ASC",
+            Self::Largest => "file_size DESC, created_at DESC",
+            Self::Smallest => "file_size ASC, created_at DESC",
+            Self::Name => "file_name COLLATE NOCASE ASC, created_at DESC",
+        }
+    }
+}
+
+/// How well a transfer matches a lowercased search query; 0 is no match
+fn search_score(transfer: &TransferInfo, query: &str, peer_names: &HashMap<String, String>) -> u32 {
+    fn score(text: &str, query: &str, weight: u32) -> u32 {
+        let text = text.to_lowercase();
+        if text == query {
+            weight * 4
+        } else if text.starts_with(query) {
+            weight * 3
+        } else if text
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| word.starts_with(query))
+        {
+            weight * 2
+        } else if text.contains(query) {
+            weight
+        } else {
+            0
+        }
+    }
+
+    let file_name = score(&transfer.file_name, query, 10);
+    let peer = transfer
+        .peer_node_id
+        .as_ref()
+        .and_then(|id| peer_names.get(id))
+        .map_or(0, |name| score(name, query, 5));
+    let note = transfer
+        .note
+        .as_deref()
+        .map_or(0, |note| score(note, query, 3));
+    file_name.max(peer).max(note)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TransferPage {
+    pub transfers: Vec<TransferInfo>,
+    /// Matches across all pages
+    pub total: u64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+/// How much history to keep; `None` disables that limit
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryRetention {
+    pub max_age_days: Option<u32>,
+    pub max_entries: Option<u32>,
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_age_days: Some(30),
+            max_entries: Some(500),
+        }
+    }
+}
+
+/// Periodically prune history according to the persisted retention setting
+pub fn spawn_retention_task(handle: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut timer = tokio::time::interval(RETENTION_INTERVAL);
+        loop {
+            timer.tick().await;
+
+            let state = handle.state::<AppState>();
+            let retention = state.get_settings().await.history_retention;
+            match state.enforce_history_retention(retention).await {
+                Ok(0) => {}
+                Ok(removed) => info!("Pruned {} transfers from history", removed),
+                Err(e) => warn!("Failed to prune transfer history: {}", e),
+            }
+        }
+    })
+}
+
+fn read_row(row: &Row) -> rusqlite::Result<TransferInfo> {
+    Ok(TransferInfo {
+        id: row.get(0)?,
+        file_name: row.get(1)?,
+        file_size: row.get::<_, i64>(2)? as u64,
+        bytes_transferred: row.get::<_, i64>(3)? as u64,
+        status: enum_column(row, 4)?,
+        error: row.get(5)?,
+        direction: enum_column(row, 6)?,
+        speed_bps: 0,
+        peer_node_id: row.get(7)?,
+        started_at: row.get::<_, Option<i64>>(8)?.map(|t| t as u64),
+        finished_at: row.get::<_, Option<i64>>(9)?.map(|t| t as u64),
+        ticket_hash: row.get(10)?,
+        note: row.get(11)?,
+        pinned: row.get(12)?,
+        actions: json_column(row, 13)?,
+        saved_path: row.get(14)?,
+    })
+}
+
+/// Bring an existing database up to the current schema
+fn migrate(conn: &Connection) -> Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(&format!(
+            "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+            migration,
+            i + 1
+        ))?;
+    }
+    Ok(())
+}
+
+/// A JSON column, empty when NULL
+fn json_column<T: DeserializeOwned + Default>(row: &Row, idx: usize) -> rusqlite::Result<T> {
+    match row.get::<_, Option<String>>(idx)? {
+        Some(value) => serde_json::from_str(&value)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, e.into())),
+        None => Ok(T::default()),
+    }
+}
+
+fn enum_column<T: DeserializeOwned>(row: &Row, idx: usize) -> rusqlite::Result<T> {
+    let value: String = row.get(idx)?;
+    enum_from_str(&value)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, e.into()))
+}
+
+/// Store enums with the same lowercase names the frontend sees
+fn enum_to_str<T: Serialize>(value: &T) -> Result<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(s) => Ok(s),
+        other => Err(anyhow::anyhow!("Expected a string enum, got {}", other)),
+    }
+}
+
+fn enum_from_str<T: DeserializeOwned>(value: &str) -> Result<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_string())).map_err(Into::into)
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(id: &str, status: TransferStatus) -> TransferInfo {
+        TransferInfo {
+            id: id.to_string(),
+            file_name: "photo.jpg".to_string(),
+            file_size: 1024,
+            bytes_transferred: 0,
+            status,
+            error: None,
+            direction: TransferDirection::Receive,
+            speed_bps: 0,
+            peer_node_id: Some("peer".to_string()),
+            started_at: None,
+            finished_at: None,
+            ticket_hash: None,
+            note: None,
+            pinned: false,
+            actions: Vec::new(),
+            saved_path: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upsert_roundtrip() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        let mut pending = transfer("a", TransferStatus::Pending);
+        pending.started_at = Some(100);
+        store.upsert(pending).await.unwrap();
+        let mut completed = transfer("a", TransferStatus::Completed);
+        completed.finished_at = Some(160);
+        completed.ticket_hash = Some("hash".to_string());
+        completed.saved_path = Some("/downloads/a".to_string());
+        store.upsert(completed).await.unwrap();
+
+        let stored = store.get("a".to_string()).await.unwrap().unwrap();
+        assert_eq!(stored.status, TransferStatus::Completed);
+        assert_eq!(stored.started_at, Some(100));
+        assert_eq!(stored.finished_at, Some(160));
+        assert_eq!(stored.ticket_hash.as_deref(), Some("hash"));
+        assert_eq!(stored.saved_path.as_deref(), Some("/downloads/a"));
+        assert_eq!(store.recent(10).await.unwrap().len(), 1);
+        assert_eq!(store.for_peer("peer".to_string()).await.unwrap().len(), 1);
+        assert!(store.get("missing".to_string()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_retention_keeps_active_transfers() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        for id in ["a", "b", "c"] {
+            store
+                .upsert(transfer(id, TransferStatus::Completed))
+                .await
+                .unwrap();
+        }
+        store
+            .upsert(transfer("running", TransferStatus::InProgress))
+            .await
+            .unwrap();
+
+        assert_eq!(store.keep_newest(2).await.unwrap(), 2);
+        assert_eq!(store.clear(None).await.unwrap(), 1);
+
+        let remaining = store.recent(10).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, "running");
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_skips_pinned_transfers() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        for id in ["a", "b", "c"] {
+            store
+                .upsert(transfer(id, TransferStatus::Completed))
+                .await
+                .unwrap();
+        }
+        assert!(store.set_pinned("a".to_string(), true).await.unwrap());
+        assert!(!store.set_pinned("missing".to_string(), true).await.unwrap());
+
+        assert_eq!(store.keep_newest(1).await.unwrap(), 1);
+        assert_eq!(store.clear(None).await.unwrap(), 1);
+
+        let remaining = store.recent(10).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert!(remaining[0].pinned);
+    }
+
+    #[tokio::test]
+    async fn test_idle_blobs_skip_pinned_and_served() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        for hash in ["a", "b", "c"] {
+            let mut blob = BlobRecord::new(hash.into(), format!("tag-{}", hash), "f".into(), 1);
+            blob.created_at = 100;
+            store.record_blob(blob).await.unwrap();
+        }
+        store.set_blob_pinned("b".into(), true).await.unwrap();
+        store
+            .mark_blobs_served(vec![("c".into(), 500)])
+            .await
+            .unwrap();
+
+        let idle = store.idle_blobs(200).await.unwrap();
+        assert_eq!(idle.len(), 1);
+        assert_eq!(idle[0].hash, "a");
+    }
+
+    #[tokio::test]
+    async fn test_journal_tracks_written_stage() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        store
+            .journal_begin(JournalEntry {
+                transfer_id: "a".to_string(),
+                temp_path: "/tmp/a.part".to_string(),
+                final_path: "/tmp/a".to_string(),
+                written: false,
+            })
+            .await
+            .unwrap();
+        store.journal_written("a".to_string()).await.unwrap();
+
+        let entries = store.journal_entries().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].written);
+
+        store.journal_end("a".to_string()).await.unwrap();
+        assert!(store.journal_entries().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resumable_receives() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        let receive = ResumableReceive {
+            transfer_id: "a".to_string(),
+            ticket: "vegam://node:data".to_string(),
+            output_path: Some("~/Videos".to_string()),
+            allow_blocked_type: false,
+        };
+        store.resumable_put(receive.clone()).await.unwrap();
+        assert_eq!(store.resumable_receives().await.unwrap(), vec![receive]);
+
+        store.resumable_remove("a".to_string()).await.unwrap();
+        assert!(store.resumable_receives().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_messages() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        let message = |id: &str, peer: &str, sent_at: u64| ChatMessage {
+            id: id.to_string(),
+            peer_node_id: peer.to_string(),
+            outgoing: id == "b",
+            text: format!("message {}", id),
+            sent_at,
+        };
+        store.save_message(message("a", "p1", 10)).await.unwrap();
+        store.save_message(message("b", "p1", 20)).await.unwrap();
+        store.save_message(message("c", "p1", 30)).await.unwrap();
+        store.save_message(message("d", "p2", 15)).await.unwrap();
+        // Redelivery keeps the first copy
+        store.save_message(message("a", "p1", 99)).await.unwrap();
+
+        let ids =
+            |messages: Vec<ChatMessage>| messages.into_iter().map(|m| m.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(store.messages("p1".into(), None, 50).await.unwrap()),
+            ["a", "b", "c"]
+        );
+        assert_eq!(
+            ids(store.messages("p1".into(), None, 2).await.unwrap()),
+            ["b", "c"]
+        );
+        assert_eq!(
+            ids(store.messages("p1".into(), Some(30), 50).await.unwrap()),
+            ["a", "b"]
+        );
+
+        assert_eq!(store.clear_messages("p1".into()).await.unwrap(), 3);
+        assert!(store
+            .messages("p1".into(), None, 50)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            store.messages("p2".into(), None, 50).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_peer_addrs_expire() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        store.save_peer_addr("a".into(), "{}".into()).await.unwrap();
+        assert_eq!(store.peer_addrs(0).await.unwrap(), vec!["{}".to_string()]);
+        assert!(store.peer_addrs(now_secs() + 1).await.unwrap().is_empty());
+        assert!(store.peer_addrs(0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_ranks_matches() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        for (id, name) in [
+            ("a", "holiday_photo.jpg"),
+            ("b", "photo.jpg"),
+            ("c", "report.pdf"),
+        ] {
+            let mut t = transfer(id, TransferStatus::Completed);
+            t.file_name = name.to_string();
+            store.upsert(t).await.unwrap();
+        }
+        store
+            .set_note("c".to_string(), Some("Photos from Sam".to_string()))
+            .await
+            .unwrap();
+        let peers = HashMap::from([("peer".to_string(), "Pixel".to_string())]);
+
+        let ids = |results: Vec<TransferInfo>| -> Vec<String> {
+            results.into_iter().map(|t| t.id).collect()
+        };
+        let results = store
+            .search("PHOTO".to_string(), peers.clone(), 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(results), ["b", "a", "c"]);
+
+        // Every transfer is from "peer", known as Pixel
+        let results = store.search("pix".to_string(), peers, 10).await.unwrap();
+        assert_eq!(results.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_list_filters_and_pages() {
+        let store = HistoryStore::open_in_memory().unwrap();
+        for (id, status) in [
+            ("a", TransferStatus::Completed),
+            ("b", TransferStatus::Failed),
+            ("c", TransferStatus::Completed),
+        ] {
+            store.upsert(transfer(id, status)).await.unwrap();
+        }
+
+        let filter = TransferFilter {
+            status: vec![TransferStatus::Completed],
+            ..Default::default()
+        };
+        let page = store.list(filter.clone(), 0, 1).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.transfers[0].id, "c");
+
+        let page = store.list(filter, 1, 1).await.unwrap();
+        assert_eq!(page.transfers[0].id, "a");
+
+        let filter = TransferFilter {
+            direction: Some(TransferDirection::Send),
+            ..Default::default()
+        };
+        assert_eq!(store.list(filter, 0, 10).await.unwrap().total, 0);
+    }
+}
@@ -0,0 +1,375 @@
+// Browser download links
+//
+// `send_file` with `http_link` also registers the shared blob with a small
+// embedded HTTP server, so someone without the app can download the file from
+// any browser on the same network. Each link carries a random token and
+// expires after `LINK_TTL`; the server only answers `GET`/`HEAD
+// /download/<token>` and decrypts encrypted blobs while streaming them. It is
+// plain HTTP on a LAN address: reaching it from the internet needs a port
+// forward, there is no public gateway or TLS.
+//
+// The server listens on all interfaces unless `http_share_bind` names one of
+// our addresses (e.g. the LAN interface), which then also goes in the URLs.
+// A browser can't prove who it is, so while `trusted_only_provider` is on no
+// links are created and existing ones answer 403.
+
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use iroh_blobs::Hash;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn};
+
+use crate::history::now_secs;
+use crate::iroh::at_rest::BlobKey;
+use crate::state::AppState;
+
+/// How long a link works
+const LINK_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Largest request head we read before giving up
+const MAX_HEAD_SIZE: usize = 8 * 1024;
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+const DOWNLOAD_PREFIX: &str = "/download/";
+
+#[derive(Clone, Debug)]
+struct HttpLink {
+    hash: Hash,
+    key: Option<BlobKey>,
+    file_name: String,
+    file_size: u64,
+    expires_at: u64,
+}
+
+/// A live download link, as shown to the user
+#[derive(Clone, Debug, Serialize)]
+pub struct HttpLinkInfo {
+    pub token: String,
+    pub url: String,
+    pub file_name: String,
+    pub file_size: u64,
+    pub expires_at: u64,
+}
+
+/// The running server
+struct Server {
+    /// Address it listens on; all interfaces if `None`
+    bind: Option<IpAddr>,
+    port: u16,
+    task: JoinHandle<()>,
+}
+
+impl Server {
+    /// Address put in links
+    fn host(&self) -> Option<IpAddr> {
+        self.bind.or_else(lan_address)
+    }
+}
+
+/// Registered links and the server answering them
+#[derive(Clone, Default)]
+pub struct HttpShare {
+    links: Arc<RwLock<HashMap<String, HttpLink>>>,
+    /// Started on first use
+    server: Arc<Mutex<Option<Server>>>,
+}
+
+impl HttpShare {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make a blob downloadable over HTTP and return its link
+    pub async fn share(
+        &self,
+        handle: &AppHandle,
+        hash: Hash,
+        key: Option<BlobKey>,
+        file_name: String,
+        file_size: u64,
+    ) -> Result<HttpLinkInfo> {
+        let settings = handle.state::<AppState>().get_settings().await;
+        if settings.trusted_only_provider {
+            anyhow::bail!("Download links are off while only trusted peers may download");
+        }
+        let (host, port) = self.ensure_server(handle, settings.http_share_bind).await?;
+        let host = host.ok_or_else(|| anyhow::anyhow!("No local network address to share from"))?;
+
+        let mut token = [0u8; 16];
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut token);
+        let token = URL_SAFE_NO_PAD.encode(token);
+        let link = HttpLink {
+            hash,
+            key,
+            file_name,
+            file_size,
+            expires_at: now_secs() + LINK_TTL.as_secs(),
+        };
+        let info = link_info(&token, &link, host, port);
+        self.links.write().await.insert(token, link);
+        Ok(info)
+    }
+
+    /// Links that haven't expired
+    pub async fn list(&self) -> Vec<HttpLinkInfo> {
+        let Some((host, port)) = self
+            .server
+            .lock()
+            .await
+            .as_ref()
+            .and_then(|server| Some((server.host()?, server.port)))
+        else {
+            return Vec::new();
+        };
+        let now = now_secs();
+        let mut links = self.links.write().await;
+        links.retain(|_, link| link.expires_at > now);
+        links
+            .iter()
+            .map(|(token, link)| link_info(token, link, host, port))
+            .collect()
+    }
+
+    /// Stop serving a link; false if it didn't exist
+    pub async fn revoke(&self, token: &str) -> bool {
+        self.links.write().await.remove(token).is_some()
+    }
+
+    async fn lookup(&self, token: &str) -> Option<HttpLink> {
+        let links = self.links.read().await;
+        links
+            .get(token)
+            .filter(|link| link.expires_at > now_secs())
+            .cloned()
+    }
+
+    /// Listen on `bind` from now on; a running server is restarted there
+    pub async fn set_bind(&self, handle: &AppHandle, bind: Option<IpAddr>) -> Result<()> {
+        let running = self.server.lock().await.is_some();
+        if running {
+            self.ensure_server(handle, bind).await?;
+        } else if let Some(ip) = bind {
+            // Fail now rather than on the next share if it isn't one of ours
+            TcpListener::bind(SocketAddr::new(ip, 0)).await?;
+        }
+        Ok(())
+    }
+
+    /// Start the server on `bind` if it isn't already listening there; returns the
+    /// host for links and the port
+    async fn ensure_server(
+        &self,
+        handle: &AppHandle,
+        bind: Option<IpAddr>,
+    ) -> Result<(Option<IpAddr>, u16)> {
+        let mut server = self.server.lock().await;
+        if let Some(running) = server.as_ref().filter(|s| s.bind == bind) {
+            return Ok((running.host(), running.port));
+        }
+
+        let addr = SocketAddr::new(bind.unwrap_or(IpAddr::from([0, 0, 0, 0])), 0);
+        let listener = TcpListener::bind(addr).await?;
+        let bound = listener.local_addr()?;
+        info!("HTTP share server listening on {}", bound);
+        if let Some(old) = server.take() {
+            old.task.abort();
+        }
+
+        let share = self.clone();
+        let handle = handle.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let (stream, remote) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        warn!("HTTP share accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let share = share.clone();
+                let handle = handle.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = share.serve(stream, &handle).await {
+                        warn!("HTTP share request from {} failed: {}", remote, e);
+                    }
+                });
+            }
+        });
+
+        let running = server.insert(Server {
+            bind,
+            port: bound.port(),
+            task,
+        });
+        Ok((running.host(), running.port))
+    }
+
+    async fn serve(&self, mut stream: TcpStream, handle: &AppHandle) -> Result<()> {
+        let head = timeout(HEAD_TIMEOUT, read_head(&mut stream))
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out reading request"))??;
+
+        let Some((method, path)) = parse_request_line(&head) else {
+            return respond(&mut stream, "400 Bad Request").await;
+        };
+        if method != "GET" && method != "HEAD" {
+            return respond(&mut stream, "405 Method Not Allowed").await;
+        }
+        let Some(token) = download_token(path) else {
+            return respond(&mut stream, "404 Not Found").await;
+        };
+        let Some(link) = self.lookup(token).await else {
+            return respond(&mut stream, "404 Not Found").await;
+        };
+        let state = handle.state::<AppState>();
+        if state.get_settings().await.trusted_only_provider {
+            return respond(&mut stream, "403 Forbidden").await;
+        }
+        let Ok(iroh) = state.get_iroh().await else {
+            return respond(&mut stream, "503 Service Unavailable").await;
+        };
+        if !iroh.blobs.has(link.hash).await.unwrap_or(false) {
+            return respond(&mut stream, "410 Gone").await;
+        }
+
+        let head = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/octet-stream\r\n\
+             Content-Length: {}\r\n\
+             Content-Disposition: {}\r\n\
+             Cache-Control: no-store\r\n\
+             Connection: close\r\n\r\n",
+            link.file_size,
+            content_disposition(&link.file_name)
+        );
+        stream.write_all(head.as_bytes()).await?;
+        if method == "GET" {
+            info!("Serving {} over HTTP", link.file_name);
+            crate::iroh::transfer::export_blob(&iroh, link.hash, link.key.as_ref(), &mut stream)
+                .await?;
+        }
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+fn link_info(token: &str, link: &HttpLink, host: IpAddr, port: u16) -> HttpLinkInfo {
+    HttpLinkInfo {
+        token: token.to_string(),
+        url: format!(
+            "http://{}{}{}",
+            SocketAddr::new(host, port),
+            DOWNLOAD_PREFIX,
+            token
+        ),
+        file_name: link.file_name.clone(),
+        file_size: link.file_size,
+        expires_at: link.expires_at,
+    }
+}
+
+/// Address other devices on the LAN can reach us at, IPv4 first
+fn lan_address() -> Option<IpAddr> {
+    let (ipv4, ipv6) = crate::iroh::network::local_routes();
+    ipv4.or(ipv6)
+}
+
+async fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(anyhow::anyhow!("Request head too large"));
+        }
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+async fn respond(stream: &mut TcpStream, status: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Method and path of an HTTP/1.x request
+fn parse_request_line(head: &str) -> Option<(&str, &str)> {
+    let mut parts = head.lines().next()?.split(' ');
+    let method = parts.next()?;
+    let path = parts.next()?;
+    parts
+        .next()
+        .filter(|version| version.starts_with("HTTP/1."))?;
+    Some((method, path))
+}
+
+fn download_token(path: &str) -> Option<&str> {
+    let path = path.split('?').next()?;
+    path.strip_prefix(DOWNLOAD_PREFIX)
+        .filter(|token| !token.is_empty() && !token.contains('/'))
+}
+
+/// Attachment header with an ASCII fallback name and the UTF-8 original
+fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| {
+            if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_parsing() {
+        let head = "GET /download/abc?x=1 HTTP/1.1\r\nHost: a\r\n\r\n";
+        let (method, path) = parse_request_line(head).unwrap();
+        assert_eq!(method, "GET");
+        assert_eq!(download_token(path), Some("abc"));
+        assert!(parse_request_line("GET /download/abc\r\n\r\n").is_none());
+        assert_eq!(download_token("/download/"), None);
+        assert_eq!(download_token("/download/a/b"), None);
+        assert_eq!(download_token("/other/abc"), None);
+
+        assert_eq!(
+            content_disposition("naïve \"x\".txt"),
+            "attachment; filename=\"na_ve _x_.txt\"; filename*=UTF-8''na%C3%AFve%20%22x%22.txt"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+// Connection access control for the blob provider
+//
+// The provider asks us about every incoming connection; peers on the blocklist
+// are refused before any data is served. In trusted-only mode every node not
+// in the trust store is refused too, even if it holds a ticket. Accepted
+// connections count as activity and keep the peer from being dropped as stale,
+// and are counted while open so uploads can keep the app alive in the
+// background. Get requests are noted so idle blobs can be garbage collected.
+// Connections and requests also go to the audit log. Outgoing data is paced by
+// the global upload cap through throttle events.
+
+use iroh_base::EndpointId;
+use iroh_blobs::provider::events::{
+    AbortReason, ConnectMode, EventMask, EventSender, ProviderMessage, RequestMode, ThrottleMode,
+};
+use iroh_blobs::Hash;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::audit::{AuditEntry, AuditKind, AuditLog};
+use crate::bandwidth::RateLimiter;
+use crate::history::now_secs;
+use crate::settings::Settings;
+use crate::state::{touch_peer, PeerInfo};
+
+#[derive(Debug, Default)]
+pub struct AccessPolicy {
+    pub blocked: HashSet<EndpointId>,
+    pub trusted: HashSet<EndpointId>,
+    /// Only serve trusted peers
+    pub trusted_only: bool,
+}
+
+impl AccessPolicy {
+    /// Build a policy from the persisted blocklist and trust store
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            blocked: parse_node_ids(&settings.blocked_peers),
+            trusted: parse_node_ids(settings.trusted_peers.keys()),
+            trusted_only: settings.trusted_only_provider,
+        }
+    }
+
+    pub fn allows(&self, endpoint_id: &EndpointId) -> bool {
+        !self.blocked.contains(endpoint_id)
+            && (!self.trusted_only || self.trusted.contains(endpoint_id))
+    }
+}
+
+/// Parse persisted node ID strings, skipping invalid entries
+fn parse_node_ids<'a>(ids: impl IntoIterator<Item = &'a String>) -> HashSet<EndpointId> {
+    ids.into_iter()
+        .filter_map(|id| match id.parse() {
+            Ok(id) => Some(id),
+            Err(e) => {
+                warn!("Ignoring invalid node ID {}: {}", id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Create a provider event sender that intercepts incoming connections
+/// and rejects those not allowed by the policy
+pub fn provider_events(
+    policy: Arc<RwLock<AccessPolicy>>,
+    peers: Arc<RwLock<HashMap<String, PeerInfo>>>,
+    served: Arc<RwLock<HashMap<Hash, u64>>>,
+    audit: AuditLog,
+    upload_limit: RateLimiter,
+    serving: Arc<AtomicUsize>,
+) -> EventSender {
+    let mask = EventMask {
+        connected: ConnectMode::Intercept,
+        get: RequestMode::Intercept,
+        throttle: ThrottleMode::Intercept,
+        ..EventMask::DEFAULT
+    };
+    let (tx, mut rx) = EventSender::channel(32, mask);
+
+    tokio::spawn(async move {
+        // Requests only carry the connection ID; remember who each one is
+        let mut connections: HashMap<u64, EndpointId> = HashMap::new();
+
+        while let Some(msg) = rx.recv().await {
+            match msg {
+                ProviderMessage::ClientConnected(msg) => {
+                    let res = match msg.endpoint_id {
+                        Some(endpoint_id) => {
+                            if policy.read().await.allows(&endpoint_id) {
+                                touch_peer(&peers, &endpoint_id.to_string()).await;
+                                connections.insert(msg.connection_id, endpoint_id);
+                                serving.fetch_add(1, Ordering::Relaxed);
+                                Ok(())
+                            } else {
+                                info!(
+                                    "Refusing blob connection from blocked or untrusted peer: {}",
+                                    endpoint_id
+                                );
+                                Err(AbortReason::Permission)
+                            }
+                        }
+                        None => {
+                            warn!("Refusing blob connection without endpoint ID");
+                            Err(AbortReason::Permission)
+                        }
+                    };
+                    let kind = if res.is_ok() {
+                        AuditKind::Connected
+                    } else {
+                        AuditKind::Refused
+                    };
+                    let node_id = msg.endpoint_id.map(|id| id.to_string());
+                    msg.tx.send(res).await.ok();
+                    audit.record(AuditEntry::new(kind, node_id)).await;
+                }
+                ProviderMessage::ConnectionClosed(msg) => {
+                    if connections.remove(&msg.connection_id).is_some() {
+                        serving.fetch_sub(1, Ordering::Relaxed);
+                    }
+                }
+                ProviderMessage::GetRequestReceived(msg) => {
+                    served.write().await.insert(msg.request.hash, now_secs());
+                    msg.tx.send(Ok(())).await.ok();
+                    let node_id = connections.get(&msg.connection_id).map(|id| id.to_string());
+                    audit
+                        .blob_requested(node_id, msg.request.hash.to_string())
+                        .await;
+                }
+                ProviderMessage::Throttle(msg) => {
+                    // Wait off the event loop so other requests aren't held up
+                    let limiter = upload_limit.clone();
+                    tokio::spawn(async move {
+                        limiter.acquire(msg.size).await;
+                        msg.tx.send(Ok(())).await.ok();
+                    });
+                }
+                _ => {}
+            }
+        }
+    });
+
+    tx
+}
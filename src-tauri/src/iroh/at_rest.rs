@@ -0,0 +1,74 @@
+// Blob store encryption at rest
+//
+// With `encrypt_blob_store` on, files are encrypted before they enter the blob
+// store, so neither the store on disk nor partial downloads of those blobs
+// hold plaintext. Each blob has its own key, derived from a store key kept in
+// the system keystore and a random salt. Blobs are sealed chunk by chunk in
+// the end-to-end format (`e2e.rs`) so large files are never buffered. The blob
+// key travels inside the ticket and the receiver decrypts only when writing
+// the finished file, so its store holds ciphertext too. Blob hashes cover the
+// ciphertext, so downloads are verified as usual.
+
+use aes_gcm::aead::OsRng;
+use anyhow::Result;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::iroh::{identity, keystore};
+
+/// Key for a single encrypted blob
+pub type BlobKey = [u8; 32];
+
+/// Random bytes mixed into each blob key
+const SALT_LEN: usize = 16;
+
+/// Load the store key for `data_dir`, creating it on first use
+///
+/// Fails without a system keystore: a key stored next to the blobs would not
+/// protect them.
+pub async fn store_key(data_dir: &Path) -> Result<[u8; 32]> {
+    let account = keystore::account("blob-store-key", data_dir);
+    tokio::task::spawn_blocking(move || {
+        let unavailable = |e: anyhow::Error| {
+            anyhow::anyhow!("Encrypting the blob store needs a system keystore: {}", e)
+        };
+        if let Some(key) = keystore::load(&account).map_err(unavailable)? {
+            return Ok(key);
+        }
+        let key = identity::random_key();
+        keystore::store(&account, &key).map_err(unavailable)?;
+        Ok(key)
+    })
+    .await?
+}
+
+/// Key for a new blob, derived from the store key and a random salt
+pub fn new_blob_key(store_key: &[u8; 32]) -> BlobKey {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    blob_key(store_key, &salt)
+}
+
+fn blob_key(store_key: &[u8; 32], salt: &[u8]) -> BlobKey {
+    let mut hasher = Sha256::new();
+    hasher.update(b"vegam-blob-key-");
+    hasher.update(store_key);
+    hasher.update(salt);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_keys_are_unique() {
+        let store_key = [7u8; 32];
+        let salt = [1u8; SALT_LEN];
+        assert_eq!(blob_key(&store_key, &salt), blob_key(&store_key, &salt));
+        assert_ne!(blob_key(&store_key, &salt), blob_key(&[8u8; 32], &salt));
+        // Each blob gets its own key
+        assert_ne!(new_blob_key(&store_key), new_blob_key(&store_key));
+    }
+}
@@ -0,0 +1,249 @@
+// Bluetooth LE proximity discovery (behind the `ble` cargo feature)
+//
+// Nearby devices expose their node ID in a GATT characteristic under the vegam
+// service UUID. We scan for that service, read the node ID, add the device to
+// the peer list and dial it so an iroh connection is established as soon as
+// any link (LAN, relay) exists. Each device is read in its own task with a
+// timeout, so one that hangs mid-connect doesn't hold up the others.
+//
+// btleplug only supports the central role. On Android the peripheral side is
+// `BleAdvertiser.kt` (advertises the service and serves our node ID), started
+// and stopped with the scan. Desktop builds only scan: they find phones but
+// aren't found themselves. btleplug's Android backend needs its Java runtime,
+// which the app doesn't bundle, so a phone that can't scan keeps advertising.
+
+use anyhow::Result;
+use tauri::AppHandle;
+use uuid::Uuid;
+
+/// GATT service advertised by vegam devices
+#[allow(dead_code)] // Only read by the scanner when the `ble` feature is on
+pub const SERVICE_UUID: Uuid = Uuid::from_u128(0x7665_6761_6d00_4000_8000_00805f9b34fb);
+
+/// Characteristic holding the 32-byte node ID
+#[allow(dead_code)]
+pub const NODE_ID_CHAR_UUID: Uuid = Uuid::from_u128(0x7665_6761_6d01_4000_8000_00805f9b34fb);
+
+/// Group ID recorded on peers found over BLE
+#[allow(dead_code)]
+pub const BLE_GROUP: &str = "ble";
+
+#[cfg(feature = "ble")]
+mod scanner {
+    use super::*;
+    use btleplug::api::{Central, CentralEvent, Manager as _, Peripheral as _, ScanFilter};
+    use btleplug::platform::{Adapter, Manager};
+    use iroh_base::EndpointId;
+    use n0_future::StreamExt;
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tauri::Manager as _;
+    use tokio::task::JoinHandle;
+    use tracing::{info, warn};
+
+    use crate::events::AppEvent;
+    use crate::state::{AppState, PeerInfo};
+
+    /// Connecting to a device and reading its node ID gives up after this
+    const READ_TIMEOUT: Duration = Duration::from_secs(15);
+
+    pub async fn spawn(handle: AppHandle) -> Result<JoinHandle<()>> {
+        let manager = Manager::new().await?;
+        let central = manager
+            .adapters()
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No Bluetooth adapter found"))?;
+
+        central
+            .start_scan(ScanFilter {
+                services: vec![SERVICE_UUID],
+            })
+            .await?;
+        info!("BLE scan started");
+
+        Ok(tokio::spawn(async move {
+            let mut events = match central.events().await {
+                Ok(events) => events,
+                Err(e) => {
+                    warn!("Failed to get BLE events: {}", e);
+                    return;
+                }
+            };
+
+            // Devices being read, so repeated sightings don't open a second connection
+            let reading = Arc::new(Mutex::new(HashSet::new()));
+            while let Some(event) = events.next().await {
+                let CentralEvent::DeviceDiscovered(id) = event else {
+                    continue;
+                };
+                if !reading.lock().unwrap().insert(id.clone()) {
+                    continue;
+                }
+                let central = central.clone();
+                let handle = handle.clone();
+                let reading = reading.clone();
+                tokio::spawn(async move {
+                    let read = handle_device(&central, &id, &handle);
+                    match tokio::time::timeout(READ_TIMEOUT, read).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => warn!("Failed to read node ID over BLE: {}", e),
+                        Err(_) => warn!("Timed out reading node ID over BLE"),
+                    }
+                    reading.lock().unwrap().remove(&id);
+                });
+            }
+        }))
+    }
+
+    async fn handle_device(
+        central: &Adapter,
+        id: &btleplug::platform::PeripheralId,
+        handle: &AppHandle,
+    ) -> Result<()> {
+        let peripheral = central.peripheral(id).await?;
+        let device_name = peripheral
+            .properties()
+            .await?
+            .and_then(|p| p.local_name)
+            .unwrap_or_else(|| "Nearby device".to_string());
+
+        peripheral.connect().await?;
+        peripheral.discover_services().await?;
+        let characteristic = peripheral
+            .characteristics()
+            .into_iter()
+            .find(|c| c.uuid == NODE_ID_CHAR_UUID)
+            .ok_or_else(|| anyhow::anyhow!("Device has no node ID characteristic"))?;
+        let value = peripheral.read(&characteristic).await?;
+        let _ = peripheral.disconnect().await;
+
+        let bytes: [u8; 32] = value
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid node ID length"))?;
+        let endpoint_id = EndpointId::from_bytes(&bytes)?;
+        let node_id = endpoint_id.to_string();
+
+        let state = handle.state::<AppState>();
+        if state.is_blocked(&node_id).await {
+            return Ok(());
+        }
+        info!("Found nearby peer over BLE: {} ({})", device_name, node_id);
+
+        let mut peer = state
+            .peers
+            .read()
+            .await
+            .get(&node_id)
+            .cloned()
+            .unwrap_or_else(|| PeerInfo {
+                node_id: node_id.clone(),
+                device_name,
+                color: crate::iroh::discovery::default_color(&node_id),
+                ..Default::default()
+            });
+        peer.last_seen = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        if !peer.groups.iter().any(|g| g == BLE_GROUP) {
+            peer.groups.push(BLE_GROUP.to_string());
+        }
+        state.get_settings().await.apply_to_peer(&mut peer);
+        state.add_peer(peer.clone()).await;
+
+        AppEvent::PeerDiscovered(peer.clone()).emit(handle)?;
+        AppEvent::PeerListUpdated(state.get_peers().await).emit(handle)?;
+
+        // Dial so the connection is ready once any network path exists
+        if let Ok(iroh) = state.get_iroh().await {
+            tokio::spawn(async move {
+                if let Err(e) = crate::iroh::ping::ping(&iroh.endpoint, endpoint_id).await {
+                    info!("BLE peer not reachable over the network yet: {}", e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Calls into `codes.imran.vegam.BleAdvertiser`
+#[cfg(all(feature = "ble", target_os = "android"))]
+mod advertiser {
+    use anyhow::Result;
+    use jni::objects::JValue;
+
+    use crate::platform::with_java_class;
+
+    const CLASS: &str = "codes.imran.vegam.BleAdvertiser";
+
+    pub fn start(node_id: &[u8; 32]) -> Result<()> {
+        with_java_class(CLASS, |env, class, context| {
+            let node_id = env.byte_array_from_slice(node_id)?;
+            env.call_static_method(
+                class,
+                "start",
+                "(Landroid/content/Context;[B)V",
+                &[JValue::Object(context), JValue::Object(&node_id)],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn stop() -> Result<()> {
+        with_java_class(CLASS, |env, class, context| {
+            env.call_static_method(
+                class,
+                "stop",
+                "(Landroid/content/Context;)V",
+                &[JValue::Object(context)],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+/// Start scanning for nearby vegam devices, and on Android advertise ourselves
+#[cfg(feature = "ble")]
+pub async fn start(handle: AppHandle) -> Result<tokio::task::JoinHandle<()>> {
+    #[cfg(target_os = "android")]
+    {
+        use tauri::Manager as _;
+        let state = handle.state::<crate::state::AppState>();
+        let node_id = *state.get_iroh().await?.endpoint.id().as_bytes();
+        advertiser::start(&node_id)?;
+        tracing::info!("BLE advertising started");
+    }
+    scanner::spawn(handle).await.or_else(scan_unavailable)
+}
+
+/// A phone that can't scan still advertises, so this stands in for the scan
+/// task that `stop_ble_discovery` ends
+#[cfg(all(feature = "ble", target_os = "android"))]
+fn scan_unavailable(e: anyhow::Error) -> Result<tokio::task::JoinHandle<()>> {
+    tracing::warn!("BLE scan unavailable, only advertising: {}", e);
+    Ok(tokio::spawn(std::future::pending()))
+}
+
+#[cfg(all(feature = "ble", not(target_os = "android")))]
+fn scan_unavailable(e: anyhow::Error) -> Result<tokio::task::JoinHandle<()>> {
+    Err(e)
+}
+
+#[cfg(not(feature = "ble"))]
+pub async fn start(_handle: AppHandle) -> Result<tokio::task::JoinHandle<()>> {
+    Err(anyhow::anyhow!(
+        "BLE discovery is not enabled in this build (enable the `ble` feature)"
+    ))
+}
+
+/// Stop advertising; the scan ends with its task
+pub fn stop() {
+    #[cfg(all(feature = "ble", target_os = "android"))]
+    if let Err(e) = advertiser::stop() {
+        tracing::warn!("Failed to stop BLE advertising: {}", e);
+    }
+}
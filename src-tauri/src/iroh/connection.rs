@@ -0,0 +1,203 @@
+// Connection path monitoring
+//
+// Periodically asks the endpoint how each discovered peer is reached (direct
+// UDP, relay, or both) and records it on PeerInfo so the UI can show it.
+// Peers of running transfers are watched too, even when they were never
+// discovered, so a transfer can be shown as "direct" or "relayed".
+
+use iroh::endpoint::ConnectionType;
+use iroh::Watcher;
+use iroh_base::{EndpointAddr, EndpointId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration};
+use tracing::info;
+
+use crate::events::AppEvent;
+use crate::iroh::Iroh;
+use crate::state::{AppState, TransferStatus};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerConnectionType {
+    /// No connection to the peer right now
+    #[default]
+    None,
+    Direct,
+    Relay,
+    /// Relay with a direct path being established alongside it
+    Mixed,
+}
+
+impl From<ConnectionType> for PeerConnectionType {
+    fn from(conn_type: ConnectionType) -> Self {
+        match conn_type {
+            ConnectionType::Direct(_) => Self::Direct,
+            ConnectionType::Relay(_) => Self::Relay,
+            ConnectionType::Mixed(_, _) => Self::Mixed,
+            ConnectionType::None => Self::None,
+        }
+    }
+}
+
+/// Payload of `peer-connection-upgraded`
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionUpgrade {
+    pub node_id: String,
+    pub from: PeerConnectionType,
+}
+
+/// Payload of `peer-connection-changed`
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionChange {
+    pub node_id: String,
+    pub from: PeerConnectionType,
+    pub to: PeerConnectionType,
+    /// Running transfers with this peer
+    pub transfer_ids: Vec<String>,
+}
+
+/// Current path type to a peer, as known by the endpoint
+pub fn connection_type(iroh: &Iroh, endpoint_id: EndpointId) -> PeerConnectionType {
+    iroh.endpoint
+        .conn_type(endpoint_id)
+        .map(|mut watcher| watcher.get().into())
+        .unwrap_or_default()
+}
+
+/// Address the endpoint currently reaches a peer at, if connected
+pub fn current_addr(iroh: &Iroh, endpoint_id: EndpointId) -> Option<EndpointAddr> {
+    let addr = EndpointAddr::new(endpoint_id);
+    match iroh.endpoint.conn_type(endpoint_id)?.get() {
+        ConnectionType::Direct(ip) => Some(addr.with_ip_addr(ip)),
+        ConnectionType::Relay(url) => Some(addr.with_relay_url(url)),
+        ConnectionType::Mixed(ip, url) => Some(addr.with_ip_addr(ip).with_relay_url(url)),
+        ConnectionType::None => None,
+    }
+}
+
+/// Spawn a task refreshing `PeerInfo::connection_type` for discovered peers
+///
+/// Emits `peer-connection-changed` whenever the path to a discovered peer or
+/// a transfer peer changes, and `peer-connection-upgraded` when it becomes
+/// direct. The address transfer peers are reached at is saved to history so
+/// the next session can dial them straight away.
+pub fn spawn_connection_monitor(iroh: Iroh, handle: AppHandle) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut timer = interval(REFRESH_INTERVAL);
+        // Last known path to transfer peers that aren't in the peer list
+        let mut transfer_peers: HashMap<String, PeerConnectionType> = HashMap::new();
+        // Last address saved per transfer peer
+        let mut saved_addrs: HashMap<EndpointId, EndpointAddr> = HashMap::new();
+
+        loop {
+            timer.tick().await;
+
+            let state = handle.state::<AppState>();
+            if state.power.is_low_power() {
+                continue;
+            }
+            let mut changed = false;
+            let mut changes = Vec::new();
+
+            // Running transfers by peer
+            let mut transfers: HashMap<String, Vec<String>> = HashMap::new();
+            for transfer in state.transfers.read().await.values() {
+                if !matches!(
+                    transfer.status,
+                    TransferStatus::Pending | TransferStatus::InProgress
+                ) {
+                    continue;
+                }
+                if let Some(node_id) = &transfer.peer_node_id {
+                    transfers
+                        .entry(node_id.clone())
+                        .or_default()
+                        .push(transfer.id.clone());
+                }
+            }
+
+            {
+                let mut peers = state.peers.write().await;
+                for peer in peers.values_mut() {
+                    let Ok(endpoint_id) = peer.node_id.parse::<EndpointId>() else {
+                        continue;
+                    };
+
+                    let current = connection_type(&iroh, endpoint_id);
+                    if current == peer.connection_type {
+                        continue;
+                    }
+
+                    changes.push(ConnectionChange {
+                        node_id: peer.node_id.clone(),
+                        from: peer.connection_type,
+                        to: current,
+                        transfer_ids: Vec::new(),
+                    });
+                    peer.connection_type = current;
+                    changed = true;
+                }
+
+                transfer_peers.retain(|node_id, _| {
+                    transfers.contains_key(node_id) && !peers.contains_key(node_id)
+                });
+                for node_id in transfers.keys() {
+                    if peers.contains_key(node_id) {
+                        continue;
+                    }
+                    let Ok(endpoint_id) = node_id.parse::<EndpointId>() else {
+                        continue;
+                    };
+                    let current = connection_type(&iroh, endpoint_id);
+                    let last = transfer_peers.insert(node_id.clone(), current);
+                    if last.unwrap_or_default() != current {
+                        changes.push(ConnectionChange {
+                            node_id: node_id.clone(),
+                            from: last.unwrap_or_default(),
+                            to: current,
+                            transfer_ids: Vec::new(),
+                        });
+                    }
+                }
+            }
+
+            for node_id in transfers.keys() {
+                let Ok(endpoint_id) = node_id.parse::<EndpointId>() else {
+                    continue;
+                };
+                let Some(addr) = current_addr(&iroh, endpoint_id) else {
+                    continue;
+                };
+                if saved_addrs.get(&endpoint_id) != Some(&addr) {
+                    state.remember_peer_addr(&addr).await;
+                    saved_addrs.insert(endpoint_id, addr);
+                }
+            }
+
+            for mut change in changes {
+                info!(
+                    "Connection to {} changed from {:?} to {:?}",
+                    change.node_id, change.from, change.to
+                );
+                if change.to == PeerConnectionType::Direct {
+                    let _ = AppEvent::PeerConnectionUpgraded(ConnectionUpgrade {
+                        node_id: change.node_id.clone(),
+                        from: change.from,
+                    })
+                    .emit(&handle);
+                }
+                change.transfer_ids = transfers.remove(&change.node_id).unwrap_or_default();
+                let _ = AppEvent::PeerConnectionChanged(change).emit(&handle);
+            }
+
+            if changed {
+                let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(&handle);
+            }
+        }
+    })
+}
@@ -0,0 +1,215 @@
+// Point-to-point control channel
+//
+// Transfer signalling between two devices (offers, accept/reject,
+// cancellation and receipts), chat messages and synced clipboards go over its own ALPN rather than the broadcast
+// gossip topic, so only the addressed peer sees it and delivery is confirmed.
+// Each message travels on a fresh bidirectional stream as JSON; the receiver
+// closes its side once the message has been handed to the frontend, which is
+// the sender's acknowledgement. Messages from blocked peers are dropped.
+
+use anyhow::Result;
+use iroh::endpoint::{Connection, RecvStream, SendStream};
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::Endpoint;
+use iroh_base::EndpointId;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::time::Duration;
+use tracing::{debug, warn};
+
+use crate::clipboard::ClipboardContent;
+use crate::events::AppEvent;
+use crate::notify::{self, Notice};
+use crate::state::AppState;
+use crate::webhook::WebhookEvent;
+
+pub const ALPN: &[u8] = b"vegam/control/0";
+
+/// Synced clipboard images are the largest payload
+const MAX_MESSAGE_SIZE: usize = 2 * 1024 * 1024;
+const SEND_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlMessage {
+    /// A file the sender would like us to download
+    Offer {
+        transfer_id: String,
+        ticket: String,
+        file_name: String,
+        file_size: u64,
+    },
+    Accept {
+        transfer_id: String,
+    },
+    Reject {
+        transfer_id: String,
+        reason: Option<String>,
+    },
+    /// Either side gave up on the transfer
+    Cancel {
+        transfer_id: String,
+    },
+    /// The receiver has the whole file
+    Receipt {
+        transfer_id: String,
+        bytes_received: u64,
+    },
+    /// A chat message, handled by `chat.rs` rather than the frontend
+    Chat {
+        id: String,
+        text: String,
+        sent_at: u64,
+    },
+    /// Clipboard of one of our own devices, handled by `clipboard.rs`
+    Clipboard {
+        content: ClipboardContent,
+    },
+}
+
+/// Payload of `control-message`
+#[derive(Clone, Debug, Serialize)]
+pub struct ControlReceived {
+    /// Node ID of the sender
+    pub from: String,
+    pub message: ControlMessage,
+}
+
+/// Handler for control messages from other devices
+#[derive(Debug, Clone)]
+pub struct Control {
+    handle: AppHandle,
+}
+
+impl Control {
+    pub fn new(handle: AppHandle) -> Self {
+        Self { handle }
+    }
+
+    async fn receive(
+        &self,
+        remote: EndpointId,
+        mut send: SendStream,
+        mut recv: RecvStream,
+    ) -> Result<()> {
+        let message: ControlMessage =
+            serde_json::from_slice(&recv.read_to_end(MAX_MESSAGE_SIZE).await?)?;
+        debug!("Control message from {}: {:?}", remote, message);
+
+        let from = remote.to_string();
+        let state = self.handle.state::<AppState>();
+        state.touch_peer(&from).await;
+        if let ControlMessage::Chat { id, text, sent_at } = message {
+            crate::chat::received(&self.handle, from, id, text, sent_at).await;
+            send.finish()?;
+            return Ok(());
+        }
+        if let ControlMessage::Clipboard { content } = message {
+            crate::clipboard::received(&self.handle, &from, content).await;
+            send.finish()?;
+            return Ok(());
+        }
+        if let ControlMessage::Offer {
+            transfer_id,
+            file_name,
+            file_size,
+            ..
+        } = &message
+        {
+            let device_name = state
+                .peers
+                .read()
+                .await
+                .get(&from)
+                .map(|peer| peer.device_name.clone());
+            let notice = Notice::Offer {
+                from: device_name
+                    .clone()
+                    .unwrap_or_else(|| from.chars().take(8).collect()),
+                file_name: file_name.clone(),
+            };
+            notify::notify(&self.handle, notice).await;
+            let offer = WebhookEvent::OfferReceived {
+                from: from.clone(),
+                device_name,
+                transfer_id: transfer_id.clone(),
+                file_name: file_name.clone(),
+                file_size: *file_size,
+            };
+            let settings = state.get_settings().await.webhook;
+            state.webhook.fire(&settings, offer);
+        }
+        crate::fanout::observe(&self.handle, &from, &message).await;
+        AppEvent::ControlMessage(ControlReceived { from, message }).emit(&self.handle)?;
+
+        send.finish()?;
+        Ok(())
+    }
+}
+
+impl ProtocolHandler for Control {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        let remote = connection.remote_id();
+        if self
+            .handle
+            .state::<AppState>()
+            .is_blocked(&remote.to_string())
+            .await
+        {
+            connection.close(1u32.into(), b"blocked");
+            return Ok(());
+        }
+
+        // One stream per message, until the peer closes the connection
+        while let Ok((send, recv)) = connection.accept_bi().await {
+            if let Err(e) = self.receive(remote, send, recv).await {
+                warn!("Bad control message from {}: {}", remote, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deliver a message to `remote`, returning once it has been received
+pub async fn send(endpoint: &Endpoint, remote: EndpointId, message: &ControlMessage) -> Result<()> {
+    let bytes = serde_json::to_vec(message)?;
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(anyhow::anyhow!("Control message too large"));
+    }
+
+    tokio::time::timeout(SEND_TIMEOUT, async {
+        let conn = endpoint.connect(remote, ALPN).await?;
+        let (mut send, mut recv) = conn.open_bi().await?;
+        send.write_all(&bytes).await?;
+        send.finish()?;
+        // The receiver finishes its side once it has the message
+        recv.read_to_end(0).await?;
+        conn.close(0u32.into(), b"done");
+        anyhow::Ok(())
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("Sending control message timed out"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_message_wire_format() {
+        let message = ControlMessage::Reject {
+            transfer_id: "t1".to_string(),
+            reason: None,
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"reject","transfer_id":"t1","reason":null}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ControlMessage>(&json).unwrap(),
+            message
+        );
+        assert!(serde_json::from_str::<ControlMessage>(r#"{"type":"bogus"}"#).is_err());
+    }
+}
@@ -5,18 +5,83 @@
 // from other peers.
 
 use anyhow::Result;
-use iroh_gossip::api::{GossipReceiver, GossipSender};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use iroh_base::{EndpointId, PublicKey, SecretKey, Signature};
+use iroh_gossip::{
+    api::{Event, GossipReceiver, GossipSender},
+    proto::TopicId,
+};
 use n0_future::StreamExt;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::{AppHandle, Emitter, Manager};
-use tokio::time::{interval, Duration};
-use tracing::{error, info, warn};
+use tauri::{AppHandle, Manager};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+use tracing::{debug, error, info, warn};
 
-use crate::state::{AppState, PeerInfo};
+use crate::events::{AppEvent, NeighborEvent};
+use crate::history::now_secs;
+use crate::iroh::identity::IdentityTransition;
+use crate::iroh::room::{self, RoomKey};
+use crate::iroh::Iroh;
+use crate::state::{AppState, Feature, PeerCapabilities, PeerInfo};
 
-const ANNOUNCEMENT_INTERVAL: Duration = Duration::from_secs(30);
-const PEER_TIMEOUT: Duration = Duration::from_secs(90);
+/// Discovery/transfer protocol version advertised in announcements
+///
+/// Version 2 requires announcements to be signed with the node key.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Features this build supports
+pub const SUPPORTED_FEATURES: &[Feature] = &[Feature::EncryptedTickets, Feature::ControlChannel];
+
+/// Version of this build, announced so peers can warn about mismatches
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Group ID used for peers discovered on the primary (room) topic
+pub const PRIMARY_GROUP: &str = "default";
+
+/// Defaults and bounds for the user-configurable discovery timings
+pub const DEFAULT_ANNOUNCEMENT_INTERVAL: Duration = Duration::from_secs(30);
+pub const MIN_ANNOUNCEMENT_INTERVAL: Duration = Duration::from_secs(5);
+pub const MAX_ANNOUNCEMENT_INTERVAL: Duration = Duration::from_secs(600);
+pub const DEFAULT_PEER_TIMEOUT: Duration = Duration::from_secs(90);
+pub const MAX_PEER_TIMEOUT: Duration = Duration::from_secs(1800);
+/// A peer must be able to miss at least one announcement before timing out
+pub const MIN_TIMEOUT_INTERVALS: u32 = 2;
+
+/// Random spread applied to each interval so peers don't announce in lockstep
+const ANNOUNCEMENT_JITTER: f64 = 0.2;
+
+/// Backoff between attempts to resubscribe after a gossip stream fails
+const RESTART_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Gossip payloads larger than this are dropped before parsing
+const MAX_MESSAGE_SIZE: usize = 4096;
+/// Upper bound on tracked peers so a flood of identities can't grow the map
+const MAX_PEERS: usize = 256;
+/// Per-sender message budget within `RATE_LIMIT_WINDOW`
+const RATE_LIMIT_MAX_MESSAGES: u32 = 10;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+
+/// How far a message timestamp may be from our clock, allowing for skew
+const MAX_MESSAGE_SKEW: Duration = Duration::from_secs(300);
+
+/// Sequence number of the next announcement or goodbye we send
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+const MAX_DEVICE_NAME_LEN: usize = 64;
+const MAX_SHORT_FIELD_LEN: usize = 32;
+const MAX_FEATURES: usize = 32;
+
+/// Palette used to derive a default peer color from its node ID
+const PEER_COLORS: [&str; 12] = [
+    "#ef4444", "#f97316", "#f59e0b", "#84cc16", "#22c55e", "#14b8a6", "#06b6d4", "#3b82f6",
+    "#6366f1", "#8b5cf6", "#d946ef", "#ec4899",
+];
 
 /// Peer announcement message broadcast via gossip
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,10 +89,35 @@ pub struct PeerAnnouncement {
     pub node_id: String,
     pub device_name: String,
     pub timestamp: u64,
+    /// Hex color (`#rrggbb`), empty from peers that predate identities
+    #[serde(default)]
+    pub color: String,
+    /// Index into the frontend's avatar/emoji set
+    #[serde(default)]
+    pub avatar: Option<u8>,
+    #[serde(default)]
+    pub capabilities: PeerCapabilities,
+    /// `std::env::consts::OS` of the sender (android, ios, macos, windows, linux)
+    #[serde(default)]
+    pub platform: String,
+    #[serde(default)]
+    pub app_version: String,
+    /// Signed proof that this node replaced an older key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_identity: Option<IdentityTransition>,
+    /// Increases with every message within a run; 0 from older peers
+    #[serde(default)]
+    pub seq: u64,
 }
 
 impl PeerAnnouncement {
-    pub fn new(node_id: String, device_name: String) -> Self {
+    pub fn new(
+        node_id: String,
+        device_name: String,
+        color: String,
+        avatar: Option<u8>,
+        capabilities: PeerCapabilities,
+    ) -> Self {
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -37,6 +127,13 @@ impl PeerAnnouncement {
             node_id,
             device_name,
             timestamp,
+            color,
+            avatar,
+            capabilities,
+            platform: std::env::consts::OS.to_string(),
+            app_version: APP_VERSION.to_string(),
+            previous_identity: None,
+            seq: NEXT_SEQ.fetch_add(1, Ordering::Relaxed),
         }
     }
 
@@ -47,113 +144,633 @@ impl PeerAnnouncement {
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         serde_json::from_slice(bytes).map_err(Into::into)
     }
+
+    /// Reject announcements with fields outside sane bounds
+    pub fn validate(&self) -> Result<()> {
+        let name_len = self.device_name.chars().count();
+        if name_len == 0 || name_len > MAX_DEVICE_NAME_LEN {
+            return Err(anyhow::anyhow!("Invalid device name length: {}", name_len));
+        }
+        if self.device_name.chars().any(char::is_control) {
+            return Err(anyhow::anyhow!("Device name contains control characters"));
+        }
+        if !self.color.is_empty() && !is_valid_color(&self.color) {
+            return Err(anyhow::anyhow!("Invalid color: {}", self.color));
+        }
+        if self.platform.len() > MAX_SHORT_FIELD_LEN || self.app_version.len() > MAX_SHORT_FIELD_LEN
+        {
+            return Err(anyhow::anyhow!("Platform or version too long"));
+        }
+        if self.capabilities.features.len() > MAX_FEATURES {
+            return Err(anyhow::anyhow!("Too many features"));
+        }
+        if self.timestamp == 0 {
+            return Err(anyhow::anyhow!("Missing timestamp"));
+        }
+        Ok(())
+    }
+}
+
+/// Fixed-window message counter per sender
+#[derive(Debug)]
+pub struct RateLimiter {
+    window: Duration,
+    max_messages: u32,
+    senders: HashMap<String, (Instant, u32)>,
+}
+
+impl RateLimiter {
+    pub fn new(window: Duration, max_messages: u32) -> Self {
+        Self {
+            window,
+            max_messages,
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Count a message from `sender`, returning false once it is over budget
+    pub fn check(&mut self, sender: &str) -> bool {
+        let now = Instant::now();
+
+        // Forget expired windows so the map stays bounded
+        if self.senders.len() > MAX_PEERS * 2 {
+            let window = self.window;
+            self.senders
+                .retain(|_, (start, _)| now.duration_since(*start) < window);
+        }
+
+        let entry = self.senders.entry(sender.to_string()).or_insert((now, 0));
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+        entry.1 += 1;
+        entry.1 <= self.max_messages
+    }
 }
 
-/// Spawn background task for peer discovery
+/// Rejects stale and replayed discovery messages
 ///
-/// This task:
+/// A message must be timestamped within `MAX_MESSAGE_SKEW` of our clock and
+/// come after the last one accepted from the same sender, ordered by
+/// (timestamp, seq) so a restarted peer whose counter reset is still accepted.
+/// Only messages whose signature and fields have been checked may be passed
+/// in, or a forgery could push a sender's mark past its real messages. The
+/// marks live in memory, so after we restart a message from the last
+/// `MAX_MESSAGE_SKEW` can be replayed once; it can only repeat an announcement
+/// the peer really made.
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    last_seen: HashMap<String, (u64, u64)>,
+}
+
+impl ReplayGuard {
+    pub fn check(&mut self, sender: &str, timestamp: u64, seq: u64, now: u64) -> bool {
+        let max_skew = MAX_MESSAGE_SKEW.as_secs();
+        if timestamp.abs_diff(now) > max_skew {
+            return false;
+        }
+
+        // Entries older than the window can't be replayed anyway
+        if self.last_seen.len() > MAX_PEERS * 2 {
+            self.last_seen
+                .retain(|_, (last, _)| now.saturating_sub(*last) <= max_skew);
+        }
+
+        match self.last_seen.get(sender) {
+            Some(&last) if (timestamp, seq) <= last => false,
+            _ => {
+                self.last_seen.insert(sender.to_string(), (timestamp, seq));
+                true
+            }
+        }
+    }
+}
+
+/// Sent when a peer stops being discoverable so others drop it immediately
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerGoodbye {
+    pub node_id: String,
+    pub timestamp: u64,
+    /// Always true; distinguishes goodbyes from announcements on the wire
+    pub goodbye: bool,
+    #[serde(default)]
+    pub seq: u64,
+}
+
+impl PeerGoodbye {
+    pub fn new(node_id: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        Self {
+            node_id,
+            timestamp,
+            goodbye: true,
+            seq: NEXT_SEQ.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(Into::into)
+    }
+}
+
+/// Any message exchanged on a discovery topic
+#[derive(Debug, Clone)]
+pub enum DiscoveryMessage {
+    Announcement(PeerAnnouncement),
+    Goodbye(PeerGoodbye),
+}
+
+/// Envelope carrying a discovery message signed with the sender's node key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedMessage {
+    /// JSON-encoded `DiscoveryMessage`, signed byte-for-byte
+    pub payload: String,
+    /// Base64url ed25519 signature over `payload`
+    pub signature: String,
+}
+
+impl DiscoveryMessage {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            Self::Announcement(a) => a.to_bytes(),
+            Self::Goodbye(g) => g.to_bytes(),
+        }
+    }
+
+    /// Serialize and wrap in a `SignedMessage` envelope
+    pub fn sign(&self, secret_key: &SecretKey) -> Result<Vec<u8>> {
+        let payload = String::from_utf8(self.to_bytes()?)?;
+        let signature = secret_key.sign(payload.as_bytes());
+        let envelope = SignedMessage {
+            payload,
+            signature: URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+        };
+        serde_json::to_vec(&envelope).map_err(Into::into)
+    }
+
+    /// Parse a signed envelope, rejecting messages not signed by the node
+    /// they claim to come from
+    pub fn from_signed_bytes(bytes: &[u8]) -> Result<Self> {
+        let envelope: SignedMessage = serde_json::from_slice(bytes)?;
+        let msg = Self::from_bytes(envelope.payload.as_bytes())?;
+
+        let public_key: PublicKey = msg
+            .node_id()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid node ID in message: {}", e))?;
+        let signature_bytes: [u8; 64] = URL_SAFE_NO_PAD
+            .decode(envelope.signature.as_bytes())?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid signature length"))?;
+        let signature = Signature::from_bytes(&signature_bytes);
+
+        public_key
+            .verify(envelope.payload.as_bytes(), &signature)
+            .map_err(|_| anyhow::anyhow!("Forged message for node {}", msg.node_id()))?;
+
+        Ok(msg)
+    }
+
+    /// Parse an unsigned gossip payload
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if let Ok(goodbye) = serde_json::from_slice::<PeerGoodbye>(bytes) {
+            if goodbye.goodbye {
+                return Ok(Self::Goodbye(goodbye));
+            }
+        }
+        PeerAnnouncement::from_bytes(bytes).map(Self::Announcement)
+    }
+
+    pub fn node_id(&self) -> &str {
+        match self {
+            Self::Announcement(a) => &a.node_id,
+            Self::Goodbye(g) => &g.node_id,
+        }
+    }
+
+    /// Send time and sequence number, for replay protection
+    pub fn ordering(&self) -> (u64, u64) {
+        match self {
+            Self::Announcement(a) => (a.timestamp, a.seq),
+            Self::Goodbye(g) => (g.timestamp, g.seq),
+        }
+    }
+}
+
+/// Run peer discovery on one topic until its gossip stream fails
+///
+/// This loop:
 /// 1. Periodically broadcasts presence announcements
 /// 2. Listens for announcements from other peers
 /// 3. Updates peer list in AppState
 /// 4. Emits events to frontend
-pub fn spawn_discovery_task(
+async fn run_discovery(
     mut receiver: GossipReceiver,
     sender: GossipSender,
-    node_id: String,
-    handle: AppHandle,
+    secret_key: &SecretKey,
+    group_id: &str,
+    room_key: Option<&RoomKey>,
+    handle: &AppHandle,
 ) {
-    tokio::spawn(async move {
-        info!("Starting peer discovery task");
+    let node_id = secret_key.public().to_string();
+    info!("Starting peer discovery for group: {}", group_id);
 
-        let device_name = get_device_name();
-        let mut announcement_timer = interval(ANNOUNCEMENT_INTERVAL);
+    let announce_now = handle.state::<AppState>().announce_now.clone();
 
-        loop {
-            tokio::select! {
-                // Periodic broadcast of our presence
-                _ = announcement_timer.tick() => {
-                    let announcement = PeerAnnouncement::new(
-                        node_id.clone(),
-                        device_name.clone()
-                    );
-
-                    match announcement.to_bytes() {
-                        Ok(bytes) => {
-                            if let Err(e) = sender.broadcast(bytes.into()).await {
-                                warn!("Failed to broadcast announcement: {}", e);
-                            } else {
-                                info!("Broadcasted presence announcement");
+    let mut rate_limiter = RateLimiter::new(RATE_LIMIT_WINDOW, RATE_LIMIT_MAX_MESSAGES);
+    let mut replay_guard = ReplayGuard::default();
+    let mut neighbors = HashSet::new();
+
+    // Fires immediately so new peers see us without waiting a full interval
+    let announcement_timer = sleep(Duration::ZERO);
+    tokio::pin!(announcement_timer);
+
+    loop {
+        tokio::select! {
+            // Periodic broadcast of our presence
+            _ = &mut announcement_timer => {
+                broadcast_announcement(&sender, secret_key, group_id, room_key, handle).await;
+                let state = handle.state::<AppState>();
+                let timings = state.get_settings().await.discovery_timings;
+                let mut interval = timings.announcement_interval();
+                if state.power.is_low_power() {
+                    interval = crate::power::stretched_interval(interval, timings.peer_timeout());
+                }
+                announcement_timer
+                    .as_mut()
+                    .reset(Instant::now() + next_announcement_delay(interval));
+
+                // Check for timed-out peers
+                if let Err(e) = cleanup_stale_peers(handle).await {
+                    warn!("Failed to cleanup stale peers: {}", e);
+                }
+            }
+
+            // Re-announce immediately when our identity or network changes
+            _ = announce_now.notified() => {
+                broadcast_announcement(&sender, secret_key, group_id, room_key, handle).await;
+            }
+
+            // Listen for announcements from other peers
+            msg = receiver.next() => {
+                match msg {
+                    Some(Ok(event)) => {
+                        let content = match event {
+                            Event::Received(m) => {
+                                // The relaying neighbor is evidently alive
+                                handle
+                                    .state::<AppState>()
+                                    .touch_peer(&m.delivered_from.to_string())
+                                    .await;
+                                m.content
                             }
-                        }
-                        Err(e) => {
-                            error!("Failed to serialize announcement: {}", e);
-                        }
-                    }
+                            // A direct gossip neighbor joined: announce right away so
+                            // it learns about us without waiting for the timer
+                            Event::NeighborUp(id) => {
+                                neighbors.insert(id);
+                                emit_neighbor_event(true, id, &neighbors, group_id, handle);
+                                broadcast_announcement(&sender, secret_key, group_id, room_key, handle)
+                                    .await;
+                                continue;
+                            }
+                            Event::NeighborDown(id) => {
+                                neighbors.remove(&id);
+                                emit_neighbor_event(false, id, &neighbors, group_id, handle);
+                                continue;
+                            }
+                            _ => continue,
+                        };
 
-                    // Check for timed-out peers
-                    if let Err(e) = cleanup_stale_peers(&handle).await {
-                        warn!("Failed to cleanup stale peers: {}", e);
+                        process_message(
+                            &content,
+                            &node_id,
+                            group_id,
+                            room_key,
+                            &mut rate_limiter,
+                            &mut replay_guard,
+                            handle,
+                        )
+                        .await;
+                    }
+                    Some(Err(e)) => {
+                        warn!("Gossip receiver failed for group {}: {:?}", group_id, e);
+                        break;
+                    }
+                    None => {
+                        warn!("Gossip receiver closed for group {}", group_id);
+                        break;
                     }
                 }
+            }
+        }
+    }
+}
 
-                // Listen for announcements from other peers
-                msg = receiver.next() => {
-                    match msg {
-                        Some(Ok(event)) => {
-                            // Extract content from Event::Received variant
-                            let content = match event {
-                                iroh_gossip::api::Event::Received(m) => m.content,
-                                _ => continue,
-                            };
-
-                            match PeerAnnouncement::from_bytes(&content) {
-                                Ok(announcement) => {
-                                    // Ignore our own announcements
-                                    if announcement.node_id != node_id {
-                                        if let Err(e) = handle_peer_announcement(
-                                            announcement,
-                                            &handle
-                                        ).await {
-                                            warn!("Failed to handle peer announcement: {}", e);
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    warn!("Failed to parse peer announcement: {}", e);
-                                }
-                            }
-                        }
-                        Some(Err(e)) => {
-                            warn!("Failed to receive gossip message: {:?}", e);
-                        }
-                        None => {
-                            warn!("Gossip receiver closed");
-                            break;
-                        }
+fn emit_neighbor_event(
+    up: bool,
+    id: EndpointId,
+    neighbors: &HashSet<EndpointId>,
+    group_id: &str,
+    handle: &AppHandle,
+) {
+    let payload = NeighborEvent {
+        group_id: group_id.to_string(),
+        node_id: id.to_string(),
+        neighbors: neighbors.len(),
+    };
+    let event = if up {
+        AppEvent::GossipNeighborUp(payload)
+    } else {
+        AppEvent::GossipNeighborDown(payload)
+    };
+    info!(
+        "{} on {}: {} ({} total)",
+        event.name(),
+        group_id,
+        id,
+        neighbors.len()
+    );
+    let _ = event.emit(handle);
+}
+
+/// Announcement interval with random jitter
+fn next_announcement_delay(interval: Duration) -> Duration {
+    let jitter = rand::thread_rng().gen_range(-ANNOUNCEMENT_JITTER..=ANNOUNCEMENT_JITTER);
+    interval.mul_f64(1.0 + jitter)
+}
+
+/// Validate, rate limit and dispatch a single gossip payload
+async fn process_message(
+    content: &[u8],
+    node_id: &str,
+    group_id: &str,
+    room_key: Option<&RoomKey>,
+    rate_limiter: &mut RateLimiter,
+    replay_guard: &mut ReplayGuard,
+    handle: &AppHandle,
+) {
+    if content.len() > MAX_MESSAGE_SIZE + room::SEAL_OVERHEAD {
+        debug!(
+            "Dropping oversized discovery message: {} bytes",
+            content.len()
+        );
+        return;
+    }
+
+    // In a room, only members holding the code can produce a readable message
+    let opened;
+    let content = match room_key {
+        Some(key) => match room::open(key, content) {
+            Ok(plaintext) => {
+                opened = plaintext;
+                opened.as_slice()
+            }
+            Err(e) => {
+                debug!("Dropping room message: {}", e);
+                return;
+            }
+        },
+        None => content,
+    };
+
+    let msg = match DiscoveryMessage::from_signed_bytes(content) {
+        Ok(msg) => msg,
+        // Unsigned, malformed or forged
+        Err(e) => {
+            warn!("Dropping discovery message: {}", e);
+            return;
+        }
+    };
+
+    // Ignore our own messages
+    if msg.node_id() == node_id {
+        return;
+    }
+
+    if !rate_limiter.check(msg.node_id()) {
+        debug!("Rate limiting discovery messages from {}", msg.node_id());
+        return;
+    }
+
+    // Only a signed, well-formed message may move the sender's high-water mark
+    if let DiscoveryMessage::Announcement(announcement) = &msg {
+        if let Err(e) = announcement.validate() {
+            warn!(
+                "Dropping invalid announcement from {}: {}",
+                announcement.node_id, e
+            );
+            return;
+        }
+    }
+    let (timestamp, seq) = msg.ordering();
+    if !replay_guard.check(msg.node_id(), timestamp, seq, now_secs()) {
+        debug!("Dropping stale or replayed message from {}", msg.node_id());
+        return;
+    }
+
+    match msg {
+        DiscoveryMessage::Announcement(announcement) => {
+            if let Err(e) = handle_peer_announcement(announcement, group_id, handle).await {
+                warn!("Failed to handle peer announcement: {}", e);
+            }
+        }
+        DiscoveryMessage::Goodbye(goodbye) => {
+            if let Err(e) = handle_peer_goodbye(goodbye, handle).await {
+                warn!("Failed to handle peer goodbye: {}", e);
+            }
+        }
+    }
+}
+
+/// Take a subscribed topic's channel and spawn its discovery task
+pub async fn start_discovery(
+    iroh: &Iroh,
+    topic_id: &TopicId,
+    group_id: String,
+    handle: AppHandle,
+) -> Result<JoinHandle<()>> {
+    let mut receiver = iroh.gossip.take_receiver(topic_id).await?;
+    let mut sender = iroh.gossip.get_sender(topic_id).await?;
+    let room_key = iroh.gossip.room_key(topic_id).await;
+    let secret_key = iroh.endpoint.secret_key().clone();
+    let iroh = iroh.clone();
+    let topic_id = *topic_id;
+
+    // Supervise the discovery loop: when the gossip stream dies, resubscribe
+    // instead of silently losing discovery until restart.
+    // Aborting the returned handle drops the topic's sender and receiver.
+    Ok(tokio::spawn(async move {
+        loop {
+            run_discovery(
+                receiver,
+                sender,
+                &secret_key,
+                &group_id,
+                room_key.as_ref(),
+                &handle,
+            )
+            .await;
+
+            let mut delay = RESTART_BACKOFF_MIN;
+            (receiver, sender) = loop {
+                sleep(delay).await;
+                match resubscribe(&iroh, &topic_id, &group_id, &handle).await {
+                    Ok(channel) => break channel,
+                    Err(e) => {
+                        warn!("Failed to restart discovery for group {}: {}", group_id, e);
+                        delay = (delay * 2).min(RESTART_BACKOFF_MAX);
                     }
                 }
+            };
+
+            info!("Discovery restarted for group: {}", group_id);
+            let _ = AppEvent::DiscoveryRestarted(group_id.clone()).emit(&handle);
+        }
+    }))
+}
+
+/// Subscribe to a topic again, bootstrapping from peers last seen in the group
+async fn resubscribe(
+    iroh: &Iroh,
+    topic_id: &TopicId,
+    group_id: &str,
+    handle: &AppHandle,
+) -> Result<(GossipReceiver, GossipSender)> {
+    let bootstrap = handle
+        .state::<AppState>()
+        .get_group_peers(group_id)
+        .await
+        .iter()
+        .filter_map(|p| p.node_id.parse().ok())
+        .collect();
+
+    iroh.gossip.subscribe(*topic_id, bootstrap).await?;
+    Ok((
+        iroh.gossip.take_receiver(topic_id).await?,
+        iroh.gossip.get_sender(topic_id).await?,
+    ))
+}
+
+/// Broadcast our presence using the current display name and identity
+async fn broadcast_announcement(
+    sender: &GossipSender,
+    secret_key: &SecretKey,
+    group_id: &str,
+    room_key: Option<&RoomKey>,
+    handle: &AppHandle,
+) {
+    let node_id = secret_key.public().to_string();
+    let settings = handle.state::<AppState>().get_settings().await;
+
+    // Invisible: keep listening but don't announce anywhere
+    if !settings.discoverable {
+        return;
+    }
+
+    // Groups can be set to listen-only
+    if settings
+        .groups
+        .get(group_id)
+        .is_some_and(|group| !group.announce)
+    {
+        return;
+    }
+
+    let mut announcement = PeerAnnouncement::new(
+        node_id.clone(),
+        settings.display_name(),
+        settings.color_for(&node_id),
+        settings.avatar,
+        local_capabilities(settings.max_file_size),
+    );
+    // Only the node that took over the key announces the transition
+    announcement.previous_identity = settings
+        .identity_transition
+        .clone()
+        .filter(|t| t.new_node_id == node_id && t.is_current());
+
+    let signed = DiscoveryMessage::Announcement(announcement)
+        .sign(secret_key)
+        .and_then(|bytes| match room_key {
+            Some(key) => room::seal(key, &bytes),
+            None => Ok(bytes),
+        });
+    match signed {
+        Ok(bytes) => {
+            if let Err(e) = sender.broadcast(bytes.into()).await {
+                warn!("Failed to broadcast announcement: {}", e);
+            } else {
+                info!("Broadcasted presence announcement");
             }
         }
-    });
+        Err(e) => {
+            error!("Failed to sign or seal announcement: {}", e);
+        }
+    }
 }
 
-/// Handle a peer announcement
+/// Handle a peer announcement received on a group's topic
 async fn handle_peer_announcement(
     announcement: PeerAnnouncement,
+    group_id: &str,
     handle: &AppHandle,
 ) -> Result<()> {
     let state = handle.state::<AppState>();
 
-    let peer_info = PeerInfo {
-        node_id: announcement.node_id.clone(),
-        device_name: announcement.device_name.clone(),
-        last_seen: announcement.timestamp,
+    if state.is_blocked(&announcement.node_id).await {
+        return Ok(());
+    }
+
+    // A trusted peer that rotated its key keeps its trust
+    if let Some(transition) = &announcement.previous_identity {
+        if transition.new_node_id == announcement.node_id && transition.verify().is_ok() {
+            if let Some(trusted) = state.migrate_trust(transition).await? {
+                info!(
+                    "Peer {} rotated its identity to {}",
+                    transition.old_node_id, trusted.node_id
+                );
+            }
+        } else {
+            warn!("Invalid identity transition from {}", announcement.node_id);
+        }
+    }
+
+    // Older peers don't send a color, derive it the same way they would
+    let color = if announcement.color.is_empty() {
+        default_color(&announcement.node_id)
+    } else {
+        announcement.color.clone()
     };
 
-    // Check if this is a new peer
-    let peers = state.peers.read().await;
-    let is_new = !peers.contains_key(&announcement.node_id);
-    drop(peers);
+    // Update the existing entry so locally tracked fields survive re-announcements
+    let existing = state.peers.read().await.get(&announcement.node_id).cloned();
+    let is_new = existing.is_none();
+
+    if is_new && state.peers.read().await.len() >= MAX_PEERS {
+        debug!("Peer list full, ignoring {}", announcement.node_id);
+        return Ok(());
+    }
+    let mut peer_info = existing.unwrap_or_else(|| PeerInfo {
+        node_id: announcement.node_id.clone(),
+        ..Default::default()
+    });
+
+    peer_info.device_name = announcement.device_name.clone();
+    peer_info.last_seen = announcement.timestamp;
+    peer_info.color = color;
+    peer_info.avatar = announcement.avatar;
+    peer_info.capabilities = announcement.capabilities.clone();
+    peer_info.version_mismatch = is_version_mismatch(&announcement.app_version);
+    peer_info.platform = announcement.platform.clone();
+    peer_info.app_version = announcement.app_version.clone();
+    if !peer_info.groups.iter().any(|g| g == group_id) {
+        peer_info.groups.push(group_id.to_string());
+    }
+    state.get_settings().await.apply_to_peer(&mut peer_info);
 
     // Add or update peer
     state.add_peer(peer_info.clone()).await;
@@ -165,16 +782,47 @@ async fn handle_peer_announcement(
         );
 
         // Emit peer discovered event
-        handle.emit("peer-discovered", peer_info)?;
+        AppEvent::PeerDiscovered(peer_info).emit(handle)?;
     }
 
     // Emit peer list updated event
-    let all_peers = state.get_peers().await;
-    handle.emit("peer-list-updated", all_peers)?;
+    AppEvent::PeerListUpdated(state.get_peers().await).emit(handle)?;
 
     Ok(())
 }
 
+/// Drop a peer that said goodbye instead of waiting for it to time out
+async fn handle_peer_goodbye(goodbye: PeerGoodbye, handle: &AppHandle) -> Result<()> {
+    let state = handle.state::<AppState>();
+
+    let known = state.peers.read().await.contains_key(&goodbye.node_id);
+    if !known {
+        return Ok(());
+    }
+
+    info!("Peer said goodbye: {}", goodbye.node_id);
+    state.remove_peer(&goodbye.node_id).await;
+
+    AppEvent::PeerLost(goodbye.node_id).emit(handle)?;
+    AppEvent::PeerListUpdated(state.get_peers().await).emit(handle)?;
+
+    Ok(())
+}
+
+/// Broadcast a goodbye on every topic the node is subscribed to
+pub async fn broadcast_goodbye(iroh: &Iroh) {
+    let goodbye = PeerGoodbye::new(iroh.node_addr.id.to_string());
+    match DiscoveryMessage::Goodbye(goodbye).sign(iroh.endpoint.secret_key()) {
+        Ok(bytes) => {
+            iroh.gossip.broadcast_all(bytes).await;
+            info!("Broadcasted goodbye");
+        }
+        Err(e) => {
+            error!("Failed to sign goodbye: {}", e);
+        }
+    }
+}
+
 /// Remove peers that haven't been seen recently
 async fn cleanup_stale_peers(handle: &AppHandle) -> Result<()> {
     let state = handle.state::<AppState>();
@@ -182,21 +830,32 @@ async fn cleanup_stale_peers(handle: &AppHandle) -> Result<()> {
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs();
+    let timeout = state
+        .get_settings()
+        .await
+        .discovery_timings
+        .peer_timeout()
+        .as_secs();
 
     let peers = state.peers.read().await;
     let stale_peers: Vec<String> = peers
         .iter()
-        .filter(|(_, peer)| current_time - peer.last_seen > PEER_TIMEOUT.as_secs())
+        .filter(|(_, peer)| current_time.saturating_sub(peer.last_seen) > timeout)
         .map(|(id, _)| id.clone())
         .collect();
     drop(peers);
 
     for node_id in stale_peers {
+        // Never drop a peer we're still exchanging a file with
+        if state.has_active_transfer(&node_id).await {
+            continue;
+        }
+
         info!("Removing stale peer: {}", node_id);
         state.remove_peer(&node_id).await;
 
         // Emit peer lost event
-        handle.emit("peer-lost", node_id)?;
+        AppEvent::PeerLost(node_id).emit(handle)?;
     }
 
     Ok(())
@@ -209,3 +868,181 @@ pub fn get_device_name() -> String {
         .and_then(|name| name.into_string().ok())
         .unwrap_or_else(|| "Unknown Device".to_string())
 }
+
+/// Capabilities advertised by this build
+pub fn local_capabilities(max_file_size: Option<u64>) -> PeerCapabilities {
+    PeerCapabilities {
+        protocol_version: PROTOCOL_VERSION,
+        features: SUPPORTED_FEATURES.to_vec(),
+        max_file_size,
+    }
+}
+
+/// Whether a peer's app version differs from ours in major or minor version
+///
+/// Peers that don't announce a version are not flagged.
+pub fn is_version_mismatch(peer_version: &str) -> bool {
+    fn major_minor(version: &str) -> Option<(&str, &str)> {
+        let mut parts = version.split('.');
+        Some((parts.next()?, parts.next()?))
+    }
+
+    match (major_minor(peer_version), major_minor(APP_VERSION)) {
+        (Some(theirs), Some(ours)) => theirs != ours,
+        _ => false,
+    }
+}
+
+/// Deterministic color for a node, so a peer looks the same on every device
+pub fn default_color(node_id: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(node_id.as_bytes());
+    PEER_COLORS[digest[0] as usize % PEER_COLORS.len()].to_string()
+}
+
+/// Check that a color is a `#rrggbb` hex string
+pub fn is_valid_color(color: &str) -> bool {
+    color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_color_is_deterministic() {
+        let color = default_color("node-a");
+        assert_eq!(color, default_color("node-a"));
+        assert!(PEER_COLORS.contains(&color.as_str()));
+    }
+
+    #[test]
+    fn test_color_validation() {
+        assert!(is_valid_color("#3b82f6"));
+        assert!(is_valid_color("#ABCDEF"));
+        assert!(!is_valid_color("3b82f6"));
+        assert!(!is_valid_color("#3b82f"));
+        assert!(!is_valid_color("#gggggg"));
+    }
+
+    #[test]
+    fn test_announcement_without_identity_fields_parses() {
+        let legacy = br#"{"node_id":"abc","device_name":"Pixel","timestamp":1}"#;
+        let announcement = PeerAnnouncement::from_bytes(legacy).unwrap();
+        assert!(announcement.color.is_empty());
+        assert_eq!(announcement.avatar, None);
+        assert_eq!(announcement.capabilities.protocol_version, 0);
+        assert!(announcement.capabilities.features.is_empty());
+    }
+
+    #[test]
+    fn test_goodbye_is_distinguished_from_announcement() {
+        let goodbye = PeerGoodbye::new("abc".to_string()).to_bytes().unwrap();
+        assert!(matches!(
+            DiscoveryMessage::from_bytes(&goodbye).unwrap(),
+            DiscoveryMessage::Goodbye(_)
+        ));
+
+        let announcement = br#"{"node_id":"abc","device_name":"Pixel","timestamp":1}"#;
+        assert!(matches!(
+            DiscoveryMessage::from_bytes(announcement).unwrap(),
+            DiscoveryMessage::Announcement(_)
+        ));
+    }
+
+    #[test]
+    fn test_signed_message_roundtrip() {
+        let key = SecretKey::from_bytes(&[7u8; 32]);
+        let goodbye = DiscoveryMessage::Goodbye(PeerGoodbye::new(key.public().to_string()));
+
+        let bytes = goodbye.sign(&key).unwrap();
+        let parsed = DiscoveryMessage::from_signed_bytes(&bytes).unwrap();
+        assert_eq!(parsed.node_id(), key.public().to_string());
+    }
+
+    #[test]
+    fn test_forged_and_unsigned_messages_rejected() {
+        let key = SecretKey::from_bytes(&[7u8; 32]);
+        let victim = SecretKey::from_bytes(&[9u8; 32]).public().to_string();
+
+        // Signed by one key while claiming another node ID
+        let forged = DiscoveryMessage::Goodbye(PeerGoodbye::new(victim))
+            .sign(&key)
+            .unwrap();
+        assert!(DiscoveryMessage::from_signed_bytes(&forged).is_err());
+
+        // Legacy unsigned payload
+        let unsigned = PeerGoodbye::new(key.public().to_string())
+            .to_bytes()
+            .unwrap();
+        assert!(DiscoveryMessage::from_signed_bytes(&unsigned).is_err());
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        assert!(!is_version_mismatch(APP_VERSION));
+        assert!(!is_version_mismatch(""));
+        assert!(is_version_mismatch("999.0.0"));
+    }
+
+    #[test]
+    fn test_announcement_validation() {
+        let mut announcement = PeerAnnouncement::new(
+            "abc".to_string(),
+            "Pixel".to_string(),
+            "#3b82f6".to_string(),
+            None,
+            PeerCapabilities::default(),
+        );
+        assert!(announcement.validate().is_ok());
+
+        announcement.device_name = "x".repeat(MAX_DEVICE_NAME_LEN + 1);
+        assert!(announcement.validate().is_err());
+
+        announcement.device_name = "bad\nname".to_string();
+        assert!(announcement.validate().is_err());
+
+        announcement.device_name = "Pixel".to_string();
+        announcement.color = "red".to_string();
+        assert!(announcement.validate().is_err());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(60), 2);
+        assert!(limiter.check("a"));
+        assert!(limiter.check("a"));
+        assert!(!limiter.check("a"));
+        assert!(limiter.check("b"));
+    }
+
+    #[test]
+    fn test_replay_guard() {
+        let mut guard = ReplayGuard::default();
+        let now = 1_000_000;
+        assert!(guard.check("a", now, 5, now));
+        // Replayed or older
+        assert!(!guard.check("a", now, 5, now));
+        assert!(!guard.check("a", now - 10, 9, now));
+        // Newer, including after a restart reset the counter
+        assert!(guard.check("a", now, 6, now));
+        assert!(guard.check("a", now + 1, 0, now));
+        // Outside the skew window
+        let skew = MAX_MESSAGE_SKEW.as_secs();
+        assert!(!guard.check("b", now - skew - 1, 0, now));
+        assert!(!guard.check("b", now + skew + 1, 0, now));
+    }
+
+    #[test]
+    fn test_unknown_features_are_tolerated() {
+        let json = br#"{"protocol_version":9,"features":["collections","teleport"]}"#;
+        let caps: PeerCapabilities = serde_json::from_slice(json).unwrap();
+        assert!(caps.supports(Feature::Collections));
+        assert!(caps.supports(Feature::Unknown));
+        assert!(!caps.supports(Feature::PushOffers));
+        assert!(caps.accepts_size(u64::MAX));
+    }
+}
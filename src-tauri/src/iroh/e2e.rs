@@ -0,0 +1,202 @@
+// End-to-end content encryption
+//
+// With `encrypt_content` on, each shared file is encrypted with a fresh random
+// ChaCha20-Poly1305 key before it is imported. The key exists only in the
+// ticket, so relays, the blob stores on both ends and anyone who learns the
+// blob hash see ciphertext only. The content is split into chunks sealed with
+// the STREAM construction, letting the receiver decrypt and verify chunk by
+// chunk while writing the file; truncation or reordering fails authentication.
+// Sending encrypts the same way, so files are imported without ever being held
+// in memory whole.
+
+use anyhow::Result;
+use bytes::Bytes;
+use chacha20poly1305::{
+    aead::{
+        generic_array::GenericArray,
+        stream::{DecryptorBE32, EncryptorBE32},
+        KeyInit, OsRng,
+    },
+    ChaCha20Poly1305,
+};
+use n0_future::{stream, Stream, StreamExt};
+use rand::RngCore;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::iroh::at_rest::BlobKey;
+
+/// Marks end-to-end encrypted blobs; followed by the STREAM nonce prefix
+const MAGIC: &[u8; 4] = b"VGE1";
+/// 12-byte ChaCha20-Poly1305 nonce minus STREAM's 4-byte counter and last-chunk flag
+const NONCE_PREFIX_LEN: usize = 7;
+const HEADER_LEN: usize = MAGIC.len() + NONCE_PREFIX_LEN;
+/// Plaintext per chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const SEALED_CHUNK_SIZE: usize = CHUNK_SIZE + TAG_LEN;
+
+/// A fresh key for one file
+pub fn generate_key() -> BlobKey {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// Whether a blob starts with the end-to-end header
+pub fn is_encrypted(header: &[u8]) -> bool {
+    header.starts_with(MAGIC)
+}
+
+/// Encrypt a file for sharing as it is read, yielding the header and then one
+/// sealed chunk at a time
+pub fn encrypt_stream<R>(
+    key: &BlobKey,
+    reader: R,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+{
+    let mut nonce = [0u8; NONCE_PREFIX_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let encryptor = EncryptorBE32::from_aead(
+        ChaCha20Poly1305::new(key.into()),
+        GenericArray::from_slice(&nonce),
+    );
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&nonce);
+
+    // Like decryption, only a chunk followed by end of data is sealed as last
+    let chunks = stream::unfold(
+        Some((reader, encryptor, None::<Vec<u8>>)),
+        |state| async move {
+            let (mut reader, mut encryptor, current) = state?;
+            let current = match current {
+                Some(current) => current,
+                None => match read_chunk(&mut reader, CHUNK_SIZE).await {
+                    Ok(chunk) => chunk,
+                    Err(e) => return Some((Err(e), None)),
+                },
+            };
+            let next = match read_chunk(&mut reader, CHUNK_SIZE).await {
+                Ok(chunk) => chunk,
+                Err(e) => return Some((Err(e), None)),
+            };
+            if next.is_empty() {
+                let sealed = encryptor.encrypt_last(current.as_slice());
+                return Some((sealed.map(Bytes::from).map_err(sealing_failed), None));
+            }
+            let sealed = encryptor.encrypt_next(current.as_slice());
+            Some((
+                sealed.map(Bytes::from).map_err(sealing_failed),
+                Some((reader, encryptor, Some(next))),
+            ))
+        },
+    );
+    stream::once(Ok(Bytes::from(header))).chain(chunks)
+}
+
+fn sealing_failed(_: chacha20poly1305::aead::Error) -> io::Error {
+    io::Error::other("Encryption failed")
+}
+
+/// Decrypt a blob from `reader` into `writer` one chunk at a time, returning
+/// the plaintext size
+pub async fn decrypt_to<R, W>(key: &BlobKey, mut reader: R, writer: &mut W) -> Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut header = [0u8; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .await
+        .map_err(|_| anyhow::anyhow!("Not an encrypted blob"))?;
+    if !is_encrypted(&header) {
+        return Err(anyhow::anyhow!("Not an encrypted blob"));
+    }
+    let mut decryptor = DecryptorBE32::from_aead(
+        ChaCha20Poly1305::new(key.into()),
+        GenericArray::from_slice(&header[MAGIC.len()..]),
+    );
+
+    let failed = |_| anyhow::anyhow!("Decryption failed: wrong key or corrupted blob");
+    let mut written = 0u64;
+    // Only a chunk followed by end of data is the last one
+    let mut current = read_chunk(&mut reader, SEALED_CHUNK_SIZE).await?;
+    loop {
+        let next = read_chunk(&mut reader, SEALED_CHUNK_SIZE).await?;
+        if next.is_empty() {
+            let plaintext = decryptor.decrypt_last(current.as_slice()).map_err(failed)?;
+            writer.write_all(&plaintext).await?;
+            return Ok(written + plaintext.len() as u64);
+        }
+        let plaintext = decryptor.decrypt_next(current.as_slice()).map_err(failed)?;
+        writer.write_all(&plaintext).await?;
+        written += plaintext.len() as u64;
+        current = next;
+    }
+}
+
+/// Read up to `size` bytes; shorter only at the end of the data
+async fn read_chunk<R: AsyncRead + Unpin>(reader: &mut R, size: usize) -> io::Result<Vec<u8>> {
+    let mut chunk = vec![0u8; size];
+    let mut filled = 0;
+    while filled < chunk.len() {
+        let n = reader.read(&mut chunk[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    chunk.truncate(filled);
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn encrypt(key: &BlobKey, plaintext: &[u8]) -> Vec<u8> {
+        let mut blob = Vec::new();
+        let mut chunks = std::pin::pin!(encrypt_stream(
+            key,
+            std::io::Cursor::new(plaintext.to_vec())
+        ));
+        while let Some(chunk) = chunks.next().await {
+            blob.extend_from_slice(&chunk.unwrap());
+        }
+        blob
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_content_roundtrips() {
+        let key = generate_key();
+        // Exactly two chunks: the last chunk is full size
+        for plaintext in [Vec::new(), vec![3u8; 10], vec![5u8; 2 * CHUNK_SIZE]] {
+            let blob = encrypt(&key, &plaintext).await;
+            assert_eq!(
+                blob.len(),
+                HEADER_LEN
+                    + plaintext.len()
+                    + plaintext.len().div_ceil(CHUNK_SIZE).max(1) * TAG_LEN
+            );
+            assert!(is_encrypted(&blob));
+
+            let mut out = Vec::new();
+            let size = decrypt_to(&key, blob.as_slice(), &mut out).await.unwrap();
+            assert_eq!(size, plaintext.len() as u64);
+            assert_eq!(out, plaintext);
+        }
+
+        let blob = encrypt(&key, &vec![1u8; CHUNK_SIZE + 1]).await;
+        // Dropping the final chunk must not go unnoticed
+        let truncated = &blob[..HEADER_LEN + SEALED_CHUNK_SIZE];
+        assert!(decrypt_to(&key, truncated, &mut Vec::new()).await.is_err());
+        assert!(
+            decrypt_to(&generate_key(), blob.as_slice(), &mut Vec::new())
+                .await
+                .is_err()
+        );
+    }
+}
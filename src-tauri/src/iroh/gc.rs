@@ -0,0 +1,176 @@
+// Blob store garbage collection
+//
+// Every shared file is kept alive by a store tag, recorded in the history
+// database. Blobs nobody has downloaded for `max_idle_days` lose their tag and
+// the store's own GC reclaims the data. When shared blobs exceed
+// `max_store_bytes`, the least recently shared or downloaded ones are evicted
+// too, invalidating their tickets. Pinned blobs are never collected.
+
+use anyhow::Result;
+use iroh_blobs::api::Tag;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::events::AppEvent;
+use crate::history::{now_secs, BlobRecord};
+use crate::iroh::Iroh;
+use crate::state::AppState;
+
+/// How often idle blobs are collected
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const DEFAULT_MAX_STORE_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// When shared blobs may be deleted; unset means keep them forever
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlobGcPolicy {
+    /// Days since the last download (or since sharing, if never downloaded)
+    pub max_idle_days: Option<u32>,
+    /// Cap on the combined size of shared blobs
+    pub max_store_bytes: Option<u64>,
+}
+
+impl Default for BlobGcPolicy {
+    fn default() -> Self {
+        Self {
+            max_idle_days: Some(7),
+            max_store_bytes: Some(DEFAULT_MAX_STORE_BYTES),
+        }
+    }
+}
+
+/// Start tracking a newly shared blob
+///
+/// Sharing the same file again creates a second tag; the old one is dropped so
+/// only the tracked tag protects the blob.
+pub async fn track_blob(iroh: &Iroh, state: &AppState, blob: BlobRecord) -> Result<()> {
+    if let Some(previous) = state.get_blob(&blob.hash).await? {
+        if previous.tag != blob.tag {
+            iroh.store
+                .tags()
+                .delete(Tag::from(previous.tag.as_str()))
+                .await?;
+        }
+    }
+    state.record_blob(blob).await
+}
+
+/// Drop the store tag for a blob and forget it
+pub async fn delete_blob(iroh: &Iroh, state: &AppState, blob: &BlobRecord) -> Result<()> {
+    iroh.store
+        .tags()
+        .delete(Tag::from(blob.tag.as_str()))
+        .await?;
+    state.forget_blob(&blob.hash).await
+}
+
+/// Remove a blob from the store now rather than at the store's next GC pass,
+/// dropping our tag first if we were sharing it too
+pub async fn purge_blob(iroh: &Iroh, state: &AppState, hash: &str) -> Result<()> {
+    // Without history (ephemeral session) nothing tracks it
+    if let Ok(Some(blob)) = state.get_blob(hash).await {
+        delete_blob(iroh, state, &blob).await?;
+    }
+    let hash: iroh_blobs::Hash = hash.parse()?;
+    iroh.store.blobs().delete([hash]).await?;
+    Ok(())
+}
+
+/// Delete unpinned blobs the policy no longer allows, returning them
+pub async fn collect(
+    iroh: &Iroh,
+    state: &AppState,
+    policy: BlobGcPolicy,
+) -> Result<Vec<BlobRecord>> {
+    state.flush_blobs_served().await?;
+
+    let mut removed = Vec::new();
+    if let Some(days) = policy.max_idle_days {
+        let cutoff = now_secs().saturating_sub(u64::from(days) * 24 * 60 * 60);
+        for blob in state.idle_blobs(cutoff).await? {
+            match delete_blob(iroh, state, &blob).await {
+                Ok(()) => removed.push(blob),
+                Err(e) => warn!("Failed to delete blob {}: {}", blob.hash, e),
+            }
+        }
+    }
+    removed.extend(enforce_quota(iroh, state, policy, None).await?);
+    Ok(removed)
+}
+
+/// Evict the least recently used unpinned blobs until the store fits the quota
+///
+/// `keep` (e.g. a blob that was just shared) is never evicted.
+pub async fn enforce_quota(
+    iroh: &Iroh,
+    state: &AppState,
+    policy: BlobGcPolicy,
+    keep: Option<&str>,
+) -> Result<Vec<BlobRecord>> {
+    let Some(max) = policy.max_store_bytes else {
+        return Ok(Vec::new());
+    };
+    let mut used = state.blobs_size().await?;
+    if used <= max {
+        return Ok(Vec::new());
+    }
+
+    let mut evicted = Vec::new();
+    for blob in state.evictable_blobs().await? {
+        if used <= max {
+            break;
+        }
+        if keep == Some(blob.hash.as_str()) {
+            continue;
+        }
+        match delete_blob(iroh, state, &blob).await {
+            Ok(()) => {
+                used = used.saturating_sub(blob.size);
+                evicted.push(blob);
+            }
+            Err(e) => warn!("Failed to evict blob {}: {}", blob.hash, e),
+        }
+    }
+    if used > max {
+        warn!("Blob store still over quota: {} of {} bytes", used, max);
+    }
+    Ok(evicted)
+}
+
+/// Tell the frontend which blobs (and so which tickets) are gone
+pub fn emit_removed(handle: &AppHandle, removed: Vec<BlobRecord>) {
+    if removed.is_empty() {
+        return;
+    }
+    info!("Removed {} blobs from the store", removed.len());
+    let _ = AppEvent::BlobsRemoved(removed).emit(handle);
+}
+
+/// Periodically collect idle blobs according to the persisted policy
+pub fn spawn_gc_task(handle: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut timer = tokio::time::interval(GC_INTERVAL);
+        loop {
+            timer.tick().await;
+
+            let state = handle.state::<AppState>();
+            // Wait for the charger; unused blobs can stay a little longer
+            if state.power.is_low_power() {
+                continue;
+            }
+            // The node may not be up yet; try again next tick
+            let Ok(iroh) = state.get_iroh().await else {
+                continue;
+            };
+            let policy = state.get_settings().await.blob_gc;
+            match collect(&iroh, &state, policy).await {
+                Ok(removed) => emit_removed(&handle, removed),
+                Err(e) => warn!("Blob garbage collection failed: {}", e),
+            }
+        }
+    })
+}
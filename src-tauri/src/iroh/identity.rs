@@ -0,0 +1,264 @@
+// Persistent node identity
+//
+// The node's secret key is kept in the platform keystore (macOS/iOS Keychain,
+// Windows Credential Manager, Secret Service on Linux, Android Keystore) so
+// the node ID survives restarts without the key sitting in a plaintext file.
+// Where no keystore is available (Linux without a Secret Service daemon) it
+// falls back to a `secret_key` file readable only by the app; that file is
+// moved into the keystore as soon as one becomes available.
+//
+// `rotate` replaces the key. The old key signs an `IdentityTransition` naming
+// the new node ID, which the new identity attaches to its announcements for a
+// while; peers that trusted the old ID move their trust entry over instead of
+// the pairing being lost.
+//
+// Node IDs are too long to compare by eye, so each one also has a short emoji
+// fingerprint that two users can read out to each other before marking a
+// peer verified.
+
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use iroh_base::{PublicKey, SecretKey, Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::iroh::keystore;
+
+/// Fallback key file in the node data dir
+const KEY_FILE: &str = "secret_key";
+
+/// Fingerprint alphabet; 64 emoji that are easy to name out loud
+const FINGERPRINT_EMOJI: [&str; 64] = [
+    "🐶", "🐱", "🐭", "🐰", "🦊", "🐻", "🐼", "🐨", "🐯", "🦁", "🐮", "🐷", "🐸", "🐵", "🐔", "🐧",
+    "🐦", "🦆", "🦉", "🐴", "🦄", "🐝", "🐛", "🦋", "🐌", "🐞", "🐢", "🐍", "🐙", "🦀", "🐬", "🐳",
+    "🌵", "🌲", "🌴", "🍀", "🍁", "🍄", "🌻", "🌹", "🌙", "⭐", "🔥", "🌈", "⚡", "❄️", "💧", "🌊",
+    "🍎", "🍌", "🍇", "🍓", "🍒", "🍍", "🥕", "🌽", "🍕", "🍩", "🎈", "🎸", "🚀", "⚓", "🔑", "🎁",
+];
+
+/// Emoji in a fingerprint; 6 bits each, so 60 bits in all. Fewer would let
+/// an attacker grind out a node ID with a matching fingerprint
+const FINGERPRINT_LENGTH: usize = 10;
+
+/// How long a new identity keeps announcing the key it replaced
+pub const TRANSITION_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Statement, signed with the old key, that a node moved to a new key
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct IdentityTransition {
+    pub old_node_id: String,
+    pub new_node_id: String,
+    pub timestamp: u64,
+    /// Base64url ed25519 signature by the old key over `statement()`
+    pub signature: String,
+}
+
+impl IdentityTransition {
+    pub fn new(old_key: &SecretKey, new_node_id: &PublicKey) -> Self {
+        let old_node_id = old_key.public().to_string();
+        let new_node_id = new_node_id.to_string();
+        let timestamp = crate::history::now_secs();
+        let signature =
+            old_key.sign(Self::statement(&old_node_id, &new_node_id, timestamp).as_bytes());
+        Self {
+            old_node_id,
+            new_node_id,
+            timestamp,
+            signature: URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+        }
+    }
+
+    fn statement(old_node_id: &str, new_node_id: &str, timestamp: u64) -> String {
+        format!(
+            "vegam-identity-transition:{}:{}:{}",
+            old_node_id, new_node_id, timestamp
+        )
+    }
+
+    /// Check the old key really signed this transition
+    pub fn verify(&self) -> Result<()> {
+        let old_key: PublicKey = self.old_node_id.parse()?;
+        let signature: [u8; 64] = URL_SAFE_NO_PAD
+            .decode(self.signature.as_bytes())?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid signature length"))?;
+        let statement = Self::statement(&self.old_node_id, &self.new_node_id, self.timestamp);
+        old_key
+            .verify(statement.as_bytes(), &Signature::from_bytes(&signature))
+            .map_err(|_| anyhow::anyhow!("Forged identity transition"))
+    }
+
+    /// Still worth announcing
+    pub fn is_current(&self) -> bool {
+        crate::history::now_secs().saturating_sub(self.timestamp) < TRANSITION_TTL_SECS
+    }
+}
+
+/// Replace the node key of `data_dir`, signing the change with `current`
+///
+/// The new key is used from the next time the node starts.
+pub async fn rotate(data_dir: &Path, current: &SecretKey) -> Result<IdentityTransition> {
+    let new_key = generate();
+    let transition = IdentityTransition::new(current, &new_key.public());
+    let data_dir = data_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || save_blocking(&data_dir, &new_key)).await??;
+    info!(
+        "Rotated node identity {} -> {}",
+        transition.old_node_id, transition.new_node_id
+    );
+    Ok(transition)
+}
+
+/// Load the node's secret key, creating and persisting one on first run
+pub async fn load_or_create(data_dir: &Path) -> Result<SecretKey> {
+    let data_dir = data_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || load_or_create_blocking(&data_dir)).await?
+}
+
+fn load_or_create_blocking(data_dir: &Path) -> Result<SecretKey> {
+    let account = keystore::account("node-secret-key", data_dir);
+    let key_file = data_dir.join(KEY_FILE);
+
+    match keystore::load(&account) {
+        Ok(Some(bytes)) => Ok(SecretKey::from_bytes(&bytes)),
+        Ok(None) => {
+            // Move a key from the fallback file into the keystore
+            let key = match read_key_file(&key_file)? {
+                Some(key) => key,
+                None => generate(),
+            };
+            match keystore::store(&account, &key.to_bytes()) {
+                Ok(()) => {
+                    if key_file.exists() {
+                        info!("Moved node secret key into the system keystore");
+                        std::fs::remove_file(&key_file)?;
+                    }
+                    return Ok(key);
+                }
+                Err(e) => warn!("Failed to save node key to the system keystore: {}", e),
+            }
+            write_key_file(&key_file, &key)?;
+            Ok(key)
+        }
+        Err(e) => {
+            info!("System keystore unavailable ({}), using key file", e);
+            match read_key_file(&key_file)? {
+                Some(key) => Ok(key),
+                None => {
+                    let key = generate();
+                    write_key_file(&key_file, &key)?;
+                    Ok(key)
+                }
+            }
+        }
+    }
+}
+
+/// Short emoji sequence derived from a node ID, the same on every device
+pub fn fingerprint(node_id: &str) -> String {
+    let digest = Sha256::digest(node_id.as_bytes());
+    let bits = u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+    (0..FINGERPRINT_LENGTH)
+        .map(|i| FINGERPRINT_EMOJI[(bits >> (58 - 6 * i)) as usize & 0x3f])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn save_blocking(data_dir: &Path, key: &SecretKey) -> Result<()> {
+    let account = keystore::account("node-secret-key", data_dir);
+    let key_file = data_dir.join(KEY_FILE);
+    match keystore::store(&account, &key.to_bytes()) {
+        Ok(()) => {
+            if key_file.exists() {
+                std::fs::remove_file(&key_file)?;
+            }
+            Ok(())
+        }
+        Err(e) => {
+            info!("System keystore unavailable ({}), using key file", e);
+            write_key_file(&key_file, key)
+        }
+    }
+}
+
+/// A fresh random key, e.g. for an ephemeral node that never stores it
+pub fn generate() -> SecretKey {
+    SecretKey::from_bytes(&random_key())
+}
+
+/// 32 random bytes from the OS
+pub fn random_key() -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut bytes);
+    bytes
+}
+
+fn read_key_file(path: &Path) -> Result<Option<SecretKey>> {
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            let bytes: [u8; 32] = bytes
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Invalid key file {:?}", path))?;
+            Ok(Some(SecretKey::from_bytes(&bytes)))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_key_file(path: &Path, key: &SecretKey) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(&key.to_bytes())?;
+        file.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    std::fs::write(path, key.to_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable_and_distinct() {
+        let a = SecretKey::from_bytes(&[1u8; 32]).public().to_string();
+        let b = SecretKey::from_bytes(&[2u8; 32]).public().to_string();
+
+        assert_eq!(fingerprint(&a), fingerprint(&a));
+        assert_ne!(fingerprint(&a), fingerprint(&b));
+        assert_eq!(fingerprint(&a).split(' ').count(), FINGERPRINT_LENGTH);
+    }
+
+    #[test]
+    fn test_identity_transition_verifies() {
+        let old = SecretKey::from_bytes(&[1u8; 32]);
+        let new = SecretKey::from_bytes(&[2u8; 32]).public();
+        let transition = IdentityTransition::new(&old, &new);
+        assert!(transition.verify().is_ok());
+        assert!(transition.is_current());
+
+        // Pointing a transition at another key breaks the signature
+        let forged = IdentityTransition {
+            new_node_id: SecretKey::from_bytes(&[3u8; 32]).public().to_string(),
+            ..transition
+        };
+        assert!(forged.verify().is_err());
+    }
+}
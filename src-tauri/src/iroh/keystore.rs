@@ -0,0 +1,113 @@
+// System keystore access
+//
+// Secrets are stored under the `vegam` service in the macOS/iOS Keychain,
+// Windows Credential Manager or Secret Service on Linux. On Android,
+// `SecretStore.kt` encrypts them with a key held by the Android Keystore and
+// keeps the ciphertext in private preferences. Other platforms have no
+// keystore and every call fails, so callers pick their own fallback.
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "windows",
+    target_os = "linux"
+))]
+mod platform {
+    use anyhow::Result;
+    use keyring::{Entry, Error};
+
+    const SERVICE: &str = "vegam";
+
+    pub fn load(account: &str) -> Result<Option<[u8; 32]>> {
+        match Entry::new(SERVICE, account)?.get_secret() {
+            Ok(secret) => Ok(Some(secret.as_slice().try_into()?)),
+            Err(Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn store(account: &str, key: &[u8; 32]) -> Result<()> {
+        Ok(Entry::new(SERVICE, account)?.set_secret(key)?)
+    }
+}
+
+/// Calls into `codes.imran.vegam.SecretStore`
+#[cfg(target_os = "android")]
+mod platform {
+    use anyhow::Result;
+    use jni::objects::{JByteArray, JValue};
+
+    use crate::platform::with_java_class;
+
+    const CLASS: &str = "codes.imran.vegam.SecretStore";
+
+    pub fn load(account: &str) -> Result<Option<[u8; 32]>> {
+        with_java_class(CLASS, |env, class, context| {
+            let account = env.new_string(account)?;
+            let secret = env
+                .call_static_method(
+                    class,
+                    "load",
+                    "(Landroid/content/Context;Ljava/lang/String;)[B",
+                    &[JValue::Object(context), JValue::Object(&account)],
+                )?
+                .l()?;
+            if secret.is_null() {
+                return Ok(None);
+            }
+            let secret = env.convert_byte_array(JByteArray::from(secret))?;
+            Ok(Some(secret.as_slice().try_into()?))
+        })
+    }
+
+    pub fn store(account: &str, key: &[u8; 32]) -> Result<()> {
+        with_java_class(CLASS, |env, class, context| {
+            let account = env.new_string(account)?;
+            let key = env.byte_array_from_slice(key)?;
+            env.call_static_method(
+                class,
+                "store",
+                "(Landroid/content/Context;Ljava/lang/String;[B)V",
+                &[
+                    JValue::Object(context),
+                    JValue::Object(&account),
+                    JValue::Object(&key),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "android"
+)))]
+mod platform {
+    use anyhow::Result;
+
+    pub fn load(_account: &str) -> Result<Option<[u8; 32]>> {
+        Err(anyhow::anyhow!("No keystore on this platform"))
+    }
+
+    pub fn store(_account: &str, _key: &[u8; 32]) -> Result<()> {
+        Err(anyhow::anyhow!("No keystore on this platform"))
+    }
+}
+
+/// Read a 32-byte secret; `Ok(None)` if the keystore works but has no entry
+pub use platform::load;
+/// Save a 32-byte secret, replacing any previous one
+pub use platform::store;
+
+/// Account name for a secret scoped to one node data dir (main and debug node)
+pub fn account(kind: &str, data_dir: &std::path::Path) -> String {
+    let dir = data_dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("iroh");
+    format!("{}:{}", kind, dir)
+}
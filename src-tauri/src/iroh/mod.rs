@@ -1,9 +1,29 @@
+pub mod access;
+pub mod at_rest;
+pub mod ble;
+pub mod connection;
+pub mod control;
 pub mod discovery;
+pub mod e2e;
+pub mod gc;
+pub mod identity;
+pub mod keystore;
+pub mod network;
 pub mod node;
+pub mod pairing;
+pub mod ping;
+pub mod room;
+pub mod spaces;
 pub mod ticket_codec;
 pub mod transfer;
 
-use std::{ops::Deref, path::PathBuf, str::FromStr, sync::Arc};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
 use anyhow::Result;
 use iroh::protocol::Router;
@@ -23,11 +43,19 @@ pub use iroh_blobs::api::blobs::Blobs;
 pub struct GossipTicket {
     pub topic_id: TopicId,
     pub node_id: EndpointId,
+    /// Payload key when the topic is a private room; without it the invited
+    /// device couldn't read or be read by the room
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_key: Option<room::RoomKey>,
 }
 
 impl GossipTicket {
     pub fn new(topic_id: TopicId, node_id: EndpointId) -> Self {
-        Self { topic_id, node_id }
+        Self {
+            topic_id,
+            node_id,
+            room_key: None,
+        }
     }
 }
 
@@ -44,8 +72,9 @@ impl GossipTicket {
 impl FromStr for GossipTicket {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self> {
+        // Display lowercases the encoding, so accept either case
         let bytes = data_encoding::BASE32
-            .decode(s.as_bytes())
+            .decode(s.trim().to_ascii_uppercase().as_bytes())
             .map_err(|_| anyhow::anyhow!("Invalid base32 string"))?;
         Self::from_bytes(&bytes)
     }
@@ -59,11 +88,18 @@ impl std::fmt::Display for GossipTicket {
     }
 }
 
+/// Gossip client holding one subscription per discovery topic.
+///
+/// The primary topic is the private room (or a random topic when not in a
+/// room); additional topics belong to peer groups.
 #[derive(Debug, Clone)]
 pub struct GossipClient {
     pub client: Gossip,
-    ticket: GossipTicket,
-    channel: Arc<RwLock<GossipChannel>>,
+    node_id: EndpointId,
+    ticket: Arc<RwLock<GossipTicket>>,
+    channels: Arc<RwLock<HashMap<TopicId, GossipChannel>>>,
+    // Payload keys of topics joined from a room code
+    room_keys: Arc<RwLock<HashMap<TopicId, room::RoomKey>>>,
 }
 
 impl Deref for GossipClient {
@@ -78,34 +114,111 @@ impl GossipClient {
     pub async fn new(gossip: Gossip, node_id: EndpointId) -> Result<Self> {
         let topic_id = TopicId::from_bytes(rand::random());
         let ticket = GossipTicket::new(topic_id, node_id);
-        let topic = gossip.subscribe(topic_id, vec![]).await?;
-        let (sender, receiver) = topic.split();
-        let gossip_chan = GossipChannel {
-            sender,
-            receiver: Some(receiver),
-        };
+        let gossip_chan = GossipChannel::subscribe(&gossip, topic_id, vec![]).await?;
 
         Ok(Self {
             client: gossip,
-            ticket,
-            channel: Arc::new(RwLock::new(gossip_chan)),
+            node_id,
+            ticket: Arc::new(RwLock::new(ticket)),
+            channels: Arc::new(RwLock::new(HashMap::from([(topic_id, gossip_chan)]))),
+            room_keys: Arc::new(RwLock::new(HashMap::new())),
         })
     }
 
-    pub async fn take_receiver(&self) -> Result<GossipReceiver> {
-        let mut chan = self.channel.write().await;
-        chan.receiver
+    pub async fn primary_topic(&self) -> TopicId {
+        self.ticket.read().await.topic_id
+    }
+
+    /// Replace the primary subscription with one on a different topic.
+    ///
+    /// The old topic is left once every clone of its sender and receiver
+    /// is dropped, so callers must stop the task consuming the old receiver.
+    pub async fn join(&self, topic_id: TopicId, bootstrap: Vec<EndpointId>) -> Result<()> {
+        let old_topic = self.primary_topic().await;
+        if old_topic != topic_id {
+            self.unsubscribe(&old_topic).await;
+        }
+        self.subscribe(topic_id, bootstrap).await?;
+        *self.ticket.write().await = GossipTicket::new(topic_id, self.node_id);
+        Ok(())
+    }
+
+    /// Add a subscription alongside the existing ones
+    pub async fn subscribe(&self, topic_id: TopicId, bootstrap: Vec<EndpointId>) -> Result<()> {
+        let gossip_chan = GossipChannel::subscribe(&self.client, topic_id, bootstrap).await?;
+        self.channels.write().await.insert(topic_id, gossip_chan);
+        Ok(())
+    }
+
+    /// Drop our handle on a topic; see `join` for when it is actually left
+    pub async fn unsubscribe(&self, topic_id: &TopicId) {
+        self.channels.write().await.remove(topic_id);
+        self.room_keys.write().await.remove(topic_id);
+    }
+
+    /// Seal payloads on a room topic with its key; set before discovery starts
+    pub async fn set_room_key(&self, topic_id: TopicId, key: room::RoomKey) {
+        self.room_keys.write().await.insert(topic_id, key);
+    }
+
+    pub async fn room_key(&self, topic_id: &TopicId) -> Option<room::RoomKey> {
+        self.room_keys.read().await.get(topic_id).copied()
+    }
+
+    pub async fn is_subscribed(&self, topic_id: &TopicId) -> bool {
+        self.channels.read().await.contains_key(topic_id)
+    }
+
+    pub async fn take_receiver(&self, topic_id: &TopicId) -> Result<GossipReceiver> {
+        let mut channels = self.channels.write().await;
+        channels
+            .get_mut(topic_id)
+            .ok_or(anyhow::anyhow!("Not subscribed to topic"))?
+            .receiver
             .take()
             .ok_or(anyhow::anyhow!("Receiver already taken"))
     }
 
-    pub async fn get_sender(&self) -> GossipSender {
-        let chan = self.channel.read().await;
-        chan.sender.clone()
+    pub async fn get_sender(&self, topic_id: &TopicId) -> Result<GossipSender> {
+        let channels = self.channels.read().await;
+        channels
+            .get(topic_id)
+            .map(|chan| chan.sender.clone())
+            .ok_or(anyhow::anyhow!("Not subscribed to topic"))
+    }
+
+    /// Broadcast the same payload on every subscribed topic, sealed for rooms
+    pub async fn broadcast_all(&self, bytes: Vec<u8>) {
+        let senders: Vec<(TopicId, GossipSender)> = self
+            .channels
+            .read()
+            .await
+            .iter()
+            .map(|(topic_id, chan)| (*topic_id, chan.sender.clone()))
+            .collect();
+
+        for (topic_id, sender) in senders {
+            let payload = match self.room_key(&topic_id).await {
+                Some(key) => match room::seal(&key, &bytes) {
+                    Ok(sealed) => sealed,
+                    Err(e) => {
+                        tracing::warn!("Failed to seal message: {}", e);
+                        continue;
+                    }
+                },
+                None => bytes.clone(),
+            };
+            if let Err(e) = sender.broadcast(payload.into()).await {
+                tracing::warn!("Failed to broadcast on topic: {}", e);
+            }
+        }
     }
 
-    pub fn ticket(&self) -> &GossipTicket {
-        &self.ticket
+    /// Invite to the primary topic, carrying its room key if it has one
+    pub async fn ticket(&self) -> GossipTicket {
+        let mut ticket = self.ticket.read().await.clone();
+        ticket.room_key = self.room_key(&ticket.topic_id).await;
+        ticket
     }
 }
 
@@ -115,6 +228,21 @@ pub struct GossipChannel {
     receiver: Option<GossipReceiver>,
 }
 
+impl GossipChannel {
+    async fn subscribe(
+        gossip: &Gossip,
+        topic_id: TopicId,
+        bootstrap: Vec<EndpointId>,
+    ) -> Result<Self> {
+        let topic = gossip.subscribe(topic_id, bootstrap).await?;
+        let (sender, receiver) = topic.split();
+        Ok(Self {
+            sender,
+            receiver: Some(receiver),
+        })
+    }
+}
+
 impl Deref for GossipChannel {
     type Target = GossipSender;
 
@@ -123,37 +251,183 @@ impl Deref for GossipChannel {
     }
 }
 
+/// Where blobs are kept
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobStoreKind {
+    /// In RAM; shared files are gone after a restart
+    Memory,
+    /// On disk under the node's data directory
+    #[default]
+    Filesystem,
+}
+
+/// Longest `Iroh::new` waits for the home relay (generous for mobile networks)
+const RELAY_WAIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Subdirectory of the node data dir holding the filesystem store
+pub const BLOB_STORE_DIR: &str = "blobs";
+
+/// How often the store reclaims blobs no longer protected by a tag
+const STORE_GC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Subdirectory of the node data dir holding the shared spaces (`spaces.rs`)
+pub const DOCS_DIR: &str = "docs";
+
+/// GC settings; `protect` keeps the content of shared space entries
+fn store_gc_config(protect: iroh_blobs::store::gc::ProtectCb) -> iroh_blobs::store::GcConfig {
+    iroh_blobs::store::GcConfig {
+        interval: STORE_GC_INTERVAL,
+        add_protected: Some(protect),
+    }
+}
+
+async fn load_fs_store(
+    dir: &Path,
+    protect: iroh_blobs::store::gc::ProtectCb,
+) -> Result<iroh_blobs::store::fs::FsStore> {
+    use iroh_blobs::store::fs::{options::Options, FsStore};
+
+    let mut options = Options::new(dir);
+    options.gc = Some(store_gc_config(protect));
+    FsStore::load_with_opts(dir.join("blobs.db"), options).await
+}
+
+/// Open the configured blob store
+///
+/// A filesystem store that can't be loaded (e.g. written by an incompatible
+/// iroh-blobs version) is moved aside and recreated instead of failing startup.
+async fn open_blob_store(
+    path: &Path,
+    kind: BlobStoreKind,
+    protect: iroh_blobs::store::gc::ProtectCb,
+) -> Result<iroh_blobs::api::Store> {
+    use iroh_blobs::store::mem::{MemStore, Options};
+
+    match kind {
+        BlobStoreKind::Memory => {
+            let store = MemStore::new_with_opts(Options {
+                gc_config: Some(store_gc_config(protect)),
+            });
+            Ok((*store).clone())
+        }
+        BlobStoreKind::Filesystem => {
+            let dir = path.join(BLOB_STORE_DIR);
+            match load_fs_store(&dir, protect.clone()).await {
+                Ok(store) => Ok((*store).clone()),
+                Err(e) => {
+                    let backup = path.join(format!(
+                        "{}.bak-{}",
+                        BLOB_STORE_DIR,
+                        std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)?
+                            .as_secs()
+                    ));
+                    tracing::warn!(
+                        "Failed to load blob store ({}), moving it to {:?}",
+                        e,
+                        backup
+                    );
+                    tokio::fs::rename(&dir, &backup).await?;
+                    Ok((*load_fs_store(&dir, protect).await?).clone())
+                }
+            }
+        }
+    }
+}
+
+/// App state and settings the node shares with the rest of the app
+#[derive(Clone)]
+pub struct NodeOptions {
+    pub access_policy: Arc<RwLock<access::AccessPolicy>>,
+    pub peers: Arc<RwLock<HashMap<String, crate::state::PeerInfo>>>,
+    pub blobs_served: Arc<RwLock<HashMap<iroh_blobs::Hash, u64>>>,
+    pub audit: crate::audit::AuditLog,
+    /// Use a throwaway key that is never stored
+    pub ephemeral: bool,
+    pub network: network::NetworkOptions,
+    pub bandwidth: crate::bandwidth::Bandwidth,
+    pub low_power: bool,
+    /// Open provider connections, which keep the app alive in the background
+    pub serving: Arc<std::sync::atomic::AtomicUsize>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Iroh {
-    #[allow(dead_code)]
     router: Router,
+    pub store: iroh_blobs::api::Store,
     pub blobs: Blobs,
     pub downloader: iroh_blobs::api::downloader::Downloader,
-    #[allow(dead_code)] // Keep for potential future direct endpoint access
     pub endpoint: iroh::Endpoint,
     pub node_addr: EndpointAddr,
     pub gossip: GossipClient,
+    /// Shared spaces (iroh-docs)
+    pub docs: iroh_docs::protocol::Docs,
+    /// Peer addresses we learned out of band, e.g. from tickets
+    pub address_book: iroh::discovery::static_provider::StaticProvider,
+    /// Global upload and download caps
+    pub bandwidth: crate::bandwidth::Bandwidth,
 }
 
 impl Iroh {
-    pub async fn new(path: PathBuf) -> Result<Self> {
+    pub async fn new(
+        path: PathBuf,
+        pairing: Option<pairing::Pairing>,
+        control: Option<control::Control>,
+        blob_store: BlobStoreKind,
+        options: NodeOptions,
+    ) -> Result<Self> {
+        let NodeOptions {
+            access_policy,
+            peers,
+            blobs_served,
+            audit,
+            ephemeral,
+            network,
+            bandwidth,
+            low_power,
+            serving,
+        } = options;
+
         // create dir if it doesn't already exist
         tokio::fs::create_dir_all(&path).await?;
 
-        // create endpoint with relay servers for NAT traversal
-        let endpoint = iroh::Endpoint::builder()
-            .relay_mode(iroh::RelayMode::Default)
-            .bind()
-            .await?;
+        // create endpoint with relay servers for NAT traversal (unless LAN-only);
+        // an ephemeral node's key is never stored
+        let secret_key = if ephemeral {
+            identity::generate()
+        } else {
+            identity::load_or_create(&path).await?
+        };
+        let network = &network.checked().await;
+
+        // Addresses from tickets, so peers are reachable without public discovery
+        let address_book = iroh::discovery::static_provider::StaticProvider::new();
+        let mut builder = network
+            .configure(iroh::Endpoint::builder().secret_key(secret_key))
+            .discovery(address_book.clone());
+        if low_power {
+            let mut transport = iroh::endpoint::TransportConfig::default();
+            transport.keep_alive_interval(Some(crate::power::LOW_POWER_KEEP_ALIVE));
+            builder = builder.transport_config(transport);
+        }
+        let endpoint = builder.bind().await?;
 
         // build the protocol router
         let mut builder = iroh::protocol::Router::builder(endpoint.clone());
 
-        // add iroh blobs - use in-memory store
-        use iroh_blobs::store::mem::MemStore;
-        use std::sync::Arc;
-        let store = MemStore::new();
-        let blobs_protocol = Arc::new(iroh_blobs::BlobsProtocol::new(&store, None));
+        // add iroh blobs
+        let (protect_handler, protect) = iroh_docs::engine::ProtectCallbackHandler::new();
+        let store = open_blob_store(&path, blob_store, protect).await?;
+        let events = access::provider_events(
+            access_policy,
+            peers,
+            blobs_served,
+            audit,
+            bandwidth.upload.clone(),
+            serving,
+        );
+        let blobs_protocol = Arc::new(iroh_blobs::BlobsProtocol::new(&store, Some(events)));
 
         builder = builder.accept(iroh_blobs::ALPN, blobs_protocol);
 
@@ -161,22 +435,56 @@ impl Iroh {
         let gossip = Gossip::builder().spawn(endpoint.clone());
         builder = builder.accept(iroh_gossip::ALPN, gossip.clone());
 
+        // add iroh docs for shared spaces, kept in memory like the blobs of an
+        // in-memory store
+        let docs = match blob_store {
+            BlobStoreKind::Memory => iroh_docs::protocol::Docs::memory(),
+            BlobStoreKind::Filesystem => iroh_docs::protocol::Docs::persistent(path.join(DOCS_DIR)),
+        }
+        .protect_handler(protect_handler)
+        .spawn(endpoint.clone(), store.clone(), gossip.clone())
+        .await?;
+        builder = builder.accept(iroh_docs::ALPN, docs.clone());
+
+        // add latency ping echo
+        builder = builder.accept(ping::ALPN, ping::Ping);
+
+        // add pairing handshakes and point-to-point transfer signalling
+        // (the app's; `vegam-cli` runs without them)
+        if let Some(pairing) = pairing {
+            builder = builder.accept(pairing::PIN_ALPN, pairing.pin_handler(endpoint.id()));
+            builder = builder.accept(pairing::ALPN, pairing);
+        }
+        if let Some(control) = control {
+            builder = builder.accept(control::ALPN, control);
+        }
+
         let router = builder.spawn();
 
         // Get API interface and downloader from store
         let blobs = store.blobs().clone();
         let downloader = store.downloader(&endpoint);
 
-        // Wait for relay connection to establish (longer timeout for mobile networks)
-        tracing::info!("Waiting for relay connection...");
-        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        // Wait for the home relay, but no longer than needed; a relay that
+        // lands later is reported by `network::spawn_relay_watch`
+        if !network.lan_only {
+            tracing::info!("Waiting for relay connection...");
+            if tokio::time::timeout(RELAY_WAIT_TIMEOUT, endpoint.online())
+                .await
+                .is_err()
+            {
+                tracing::warn!("No relay connection after {:?}", RELAY_WAIT_TIMEOUT);
+            }
+        }
 
         // Get node address with relay info (endpoint.addr() includes relay URLs)
         let node_id = endpoint.id();
-        let node_addr = endpoint.addr();
+        let node_addr = network.advertised(endpoint.addr());
 
         let relay_urls: Vec<_> = node_addr.relay_urls().collect();
-        if relay_urls.is_empty() {
+        if network.lan_only {
+            tracing::info!("LAN-only mode: relays disabled");
+        } else if relay_urls.is_empty() {
             tracing::warn!("No relay URLs found in node address - NAT traversal may fail");
             tracing::warn!("Check network connectivity and relay server accessibility");
         } else {
@@ -188,15 +496,46 @@ impl Iroh {
         Ok(Self {
             node_addr,
             router,
+            store,
             blobs,
             downloader,
             endpoint,
             gossip,
+            docs,
+            address_book,
+            bandwidth,
         })
     }
 
-    #[allow(dead_code)]
+    /// Close all protocol handlers and the endpoint
     pub async fn shutdown(&self) -> Result<(), String> {
         self.router.shutdown().await.map_err(|e| e.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_invite_carries_room_key() {
+        let node_id = iroh_base::SecretKey::from_bytes(&[1u8; 32]).public();
+        let code = "abcd-efgh-ijkl-mnop";
+        let mut ticket = GossipTicket::new(room::topic_for_room(code).unwrap(), node_id);
+        ticket.room_key = Some(room::key_for_room(code).unwrap());
+
+        // The joining device can open what room members seal
+        let parsed: GossipTicket = ticket.to_string().parse().unwrap();
+        assert_eq!(parsed.topic_id, ticket.topic_id);
+        let key = parsed.room_key.expect("invite lost the room key");
+        let sealed = room::seal(&room::key_for_room(code).unwrap(), b"announcement").unwrap();
+        assert_eq!(room::open(&key, &sealed).unwrap(), b"announcement");
+
+        // Invites to a plain topic don't carry a key and still parse
+        let plain: GossipTicket = GossipTicket::new(ticket.topic_id, node_id)
+            .to_string()
+            .parse()
+            .unwrap();
+        assert!(plain.room_key.is_none());
+    }
+}
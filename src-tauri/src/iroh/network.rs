@@ -0,0 +1,716 @@
+// Network change detection
+//
+// Watches the endpoint's own address (direct addresses and home relay) and
+// re-announces presence when it changes, e.g. after switching Wi-Fi networks.
+// The monitor also tracks which local address the OS routes outbound traffic
+// from; when that changes (Wi-Fi to hotspot, VPN up/down) the endpoint is told
+// to rebind and re-probe paths right away instead of waiting for stale
+// connections to time out.
+//
+// `NetworkOptions` holds the transport settings applied when the endpoint is
+// built. In LAN-only mode relays are disabled and the public DNS/pkarr address
+// lookup is replaced by mDNS, so no traffic leaves the local network.
+//
+// Otherwise the endpoint publishes its address to the n0 pkarr/DNS servers and
+// resolves peers there, so a node ID is enough to dial a peer whose address
+// changed since it handed out a ticket. Turning `public_discovery` off stops
+// publishing (nobody can look up where we are by node ID); peers are then
+// reached only through addresses from tickets, gossip and mDNS.
+//
+// The IPv6 mode decides which direct addresses we hand out in tickets and
+// invites. The endpoint still binds both families; peers just aren't told
+// about the addresses we don't want used, and the relay stays the fallback.
+//
+// A proxy (SOCKS5 or HTTP CONNECT) carries the relay connections and discovery
+// lookups, which run over TCP. Direct paths are QUIC over UDP and can't be
+// proxied; they are still attempted and fall back to the relay when blocked.
+//
+// Custom relays replace the public n0 relays and are all handed to the
+// endpoint, which picks the closest reachable one as home relay. The relay
+// health task reports home relay changes as `relay-changed` and probes the
+// configured relays; when the home relay stops answering while another one
+// does, it asks the endpoint to redo relay selection instead of waiting for
+// the next periodic net report.
+//
+// By default the endpoint measures its latency to every relay and picks the
+// closest. A preferred relay narrows the relay map to that one relay, e.g.
+// when both peers are in Asia but latency noise keeps choosing a US relay. If
+// the preferred relay doesn't answer when the node starts, selection falls
+// back to all relays. `probe_relays` measures TCP connect times so the UI can
+// show the options.
+//
+// `NetworkInfo` collects what the endpoint knows about its own reachability
+// (addresses, home relay, the latest net report) and the path to each known
+// peer, for the connection details screen and bug reports.
+// `run_diagnostics` first asks the endpoint for a fresh net report (UDP
+// reachability, whether the NAT mapping varies by destination) and probes the
+// relays, for support tickets.
+
+use anyhow::Result;
+use iroh_base::EndpointAddr;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use tauri::{AppHandle, Manager};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration};
+use tracing::info;
+
+use crate::events::AppEvent;
+use crate::iroh::connection::{self, PeerConnectionType};
+use crate::iroh::Iroh;
+use crate::state::{AppState, PeerInfo};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Public addresses used only to ask the OS for a route; nothing is sent
+const ROUTE_PROBE_V4: &str = "192.0.2.1:9";
+const ROUTE_PROBE_V6: &str = "[2001:db8::1]:9";
+
+/// Transport settings, applied the next time the node starts
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkOptions {
+    /// Never contact relays or public discovery servers; peers are found by mDNS
+    pub lan_only: bool,
+    /// Publish and look up node addresses through the n0 pkarr/DNS servers
+    pub public_discovery: bool,
+    pub ipv6: Ipv6Mode,
+    /// `socks5://`, `socks5h://`, `http://` or `https://` proxy for relay traffic
+    pub proxy_url: Option<String>,
+    /// Custom relays, preferred first; the public n0 relays when empty
+    pub relay_urls: Vec<String>,
+    /// Always use this relay, one of `relay_candidates`; chosen by latency when unset
+    pub preferred_relay: Option<String>,
+}
+
+/// How often the relay health task runs
+const RELAY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const RELAY_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Check a user-entered relay URL
+pub fn parse_relay_url(relay_url: &str) -> Result<iroh::RelayUrl> {
+    let url = url::Url::parse(relay_url.trim())?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err(anyhow::anyhow!(
+            "Unsupported relay scheme '{}', expected https or http",
+            url.scheme()
+        ));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(anyhow::anyhow!("Relay URL has no host"));
+    }
+    Ok(url.into())
+}
+
+/// How long diagnostics wait for a fresh net report
+const NET_REPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Result of `run_diagnostics`
+#[derive(Clone, Debug, Serialize)]
+pub struct NetworkDiagnostics {
+    /// Unix seconds
+    pub generated_at: u64,
+    /// False if no new net report arrived in time and the last one is shown
+    pub report_fresh: bool,
+    pub udp_v4: Option<bool>,
+    pub udp_v6: Option<bool>,
+    /// Port-restricted (symmetric) NAT: our public port differs per destination
+    pub mapping_varies_by_dest_ipv4: Option<bool>,
+    pub mapping_varies_by_dest_ipv6: Option<bool>,
+    /// Closest relay according to the net report
+    pub preferred_relay: Option<String>,
+    pub relays: Vec<RelayProbe>,
+    pub network: NetworkInfo,
+}
+
+/// Connect time to a relay; `latency_ms` is unset when it didn't answer
+#[derive(Clone, Debug, Serialize)]
+pub struct RelayProbe {
+    pub url: String,
+    pub latency_ms: Option<u32>,
+}
+
+/// Re-run the endpoint's net report and probe the relays
+///
+/// Hairpinning isn't reported: iroh's net report no longer measures it.
+pub async fn run_diagnostics(
+    iroh: &Iroh,
+    options: &NetworkOptions,
+    peers: &[PeerInfo],
+) -> NetworkDiagnostics {
+    use iroh::Watcher;
+
+    let mut watcher = iroh.endpoint.net_report();
+    let report_fresh = if options.lan_only {
+        false
+    } else {
+        iroh.endpoint.network_change().await;
+        tokio::time::timeout(NET_REPORT_TIMEOUT, watcher.updated())
+            .await
+            .is_ok_and(|r| r.is_ok())
+    };
+    let relays = if options.lan_only {
+        Vec::new()
+    } else {
+        probe_relays(options.relay_candidates()).await
+    };
+
+    let report = watcher.get();
+    NetworkDiagnostics {
+        generated_at: crate::history::now_secs(),
+        report_fresh,
+        udp_v4: report.as_ref().map(|r| r.udp_v4),
+        udp_v6: report.as_ref().map(|r| r.udp_v6),
+        mapping_varies_by_dest_ipv4: report.as_ref().and_then(|r| r.mapping_varies_by_dest_ipv4),
+        mapping_varies_by_dest_ipv6: report.as_ref().and_then(|r| r.mapping_varies_by_dest_ipv6),
+        preferred_relay: report
+            .as_ref()
+            .and_then(|r| r.preferred_relay.as_ref())
+            .map(ToString::to_string),
+        relays,
+        network: NetworkInfo::collect(iroh, options, peers),
+    }
+}
+
+/// Payload of `relay-changed`
+#[derive(Clone, Debug, Serialize)]
+pub struct RelayChange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl Default for NetworkOptions {
+    fn default() -> Self {
+        Self {
+            lan_only: false,
+            public_discovery: true,
+            ipv6: Ipv6Mode::default(),
+            proxy_url: None,
+            relay_urls: Vec::new(),
+            preferred_relay: None,
+        }
+    }
+}
+
+const PROXY_SCHEMES: [&str; 4] = ["socks5", "socks5h", "http", "https"];
+
+/// Check a user-entered proxy URL
+pub fn parse_proxy_url(proxy_url: &str) -> Result<url::Url> {
+    let url = url::Url::parse(proxy_url.trim())?;
+    if !PROXY_SCHEMES.contains(&url.scheme()) {
+        return Err(anyhow::anyhow!(
+            "Unsupported proxy scheme '{}', expected one of {}",
+            url.scheme(),
+            PROXY_SCHEMES.join(", ")
+        ));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(anyhow::anyhow!("Proxy URL has no host"));
+    }
+    Ok(url)
+}
+
+/// Which address family peers should use to reach us directly
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ipv6Mode {
+    /// Advertise IPv4 and IPv6 addresses
+    #[default]
+    Enabled,
+    /// Advertise only IPv6 addresses when we have any, e.g. behind CGNAT IPv4
+    Preferred,
+    /// Never advertise IPv6 addresses
+    Disabled,
+}
+
+/// Local sockets the endpoint is bound to, by address family
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BoundSockets {
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+}
+
+impl BoundSockets {
+    pub fn new(sockets: impl IntoIterator<Item = SocketAddr>) -> Self {
+        let (ipv6, ipv4): (Vec<_>, Vec<_>) = sockets.into_iter().partition(|s| s.is_ipv6());
+        Self {
+            ipv4: ipv4.iter().map(ToString::to_string).collect(),
+            ipv6: ipv6.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// Best guess at the NAT in front of us, from the endpoint's net report
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NatType {
+    /// No net report yet, or it couldn't tell
+    Unknown,
+    /// No UDP out at all; only the relay works
+    UdpBlocked,
+    /// Our public address is a local one
+    Open,
+    /// Same public port for every destination; hole punching usually works
+    EndpointIndependent,
+    /// Public port varies by destination; direct paths are unlikely
+    Symmetric,
+}
+
+impl NatType {
+    pub fn guess(udp: bool, public_is_local: bool, mapping_varies: Option<bool>) -> Self {
+        if !udp {
+            return Self::UdpBlocked;
+        }
+        if public_is_local {
+            return Self::Open;
+        }
+        match mapping_varies {
+            Some(true) => Self::Symmetric,
+            Some(false) => Self::EndpointIndependent,
+            None => Self::Unknown,
+        }
+    }
+}
+
+/// Path to one known peer
+#[derive(Clone, Debug, Serialize)]
+pub struct PeerPath {
+    pub node_id: String,
+    pub device_name: String,
+    pub connection_type: PeerConnectionType,
+    /// Endpoint's current estimate, else the rolling average from `ping_peer`
+    pub latency_ms: Option<u32>,
+}
+
+/// Snapshot of our reachability and peer paths
+#[derive(Clone, Debug, Serialize)]
+pub struct NetworkInfo {
+    pub node_id: String,
+    pub direct_addresses: Vec<String>,
+    pub home_relay: Option<String>,
+    pub bound_sockets: BoundSockets,
+    pub public_ipv4: Option<String>,
+    pub public_ipv6: Option<String>,
+    pub nat_type: NatType,
+    pub captive_portal: Option<bool>,
+    pub lan_only: bool,
+    pub peers: Vec<PeerPath>,
+}
+
+impl NetworkInfo {
+    pub fn collect<'a>(
+        iroh: &Iroh,
+        options: &NetworkOptions,
+        peers: impl IntoIterator<Item = &'a PeerInfo>,
+    ) -> Self {
+        use iroh::Watcher;
+
+        let addr = iroh.endpoint.addr();
+        let bound: Vec<SocketAddr> = iroh.endpoint.bound_sockets().into_iter().collect();
+        let report = iroh.endpoint.net_report().get();
+
+        let (public_ipv4, public_ipv6, nat_type, captive_portal) = match report {
+            Some(report) => {
+                let public_is_local = report
+                    .global_v4
+                    .map(|a| bound.iter().any(|b| b.ip() == IpAddr::V4(*a.ip())))
+                    .unwrap_or(false)
+                    || report
+                        .global_v6
+                        .map(|a| bound.iter().any(|b| b.ip() == IpAddr::V6(*a.ip())))
+                        .unwrap_or(false);
+                let mapping_varies = report
+                    .mapping_varies_by_dest_ipv4
+                    .or(report.mapping_varies_by_dest_ipv6);
+                (
+                    report.global_v4.map(|a| a.to_string()),
+                    report.global_v6.map(|a| a.to_string()),
+                    NatType::guess(
+                        report.udp_v4 || report.udp_v6,
+                        public_is_local,
+                        mapping_varies,
+                    ),
+                    report.captive_portal,
+                )
+            }
+            None => (None, None, NatType::Unknown, None),
+        };
+
+        let peers = peers
+            .into_iter()
+            .map(|peer| {
+                let endpoint_id = peer.node_id.parse::<iroh_base::EndpointId>().ok();
+                let latency_ms = endpoint_id
+                    .and_then(|id| iroh.endpoint.latency(id))
+                    .map(|rtt| rtt.as_millis().min(u32::MAX as u128) as u32)
+                    .or(peer.latency_ms);
+                PeerPath {
+                    node_id: peer.node_id.clone(),
+                    device_name: peer.device_name.clone(),
+                    connection_type: endpoint_id
+                        .map(|id| connection::connection_type(iroh, id))
+                        .unwrap_or_default(),
+                    latency_ms,
+                }
+            })
+            .collect();
+
+        Self {
+            node_id: addr.id.to_string(),
+            direct_addresses: addr.ip_addrs().map(ToString::to_string).collect(),
+            home_relay: addr.relay_urls().next().map(ToString::to_string),
+            bound_sockets: BoundSockets::new(bound),
+            public_ipv4,
+            public_ipv6,
+            nat_type,
+            captive_portal,
+            lan_only: options.lan_only,
+            peers,
+        }
+    }
+}
+
+impl NetworkOptions {
+    pub fn configure(&self, builder: iroh::endpoint::Builder) -> iroh::endpoint::Builder {
+        if !self.lan_only {
+            let builder = if self.public_discovery {
+                builder
+                    .clear_discovery()
+                    .discovery(iroh::discovery::pkarr::PkarrPublisher::n0_dns())
+                    .discovery(iroh::discovery::dns::DnsDiscovery::n0_dns())
+            } else {
+                builder
+                    .clear_discovery()
+                    .discovery(iroh::discovery::mdns::MdnsDiscovery::builder())
+            };
+            let relays = match self.preferred() {
+                Some(preferred) => vec![preferred],
+                None => self.custom_relays(),
+            };
+            let builder = if relays.is_empty() {
+                builder.relay_mode(iroh::RelayMode::Default)
+            } else {
+                builder.relay_mode(iroh::RelayMode::Custom(iroh::RelayMap::from_iter(relays)))
+            };
+            // Validated when saved; a proxy that stopped parsing is skipped
+            return match self.proxy_url.as_deref().map(parse_proxy_url) {
+                Some(Ok(url)) => builder.proxy_url(url),
+                Some(Err(e)) => {
+                    tracing::warn!("Ignoring proxy setting: {}", e);
+                    builder
+                }
+                None => builder,
+            };
+        }
+        builder
+            .relay_mode(iroh::RelayMode::Disabled)
+            .clear_discovery()
+            .discovery(iroh::discovery::mdns::MdnsDiscovery::builder())
+    }
+
+    /// Relays the node may use: the custom ones, or the public n0 relays
+    pub fn relay_candidates(&self) -> Vec<iroh::RelayUrl> {
+        let custom = self.custom_relays();
+        if !custom.is_empty() {
+            return custom;
+        }
+        iroh::defaults::prod::default_relay_map().urls::<Vec<_>>()
+    }
+
+    fn preferred(&self) -> Option<iroh::RelayUrl> {
+        let preferred = parse_relay_url(self.preferred_relay.as_deref()?).ok()?;
+        self.relay_candidates()
+            .contains(&preferred)
+            .then_some(preferred)
+    }
+
+    /// These options without the preferred relay if it doesn't answer, so
+    /// the node can still pick another one
+    pub async fn checked(&self) -> Self {
+        let Some(preferred) = self.preferred() else {
+            return self.clone();
+        };
+        // Behind a proxy a direct probe says nothing
+        if self.lan_only || self.proxy_url.is_some() || relay_latency(&preferred).await.is_some() {
+            return self.clone();
+        }
+        tracing::warn!(
+            "Preferred relay {} is unreachable, using all relays",
+            preferred
+        );
+        Self {
+            preferred_relay: None,
+            ..self.clone()
+        }
+    }
+
+    /// Configured relays that parse; validated when saved
+    pub fn custom_relays(&self) -> Vec<iroh::RelayUrl> {
+        self.relay_urls
+            .iter()
+            .filter_map(|url| match parse_relay_url(url) {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    tracing::warn!("Ignoring relay {}: {}", url, e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Our address with the direct addresses the IPv6 mode allows
+    pub fn advertised(&self, addr: EndpointAddr) -> EndpointAddr {
+        let has_ipv6 = addr.ip_addrs().any(|a| a.is_ipv6());
+        let keep = |a: &SocketAddr| match self.ipv6 {
+            Ipv6Mode::Enabled => true,
+            Ipv6Mode::Preferred => a.is_ipv6() || !has_ipv6,
+            Ipv6Mode::Disabled => a.is_ipv4(),
+        };
+
+        let mut advertised = EndpointAddr::new(addr.id);
+        for url in addr.relay_urls() {
+            advertised = advertised.with_relay_url(url.clone());
+        }
+        for ip in addr.ip_addrs().filter(|a| keep(a)) {
+            advertised = advertised.with_ip_addr(*ip);
+        }
+        advertised
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_url_validation() {
+        assert!(parse_proxy_url("socks5://127.0.0.1:9050").is_ok());
+        assert!(parse_proxy_url(" http://proxy.example:3128 ").is_ok());
+        assert!(parse_proxy_url("ftp://proxy.example").is_err());
+        assert!(parse_proxy_url("socks5://").is_err());
+        assert!(parse_proxy_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_relay_url_validation() {
+        assert!(parse_relay_url("https://relay.example.com").is_ok());
+        assert!(parse_relay_url("http://10.0.0.2:3340").is_ok());
+        assert!(parse_relay_url("socks5://relay.example.com").is_err());
+        assert!(parse_relay_url("relay.example.com").is_err());
+
+        let options = NetworkOptions {
+            relay_urls: vec!["https://relay.example.com".into(), "nope".into()],
+            ..Default::default()
+        };
+        assert_eq!(options.custom_relays().len(), 1);
+    }
+
+    #[test]
+    fn test_nat_type_guess() {
+        assert_eq!(
+            NatType::guess(false, true, Some(false)),
+            NatType::UdpBlocked
+        );
+        assert_eq!(NatType::guess(true, true, Some(true)), NatType::Open);
+        assert_eq!(NatType::guess(true, false, Some(true)), NatType::Symmetric);
+        assert_eq!(
+            NatType::guess(true, false, Some(false)),
+            NatType::EndpointIndependent
+        );
+        assert_eq!(NatType::guess(true, false, None), NatType::Unknown);
+    }
+
+    #[test]
+    fn test_ipv6_mode_filters_advertised_addrs() {
+        let id = iroh_base::SecretKey::from_bytes(&[1u8; 32]).public();
+        let v4: SocketAddr = "192.0.2.1:4433".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:4433".parse().unwrap();
+        let addr = EndpointAddr::new(id).with_ip_addr(v4).with_ip_addr(v6);
+        let ips = |options: NetworkOptions| -> Vec<SocketAddr> {
+            options
+                .advertised(addr.clone())
+                .ip_addrs()
+                .copied()
+                .collect()
+        };
+
+        assert_eq!(ips(NetworkOptions::default()).len(), 2);
+        let disabled = NetworkOptions {
+            ipv6: Ipv6Mode::Disabled,
+            ..Default::default()
+        };
+        assert_eq!(ips(disabled), vec![v4]);
+        let preferred = NetworkOptions {
+            ipv6: Ipv6Mode::Preferred,
+            ..Default::default()
+        };
+        assert_eq!(ips(preferred.clone()), vec![v6]);
+
+        // Without IPv6, preferring it keeps IPv4 reachable
+        let v4_only = EndpointAddr::new(id).with_ip_addr(v4);
+        assert_eq!(preferred.advertised(v4_only).ip_addrs().count(), 1);
+    }
+}
+
+/// Spawn a task that emits `relay-connected` with the relay URL once the
+/// endpoint reaches its home relay, right away if it already has
+pub fn spawn_relay_watch(iroh: Iroh, handle: AppHandle) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        iroh.endpoint.online().await;
+        let Some(relay_url) = iroh
+            .endpoint
+            .addr()
+            .relay_urls()
+            .next()
+            .map(|u| u.to_string())
+        else {
+            return;
+        };
+        info!("Relay connected: {}", relay_url);
+        let _ = AppEvent::RelayConnected(relay_url).emit(&handle);
+    })
+}
+
+/// How long a TCP connection to a relay takes; `None` if it doesn't answer
+async fn relay_latency(relay_url: &iroh::RelayUrl) -> Option<Duration> {
+    let host = relay_url.host_str()?;
+    let port = relay_url.port_or_known_default().unwrap_or(443);
+    let start = std::time::Instant::now();
+    match tokio::time::timeout(
+        RELAY_PROBE_TIMEOUT,
+        tokio::net::TcpStream::connect((host, port)),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Some(start.elapsed()),
+        _ => None,
+    }
+}
+
+async fn relay_reachable(relay_url: &iroh::RelayUrl) -> bool {
+    relay_latency(relay_url).await.is_some()
+}
+
+/// Probe relays concurrently, fastest first and unreachable ones last
+pub async fn probe_relays(relays: Vec<iroh::RelayUrl>) -> Vec<RelayProbe> {
+    let mut probes = tokio::task::JoinSet::new();
+    for relay in relays {
+        probes.spawn(async move {
+            let latency = relay_latency(&relay).await;
+            RelayProbe {
+                url: relay.to_string(),
+                latency_ms: latency.map(|l| l.as_millis().min(u32::MAX as u128) as u32),
+            }
+        });
+    }
+    let mut results = probes.join_all().await;
+    results.sort_by_key(|p| p.latency_ms.unwrap_or(u32::MAX));
+    results
+}
+
+/// Spawn a task that emits `relay-changed` when the home relay changes and
+/// fails over between the configured relays
+///
+/// Probing is skipped behind a proxy, where direct connections to a relay
+/// aren't expected to work.
+pub fn spawn_relay_health(
+    iroh: Iroh,
+    handle: AppHandle,
+    options: NetworkOptions,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        // A preferred relay is the only one in the map
+        let relays = if options.proxy_url.is_some() || options.preferred_relay.is_some() {
+            Vec::new()
+        } else {
+            options.custom_relays()
+        };
+        let home_relay = || iroh.endpoint.addr().relay_urls().next().cloned();
+        let mut timer = interval(RELAY_CHECK_INTERVAL);
+        let mut last_home = home_relay();
+
+        loop {
+            timer.tick().await;
+
+            let home = home_relay();
+            if home != last_home {
+                info!("Home relay changed: {:?} -> {:?}", last_home, home);
+                let change = RelayChange {
+                    from: last_home.as_ref().map(ToString::to_string),
+                    to: home.as_ref().map(ToString::to_string),
+                };
+                let _ = AppEvent::RelayChanged(change).emit(&handle);
+                last_home = home.clone();
+            }
+
+            if relays.len() < 2 {
+                continue;
+            }
+            if let Some(home) = &home {
+                if relay_reachable(home).await {
+                    continue;
+                }
+                tracing::warn!("Home relay {} is unreachable", home);
+            }
+            for relay in relays.iter().filter(|r| Some(*r) != home.as_ref()) {
+                if relay_reachable(relay).await {
+                    info!("Relay {} reachable, redoing relay selection", relay);
+                    iroh.endpoint.network_change().await;
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// Local source addresses of the default IPv4 and IPv6 routes
+///
+/// Connecting a UDP socket only selects a route, so no packet leaves the host.
+pub fn local_routes() -> (Option<IpAddr>, Option<IpAddr>) {
+    let probe = |bind: &str, target: &str| {
+        let socket = UdpSocket::bind(bind).ok()?;
+        socket.connect(target).ok()?;
+        socket.local_addr().ok().map(|a| a.ip())
+    };
+    (
+        probe("0.0.0.0:0", ROUTE_PROBE_V4),
+        probe("[::]:0", ROUTE_PROBE_V6),
+    )
+}
+
+/// Spawn a task that rebinds on route changes and triggers an announcement
+/// whenever our address changes
+///
+/// Emits `network-changed` with the new relay URLs.
+pub fn spawn_network_monitor(iroh: Iroh, handle: AppHandle) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut timer = interval(POLL_INTERVAL);
+        let mut last_addr = iroh.endpoint.addr();
+        let mut last_routes = local_routes();
+
+        loop {
+            timer.tick().await;
+
+            let routes = local_routes();
+            if routes != last_routes {
+                info!("Local routes changed: {:?} -> {:?}", last_routes, routes);
+                last_routes = routes;
+                iroh.endpoint.network_change().await;
+                // Peers may have lost us with the old path
+                handle.state::<AppState>().trigger_announcement();
+            }
+
+            let addr = iroh.endpoint.addr();
+            if addr == last_addr {
+                continue;
+            }
+
+            let relay_urls: Vec<String> = addr.relay_urls().map(|u| u.to_string()).collect();
+            info!("Network changed, relays now: {:?}", relay_urls);
+            last_addr = addr;
+
+            handle.state::<AppState>().trigger_announcement();
+            let _ = AppEvent::NetworkChanged(relay_urls).emit(&handle);
+        }
+    })
+}
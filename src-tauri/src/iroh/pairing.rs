@@ -0,0 +1,524 @@
+// Mutual pairing handshake
+//
+// One device calls `start_pairing` and shows the resulting code (as text or a
+// QR code). The other device scans it and dials the first over the pairing
+// ALPN, proving it saw the code by sending the one-time secret. Both sides
+// then persist each other's node ID and name as trusted peers. The secret is
+// used up by the first device that pairs with it; a wrong one only counts
+// against the node that sent it, which is refused after a few, so another
+// device on the network can't burn the code before the right one dials in.
+//
+// For devices that can't scan a code, `start_pin_pairing` shows a 6-digit PIN
+// instead. The other device picks us from its peer list, the user types the
+// PIN, and both sides run SPAKE2 over the `vegam/pair-pin/0` ALPN with the PIN
+// as the password and both node IDs as the identity. Each side then proves it
+// derived the same key before trusting the other, so a device announcing
+// itself under someone else's name on a busy topic can't complete the
+// exchange without having seen the PIN. A PIN allows a single attempt.
+
+use anyhow::Result;
+use iroh::endpoint::{Connection, RecvStream, SendStream};
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::Endpoint;
+use iroh_base::EndpointId;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+use tauri::{AppHandle, Manager};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::events::AppEvent;
+use crate::settings::TrustedPeer;
+use crate::state::AppState;
+
+pub const ALPN: &[u8] = b"vegam/pair/0";
+pub const PIN_ALPN: &[u8] = b"vegam/pair-pin/0";
+
+/// How long a displayed pairing code stays valid
+pub const PAIRING_TTL: Duration = Duration::from_secs(300);
+
+/// Prefix of the text encoded in pairing codes / QR codes
+const CODE_PREFIX: &str = "vegam-pair:";
+
+const PIN_LENGTH: usize = 6;
+
+const MAX_MESSAGE_SIZE: usize = 1024;
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Wrong secrets a node may send before it is refused for this code
+const MAX_ATTEMPTS_PER_NODE: u32 = 3;
+/// Nodes whose wrong secrets are counted; past this they are only refused
+const MAX_TRACKED_NODES: usize = 1024;
+
+/// Secret waiting for the other device to dial in
+#[derive(Clone, Debug)]
+pub struct PendingPairing {
+    pub secret: String,
+    pub expires_at: Instant,
+    /// Wrong secrets per dialing node
+    failures: HashMap<EndpointId, u32>,
+}
+
+impl PendingPairing {
+    pub fn generate() -> Self {
+        Self {
+            secret: crate::iroh::room::generate_room_code().replace('-', ""),
+            expires_at: Instant::now() + PAIRING_TTL,
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Whether `remote` sent the right secret, counting a wrong one against it
+    pub fn check(&mut self, remote: EndpointId, secret: &str) -> bool {
+        if self.expires_at <= Instant::now() {
+            return false;
+        }
+        if self
+            .failures
+            .get(&remote)
+            .is_some_and(|n| *n >= MAX_ATTEMPTS_PER_NODE)
+        {
+            return false;
+        }
+        if bool::from(self.secret.as_bytes().ct_eq(secret.as_bytes())) {
+            return true;
+        }
+        if self.failures.len() < MAX_TRACKED_NODES || self.failures.contains_key(&remote) {
+            *self.failures.entry(remote).or_default() += 1;
+        }
+        false
+    }
+}
+
+/// PIN waiting for the other device to dial in
+#[derive(Clone, Debug)]
+pub struct PendingPin {
+    pub pin: String,
+    pub expires_at: Instant,
+}
+
+impl PendingPin {
+    pub fn generate() -> Self {
+        use rand::Rng;
+        Self {
+            pin: format!("{:06}", rand::thread_rng().gen_range(0..1_000_000)),
+            expires_at: Instant::now() + PAIRING_TTL,
+        }
+    }
+}
+
+/// Check a PIN typed by the user
+pub fn validate_pin(pin: &str) -> Result<()> {
+    if pin.len() != PIN_LENGTH || !pin.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(anyhow::anyhow!("PIN must be {} digits", PIN_LENGTH));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PairRequest {
+    secret: String,
+    device_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PairResponse {
+    accepted: bool,
+    device_name: String,
+}
+
+/// First PIN pairing message, from the device that typed the PIN
+#[derive(Debug, Serialize, Deserialize)]
+struct PinHello {
+    spake: Vec<u8>,
+    device_name: String,
+}
+
+/// Reply from the device showing the PIN; `confirm` is empty when it has no PIN
+#[derive(Debug, Serialize, Deserialize)]
+struct PinReply {
+    spake: Vec<u8>,
+    device_name: String,
+    confirm: Vec<u8>,
+}
+
+/// Key confirmation from the device that typed the PIN
+#[derive(Debug, Serialize, Deserialize)]
+struct PinConfirm {
+    confirm: Vec<u8>,
+}
+
+/// Text to display or encode as a QR code for the other device
+pub fn pairing_code(node_id: &EndpointId, pending: &PendingPairing) -> String {
+    format!("{}{}:{}", CODE_PREFIX, node_id, pending.secret)
+}
+
+/// Split a scanned pairing code into the inviting node and its secret
+pub fn parse_pairing_code(code: &str) -> Result<(EndpointId, String)> {
+    let rest = code
+        .trim()
+        .strip_prefix(CODE_PREFIX)
+        .ok_or_else(|| anyhow::anyhow!("Not a pairing code"))?;
+    let (node_id, secret) = rest
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Malformed pairing code"))?;
+    if secret.is_empty() {
+        return Err(anyhow::anyhow!("Malformed pairing code"));
+    }
+    Ok((node_id.parse()?, secret.to_string()))
+}
+
+/// Handler for devices dialing in with our pairing code
+#[derive(Debug, Clone)]
+pub struct Pairing {
+    handle: AppHandle,
+}
+
+impl Pairing {
+    pub fn new(handle: AppHandle) -> Self {
+        Self { handle }
+    }
+
+    async fn respond(&self, connection: &Connection) -> Result<()> {
+        let remote = connection.remote_id();
+        let (mut send, mut recv) = connection.accept_bi().await?;
+        let request: PairRequest =
+            serde_json::from_slice(&recv.read_to_end(MAX_MESSAGE_SIZE).await?)?;
+
+        let state = self.handle.state::<AppState>();
+
+        let blocked = state.is_blocked(&remote.to_string()).await;
+        let accepted = {
+            let mut pairing = state.pairing.write().await;
+            let accepted = !blocked
+                && pairing
+                    .as_mut()
+                    .is_some_and(|p| p.check(remote, &request.secret));
+            // Used up by the device that pairs with it
+            if accepted {
+                *pairing = None;
+            }
+            accepted
+        };
+
+        let response = PairResponse {
+            accepted,
+            device_name: state.get_settings().await.display_name(),
+        };
+        send.write_all(&serde_json::to_vec(&response)?).await?;
+        send.finish()?;
+
+        if !accepted {
+            warn!("Rejected pairing attempt from {}", remote);
+            return Ok(());
+        }
+
+        complete(&self.handle, remote, request.device_name).await
+    }
+}
+
+impl ProtocolHandler for Pairing {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        if let Err(e) = self.respond(&connection).await {
+            warn!("Pairing handshake failed: {}", e);
+        }
+        connection.closed().await;
+        Ok(())
+    }
+}
+
+/// Handler for devices dialing in to pair with our PIN
+#[derive(Debug, Clone)]
+pub struct PinPairing {
+    handle: AppHandle,
+    local: EndpointId,
+}
+
+impl Pairing {
+    /// PIN pairing handler for the endpoint `local`
+    pub fn pin_handler(&self, local: EndpointId) -> PinPairing {
+        PinPairing {
+            handle: self.handle.clone(),
+            local,
+        }
+    }
+}
+
+impl PinPairing {
+    async fn respond(&self, connection: &Connection) -> Result<()> {
+        let remote = connection.remote_id();
+        let (mut send, mut recv) = connection.accept_bi().await?;
+        let hello: PinHello = read_frame(&mut recv).await?;
+
+        let state = self.handle.state::<AppState>();
+        let device_name = state.get_settings().await.display_name();
+
+        // The PIN is single use: a wrong guess ends this pairing attempt
+        let pending = state
+            .pin_pairing
+            .write()
+            .await
+            .take()
+            .filter(|p| p.expires_at > Instant::now());
+        let pending = match pending {
+            Some(pending) if !state.is_blocked(&remote.to_string()).await => pending,
+            _ => {
+                let reply = PinReply {
+                    spake: Vec::new(),
+                    device_name,
+                    confirm: Vec::new(),
+                };
+                write_frame(&mut send, &reply).await?;
+                send.finish()?;
+                warn!("Rejected PIN pairing attempt from {}", remote);
+                return Ok(());
+            }
+        };
+
+        let (spake, outbound) = start_spake(&pending.pin, &self.local, &remote);
+        let key = finish_spake(spake, &hello.spake)?;
+        let reply = PinReply {
+            spake: outbound,
+            device_name,
+            confirm: confirmation(&key, b"responder"),
+        };
+        write_frame(&mut send, &reply).await?;
+
+        let confirm: PinConfirm = read_frame(&mut recv).await?;
+        if confirm.confirm != confirmation(&key, b"initiator") {
+            warn!("PIN pairing with {} failed: wrong PIN", remote);
+            connection.close(1u32.into(), b"wrong pin");
+            return Ok(());
+        }
+        send.finish()?;
+
+        complete(&self.handle, remote, hello.device_name).await
+    }
+}
+
+impl ProtocolHandler for PinPairing {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        if let Err(e) = self.respond(&connection).await {
+            warn!("PIN pairing handshake failed: {}", e);
+        }
+        connection.closed().await;
+        Ok(())
+    }
+}
+
+/// Pair with `remote`, which is showing `pin`
+pub async fn pair_with_pin(
+    endpoint: &Endpoint,
+    remote: EndpointId,
+    pin: &str,
+    handle: &AppHandle,
+) -> Result<TrustedPeer> {
+    validate_pin(pin)?;
+    let state = handle.state::<AppState>();
+    let device_name = state.get_settings().await.display_name();
+
+    let remote_name = tokio::time::timeout(PAIRING_TIMEOUT, async {
+        let conn = endpoint.connect(remote, PIN_ALPN).await?;
+        let (mut send, mut recv) = conn.open_bi().await?;
+
+        let (spake, outbound) = start_spake(pin, &remote, &endpoint.id());
+        let hello = PinHello {
+            spake: outbound,
+            device_name,
+        };
+        write_frame(&mut send, &hello).await?;
+
+        let reply: PinReply = read_frame(&mut recv).await?;
+        if reply.confirm.is_empty() {
+            return Err(anyhow::anyhow!("Device is not waiting for a PIN"));
+        }
+        let key = finish_spake(spake, &reply.spake)?;
+        if reply.confirm != confirmation(&key, b"responder") {
+            conn.close(0u32.into(), b"wrong pin");
+            return Err(anyhow::anyhow!("Wrong PIN"));
+        }
+
+        let confirm = PinConfirm {
+            confirm: confirmation(&key, b"initiator"),
+        };
+        write_frame(&mut send, &confirm).await?;
+        send.finish()?;
+
+        // The other side closes the stream once it has checked our confirmation
+        recv.read_to_end(0).await?;
+        conn.close(0u32.into(), b"done");
+        anyhow::Ok(reply.device_name)
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("Pairing timed out"))??;
+
+    complete(handle, remote, remote_name).await
+}
+
+/// SPAKE2 over the PIN, bound to the node showing it and the node typing it
+fn start_spake(
+    pin: &str,
+    responder: &EndpointId,
+    initiator: &EndpointId,
+) -> (Spake2<Ed25519Group>, Vec<u8>) {
+    let identity = format!("vegam-pair-pin:{}:{}", responder, initiator);
+    Spake2::<Ed25519Group>::start_symmetric(
+        &Password::new(pin.as_bytes()),
+        &Identity::new(identity.as_bytes()),
+    )
+}
+
+fn finish_spake(spake: Spake2<Ed25519Group>, inbound: &[u8]) -> Result<Vec<u8>> {
+    spake
+        .finish(inbound)
+        .map_err(|e| anyhow::anyhow!("Invalid PIN pairing message: {:?}", e))
+}
+
+/// Proof that we derived `key`, distinct per role so it can't be reflected
+fn confirmation(key: &[u8], role: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"vegam-pair-pin-confirm:");
+    hasher.update(role);
+    hasher.update(key);
+    hasher.finalize().to_vec()
+}
+
+/// Length-prefixed JSON message, for handshakes with more than one round trip
+async fn write_frame<T: Serialize>(send: &mut SendStream, message: &T) -> Result<()> {
+    let bytes = serde_json::to_vec(message)?;
+    send.write_all(&(bytes.len() as u32).to_be_bytes()).await?;
+    send.write_all(&bytes).await?;
+    Ok(())
+}
+
+async fn read_frame<T: DeserializeOwned>(recv: &mut RecvStream) -> Result<T> {
+    let mut len = [0u8; 4];
+    recv.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(anyhow::anyhow!("Pairing message too large"));
+    }
+    let mut bytes = vec![0u8; len];
+    recv.read_exact(&mut bytes).await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// Dial the device that showed `code` and exchange identities
+pub async fn pair(endpoint: &Endpoint, code: &str, handle: &AppHandle) -> Result<TrustedPeer> {
+    let (remote, secret) = parse_pairing_code(code)?;
+    let state = handle.state::<AppState>();
+    let request = PairRequest {
+        secret,
+        device_name: state.get_settings().await.display_name(),
+    };
+
+    let response: PairResponse = tokio::time::timeout(PAIRING_TIMEOUT, async {
+        let conn = endpoint.connect(remote, ALPN).await?;
+        let (mut send, mut recv) = conn.open_bi().await?;
+        send.write_all(&serde_json::to_vec(&request)?).await?;
+        send.finish()?;
+        let bytes = recv.read_to_end(MAX_MESSAGE_SIZE).await?;
+        conn.close(0u32.into(), b"done");
+        anyhow::Ok(serde_json::from_slice(&bytes)?)
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("Pairing timed out"))??;
+
+    if !response.accepted {
+        return Err(anyhow::anyhow!("Pairing code was rejected or has expired"));
+    }
+
+    complete(handle, remote, response.device_name).await
+}
+
+/// Trust the paired device and tell the frontend
+async fn complete(
+    handle: &AppHandle,
+    remote: EndpointId,
+    device_name: String,
+) -> Result<TrustedPeer> {
+    let state = handle.state::<AppState>();
+    let trusted = state.trust_peer(remote.to_string(), device_name).await?;
+    info!("Paired with {} ({})", trusted.device_name, trusted.node_id);
+
+    AppEvent::PeerPaired(trusted.clone()).emit(handle)?;
+    AppEvent::PeerListUpdated(state.get_peers().await).emit(handle)?;
+    Ok(trusted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh_base::SecretKey;
+
+    #[test]
+    fn test_pairing_code_roundtrips() {
+        let node_id = SecretKey::from_bytes(&[3u8; 32]).public();
+        let pending = PendingPairing::generate();
+        let code = pairing_code(&node_id, &pending);
+
+        let (parsed_id, secret) = parse_pairing_code(&code).unwrap();
+        assert_eq!(parsed_id, node_id);
+        assert_eq!(secret, pending.secret);
+    }
+
+    #[test]
+    fn test_wrong_secrets_only_lock_out_their_sender() {
+        let guesser = SecretKey::from_bytes(&[4u8; 32]).public();
+        let device = SecretKey::from_bytes(&[5u8; 32]).public();
+        let mut pending = PendingPairing::generate();
+        let secret = pending.secret.clone();
+
+        for _ in 0..MAX_ATTEMPTS_PER_NODE {
+            assert!(!pending.check(guesser, "wrong"));
+        }
+        // Out of attempts, even with the right secret
+        assert!(!pending.check(guesser, &secret));
+        assert!(pending.check(device, &secret));
+
+        pending.expires_at = Instant::now();
+        assert!(!pending.check(device, &secret));
+    }
+
+    #[test]
+    fn test_pin_spake_agrees_only_on_same_pin() {
+        let responder = SecretKey::from_bytes(&[1u8; 32]).public();
+        let initiator = SecretKey::from_bytes(&[2u8; 32]).public();
+
+        let (a, a_msg) = start_spake("123456", &responder, &initiator);
+        let (b, b_msg) = start_spake("123456", &responder, &initiator);
+        let a_key = finish_spake(a, &b_msg).unwrap();
+        let b_key = finish_spake(b, &a_msg).unwrap();
+        assert_eq!(
+            confirmation(&a_key, b"responder"),
+            confirmation(&b_key, b"responder")
+        );
+        assert_ne!(
+            confirmation(&a_key, b"responder"),
+            confirmation(&a_key, b"initiator")
+        );
+
+        let (a, a_msg) = start_spake("123456", &responder, &initiator);
+        let (b, b_msg) = start_spake("654321", &responder, &initiator);
+        let a_key = finish_spake(a, &b_msg).unwrap();
+        let b_key = finish_spake(b, &a_msg).unwrap();
+        assert_ne!(a_key, b_key);
+    }
+
+    #[test]
+    fn test_validate_pin() {
+        assert!(validate_pin("012345").is_ok());
+        assert_eq!(PendingPin::generate().pin.len(), PIN_LENGTH);
+        assert!(validate_pin("12345").is_err());
+        assert!(validate_pin("12345a").is_err());
+    }
+
+    #[test]
+    fn test_invalid_pairing_codes() {
+        assert!(parse_pairing_code("hello").is_err());
+        assert!(parse_pairing_code("vegam-pair:notanode:abc").is_err());
+    }
+}
@@ -0,0 +1,55 @@
+// Lightweight round-trip protocol for measuring peer latency
+//
+// The initiator opens a bidirectional stream, sends a few bytes and waits for
+// them to be echoed back. Only the echo is timed, not connection setup.
+
+use anyhow::Result;
+use iroh::endpoint::Connection;
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::Endpoint;
+use iroh_base::EndpointId;
+use std::time::{Duration, Instant};
+
+pub const ALPN: &[u8] = b"vegam/ping/0";
+
+/// Give up on unreachable peers instead of waiting for the QUIC idle timeout
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+const PAYLOAD: &[u8] = b"ping";
+
+/// Echo handler registered on the router
+#[derive(Debug, Clone)]
+pub struct Ping;
+
+impl ProtocolHandler for Ping {
+    async fn accept(&self, connection: Connection) -> Result<(), AcceptError> {
+        let (mut send, mut recv) = connection.accept_bi().await?;
+        tokio::io::copy(&mut recv, &mut send).await?;
+        send.finish()?;
+        connection.closed().await;
+        Ok(())
+    }
+}
+
+/// Measure the round-trip time to a peer
+pub async fn ping(endpoint: &Endpoint, endpoint_id: EndpointId) -> Result<Duration> {
+    tokio::time::timeout(PING_TIMEOUT, async {
+        let conn = endpoint.connect(endpoint_id, ALPN).await?;
+        let (mut send, mut recv) = conn.open_bi().await?;
+
+        let start = Instant::now();
+        send.write_all(PAYLOAD).await?;
+        send.finish()?;
+        let echoed = recv.read_to_end(PAYLOAD.len()).await?;
+        let rtt = start.elapsed();
+
+        conn.close(0u32.into(), b"done");
+
+        if echoed != PAYLOAD {
+            return Err(anyhow::anyhow!("Unexpected ping response"));
+        }
+        Ok(rtt)
+    })
+    .await
+    .map_err(|_| anyhow::anyhow!("Ping timed out"))?
+}
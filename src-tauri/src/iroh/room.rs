@@ -0,0 +1,151 @@
+// Private discovery rooms
+//
+// A room code is a shared secret. Every device that knows the code derives the
+// same gossip TopicId from it, so discovery is scoped to people who were given
+// the code instead of whoever happens to share a topic.
+//
+// The topic ID is visible to every node it passes through, so knowing it must
+// not be enough to read the room. Gossip payloads in a room are sealed with
+// ChaCha20-Poly1305 under a separate key derived from the code; messages that
+// don't open with it are dropped.
+
+use anyhow::Result;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, OsRng},
+    ChaCha20Poly1305, Nonce,
+};
+use iroh_gossip::proto::TopicId;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Random bytes in a generated room code (16 base32 characters)
+const ROOM_CODE_BYTES: usize = 10;
+
+/// Key sealing a room's gossip payloads
+pub type RoomKey = [u8; 32];
+
+/// Marks sealed payloads; followed by the nonce and ciphertext
+const SEALED_MAGIC: &[u8; 4] = b"VGR1";
+const NONCE_LEN: usize = 12;
+/// Bytes sealing adds to a payload: header plus Poly1305 tag
+pub const SEAL_OVERHEAD: usize = SEALED_MAGIC.len() + NONCE_LEN + 16;
+
+/// Generate a new room code formatted as `xxxx-xxxx-xxxx-xxxx`
+pub fn generate_room_code() -> String {
+    let mut bytes = [0u8; ROOM_CODE_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let mut code = data_encoding::BASE32_NOPAD.encode(&bytes);
+    code.make_ascii_lowercase();
+
+    code.as_bytes()
+        .chunks(4)
+        .map(|c| std::str::from_utf8(c).expect("base32 is ascii"))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Canonical form of a user-entered room code: lowercase, no separators
+pub fn normalize_room_code(code: &str) -> Result<String> {
+    let normalized: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    if normalized.len() < 8 {
+        return Err(anyhow::anyhow!("Room code is too short"));
+    }
+    if !normalized.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(anyhow::anyhow!(
+            "Room code may only contain letters and digits"
+        ));
+    }
+
+    Ok(normalized)
+}
+
+/// Derive the gossip topic for a room code
+pub fn topic_for_room(code: &str) -> Result<TopicId> {
+    let normalized = normalize_room_code(code)?;
+    let mut hasher = Sha256::new();
+    hasher.update(b"vegam-room-");
+    hasher.update(normalized.as_bytes());
+    let digest: [u8; 32] = hasher.finalize().into();
+    Ok(TopicId::from_bytes(digest))
+}
+
+/// Derive the payload key for a room code, independent of its topic ID
+pub fn key_for_room(code: &str) -> Result<RoomKey> {
+    let normalized = normalize_room_code(code)?;
+    let mut hasher = Sha256::new();
+    hasher.update(b"vegam-room-key-");
+    hasher.update(normalized.as_bytes());
+    Ok(hasher.finalize().into())
+}
+
+/// Encrypt a gossip payload for the room
+pub fn seal(key: &RoomKey, payload: &[u8]) -> Result<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = ChaCha20Poly1305::new(key.into())
+        .encrypt(&Nonce::from(nonce), payload)
+        .map_err(|_| anyhow::anyhow!("Failed to seal room message"))?;
+
+    let mut sealed = Vec::with_capacity(SEAL_OVERHEAD + payload.len());
+    sealed.extend_from_slice(SEALED_MAGIC);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypt a payload sealed with `seal`
+pub fn open(key: &RoomKey, sealed: &[u8]) -> Result<Vec<u8>> {
+    let header = SEALED_MAGIC.len() + NONCE_LEN;
+    if sealed.len() < SEAL_OVERHEAD || !sealed.starts_with(SEALED_MAGIC) {
+        return Err(anyhow::anyhow!("Unsealed message in a private room"));
+    }
+    let nonce: [u8; NONCE_LEN] = sealed[SEALED_MAGIC.len()..header].try_into()?;
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(&Nonce::from(nonce), &sealed[header..])
+        .map_err(|_| anyhow::anyhow!("Room message sealed with another key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_code_roundtrips() {
+        let code = generate_room_code();
+        assert_eq!(code.len(), 19);
+        assert!(topic_for_room(&code).is_ok());
+    }
+
+    #[test]
+    fn test_topic_ignores_formatting() {
+        let a = topic_for_room("abcd-efgh-ijkl-mnop").unwrap();
+        let b = topic_for_room(" ABCD EFGH ijkl mnop ").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, topic_for_room("abcd-efgh-ijkl-mnoq").unwrap());
+    }
+
+    #[test]
+    fn test_sealed_payload_needs_room_key() {
+        let key = key_for_room("abcd-efgh-ijkl-mnop").unwrap();
+        let other = key_for_room("abcd-efgh-ijkl-mnoq").unwrap();
+        let sealed = seal(&key, b"announcement").unwrap();
+
+        assert_eq!(open(&key, &sealed).unwrap(), b"announcement");
+        assert!(open(&other, &sealed).is_err());
+        assert!(open(&key, b"announcement").is_err());
+        // The key isn't the topic ID
+        let topic = topic_for_room("abcd-efgh-ijkl-mnop").unwrap();
+        assert_ne!(&key, topic.as_bytes());
+    }
+
+    #[test]
+    fn test_invalid_codes_rejected() {
+        assert!(normalize_room_code("abc").is_err());
+        assert!(normalize_room_code("abcd-efgh-!!").is_err());
+    }
+}
@@ -0,0 +1,274 @@
+// Shared spaces
+//
+// A space is an iroh-docs document that members add files to and everyone
+// else sees. The document is the namespace: its entries are keyed
+// `files/<name>\0` and point at blobs in the node's store, plus a `meta/name`
+// entry holding the space's name. The terminating NUL keeps one name from
+// being a prefix of another, since deletes remove every key under a prefix.
+// Creating a space makes a new document; `share` hands out a `DocTicket`
+// (read-only or writable) that `join` imports, after which iroh-docs syncs
+// entries with the other members over gossip and downloads their content
+// into the store, where the docs protect callback keeps it safe from GC.
+// `save_file` exports a file once its content has arrived. Each open space
+// has a subscription emitting `space-updated`.
+
+use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
+use iroh_docs::api::protocol::{AddrInfoOptions, ShareMode};
+use iroh_docs::api::Doc;
+use iroh_docs::engine::LiveEvent;
+use iroh_docs::store::Query;
+use iroh_docs::{CapabilityKind, DocTicket, NamespaceId};
+use n0_future::StreamExt;
+use serde::Serialize;
+use std::path::Path;
+use std::str::FromStr;
+use tauri::AppHandle;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::events::AppEvent;
+use crate::iroh::Iroh;
+
+const NAME_KEY: &[u8] = b"meta/name";
+const FILE_PREFIX: &[u8] = b"files/";
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SpaceInfo {
+    /// Namespace ID
+    pub id: String,
+    pub name: String,
+    /// Whether we can add files
+    pub writable: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SpaceFile {
+    pub name: String,
+    pub hash: String,
+    pub size: u64,
+    /// Author that last wrote the entry
+    pub author: String,
+    /// Unix seconds of the last write
+    pub updated_at: u64,
+    /// Content is in the local store, so `save_file` can export it
+    pub local: bool,
+}
+
+/// Payload of `space-updated`
+#[derive(Clone, Debug, Serialize)]
+pub struct SpaceUpdated {
+    pub space_id: String,
+}
+
+fn parse_id(id: &str) -> Result<NamespaceId> {
+    NamespaceId::from_str(id).map_err(|e| anyhow!("Invalid space ID: {}", e))
+}
+
+async fn open(iroh: &Iroh, id: &str) -> Result<Doc> {
+    iroh.docs
+        .open(parse_id(id)?)
+        .await?
+        .ok_or_else(|| anyhow!("Unknown space"))
+}
+
+/// Key of a file entry; names are single path components
+fn file_key(name: &str) -> Result<Vec<u8>> {
+    if name.is_empty() || name.contains(['/', '\\', '\0']) || name == "." || name == ".." {
+        return Err(anyhow!("Invalid file name {:?}", name));
+    }
+    Ok([FILE_PREFIX, name.as_bytes(), b"\0"].concat())
+}
+
+fn file_name(key: &[u8]) -> Option<&str> {
+    let name = key.strip_prefix(FILE_PREFIX)?.strip_suffix(b"\0")?;
+    std::str::from_utf8(name).ok()
+}
+
+/// Create a space named `name`
+pub async fn create(iroh: &Iroh, name: &str) -> Result<SpaceInfo> {
+    let author = iroh.docs.author_default().await?;
+    let doc = iroh.docs.create().await?;
+    doc.set_bytes(author, Bytes::from_static(NAME_KEY), name.to_string())
+        .await?;
+    info!("Created space {} ({})", name, doc.id());
+    Ok(SpaceInfo {
+        id: doc.id().to_string(),
+        name: name.to_string(),
+        writable: true,
+    })
+}
+
+/// Ticket for joining the space; `writable` lets members add files
+pub async fn share(iroh: &Iroh, id: &str, writable: bool) -> Result<String> {
+    let doc = open(iroh, id).await?;
+    let mode = if writable {
+        ShareMode::Write
+    } else {
+        ShareMode::Read
+    };
+    let ticket = doc.share(mode, AddrInfoOptions::RelayAndAddresses).await?;
+    Ok(ticket.to_string())
+}
+
+/// Join a space from a ticket, returning its ID
+pub async fn join(iroh: &Iroh, ticket: &str) -> Result<String> {
+    let ticket = DocTicket::from_str(ticket.trim()).context("Invalid space ticket")?;
+    let doc = iroh.docs.import(ticket).await?;
+    info!("Joined space {}", doc.id());
+    Ok(doc.id().to_string())
+}
+
+/// Stop syncing a space and delete our copy of it
+pub async fn leave(iroh: &Iroh, id: &str) -> Result<()> {
+    let id = parse_id(id)?;
+    iroh.docs.drop_doc(id).await?;
+    info!("Left space {}", id);
+    Ok(())
+}
+
+pub async fn list(iroh: &Iroh) -> Result<Vec<SpaceInfo>> {
+    let mut docs = iroh.docs.list().await?;
+    let mut spaces = Vec::new();
+    while let Some((id, capability)) = docs.try_next().await? {
+        let name = match iroh.docs.open(id).await? {
+            Some(doc) => space_name(iroh, &doc).await,
+            None => None,
+        };
+        spaces.push(SpaceInfo {
+            id: id.to_string(),
+            name: name.unwrap_or_else(|| "Untitled space".to_string()),
+            writable: capability == CapabilityKind::Write,
+        });
+    }
+    Ok(spaces)
+}
+
+/// Latest `meta/name`, once synced
+async fn space_name(iroh: &Iroh, doc: &Doc) -> Option<String> {
+    let entry = doc
+        .get_one(Query::single_latest_per_key().key_exact(NAME_KEY))
+        .await
+        .ok()??;
+    let name = iroh.blobs.get_bytes(entry.content_hash()).await.ok()?;
+    String::from_utf8(name.to_vec()).ok()
+}
+
+/// Files in a space, newest entry per name
+pub async fn files(iroh: &Iroh, id: &str) -> Result<Vec<SpaceFile>> {
+    let doc = open(iroh, id).await?;
+    let mut entries = doc
+        .get_many(Query::single_latest_per_key().key_prefix(FILE_PREFIX))
+        .await?;
+    let mut files = Vec::new();
+    while let Some(entry) = entries.try_next().await? {
+        let Some(name) = file_name(entry.key()) else {
+            continue;
+        };
+        // An empty entry marks a removed file
+        if entry.content_len() == 0 {
+            continue;
+        }
+        files.push(SpaceFile {
+            name: name.to_string(),
+            hash: entry.content_hash().to_string(),
+            size: entry.content_len(),
+            author: entry.author().to_string(),
+            updated_at: entry.timestamp() / 1_000_000,
+            local: iroh.blobs.has(entry.content_hash()).await.unwrap_or(false),
+        });
+    }
+    Ok(files)
+}
+
+/// Import a file and add it to the space under `name`
+pub async fn add_file(iroh: &Iroh, id: &str, path: &Path, name: &str) -> Result<SpaceFile> {
+    let doc = open(iroh, id).await?;
+    let key = file_key(name)?;
+    let author = iroh.docs.author_default().await?;
+    let tag = iroh.blobs.add_path(path).await?;
+    let size = tokio::fs::metadata(path).await?.len();
+    doc.set_hash(author, key, tag.hash, size).await?;
+    info!("Added {} to space {}", name, id);
+    Ok(SpaceFile {
+        name: name.to_string(),
+        hash: tag.hash.to_string(),
+        size,
+        author: author.to_string(),
+        updated_at: crate::history::now_secs(),
+        local: true,
+    })
+}
+
+/// Remove a file from the space for every member
+pub async fn remove_file(iroh: &Iroh, id: &str, name: &str) -> Result<()> {
+    let doc = open(iroh, id).await?;
+    let author = iroh.docs.author_default().await?;
+    doc.del(author, file_key(name)?).await?;
+    Ok(())
+}
+
+/// Write a file's content to `output`, returning its size; fails until the
+/// content has synced
+pub async fn save_file(iroh: &Iroh, id: &str, name: &str, output: &Path) -> Result<u64> {
+    let doc = open(iroh, id).await?;
+    let entry = doc
+        .get_one(Query::single_latest_per_key().key_exact(file_key(name)?))
+        .await?
+        .filter(|entry| entry.content_len() > 0)
+        .ok_or_else(|| anyhow!("No file {:?} in this space", name))?;
+    if !iroh.blobs.has(entry.content_hash()).await? {
+        return Err(anyhow!("{} hasn't synced yet", name));
+    }
+    let partial = crate::iroh::transfer::partial_path(output);
+    let mut file = tokio::fs::File::create(&partial).await?;
+    let size =
+        crate::iroh::transfer::export_blob(iroh, entry.content_hash(), None, &mut file).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&partial, output).await?;
+    Ok(size)
+}
+
+/// Emit `space-updated` whenever the space changes or content arrives
+pub async fn watch(iroh: &Iroh, id: &str, handle: AppHandle) -> Result<JoinHandle<()>> {
+    let doc = open(iroh, id).await?;
+    let mut events = doc.subscribe().await?;
+    let space_id = id.to_string();
+    Ok(tauri::async_runtime::spawn(async move {
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(
+                    LiveEvent::InsertLocal { .. }
+                    | LiveEvent::InsertRemote { .. }
+                    | LiveEvent::ContentReady { .. },
+                ) => {
+                    let updated = SpaceUpdated {
+                        space_id: space_id.clone(),
+                    };
+                    let _ = AppEvent::SpaceUpdated(updated).emit(&handle);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Space {} subscription failed: {}", space_id, e);
+                    break;
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_keys() {
+        let key = file_key("holiday.jpg").unwrap();
+        assert_eq!(key, b"files/holiday.jpg\0");
+        assert_eq!(file_name(&key), Some("holiday.jpg"));
+        assert_eq!(file_name(NAME_KEY), None);
+        assert!(file_key("../etc/passwd").is_err());
+        assert!(file_key("a/b").is_err());
+        assert!(file_key("").is_err());
+    }
+}
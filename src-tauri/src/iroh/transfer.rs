@@ -1,17 +1,35 @@
 use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use bytes::Bytes;
 use iroh_blobs::api::tags::TagInfo;
 use iroh_blobs::ticket::BlobTicket;
 use iroh_blobs::BlobFormat;
-use std::path::PathBuf;
-use tracing::info;
+use n0_future::{stream, Stream};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::history::now_secs;
+use crate::iroh::at_rest::{self, BlobKey};
+use crate::iroh::e2e;
 use crate::iroh::ticket_codec::{decrypt_ticket, encrypt_ticket};
 use crate::iroh::Iroh;
 use crate::state::{TransferDirection, TransferInfo, TransferStatus};
 
 use std::sync::Arc;
 
+/// Download attempts before a receive fails
+const MAX_DOWNLOAD_ATTEMPTS: u32 = 4;
+/// Wait before retrying, multiplied by the attempt number
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(3);
+/// Bytes read per step when streaming a file into the blob store
+const IMPORT_CHUNK_SIZE: usize = 256 * 1024;
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct BlobTicketInfo {
     pub ticket: String,
@@ -20,30 +38,65 @@ pub struct BlobTicketInfo {
     pub transfer_id: String,
     #[serde(skip)]
     pub tag: Option<Arc<TagInfo>>,
+    /// Key of an encrypted blob, kept for serving it over HTTP
+    #[serde(skip)]
+    pub blob_key: Option<BlobKey>,
+    /// Browser download link, when requested
+    pub http_url: Option<String>,
 }
 
-/// Add file bytes to blob store and create transfer ticket
-pub async fn create_send_ticket(
-    iroh: &Iroh,
-    file_data: Vec<u8>,
-    file_path: String,
-) -> Result<BlobTicketInfo> {
-    info!(
-        "Creating send ticket from bytes, original path: {}",
-        file_path
-    );
-
-    let file_size = file_data.len() as u64;
-
-    // Extract file name from path or use default
-    let file_name = PathBuf::from(&file_path)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("file")
-        .to_string();
+/// How a shared file is encrypted before it enters the blob store
+#[derive(Clone, Copy, Debug)]
+pub enum SendEncryption<'a> {
+    None,
+    /// Per-blob key derived from the store key (`at_rest`)
+    AtRest(&'a [u8; 32]),
+    /// Random key that only the ticket carries (`e2e`)
+    EndToEnd,
+}
 
-    // Import bytes into blob store using Blobs API
-    let tag = iroh.blobs.add_bytes(file_data).await?;
+/// Import a file into the blob store and create a transfer ticket
+///
+/// The file is streamed into the store in `IMPORT_CHUNK_SIZE` reads, so files
+/// of any size (including multi-gigabyte videos from Android content URIs)
+/// never sit in memory whole; `progress` gets the bytes read so far after
+/// each chunk. Unless `encryption` is `None` the chunks are encrypted before
+/// entering the store and the blob key is added to the ticket.
+pub async fn create_send_ticket<R, P>(
+    iroh: &Iroh,
+    reader: R,
+    file_name: String,
+    encryption: SendEncryption<'_>,
+    progress: P,
+) -> Result<BlobTicketInfo>
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+    P: Fn(u64) + Send + Sync + 'static,
+{
+    info!("Creating send ticket for {}", file_name);
+
+    let read = Arc::new(AtomicU64::new(0));
+    let reader = CountingReader {
+        inner: reader,
+        read: read.clone(),
+        progress: Box::new(progress),
+    };
+    let (tag, blob_key) = match encryption {
+        SendEncryption::None => (iroh.blobs.add_stream(read_stream(reader)).await?, None),
+        // Sealed in chunks like end-to-end content, with a key derived from
+        // the store key; receivers tell the formats apart by the header
+        SendEncryption::AtRest(store_key) => {
+            let key = at_rest::new_blob_key(store_key);
+            let blob = e2e::encrypt_stream(&key, reader);
+            (iroh.blobs.add_stream(blob).await?, Some(key))
+        }
+        SendEncryption::EndToEnd => {
+            let key = e2e::generate_key();
+            let blob = e2e::encrypt_stream(&key, reader);
+            (iroh.blobs.add_stream(blob).await?, Some(key))
+        }
+    };
+    let file_size = read.load(Ordering::Relaxed);
     let hash = tag.hash;
 
     info!("File imported with hash: {}", hash);
@@ -64,8 +117,12 @@ pub async fn create_send_ticket(
 
     let transfer_id = Uuid::new_v4().to_string();
 
-    // Encode filename and size in ticket format: filename|size|blob_ticket
-    let enhanced_ticket = format!("{}|{}|{}", file_name, file_size, ticket_str);
+    // Encode filename and size in ticket format: filename|size|blob_ticket[|blob_key]
+    let mut enhanced_ticket = format!("{}|{}|{}", file_name, file_size, ticket_str);
+    if let Some(key) = blob_key {
+        enhanced_ticket.push('|');
+        enhanced_ticket.push_str(&URL_SAFE_NO_PAD.encode(key));
+    }
 
     // Encrypt the ticket using AES-256-GCM with node ID as key derivation
     let node_id = iroh.node_addr.id.to_string();
@@ -77,31 +134,96 @@ pub async fn create_send_ticket(
         file_size,
         transfer_id,
         tag: Some(Arc::new(tag)), // Keep tag alive
+        blob_key,
+        http_url: None,
     })
 }
 
-/// Parse enhanced ticket format: filename|size|blob_ticket
-/// Returns (filename, size, BlobTicket)
+/// Reader that counts the bytes passing through it, reporting each read
+struct CountingReader<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+    progress: Box<dyn Fn(u64) + Send + Sync>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        if read > 0 {
+            let total = self.read.fetch_add(read, Ordering::Relaxed) + read;
+            (self.progress)(total);
+        }
+        poll
+    }
+}
+
+/// A file as a stream of chunks for the blob store
+fn read_stream<R>(reader: R) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static
+where
+    R: AsyncRead + Send + Sync + Unpin + 'static,
+{
+    stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        let mut chunk = vec![0u8; IMPORT_CHUNK_SIZE];
+        match reader.read(&mut chunk).await {
+            Ok(0) => None,
+            Ok(n) => {
+                chunk.truncate(n);
+                Some((Ok(Bytes::from(chunk)), Some(reader)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/// Parse enhanced ticket format: filename|size|blob_ticket[|blob_key]
+/// Returns (filename, size, BlobTicket, key of an encrypted blob)
 /// Decrypts the ticket using AES-256-GCM with the receiver's node ID
-pub fn parse_enhanced_ticket(ticket_str: &str, node_id: &str) -> Result<(String, u64, BlobTicket)> {
+pub fn parse_enhanced_ticket(
+    ticket_str: &str,
+    node_id: &str,
+) -> Result<(String, u64, BlobTicket, Option<BlobKey>)> {
     // Decrypt the ticket using the receiver's node ID
     let decrypted = decrypt_ticket(ticket_str, node_id)?;
 
-    let parts: Vec<&str> = decrypted.splitn(3, '|').collect();
+    let parts: Vec<&str> = decrypted.splitn(4, '|').collect();
 
-    if parts.len() == 3 {
+    if parts.len() >= 3 {
         // Enhanced format with metadata
         let filename = parts[0].to_string();
         let size = parts[1].parse::<u64>()?;
         let ticket: BlobTicket = parts[2].parse()?;
-        Ok((filename, size, ticket))
+        let blob_key = match parts.get(3) {
+            Some(key) => Some(
+                URL_SAFE_NO_PAD
+                    .decode(key)?
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Invalid blob key in ticket"))?,
+            ),
+            None => None,
+        };
+        Ok((filename, size, ticket, blob_key))
     } else {
         // Legacy format without metadata (shouldn't happen with encryption)
         let ticket: BlobTicket = decrypted.parse()?;
-        Ok(("received_file".to_string(), 0, ticket))
+        Ok(("received_file".to_string(), 0, ticket, None))
     }
 }
 
+/// Temporary path a download is written to before being moved to `path`
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
 // Blob provider is now handled automatically by the Router pattern
 // No need for manual start_blob_provider function
 
@@ -115,18 +237,18 @@ pub async fn receive_file<F>(
 where
     F: Fn(String, u64, u64) + Send + 'static,
 {
-    use iroh_blobs::api::downloader::DownloadProgressItem;
-    use n0_future::StreamExt;
-
     info!("Receiving file from ticket");
 
     // Get receiver's node ID for decryption
     let receiver_node_id = iroh.node_addr.id.to_string();
 
     // Parse and decrypt the ticket to get file size
-    let (_filename, file_size, ticket) = parse_enhanced_ticket(&ticket_str, &receiver_node_id)?;
+    let (_filename, file_size, ticket, blob_key) =
+        parse_enhanced_ticket(&ticket_str, &receiver_node_id)?;
     let hash = ticket.hash();
     let sender_addr = ticket.addr().clone();
+    // Dial the ticket's addresses even when the sender can't be looked up
+    iroh.address_book.add_endpoint_info(sender_addr.clone());
 
     let transfer_id = Uuid::new_v4().to_string();
     let file_name = output_path
@@ -145,35 +267,29 @@ where
         progress_callback(transfer_id.clone(), 0, file_size);
     }
 
-    // Download blob using downloader API with progress tracking
-    let download = iroh.downloader.download(hash, Some(sender_addr.id));
-    let mut stream = download.stream().await?;
-
-    // Track bytes downloaded during network transfer
-    let mut bytes_downloaded: u64 = 0;
-
-    // Iterate through progress events
-    while let Some(item) = stream.next().await {
-        match item {
-            DownloadProgressItem::Progress(bytes) => {
-                bytes_downloaded = bytes;
-                // Report download progress
-                let total = if file_size > 0 {
-                    file_size
-                } else {
-                    bytes_downloaded
-                };
-                progress_callback(transfer_id.clone(), bytes_downloaded, total);
+    // The store keeps what already arrived, so a retry after a dropped
+    // connection (e.g. a network switch) only fetches the missing ranges
+    let mut attempt = 1;
+    let bytes_downloaded = loop {
+        match download_blob(
+            iroh,
+            hash,
+            sender_addr.id,
+            &transfer_id,
+            file_size,
+            &progress_callback,
+        )
+        .await
+        {
+            Ok(bytes) => break bytes,
+            Err(e) if attempt < MAX_DOWNLOAD_ATTEMPTS => {
+                warn!("Download attempt {} failed, retrying: {}", attempt, e);
+                tokio::time::sleep(RETRY_DELAY * attempt).await;
+                attempt += 1;
             }
-            DownloadProgressItem::Error(e) => {
-                log::error!("✗ Download error: {}", e);
-                log::error!("  This likely means sender doesn't have blob available");
-                log::error!("  Ensure sender has updated APK with tag storage fix");
-                return Err(e);
-            }
-            _ => {}
+            Err(e) => return Err(e),
         }
-    }
+    };
 
     info!(
         "✓ Download complete, {} bytes received from network",
@@ -181,12 +297,11 @@ where
     );
 
     // Now blob is in store, read it and write to file
-    let mut reader = iroh.blobs.reader(hash);
-    let mut file_data = Vec::new();
-    tokio::io::copy(&mut reader, &mut file_data).await?;
-    tokio::fs::write(&output_path, &file_data).await?;
+    let mut file = tokio::fs::File::create(&output_path).await?;
+    let actual_file_size = export_blob(iroh, hash, blob_key.as_ref(), &mut file).await?;
+    // Make sure the data is on disk before the caller renames it into place
+    file.sync_all().await?;
 
-    let actual_file_size = file_data.len() as u64;
     info!(
         "✓ File written to disk successfully, {} bytes",
         actual_file_size
@@ -204,5 +319,87 @@ where
         error: None,
         direction: TransferDirection::Receive,
         speed_bps: 0,
+        peer_node_id: Some(sender_addr.id.to_string()),
+        started_at: None,
+        finished_at: Some(now_secs()),
+        ticket_hash: Some(hash.to_string()),
+        note: None,
+        pinned: false,
+        actions: Vec::new(),
+        saved_path: None,
     })
 }
+
+/// Write a blob from the local store to `writer`, decrypting it if `key` is
+/// set; returns the plaintext size
+pub async fn export_blob<W>(
+    iroh: &Iroh,
+    hash: iroh_blobs::Hash,
+    key: Option<&BlobKey>,
+    writer: &mut W,
+) -> Result<u64>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    let mut reader = iroh.blobs.reader(hash);
+    match key {
+        // At-rest and end-to-end encrypted blobs share the streaming format
+        Some(key) => e2e::decrypt_to(key, reader, writer).await,
+        None => Ok(tokio::io::copy(&mut reader, writer).await?),
+    }
+}
+
+/// Fetch a blob into the local store with progress; returns the bytes fetched
+async fn download_blob<F>(
+    iroh: &Iroh,
+    hash: iroh_blobs::Hash,
+    provider: iroh_base::EndpointId,
+    transfer_id: &str,
+    file_size: u64,
+    progress_callback: &F,
+) -> Result<u64>
+where
+    F: Fn(String, u64, u64),
+{
+    use iroh_blobs::api::downloader::DownloadProgressItem;
+    use n0_future::StreamExt;
+
+    // Download blob using downloader API with progress tracking
+    let download = iroh.downloader.download(hash, Some(provider));
+    let mut stream = download.stream().await?;
+
+    // Track bytes downloaded during network transfer
+    let mut bytes_downloaded: u64 = 0;
+    // What arrived before a retry doesn't count against the cap
+    let mut paced: Option<u64> = None;
+
+    // Iterate through progress events
+    while let Some(item) = stream.next().await {
+        match item {
+            DownloadProgressItem::Progress(bytes) => {
+                // Holding the stream back stalls the download (see `bandwidth.rs`)
+                iroh.bandwidth
+                    .download
+                    .acquire(bytes.saturating_sub(paced.unwrap_or(bytes)))
+                    .await;
+                paced = Some(bytes);
+                bytes_downloaded = bytes;
+                // Report download progress; encrypted blobs are a little larger than the file
+                let total = if file_size > 0 {
+                    file_size
+                } else {
+                    bytes_downloaded
+                };
+                progress_callback(transfer_id.to_string(), bytes_downloaded.min(total), total);
+            }
+            DownloadProgressItem::Error(e) => {
+                log::error!("✗ Download error: {}", e);
+                log::error!("  This likely means sender doesn't have blob available");
+                log::error!("  Ensure sender has updated APK with tag storage fix");
+                return Err(e);
+            }
+            _ => {}
+        }
+    }
+    Ok(bytes_downloaded)
+}
@@ -0,0 +1,331 @@
+// Files handed over by the desktop shell
+//
+// On Windows the installer adds "Send with Vegam" to Explorer's context menu
+// and a Vegam shortcut to the Send To menu, both starting the app as
+// `vegam.exe --send <paths>`. With the single-instance plugin a second launch
+// never starts another node: its arguments are forwarded to the running app.
+// A launch that starts the app handles its own arguments once set up.
+//
+// On macOS Finder's Services menu has "Send with Vegam" (NSServices in
+// Info.plist); `macos/services.m` receives the selected files in the running
+// app, which macOS starts first if needed, and passes their paths on.
+//
+// Either way the window is raised and the paths are treated as files dropped
+// on it (see `drag_drop.rs`): staged until the frontend takes them, or
+// offered to the drop target.
+//
+// The app also opens tickets: `vegam://` links, which are tickets themselves,
+// and `.vegamticket` files holding one. Windows and Linux pass them on the
+// command line (forwarded like `--send`); on macOS files arrive as
+// `RunEvent::Opened` and links through an Apple Event handler in
+// `macos/services.m`, since tickets don't parse as `Url`s.
+// Each is read and parsed, staged for `take_opened_tickets` and announced
+// with `ticket-opened`, so the frontend can offer to receive it.
+#![cfg_attr(any(target_os = "android", target_os = "ios"), allow(dead_code))]
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::events::AppEvent;
+
+/// Marks the arguments after it as files to send
+pub const SEND_FLAG: &str = "--send";
+
+/// Files to send from a command line (program name included); none without
+/// `--send`. Relative paths are resolved against `cwd`.
+pub fn send_paths(args: &[String], cwd: &Path) -> Vec<PathBuf> {
+    args.iter()
+        .skip_while(|arg| *arg != SEND_FLAG)
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .map(|arg| cwd.join(arg))
+        .collect()
+}
+
+/// Extension of files holding a ticket
+pub const TICKET_EXTENSION: &str = "vegamticket";
+/// Anything bigger isn't a ticket file
+const MAX_TICKET_FILE: u64 = 64 * 1024;
+
+/// A ticket the app was opened with
+#[derive(Clone, Debug, PartialEq)]
+pub enum Opened {
+    /// `vegam://` link, itself a ticket
+    Link(String),
+    /// `.vegamticket` file
+    File(PathBuf),
+}
+
+/// Payload of `ticket-opened`
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct OpenedTicket {
+    /// Ticket for `receive_file`; unset when it couldn't be read
+    pub ticket: Option<String>,
+    /// Ticket file it was read from
+    pub path: Option<String>,
+    pub filename: Option<String>,
+    pub size: Option<u64>,
+    /// Why the link or file can't be received
+    pub error: Option<String>,
+}
+
+/// Opened tickets the frontend hasn't taken yet
+static STAGED: Mutex<Vec<OpenedTicket>> = Mutex::new(Vec::new());
+
+/// Links and ticket files on a command line (program name included), before
+/// any `--send`
+pub fn opened_args(args: &[String], cwd: &Path) -> Vec<Opened> {
+    args.iter()
+        .skip(1)
+        .take_while(|arg| *arg != SEND_FLAG)
+        .filter_map(|arg| {
+            if arg.starts_with("vegam://") {
+                Some(Opened::Link(arg.clone()))
+            } else if Path::new(arg)
+                .extension()
+                .is_some_and(|ext| ext == TICKET_EXTENSION)
+            {
+                Some(Opened::File(cwd.join(arg)))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// A URL macOS opened the app with
+pub fn opened_url(url: &url::Url) -> Option<Opened> {
+    match url.scheme() {
+        "vegam" => Some(Opened::Link(url.to_string())),
+        "file" => url
+            .to_file_path()
+            .ok()
+            .filter(|path| path.extension().is_some_and(|ext| ext == TICKET_EXTENSION))
+            .map(Opened::File),
+        _ => None,
+    }
+}
+
+/// Read and parse an opened ticket
+pub async fn open(opened: Opened) -> OpenedTicket {
+    let (ticket, path) = match opened {
+        Opened::Link(link) => (Ok(link), None),
+        Opened::File(path) => (
+            read_ticket_file(&path).await,
+            Some(path.display().to_string()),
+        ),
+    };
+    let parsed = ticket.and_then(|ticket| {
+        // Decrypting only takes the sender's ID, which the ticket carries
+        let (filename, size, _, _) = crate::iroh::transfer::parse_enhanced_ticket(&ticket, "")
+            .map_err(|e| format!("Invalid ticket: {}", e))?;
+        Ok((ticket, filename, size))
+    });
+    match parsed {
+        Ok((ticket, filename, size)) => OpenedTicket {
+            ticket: Some(ticket),
+            path,
+            filename: Some(filename),
+            size: Some(size),
+            error: None,
+        },
+        Err(error) => OpenedTicket {
+            path,
+            error: Some(error),
+            ..OpenedTicket::default()
+        },
+    }
+}
+
+async fn read_ticket_file(path: &Path) -> Result<String, String> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| format!("Can't read ticket file: {}", e))?;
+    if metadata.len() > MAX_TICKET_FILE {
+        return Err("Not a ticket file".to_string());
+    }
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("Can't read ticket file: {}", e))?;
+    Ok(contents.trim().to_string())
+}
+
+/// Open tickets, staging each and emitting `ticket-opened`
+pub async fn handle_opened(app: tauri::AppHandle, opened: Vec<Opened>) {
+    for opened in opened {
+        let ticket = open(opened).await;
+        match &ticket.error {
+            Some(e) => tracing::warn!("Opened an unusable ticket: {}", e),
+            None => tracing::info!("Opened a ticket for {:?}", ticket.filename),
+        }
+        STAGED.lock().unwrap().push(ticket.clone());
+        let _ = AppEvent::TicketOpened(ticket).emit(&app);
+    }
+}
+
+/// Take the staged tickets, leaving none
+pub fn take_opened() -> Vec<OpenedTicket> {
+    std::mem::take(&mut *STAGED.lock().unwrap())
+}
+
+/// Desktop entry handling `vegam://` links, written at startup on Linux
+#[cfg(target_os = "linux")]
+const LINK_HANDLER: &str = "vegam-handler.desktop";
+
+/// Make vegam open `vegam://` links unless something already does (Linux)
+///
+/// Packages register through their desktop file; this covers AppImages and
+/// builds run from a directory.
+#[cfg(target_os = "linux")]
+pub fn register_link_handler() -> std::io::Result<()> {
+    use std::process::Command;
+
+    let current = Command::new("xdg-mime")
+        .args(["query", "default", "x-scheme-handler/vegam"])
+        .output()?;
+    if !String::from_utf8_lossy(&current.stdout).trim().is_empty() {
+        return Ok(());
+    }
+
+    let exe = match std::env::var_os("APPIMAGE") {
+        Some(appimage) => PathBuf::from(appimage),
+        None => std::env::current_exe()?,
+    };
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No data directory"))?;
+    let applications = data_home.join("applications");
+    std::fs::create_dir_all(&applications)?;
+    std::fs::write(
+        applications.join(LINK_HANDLER),
+        format!(
+            "[Desktop Entry]\nType=Application\nName=Vegam\nExec=\"{}\" %u\nNoDisplay=true\nMimeType=x-scheme-handler/vegam;\n",
+            exe.display()
+        ),
+    )?;
+    Command::new("xdg-mime")
+        .args(["default", LINK_HANDLER, "x-scheme-handler/vegam"])
+        .status()?;
+    Ok(())
+}
+
+/// Handle a launch's command line
+pub fn handle_args(app: &tauri::AppHandle, argv: Vec<String>, cwd: &Path) {
+    raise(app);
+    let opened = opened_args(&argv, cwd);
+    if !opened.is_empty() {
+        tauri::async_runtime::spawn(handle_opened(app.clone(), opened));
+    }
+    send(app, send_paths(&argv, cwd));
+}
+
+/// Send `paths` as if dropped on the window
+pub fn send(app: &tauri::AppHandle, paths: Vec<PathBuf>) {
+    if !paths.is_empty() {
+        tracing::info!("Handed {} file(s) to send", paths.len());
+        tauri::async_runtime::spawn(crate::handle_dropped_files(app.clone(), paths));
+    }
+}
+
+/// Bring the main window to the front
+pub fn raise(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// A `vegam://` link, from the Apple Event handler in `services.m`
+#[cfg(target_os = "macos")]
+#[no_mangle]
+pub extern "C" fn vegam_link_opened(link: *const std::ffi::c_char) {
+    let link = unsafe { std::ffi::CStr::from_ptr(link) }
+        .to_string_lossy()
+        .into_owned();
+    if let Some(app) = APP.get() {
+        raise(app);
+        tauri::async_runtime::spawn(handle_opened(app.clone(), vec![Opened::Link(link)]));
+    }
+}
+
+/// Set once the Finder service is registered
+#[cfg(target_os = "macos")]
+static APP: std::sync::OnceLock<tauri::AppHandle> = std::sync::OnceLock::new();
+
+/// Register the Finder service
+#[cfg(target_os = "macos")]
+pub fn init_services(handle: tauri::AppHandle) {
+    extern "C" {
+        fn vegam_register_services();
+    }
+    let _ = APP.set(handle);
+    unsafe { vegam_register_services() };
+}
+
+/// Files from Finder's "Send with Vegam", as a JSON array of paths
+#[cfg(target_os = "macos")]
+#[no_mangle]
+pub extern "C" fn vegam_services_send(paths_json: *const std::ffi::c_char) {
+    let json = unsafe { std::ffi::CStr::from_ptr(paths_json) }.to_string_lossy();
+    let paths: Vec<PathBuf> = match serde_json::from_str(&json) {
+        Ok(paths) => paths,
+        Err(e) => {
+            tracing::warn!("Invalid service request {}: {}", json, e);
+            return;
+        }
+    };
+    if let Some(app) = APP.get() {
+        raise(app);
+        send(app, paths);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line() {
+        let cwd = Path::new("/home/me");
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        assert!(send_paths(&args(&["vegam"]), cwd).is_empty());
+        assert!(send_paths(&args(&["vegam", "notes.txt"]), cwd).is_empty());
+        assert_eq!(
+            send_paths(
+                &args(&["vegam", "--send", "/tmp/a.jpg", "docs/b.pdf", "--verbose"]),
+                cwd
+            ),
+            vec![
+                PathBuf::from("/tmp/a.jpg"),
+                PathBuf::from("/home/me/docs/b.pdf")
+            ]
+        );
+
+        assert_eq!(
+            opened_args(
+                &args(&[
+                    "vegam",
+                    "vegam://abc:def",
+                    "a.vegamticket",
+                    "--send",
+                    "b.vegamticket"
+                ]),
+                cwd
+            ),
+            vec![
+                Opened::Link("vegam://abc:def".to_string()),
+                Opened::File(PathBuf::from("/home/me/a.vegamticket"))
+            ]
+        );
+        let url = url::Url::parse("file:///tmp/x.vegamticket").unwrap();
+        assert_eq!(
+            opened_url(&url),
+            Some(Opened::File(PathBuf::from("/tmp/x.vegamticket")))
+        );
+    }
+}
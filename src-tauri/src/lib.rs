@@ -1,43 +1,238 @@
+mod audit;
+mod bandwidth;
+mod chat;
+pub mod cli;
+mod clipboard;
+mod downloads;
+mod doze;
+mod drag_drop;
+mod events;
+mod fanout;
+mod foreground;
+mod gallery;
+mod history;
+mod http_share;
 mod iroh;
+mod launch;
+mod local_api;
+mod lock;
+mod metered;
+mod migrations;
+mod notify;
 mod platform;
+mod post_receive;
+mod power;
+mod quick;
+mod settings;
+mod share;
+mod shred;
 mod state;
+mod storage;
+mod watch;
+mod webhook;
 
+use events::AppEvent;
+use history::{BlobRecord, HistoryRetention, TransferFilter, TransferPage};
+use iroh::gc::BlobGcPolicy;
 use iroh::transfer::BlobTicketInfo;
-use state::{AppState, PeerInfo, TransferDirection, TransferInfo, TransferStatus};
+use settings::{DiscoveryTimings, FileTypePolicy, PeerGroup, TrustedPeer};
+use state::{
+    AppState, PeerCapabilities, PeerInfo, TransferDirection, TransferInfo, TransferStatus,
+};
 use std::path::PathBuf;
-use tauri::{Emitter, Manager, State};
+use storage::{StoragePaths, StorageUsage};
+use tauri::{Manager, State};
 use tauri_plugin_log::{log, Target, TargetKind};
 use tracing::info;
 
+/// Transfers returned by `get_transfer_history` when no limit is given
+const DEFAULT_HISTORY_LIMIT: u32 = 100;
+
+/// Transfers per page in `list_transfers` when no page size is given
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Results returned by `search_transfers` when no limit is given
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+
+/// Entries returned by `get_audit_log` when no limit is given
+const DEFAULT_AUDIT_LIMIT: u32 = 200;
+
+/// Upper bound on how long exit waits for goodbye broadcasts
+const EXIT_GOODBYE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+/// Upper bound on how long exit waits for the router to close connections
+const EXIT_SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Start the node; with `ephemeral` the session uses a throwaway identity and
+/// persists nothing (see `AppState::enter_ephemeral_mode`)
 #[tauri::command]
-async fn init_node(state: State<'_, AppState>, app: tauri::AppHandle) -> Result<String, String> {
+async fn init_node(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    ephemeral: Option<bool>,
+) -> Result<String, String> {
     info!("Initializing Iroh node with gossip protocol");
 
     // Get data directory for persistent blob store
-    let data_dir = app
-        .path()
-        .app_local_data_dir()
-        .map_err(|e| format!("Failed to get data directory: {}", e))?
-        .join("iroh");
+    let data_dir = node_data_dir(&app)?;
+
+    if ephemeral.unwrap_or(false) && !state.is_ephemeral().await {
+        if state.get_iroh().await.is_ok() {
+            return Err("Ephemeral mode must be chosen before the node starts".to_string());
+        }
+        info!("Starting an ephemeral session");
+        state.enter_ephemeral_mode().await;
+    }
+    let ephemeral = state.is_ephemeral().await;
+
+    let settings = state.get_settings().await;
+    let blob_store = if ephemeral {
+        iroh::BlobStoreKind::Memory
+    } else {
+        settings.blob_store
+    };
 
     // Initialize Iroh with Router, Blobs, and Gossip
-    let iroh = crate::iroh::Iroh::new(data_dir.clone())
-        .await
-        .map_err(|e| format!("Failed to initialize Iroh: {}", e))?;
+    let options = iroh::NodeOptions {
+        access_policy: state.access_policy.clone(),
+        peers: state.peers.clone(),
+        blobs_served: state.blobs_served.clone(),
+        audit: state.audit.clone(),
+        ephemeral,
+        network: settings.network.clone(),
+        bandwidth: state.bandwidth.clone(),
+        low_power: state.power.is_low_power(),
+        serving: state.serving.clone(),
+    };
+    let iroh = crate::iroh::Iroh::new(
+        data_dir.clone(),
+        Some(iroh::pairing::Pairing::new(app.clone())),
+        Some(iroh::control::Control::new(app.clone())),
+        blob_store,
+        options.clone(),
+    )
+    .await
+    .map_err(|e| format!("Failed to initialize Iroh: {}", e))?;
 
     let node_id = iroh.node_addr.id.to_string();
 
-    // Extract gossip receiver and sender for peer discovery
-    let receiver = iroh
-        .gossip
-        .take_receiver()
-        .await
-        .map_err(|e| format!("Failed to get gossip receiver: {}", e))?;
+    // Dial recent transfer peers at their last address instead of waiting
+    // for discovery to find them
+    match state.recent_peer_addrs().await {
+        Ok(addrs) => {
+            for addr in addrs {
+                iroh.address_book.add_endpoint_info(addr);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to load recent peer addresses: {}", e),
+    }
+
+    // Transfers still running when the app last exited can't continue
+    if !ephemeral {
+        match state.recover_transfers().await {
+            Ok(recovered) => {
+                for transfer in recovered {
+                    info!("Recovered transfer {}: {:?}", transfer.id, transfer.status);
+                    let _ = AppEvent::TransferUpdate(transfer).emit(&app);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to recover transfers: {}", e),
+        }
+        match state.resumable_receives().await {
+            Ok(resumable) if !resumable.is_empty() => {
+                let transfers = resumable
+                    .into_iter()
+                    .map(|(transfer, _)| transfer)
+                    .collect();
+                let _ = AppEvent::ReceivesResumable(transfers).emit(&app);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to read interrupted receives: {}", e),
+        }
+    }
+
+    // Rejoin the private room from the previous session, if any
+    if let Some(room_code) = &settings.room_code {
+        let topic_id = iroh::room::topic_for_room(room_code)
+            .map_err(|e| format!("Invalid saved room code: {}", e))?;
+        let room_key = iroh::room::key_for_room(room_code)
+            .map_err(|e| format!("Invalid saved room code: {}", e))?;
+        iroh.gossip
+            .join(topic_id, room_bootstrap(&settings))
+            .await
+            .map_err(|e| format!("Failed to join room: {}", e))?;
+        iroh.gossip.set_room_key(topic_id, room_key).await;
+    } else if let Some(invite) = &settings.room_invite {
+        let ticket = invite
+            .parse::<iroh::GossipTicket>()
+            .map_err(|e| format!("Invalid saved room invite: {}", e))?;
+        iroh.gossip
+            .join(ticket.topic_id, invite_bootstrap(&settings, &ticket))
+            .await
+            .map_err(|e| format!("Failed to join room: {}", e))?;
+        if let Some(room_key) = ticket.room_key {
+            iroh.gossip.set_room_key(ticket.topic_id, room_key).await;
+        }
+    }
+
+    // Spawn peer discovery task on the primary topic
+    let primary_topic = iroh.gossip.primary_topic().await;
+    let task = iroh::discovery::start_discovery(
+        &iroh,
+        &primary_topic,
+        iroh::discovery::PRIMARY_GROUP.to_string(),
+        app.clone(),
+    )
+    .await
+    .map_err(|e| format!("Failed to start discovery: {}", e))?;
+    state.set_discovery_task(primary_topic, task).await;
+
+    // Rejoin additional peer groups
+    for group in settings.groups.values() {
+        if let Err(e) = start_group_discovery(&state, &app, &iroh, group).await {
+            tracing::warn!("Failed to rejoin group {}: {}", group.name, e);
+        }
+    }
+
+    // Follow changes in the shared spaces we're a member of
+    match iroh::spaces::list(&iroh).await {
+        Ok(spaces) => {
+            for space in spaces {
+                match iroh::spaces::watch(&iroh, &space.id, app.clone()).await {
+                    Ok(task) => state.add_node_task(task).await,
+                    Err(e) => tracing::warn!("Failed to watch space {}: {}", space.id, e),
+                }
+            }
+        }
+        Err(e) => tracing::warn!("Failed to list shared spaces: {}", e),
+    }
 
-    let sender = iroh.gossip.get_sender().await;
+    // Keep PeerInfo.connection_type fresh
+    state
+        .add_node_task(iroh::connection::spawn_connection_monitor(
+            iroh.clone(),
+            app.clone(),
+        ))
+        .await;
 
-    // Spawn peer discovery task
-    iroh::discovery::spawn_discovery_task(receiver, sender, node_id.clone(), app.clone());
+    // Re-announce when our addresses change
+    state
+        .add_node_task(iroh::network::spawn_network_monitor(
+            iroh.clone(),
+            app.clone(),
+        ))
+        .await;
+    if !settings.network.lan_only {
+        state
+            .add_node_task(iroh::network::spawn_relay_watch(iroh.clone(), app.clone()))
+            .await;
+        state
+            .add_node_task(iroh::network::spawn_relay_health(
+                iroh.clone(),
+                app.clone(),
+                settings.network.clone(),
+            ))
+            .await;
+    }
 
     // Store iroh instance in state
     state.set_iroh(iroh).await;
@@ -46,25 +241,26 @@ async fn init_node(state: State<'_, AppState>, app: tauri::AppHandle) -> Result<
     #[cfg(debug_assertions)]
     {
         let debug_dir = data_dir.with_file_name("iroh-debug");
-        let iroh_debug = crate::iroh::Iroh::new(debug_dir)
-            .await
-            .map_err(|e| format!("Failed to initialize debug Iroh: {}", e))?;
-
-        let debug_receiver = iroh_debug
-            .gossip
-            .take_receiver()
-            .await
-            .map_err(|e| format!("Failed to get debug gossip receiver: {}", e))?;
-
-        let debug_sender = iroh_debug.gossip.get_sender().await;
-        let debug_node_id = iroh_debug.node_addr.id.to_string();
+        let iroh_debug = crate::iroh::Iroh::new(
+            debug_dir,
+            Some(iroh::pairing::Pairing::new(app.clone())),
+            Some(iroh::control::Control::new(app.clone())),
+            blob_store,
+            options,
+        )
+        .await
+        .map_err(|e| format!("Failed to initialize debug Iroh: {}", e))?;
 
-        iroh::discovery::spawn_discovery_task(
-            debug_receiver,
-            debug_sender,
-            debug_node_id,
+        let debug_topic = iroh_debug.gossip.primary_topic().await;
+        let debug_task = iroh::discovery::start_discovery(
+            &iroh_debug,
+            &debug_topic,
+            iroh::discovery::PRIMARY_GROUP.to_string(),
             app.clone(),
-        );
+        )
+        .await
+        .map_err(|e| format!("Failed to start debug discovery: {}", e))?;
+        state.add_node_task(debug_task).await;
 
         state.set_iroh_debug(iroh_debug).await;
     }
@@ -77,6 +273,76 @@ async fn init_node(state: State<'_, AppState>, app: tauri::AppHandle) -> Result<
     Ok(node_id)
 }
 
+/// Shut the node down and start it again, applying `network` first if given
+///
+/// Refused while transfers are running, since their connections would drop.
+#[tauri::command]
+async fn restart_node(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    network: Option<iroh::network::NetworkOptions>,
+) -> Result<String, String> {
+    let running = state.transfers.read().await.values().any(|t| {
+        matches!(
+            t.status,
+            TransferStatus::Pending | TransferStatus::InProgress
+        )
+    });
+    if running {
+        return Err("Can't restart the node while transfers are running".to_string());
+    }
+
+    if let Some(network) = network {
+        if let Some(proxy_url) = &network.proxy_url {
+            iroh::network::parse_proxy_url(proxy_url)
+                .map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        }
+        for relay_url in &network.relay_urls {
+            iroh::network::parse_relay_url(relay_url)
+                .map_err(|e| format!("Invalid relay URL {}: {}", relay_url, e))?;
+        }
+        state
+            .update_settings(|s| s.network = network)
+            .await
+            .map_err(|e| format!("Failed to save settings: {}", e))?;
+    }
+
+    info!("Restarting Iroh node");
+    shutdown_node(&state).await;
+    init_node(state, app, None).await
+}
+
+/// Stop the node's background tasks, say goodbye to peers and close the
+/// router, which shuts down gossip, the blob provider and the endpoint
+async fn shutdown_node(state: &AppState) {
+    state.stop_node_tasks().await;
+    if let Some(old) = state.take_iroh().await {
+        if state.get_settings().await.discoverable {
+            let goodbye = iroh::discovery::broadcast_goodbye(&old);
+            if tokio::time::timeout(EXIT_GOODBYE_TIMEOUT, goodbye)
+                .await
+                .is_err()
+            {
+                tracing::warn!("Timed out sending goodbye");
+            }
+        }
+        match tokio::time::timeout(EXIT_SHUTDOWN_TIMEOUT, old.shutdown()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!("Node shutdown failed: {}", e),
+            Err(_) => tracing::warn!("Timed out shutting down the node"),
+        }
+    }
+    #[cfg(debug_assertions)]
+    if let Some(old) = state.iroh_debug.write().await.take() {
+        let _ = tokio::time::timeout(EXIT_SHUTDOWN_TIMEOUT, old.shutdown()).await;
+    }
+}
+
+#[tauri::command]
+async fn is_ephemeral(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.is_ephemeral().await)
+}
+
 #[tauri::command]
 async fn get_node_id(state: State<'_, AppState>) -> Result<String, String> {
     let iroh = state
@@ -87,47 +353,227 @@ async fn get_node_id(state: State<'_, AppState>) -> Result<String, String> {
     Ok(iroh.node_addr.id.to_string())
 }
 
+/// Files shared into the app that haven't been picked up yet; each file is
+/// returned once
+#[tauri::command]
+async fn take_shared_files() -> Result<Vec<share::SharedFile>, String> {
+    Ok(share::take())
+}
+
+/// Tickets opened from links or ticket files, each returned once
+#[tauri::command]
+async fn take_opened_tickets() -> Result<Vec<launch::OpenedTicket>, String> {
+    Ok(launch::take_opened())
+}
+
+/// Files dropped on the window that haven't been picked up yet; each file is
+/// returned once
+#[tauri::command]
+async fn take_dropped_files(
+    state: State<'_, AppState>,
+) -> Result<Vec<drag_drop::DroppedFile>, String> {
+    Ok(state.drop_zone.take().await)
+}
+
+/// Send files dropped from now on straight to a peer, or stage them (`None`)
+#[tauri::command]
+async fn set_drop_target(
+    state: State<'_, AppState>,
+    node_id: Option<String>,
+) -> Result<(), String> {
+    if let Some(node_id) = &node_id {
+        node_id
+            .parse::<iroh_base::EndpointId>()
+            .map_err(|e| format!("Invalid node ID: {}", e))?;
+    }
+    state.drop_zone.set_target(node_id).await;
+    Ok(())
+}
+
 #[tauri::command]
 async fn send_file(
     state: State<'_, AppState>,
     app: tauri::AppHandle,
     file_path: String,
+    http_link: Option<bool>,
 ) -> Result<BlobTicketInfo, String> {
+    state.require_unlocked().await?;
     info!("Sending file: {}", file_path);
+    share_file(&state, app, file_path, http_link.unwrap_or(false)).await
+}
+
+/// Import a file once and offer it to every peer in `node_ids`
+#[tauri::command]
+async fn send_to_peers(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_ids: Vec<String>,
+    file_path: String,
+) -> Result<fanout::TransferGroup, String> {
+    state.require_unlocked().await?;
+    info!("Sending {} to {} peers", file_path, node_ids.len());
+    fanout::send_to_peers(&app, node_ids, file_path).await
+}
+
+#[tauri::command]
+async fn get_transfer_groups(
+    state: State<'_, AppState>,
+) -> Result<Vec<fanout::TransferGroup>, String> {
+    Ok(state.transfer_groups.list().await)
+}
+
+#[tauri::command]
+async fn get_transfer_group(
+    state: State<'_, AppState>,
+    group_id: String,
+) -> Result<Option<fanout::TransferGroup>, String> {
+    Ok(state.transfer_groups.get(&group_id).await)
+}
 
+/// Import a file into the blob store and create its ticket, recording the
+/// send as a transfer
+async fn share_file(
+    state: &AppState,
+    app: tauri::AppHandle,
+    file_path: String,
+    http_link: bool,
+) -> Result<BlobTicketInfo, String> {
     let iroh = state
         .get_iroh()
         .await
         .map_err(|e| format!("Node not initialized: {}", e))?;
+    // Checked before the import too, so the file isn't shared without the link
+    if http_link && state.get_settings().await.trusted_only_provider {
+        return Err("Download links are off while only trusted peers may download".to_string());
+    }
 
     // Generate transfer ID upfront
     let transfer_id = uuid::Uuid::new_v4().to_string();
+    let started_at = history::now_secs();
 
-    // Emit initial pending status
-    let initial_transfer = TransferInfo {
-        id: transfer_id.clone(),
-        file_name: std::path::PathBuf::from(&file_path)
+    // Name and size without reading the file, so the pending transfer shows
+    // them while the import runs (content URIs have no usable name otherwise)
+    let metadata = platform::file_metadata(&file_path)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("No metadata for {}: {}", file_path, e);
+            platform::FileMetadata::default()
+        });
+    info!(
+        "Importing {:?} ({:?}, {:?} bytes)",
+        metadata.name, metadata.mime_type, metadata.size
+    );
+    let file_name = metadata.name.unwrap_or_else(|| {
+        std::path::PathBuf::from(&file_path)
             .file_name()
             .and_then(|n| n.to_str())
             .unwrap_or("file")
-            .to_string(),
-        file_size: 0,
+            .to_string()
+    });
+
+    // Emit initial pending status
+    let initial_transfer = TransferInfo {
+        id: transfer_id.clone(),
+        file_name: file_name.clone(),
+        file_size: metadata.size.unwrap_or(0),
         bytes_transferred: 0,
         status: TransferStatus::Pending,
         error: None,
         direction: TransferDirection::Send,
         speed_bps: 0,
+        // Tickets are shared out-of-band, so the downloader isn't known
+        peer_node_id: None,
+        started_at: Some(started_at),
+        finished_at: None,
+        ticket_hash: None,
+        note: None,
+        pinned: false,
+        actions: Vec::new(),
+        saved_path: None,
     };
     state.add_transfer(initial_transfer.clone()).await;
-    let _ = app.emit("transfer-update", &initial_transfer);
+    let _ = AppEvent::TransferUpdate(initial_transfer.clone()).emit(&app);
 
-    // Read file using platform-specific handler (handles Android content URIs)
+    // Large sends wait while the battery is low unless forced (see `power.rs`)
+    if let Some(size) = metadata.size {
+        let policy = state.get_settings().await.battery_defer;
+        state
+            .power
+            .wait_for_battery(&transfer_id, size, policy, |status| {
+                info!("Deferring {} until the battery recovers", file_name);
+                let deferred = power::DeferredSend {
+                    transfer: initial_transfer.clone(),
+                    battery_level: status.level,
+                    battery_threshold: policy.battery_threshold,
+                };
+                let _ = AppEvent::SendDeferred(deferred).emit(&app);
+            })
+            .await;
+    }
+
+    // Open file using platform-specific handler (handles Android content URIs);
+    // it is streamed into the blob store rather than read into memory
     let start_time = std::time::Instant::now();
-    let file_data = platform::read_file(&app, &file_path)
+    let file = platform::open_file(&app, &file_path)
         .await
         .map_err(|e| format!("Failed to read file: {}", e))?;
 
-    let file_size = file_data.len() as u64;
+    // End-to-end encryption already keeps plaintext out of the store
+    let settings = state.get_settings().await;
+    let store_key = if settings.encrypt_blob_store && !settings.encrypt_content {
+        let key = iroh::at_rest::store_key(&node_data_dir(&app)?)
+            .await
+            .map_err(|e| e.to_string())?;
+        Some(key)
+    } else {
+        None
+    };
+    let encryption = match &store_key {
+        Some(key) => iroh::transfer::SendEncryption::AtRest(key),
+        None if settings.encrypt_content => iroh::transfer::SendEncryption::EndToEnd,
+        None => iroh::transfer::SendEncryption::None,
+    };
+
+    // Progress per imported chunk, throttled like receives
+    let app_progress = app.clone();
+    let importing = TransferInfo {
+        status: TransferStatus::InProgress,
+        ..initial_transfer.clone()
+    };
+    let last_emit = std::sync::Mutex::new((start_time, 0u64));
+    let progress_callback = move |bytes_read: u64| {
+        let mut last = last_emit.lock().unwrap();
+        let now = std::time::Instant::now();
+        let elapsed = now.duration_since(last.0);
+        if elapsed.as_millis() < 250 {
+            return;
+        }
+        let speed_bps = (bytes_read.saturating_sub(last.1) as f64 / elapsed.as_secs_f64()) as u64;
+        *last = (now, bytes_read);
+
+        let progress = TransferInfo {
+            bytes_transferred: bytes_read,
+            file_size: importing.file_size.max(bytes_read),
+            speed_bps,
+            ..importing.clone()
+        };
+        let _ = AppEvent::TransferProgress(progress).emit(&app_progress);
+
+        let app = app_progress.clone();
+        let id = importing.id.clone();
+        tauri::async_runtime::spawn(async move {
+            app.state::<AppState>()
+                .update_transfer_progress(&id, bytes_read)
+                .await;
+        });
+    };
+
+    let ticket_info =
+        iroh::transfer::create_send_ticket(&iroh, file, file_name, encryption, progress_callback)
+            .await
+            .map_err(|e| format!("Failed to create ticket: {}", e))?;
+
+    let file_size = ticket_info.file_size;
     let elapsed = start_time.elapsed().as_secs_f64();
     let speed_bps = if elapsed > 0.0 {
         (file_size as f64 / elapsed) as u64
@@ -135,7 +581,7 @@ async fn send_file(
         0
     };
 
-    // Emit reading complete status
+    // Emit import complete status
     let reading_transfer = TransferInfo {
         id: transfer_id.clone(),
         file_name: initial_transfer.file_name.clone(),
@@ -145,18 +591,37 @@ async fn send_file(
         error: None,
         direction: TransferDirection::Send,
         speed_bps,
+        peer_node_id: None,
+        started_at: Some(started_at),
+        finished_at: None,
+        ticket_hash: None,
+        note: None,
+        pinned: false,
+        actions: Vec::new(),
+        saved_path: None,
     };
     state.add_transfer(reading_transfer.clone()).await;
-    let _ = app.emit("transfer-progress", &reading_transfer);
-
-    let ticket_info = iroh::transfer::create_send_ticket(&iroh, file_data, file_path)
-        .await
-        .map_err(|e| format!("Failed to create ticket: {}", e))?;
+    let _ = AppEvent::TransferProgress(reading_transfer.clone()).emit(&app);
 
-    // Store tag to keep blob alive in MemStore until transfer completes
+    // Track the tag protecting the blob so it can be garbage collected later
     if let Some(tag) = ticket_info.tag.clone() {
-        state.add_blob_tag(tag.hash, tag.clone()).await;
-        info!("✓ Tag stored in AppState for hash: {} - blob protected from GC", tag.hash);
+        let blob = BlobRecord::new(
+            tag.hash.to_string(),
+            tag.name.to_string(),
+            ticket_info.file_name.clone(),
+            ticket_info.file_size,
+        );
+        if let Err(e) = iroh::gc::track_blob(&iroh, state, blob).await {
+            tracing::warn!("Failed to record blob {}: {}", tag.hash, e);
+        }
+
+        // Make room under the storage quota, keeping the blob just shared
+        let policy = state.get_settings().await.blob_gc;
+        let hash = tag.hash.to_string();
+        match iroh::gc::enforce_quota(&iroh, state, policy, Some(&hash)).await {
+            Ok(evicted) => iroh::gc::emit_removed(&app, evicted),
+            Err(e) => tracing::warn!("Failed to enforce storage quota: {}", e),
+        }
     } else {
         info!("⚠ Warning: No tag returned from create_send_ticket");
     }
@@ -171,11 +636,37 @@ async fn send_file(
         error: None,
         direction: TransferDirection::Send,
         speed_bps,
+        peer_node_id: None,
+        started_at: Some(started_at),
+        finished_at: Some(history::now_secs()),
+        ticket_hash: ticket_info.tag.as_ref().map(|tag| tag.hash.to_string()),
+        note: None,
+        pinned: false,
+        actions: Vec::new(),
+        saved_path: None,
     };
     state.add_transfer(transfer.clone()).await;
 
     // Emit completed event
-    let _ = app.emit("transfer-update", &transfer);
+    let _ = AppEvent::TransferUpdate(transfer).emit(&app);
+
+    let http_url = match (&ticket_info.tag, http_link) {
+        (Some(tag), true) => {
+            let link = state
+                .http_share
+                .share(
+                    &app,
+                    tag.hash,
+                    ticket_info.blob_key,
+                    ticket_info.file_name.clone(),
+                    ticket_info.file_size,
+                )
+                .await
+                .map_err(|e| format!("Failed to create download link: {}", e))?;
+            Some(link.url)
+        }
+        _ => None,
+    };
 
     // Return ticket info with transfer ID (without tag in JSON)
     Ok(BlobTicketInfo {
@@ -184,39 +675,230 @@ async fn send_file(
         file_size: ticket_info.file_size,
         transfer_id,
         tag: None, // Don't serialize tag to frontend
+        blob_key: None,
+        http_url,
     })
 }
 
+#[tauri::command]
+async fn list_http_links(
+    state: State<'_, AppState>,
+) -> Result<Vec<http_share::HttpLinkInfo>, String> {
+    state.require_unlocked().await?;
+    Ok(state.http_share.list().await)
+}
+
+#[tauri::command]
+async fn get_http_share_bind(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    Ok(state
+        .get_settings()
+        .await
+        .http_share_bind
+        .map(|ip| ip.to_string()))
+}
+
+/// Listen for browser downloads on one of our addresses, or all of them with `None`
+#[tauri::command]
+async fn set_http_share_bind(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    address: Option<String>,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let bind = match address.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(address) => Some(
+            address
+                .parse::<std::net::IpAddr>()
+                .map_err(|_| format!("Invalid IP address: {}", address))?,
+        ),
+    };
+    info!("Setting HTTP share bind address: {:?}", bind);
+    state
+        .http_share
+        .set_bind(&app, bind)
+        .await
+        .map_err(|e| format!("Can't listen on that address: {}", e))?;
+    state
+        .update_settings(|s| s.http_share_bind = bind)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn revoke_http_link(state: State<'_, AppState>, token: String) -> Result<(), String> {
+    state.require_unlocked().await?;
+    if !state.http_share.revoke(&token).await {
+        return Err("Download link not found".to_string());
+    }
+    Ok(())
+}
+
 #[tauri::command]
 async fn receive_file(
     state: State<'_, AppState>,
     app: tauri::AppHandle,
     ticket: String,
-    output_path: String,
+    output_path: Option<String>,
+    allow_blocked_type: Option<bool>,
+) -> Result<TransferInfo, String> {
+    state.require_unlocked().await?;
+    start_receive(
+        &state,
+        app,
+        ticket,
+        output_path,
+        allow_blocked_type.unwrap_or(false),
+        None,
+    )
+    .await
+}
+
+/// Receives interrupted by an exit or by the OS killing the app, which
+/// `resume_receive` can restart
+#[tauri::command]
+async fn list_resumable_receives(state: State<'_, AppState>) -> Result<Vec<TransferInfo>, String> {
+    state.require_unlocked().await?;
+    let resumable = state
+        .resumable_receives()
+        .await
+        .map_err(|e| format!("Failed to read interrupted receives: {}", e))?;
+    Ok(resumable
+        .into_iter()
+        .map(|(transfer, _)| transfer)
+        .collect())
+}
+
+/// Restart an interrupted receive under the same transfer ID; whatever the
+/// blob store already has isn't downloaded again
+#[tauri::command]
+async fn resume_receive(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    transfer_id: String,
 ) -> Result<TransferInfo, String> {
-    info!("Receiving file to: {}", output_path);
+    state.require_unlocked().await?;
+    let (transfer, receive) = state
+        .resumable_receives()
+        .await
+        .map_err(|e| format!("Failed to read interrupted receives: {}", e))?
+        .into_iter()
+        .find(|(transfer, _)| transfer.id == transfer_id)
+        .ok_or_else(|| "Transfer can't be resumed".to_string())?;
+    info!("Resuming receive {}", transfer_id);
+    start_receive(
+        &state,
+        app,
+        receive.ticket,
+        receive.output_path,
+        receive.allow_blocked_type,
+        Some(transfer),
+    )
+    .await
+}
 
+/// Start a receive in the background, returning the pending transfer
+///
+/// `resumed` is the interrupted transfer being restarted, whose ID, start
+/// time, note and pin are kept.
+async fn start_receive(
+    state: &AppState,
+    app: tauri::AppHandle,
+    ticket: String,
+    output_path: Option<String>,
+    allow_blocked_type: bool,
+    resumed: Option<TransferInfo>,
+) -> Result<TransferInfo, String> {
+    let requested_path = output_path.clone();
     let iroh = state
         .get_iroh()
         .await
         .map_err(|e| format!("Node not initialized: {}", e))?;
 
-    // Resolve to absolute path (handles relative paths from dialog)
-    let path = if PathBuf::from(&output_path).is_absolute() {
-        PathBuf::from(&output_path)
-    } else {
-        // Resolve relative to home directory for Downloads/ paths
-        app.path()
-            .resolve(&output_path, tauri::path::BaseDirectory::Home)
-            .map_err(|e| format!("Failed to resolve path: {}", e))?
-    };
-
     // Get node ID for ticket decryption
     let node_id = iroh.node_addr.id.to_string();
 
     // Parse and decrypt ticket to get file info for initial transfer
-    let (filename, file_size, _) = iroh::transfer::parse_enhanced_ticket(&ticket, &node_id)
-        .map_err(|e| format!("Invalid ticket: {}", e))?;
+    let (filename, file_size, blob_ticket, _) =
+        iroh::transfer::parse_enhanced_ticket(&ticket, &node_id)
+            .map_err(|e| format!("Invalid ticket: {}", e))?;
+
+    // Auto-reject offers from blocked peers
+    let sender_id = blob_ticket.addr().id.to_string();
+    if state.is_blocked(&sender_id).await {
+        return Err(format!("Sender {} is blocked", sender_id));
+    }
+    state.touch_peer(&sender_id).await;
+
+    if let Some(max) = state.get_settings().await.max_file_size {
+        if file_size > max {
+            return Err(format!(
+                "File is {} bytes, larger than the {} byte limit",
+                file_size, max
+            ));
+        }
+    }
+
+    // Nothing picked for this file or the sender, so it would go to Downloads
+    let default_location = output_path.is_none()
+        && !state
+            .get_settings()
+            .await
+            .peer_download_dirs
+            .contains_key(&sender_id);
+
+    // A SAF folder (content:// tree URI) on Android, picked for this file or
+    // configured for the sender or as the download directory
+    let saf_tree = match &output_path {
+        Some(output_path) => Some(output_path.clone()),
+        None => {
+            let settings = state.get_settings().await;
+            match settings.peer_download_dirs.get(&sender_id) {
+                Some(dir) => Some(dir.clone()),
+                None => settings.download_dir,
+            }
+        }
+    }
+    .filter(|dir| platform::is_content_uri(dir));
+
+    // An explicit path wins; otherwise use the sender's download directory
+    let path = match output_path {
+        // Staged in app storage, then copied into the folder once complete
+        _ if saf_tree.is_some() => {
+            let dir = node_data_dir(&app)?.with_file_name("incoming");
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .map_err(|e| format!("Failed to create staging directory: {}", e))?;
+            let name = std::path::Path::new(&filename)
+                .file_name()
+                .unwrap_or(std::ffi::OsStr::new("received_file"));
+            dir.join(name)
+        }
+        Some(output_path) => resolve_user_path(state, &app, &output_path).await?,
+        None => {
+            let dir = download_dir_for(state, &app, &sender_id).await?;
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .map_err(|e| format!("Failed to create download directory: {}", e))?;
+            let name = std::path::Path::new(&filename)
+                .file_name()
+                .unwrap_or(std::ffi::OsStr::new("received_file"));
+            dir.join(name)
+        }
+    };
+    info!("Receiving file to: {}", path.display());
+
+    // Executables etc. need the user to confirm (see `parse_ticket_metadata`)
+    if !allow_blocked_type {
+        let saved_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        state
+            .get_settings()
+            .await
+            .file_type_policy
+            .check(saved_name)
+            .map_err(|e| e.to_string())?;
+    }
 
     let file_name = if filename != "received_file" {
         filename
@@ -227,8 +909,16 @@ async fn receive_file(
             .to_string()
     };
 
-    // Generate transfer ID and create initial transfer info
-    let transfer_id = uuid::Uuid::new_v4().to_string();
+    // Generate transfer ID (or keep the resumed one) and create initial transfer info
+    let transfer_id = match &resumed {
+        Some(transfer) => transfer.id.clone(),
+        None => uuid::Uuid::new_v4().to_string(),
+    };
+    let started_at = resumed
+        .as_ref()
+        .and_then(|t| t.started_at)
+        .unwrap_or_else(history::now_secs);
+    let ticket_hash = blob_ticket.hash().to_string();
     let initial_transfer = TransferInfo {
         id: transfer_id.clone(),
         file_name: file_name.clone(),
@@ -238,15 +928,40 @@ async fn receive_file(
         error: None,
         direction: TransferDirection::Receive,
         speed_bps: 0,
+        peer_node_id: Some(sender_id.clone()),
+        started_at: Some(started_at),
+        finished_at: None,
+        ticket_hash: Some(ticket_hash.clone()),
+        note: resumed.as_ref().and_then(|t| t.note.clone()),
+        pinned: resumed.as_ref().is_some_and(|t| t.pinned),
+        actions: Vec::new(),
+        saved_path: None,
     };
 
     // Add to state and emit initial event
     state.add_transfer(initial_transfer.clone()).await;
-    let _ = app.emit("transfer-update", &initial_transfer);
+    let _ = AppEvent::TransferUpdate(initial_transfer.clone()).emit(&app);
+
+    // Lets the next launch restart this if the process dies (see `history.rs`)
+    let resumable = history::ResumableReceive {
+        transfer_id: transfer_id.clone(),
+        ticket: ticket.clone(),
+        output_path: requested_path,
+        allow_blocked_type,
+    };
+    if let Err(e) = state.remember_receive(resumable).await {
+        tracing::warn!("Failed to record receive {}: {}", transfer_id, e);
+    }
 
     // Clone necessary data before spawning to avoid lifetime issues
     let iroh_clone = iroh.clone();
-    let transfers_arc = state.transfers.clone();
+    let metered_policy = state.get_settings().await.metered;
+    // Photos and videos go to the gallery instead, unless a location was picked
+    let to_gallery = gallery::AVAILABLE
+        && default_location
+        && saf_tree.is_none()
+        && state.get_settings().await.save_media_to_gallery
+        && gallery::media_type(&path).is_some();
 
     // Spawn background task for download
     let app_clone = app.clone();
@@ -255,8 +970,31 @@ async fn receive_file(
     let transfer_id_progress = transfer_id.clone();
     let file_name_clone = file_name.clone();
     let file_name_progress = file_name.clone();
+    let peer_progress = sender_id.clone();
+    let hash_progress = ticket_hash.clone();
+    let deferred_transfer = initial_transfer.clone();
+    let dozing_transfer = initial_transfer.clone();
 
     tokio::spawn(async move {
+        // Large downloads wait for Wi-Fi unless forced (see `metered.rs`)
+        let state = app_clone.state::<AppState>();
+        state
+            .metered
+            .wait(&transfer_id_clone, file_size, metered_policy, || {
+                info!(
+                    "Deferring {} until the connection is unmetered",
+                    file_name_clone
+                );
+                let _ = AppEvent::TransferDeferred(deferred_transfer).emit(&app_clone);
+            })
+            .await;
+        // No network in Doze; wait for the device to wake (see `doze.rs`)
+        let on_dozing = || {
+            info!("Deferring {} until the device leaves Doze", file_name_clone);
+            let _ = AppEvent::TransferDeferred(dozing_transfer.clone()).emit(&app_clone);
+        };
+        state.doze.wait(on_dozing).await;
+
         // Create progress callback with 100ms throttling and speed tracking
         let app_progress = app_clone.clone();
         let last_emit = std::sync::Arc::new(std::sync::Mutex::new((
@@ -289,26 +1027,132 @@ async fn receive_file(
                     error: None,
                     direction: TransferDirection::Receive,
                     speed_bps,
+                    peer_node_id: Some(peer_progress.clone()),
+                    started_at: Some(started_at),
+                    finished_at: None,
+                    ticket_hash: Some(hash_progress.clone()),
+                    note: None,
+                    pinned: false,
+                    actions: Vec::new(),
+                    saved_path: None,
                 };
-                let _ = app_progress.emit("transfer-progress", &progress);
+                let _ = AppEvent::TransferProgress(progress).emit(&app_progress);
+
+                // Keep the cached transfer current for status queries
+                let app = app_progress.clone();
+                let id = transfer_id_progress.clone();
+                tauri::async_runtime::spawn(async move {
+                    app.state::<AppState>()
+                        .update_transfer_progress(&id, bytes_transferred)
+                        .await;
+                });
             }
         };
 
-        // Attempt download
-        let result =
-            iroh::transfer::receive_file(&iroh_clone, ticket_clone, path, progress_callback).await;
+        // Download to a temporary file, journaled so a killed process can be repaired
+        let temp_path = iroh::transfer::partial_path(&path);
+        if let Err(e) = state
+            .begin_file_write(&transfer_id_clone, &temp_path, &path)
+            .await
+        {
+            tracing::warn!("Failed to journal transfer {}: {}", transfer_id_clone, e);
+        }
+
+        // Attempt download; one cut off by Doze resumes once the device wakes,
+        // picking up what the store already has
+        let result = loop {
+            let result = iroh::transfer::receive_file(
+                &iroh_clone,
+                ticket_clone.clone(),
+                temp_path.clone(),
+                progress_callback.clone(),
+            )
+            .await;
+            match result {
+                Err(e) if state.doze.is_blocked().await => {
+                    info!("{} interrupted by Doze: {}", file_name_clone, e);
+                    state.doze.wait(on_dozing).await;
+                }
+                result => break result,
+            }
+        };
+        let result = match result {
+            Ok(transfer) => state
+                .commit_file_write(&transfer_id_clone, &temp_path, &path)
+                .await
+                .map(|()| transfer),
+            Err(e) => Err(e),
+        };
+        let result = match (result, &saf_tree) {
+            (Ok(transfer), Some(tree)) => {
+                let saved = platform::save_to_tree(&app_clone, tree, &path).await;
+                let _ = tokio::fs::remove_file(&path).await;
+                saved.map(|()| transfer).map_err(Into::into)
+            }
+            (result, _) => result,
+        };
+        if result.is_ok() && to_gallery {
+            match gallery::save(&path).await {
+                Ok(()) => {
+                    let _ = tokio::fs::remove_file(&path).await;
+                }
+                Err(e) => tracing::warn!("Keeping {} in downloads: {}", path.display(), e),
+            }
+        }
+        state.touch_peer(&sender_id).await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+        }
 
         // Update final state based on result
         match result {
             Ok(mut transfer) => {
-                // Use the original transfer_id
+                // Use the original transfer_id, name and start time
                 transfer.id = transfer_id_clone.clone();
-                let mut transfers = transfers_arc.write().await;
-                transfers.insert(transfer.id.clone(), transfer.clone());
-                drop(transfers);
-                let _ = app_clone.emit("transfer-update", &transfer);
+                transfer.file_name = file_name_clone.clone();
+                transfer.started_at = Some(started_at);
+                // Not for files handed to a SAF folder or the gallery
+                let on_disk =
+                    saf_tree.is_none() && tokio::fs::try_exists(&path).await.unwrap_or(false);
+                if on_disk {
+                    transfer.saved_path = Some(path.display().to_string());
+                }
+                state.add_transfer(transfer.clone()).await;
+                let _ = AppEvent::TransferUpdate(transfer.clone()).emit(&app_clone);
+                let notice = notify::Notice::Completed {
+                    file_name: file_name_clone.clone(),
+                };
+                notify::notify(&app_clone, notice).await;
+
+                // Rules need the file on disk, not in a SAF folder or the gallery
+                if on_disk {
+                    let (actions, moved) =
+                        post_receive::run(&state, &app_clone, path.clone()).await;
+                    if moved != path {
+                        let moved = moved.display().to_string();
+                        if let Err(e) = state.set_transfer_saved_path(&transfer.id, &moved).await {
+                            tracing::warn!("Failed to record new path of {}: {}", transfer.id, e);
+                        }
+                        transfer.saved_path = Some(moved);
+                    }
+                    if !actions.is_empty() {
+                        if let Err(e) = state
+                            .set_transfer_actions(&transfer.id, actions.clone())
+                            .await
+                        {
+                            tracing::warn!("Failed to record actions for {}: {}", transfer.id, e);
+                        }
+                        transfer.actions = actions;
+                        let _ = AppEvent::TransferUpdate(transfer).emit(&app_clone);
+                    }
+                }
             }
             Err(e) => {
+                let notice = notify::Notice::Failed {
+                    file_name: file_name_clone.clone(),
+                    error: e.to_string(),
+                };
+                notify::notify(&app_clone, notice).await;
                 let error_transfer = TransferInfo {
                     id: transfer_id_clone.clone(),
                     file_name: file_name_clone.clone(),
@@ -318,41 +1162,2666 @@ async fn receive_file(
                     error: Some(e.to_string()),
                     direction: TransferDirection::Receive,
                     speed_bps: 0,
+                    peer_node_id: Some(sender_id.clone()),
+                    started_at: Some(started_at),
+                    finished_at: Some(history::now_secs()),
+                    ticket_hash: Some(ticket_hash),
+                    note: None,
+                    pinned: false,
+                    actions: Vec::new(),
+                    saved_path: None,
                 };
-                let mut transfers = transfers_arc.write().await;
-                transfers.insert(error_transfer.id.clone(), error_transfer.clone());
-                drop(transfers);
-                let _ = app_clone.emit("transfer-update", &error_transfer);
+                state.add_transfer(error_transfer.clone()).await;
+                let _ = AppEvent::TransferUpdate(error_transfer).emit(&app_clone);
             }
         }
+        state.end_file_write(&transfer_id_clone).await;
+        state.forget_receive(&transfer_id_clone).await;
     });
 
     // Return immediately with pending transfer info
     Ok(initial_transfer)
 }
 
+/// Data directory of the main Iroh node
+fn node_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_local_data_dir()
+        .map_err(|e| format!("Failed to get data directory: {}", e))?
+        .join("iroh"))
+}
+
+/// Resolve a user-supplied path; `~/` paths are under the home directory,
+/// other relative paths under the download directory
+async fn resolve_user_path(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    path: &str,
+) -> Result<PathBuf, String> {
+    let home = downloads::home_dir(app)?;
+    let download_dir = downloads::download_dir(state, app).await?;
+    Ok(downloads::resolve(path, &home, &download_dir))
+}
+
+/// Directory for files from `node_id`: its configured directory or the
+/// download directory
+async fn download_dir_for(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    node_id: &str,
+) -> Result<PathBuf, String> {
+    match state.get_settings().await.peer_download_dirs.get(node_id) {
+        Some(dir) => resolve_user_path(state, app, dir).await,
+        None => downloads::download_dir(state, app).await,
+    }
+}
+
 #[tauri::command]
-async fn get_transfer_status(
+async fn get_download_dir(
     state: State<'_, AppState>,
-    transfer_id: String,
-) -> Result<Option<TransferInfo>, String> {
-    Ok(state.get_transfer(&transfer_id).await)
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    match state.get_settings().await.download_dir {
+        Some(dir) if platform::is_content_uri(&dir) => Ok(dir),
+        _ => Ok(downloads::download_dir(&state, &app)
+            .await?
+            .to_string_lossy()
+            .into_owned()),
+    }
 }
 
+/// Where received files go when no path is given; `None` restores the
+/// platform default
 #[tauri::command]
-async fn list_peers(state: State<'_, AppState>) -> Result<Vec<PeerInfo>, String> {
-    Ok(state.get_peers().await)
+async fn set_download_dir(state: State<'_, AppState>, dir: Option<String>) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let dir = dir.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+    if let Some(dir) = &dir {
+        let valid = dir.starts_with("~/")
+            || std::path::Path::new(dir).is_absolute()
+            || platform::is_content_uri(dir);
+        if !valid {
+            return Err(format!(
+                "Download directory must be an absolute path: {}",
+                dir
+            ));
+        }
+    }
+    info!("Setting download directory: {:?}", dir);
+    state
+        .update_settings(|s| s.download_dir = dir)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
 }
 
+/// Flatpak or Snap sandbox the app runs in, if any
 #[tauri::command]
-fn get_device_name() -> String {
-    iroh::discovery::get_device_name()
+async fn get_sandbox() -> Result<Option<platform::Sandbox>, String> {
+    Ok(platform::sandbox())
 }
 
-#[derive(serde::Serialize)]
-struct TicketMetadata {
-    filename: String,
+/// Pick files to send through the desktop portal (Linux), returning paths
+/// that stay readable inside a sandbox
+#[tauri::command]
+async fn portal_pick_files(multiple: Option<bool>) -> Result<Vec<String>, String> {
+    platform::portal_pick(false, multiple.unwrap_or(true))
+        .await
+        .map_err(|e| format!("Failed to pick files: {}", e))
+}
+
+/// Pick the download directory through the desktop portal (Linux) and save it
+#[tauri::command]
+async fn portal_pick_download_dir(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    let picked = platform::portal_pick(true, false)
+        .await
+        .map_err(|e| format!("Failed to pick a folder: {}", e))?;
+    let Some(dir) = picked.into_iter().next() else {
+        return Ok(None);
+    };
+    info!("Setting download directory from the portal: {}", dir);
+    let saved = dir.clone();
+    state
+        .update_settings(|s| s.download_dir = Some(saved))
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(Some(dir))
+}
+
+#[tauri::command]
+async fn get_bandwidth_limits(
+    state: State<'_, AppState>,
+) -> Result<bandwidth::BandwidthLimits, String> {
+    Ok(state.get_settings().await.bandwidth)
+}
+
+/// Cap total upload and download rates; applies to running transfers at once
+#[tauri::command]
+async fn set_bandwidth_limits(
+    state: State<'_, AppState>,
+    limits: bandwidth::BandwidthLimits,
+) -> Result<(), String> {
+    info!("Setting bandwidth limits: {:?}", limits);
+    state
+        .update_settings(|s| s.bandwidth = limits)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    state.bandwidth.apply(limits);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_notification_policy(
+    state: State<'_, AppState>,
+) -> Result<notify::NotificationPolicy, String> {
+    Ok(state.get_settings().await.notifications)
+}
+
+/// Choose which events raise OS notifications; asks for permission where the
+/// OS requires it
+#[tauri::command]
+async fn set_notification_policy(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    policy: notify::NotificationPolicy,
+) -> Result<(), String> {
+    info!("Setting notification policy: {:?}", policy);
+    if policy.transfer_completed || policy.transfer_failed || policy.incoming_offer {
+        use tauri::plugin::PermissionState;
+        use tauri_plugin_notification::NotificationExt;
+        let granted = matches!(
+            app.notification().permission_state(),
+            Ok(PermissionState::Granted)
+        );
+        if !granted {
+            if let Err(e) = app.notification().request_permission() {
+                tracing::warn!("Failed to request notification permission: {}", e);
+            }
+        }
+    }
+    state
+        .update_settings(|s| s.notifications = policy)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_low_power_policy(state: State<'_, AppState>) -> Result<power::LowPowerPolicy, String> {
+    Ok(state.get_settings().await.low_power)
+}
+
+#[tauri::command]
+async fn set_low_power_policy(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    policy: power::LowPowerPolicy,
+) -> Result<(), String> {
+    info!("Setting low-power policy: {:?}", policy);
+    state
+        .update_settings(|s| s.low_power = policy)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    if let Some(low_power) = state.power.refresh(policy).await {
+        let _ = AppEvent::LowPowerChanged(low_power).emit(&app);
+    }
+    Ok(())
+}
+
+/// Report battery status where the backend can't read it; `None` goes back
+/// to OS detection
+#[tauri::command]
+async fn set_power_status(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    status: Option<power::PowerStatus>,
+) -> Result<(), String> {
+    state.power.set_reported(status).await;
+    let policy = state.get_settings().await.low_power;
+    if let Some(low_power) = state.power.refresh(policy).await {
+        let _ = AppEvent::LowPowerChanged(low_power).emit(&app);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn is_low_power(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.power.is_low_power())
+}
+
+#[tauri::command]
+async fn get_battery_defer_policy(
+    state: State<'_, AppState>,
+) -> Result<power::BatteryDeferPolicy, String> {
+    Ok(state.get_settings().await.battery_defer)
+}
+
+#[tauri::command]
+async fn set_battery_defer_policy(
+    state: State<'_, AppState>,
+    policy: power::BatteryDeferPolicy,
+) -> Result<(), String> {
+    info!("Setting battery deferral policy: {:?}", policy);
+    state
+        .update_settings(|s| s.battery_defer = policy)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_clipboard_sync(
+    state: State<'_, AppState>,
+) -> Result<clipboard::ClipboardSyncSettings, String> {
+    Ok(state.get_settings().await.clipboard_sync)
+}
+
+#[tauri::command]
+async fn set_clipboard_sync(
+    state: State<'_, AppState>,
+    settings: clipboard::ClipboardSyncSettings,
+) -> Result<(), String> {
+    info!("Setting clipboard sync: {:?}", settings);
+    state
+        .update_settings(|s| s.clipboard_sync = settings)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+/// Turn clipboard sync with one trusted peer on or off
+#[tauri::command]
+async fn set_clipboard_sync_device(
+    state: State<'_, AppState>,
+    node_id: String,
+    enabled: bool,
+) -> Result<(), String> {
+    if enabled && !state.is_trusted(&node_id).await {
+        return Err("Only trusted devices can sync the clipboard".to_string());
+    }
+    state
+        .update_settings(|s| {
+            if enabled {
+                s.clipboard_sync.devices.insert(node_id);
+            } else {
+                s.clipboard_sync.devices.remove(&node_id);
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_webhook(state: State<'_, AppState>) -> Result<webhook::WebhookSettings, String> {
+    Ok(state.get_settings().await.webhook)
+}
+
+#[tauri::command]
+async fn set_webhook(
+    state: State<'_, AppState>,
+    settings: webhook::WebhookSettings,
+) -> Result<webhook::WebhookSettings, String> {
+    state.require_unlocked().await?;
+    let settings = settings.validated()?;
+    info!(
+        "Setting webhook: enabled={} url={}",
+        settings.enabled, settings.url
+    );
+    state
+        .update_settings(|s| s.webhook = settings.clone())
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(settings)
+}
+
+/// Send a `ping` to the configured webhook and wait for the result
+#[tauri::command]
+async fn test_webhook(state: State<'_, AppState>) -> Result<(), String> {
+    let settings = state.get_settings().await.webhook;
+    webhook::validate_url(&settings.url)?;
+    if settings.secret.trim().is_empty() {
+        return Err("Set a webhook secret first".to_string());
+    }
+    state
+        .webhook
+        .test(&settings)
+        .await
+        .map_err(|e| format!("Webhook failed: {}", e))
+}
+
+#[tauri::command]
+async fn get_local_api(state: State<'_, AppState>) -> Result<local_api::LocalApiInfo, String> {
+    // The token grants full control over the API
+    state.require_unlocked().await?;
+    let settings = state.get_settings().await.local_api;
+    let url = state
+        .local_api
+        .port()
+        .await
+        .map(|port| format!("http://127.0.0.1:{}/v1", port));
+    Ok(local_api::LocalApiInfo {
+        enabled: settings.enabled,
+        port: settings.port,
+        token: settings.token,
+        url,
+    })
+}
+
+/// Turn the local API on or off, generating its token the first time
+#[tauri::command]
+async fn set_local_api(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    enabled: bool,
+    port: Option<u16>,
+) -> Result<local_api::LocalApiInfo, String> {
+    state.require_unlocked().await?;
+    info!("Setting local API: enabled={} port={:?}", enabled, port);
+    let settings = state
+        .update_settings(|s| {
+            s.local_api.enabled = enabled;
+            if let Some(port) = port {
+                s.local_api.port = port;
+            }
+            if s.local_api.token.is_empty() {
+                s.local_api.token = local_api::generate_token();
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    state
+        .local_api
+        .apply(&app, &settings.local_api)
+        .await
+        .map_err(|e| format!("Failed to start the local API: {}", e))?;
+    get_local_api(state).await
+}
+
+/// Replace the local API token; clients using the old one are refused
+#[tauri::command]
+async fn regenerate_local_api_token(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<local_api::LocalApiInfo, String> {
+    state.require_unlocked().await?;
+    let settings = state
+        .update_settings(|s| s.local_api.token = local_api::generate_token())
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    state
+        .local_api
+        .apply(&app, &settings.local_api)
+        .await
+        .map_err(|e| format!("Failed to start the local API: {}", e))?;
+    get_local_api(state).await
+}
+
+#[tauri::command]
+async fn list_watch_folders(state: State<'_, AppState>) -> Result<Vec<watch::WatchFolder>, String> {
+    Ok(state.get_settings().await.watch_folders)
+}
+
+/// Send new files in `path` to a peer or group; `ignore` defaults to hidden
+/// files and partial downloads
+#[tauri::command]
+async fn add_watch_folder(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    path: String,
+    target: watch::WatchTarget,
+    ignore: Option<Vec<String>>,
+    debounce_secs: Option<u64>,
+) -> Result<watch::WatchFolder, String> {
+    state.require_unlocked().await?;
+    if !tokio::fs::metadata(&path)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+    {
+        return Err(format!("{} is not a directory", path));
+    }
+    let folder = watch::WatchFolder {
+        id: uuid::Uuid::new_v4().to_string(),
+        path,
+        target,
+        ignore: ignore.unwrap_or_else(watch::default_ignore),
+        debounce_secs: debounce_secs.unwrap_or_else(watch::default_debounce_secs),
+        enabled: true,
+    };
+    info!("Adding watch folder {} ({})", folder.path, folder.id);
+    let settings = state
+        .update_settings(|s| s.watch_folders.push(folder.clone()))
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    state
+        .watch_folders
+        .sync(&app, &settings.watch_folders)
+        .await;
+    Ok(folder)
+}
+
+#[tauri::command]
+async fn remove_watch_folder(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let mut found = false;
+    let settings = state
+        .update_settings(|s| {
+            let before = s.watch_folders.len();
+            s.watch_folders.retain(|folder| folder.id != id);
+            found = s.watch_folders.len() != before;
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    if !found {
+        return Err("Unknown watch folder".to_string());
+    }
+    state
+        .watch_folders
+        .sync(&app, &settings.watch_folders)
+        .await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_watch_folder_enabled(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    id: String,
+    enabled: bool,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let mut found = false;
+    let settings = state
+        .update_settings(|s| {
+            if let Some(folder) = s.watch_folders.iter_mut().find(|f| f.id == id) {
+                folder.enabled = enabled;
+                found = true;
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    if !found {
+        return Err("Unknown watch folder".to_string());
+    }
+    state
+        .watch_folders
+        .sync(&app, &settings.watch_folders)
+        .await;
+    Ok(())
+}
+
+/// IDs of watch folders being watched right now
+#[tauri::command]
+async fn get_running_watches(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.watch_folders.running().await)
+}
+
+#[tauri::command]
+async fn list_spaces(state: State<'_, AppState>) -> Result<Vec<iroh::spaces::SpaceInfo>, String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    iroh::spaces::list(&iroh)
+        .await
+        .map_err(|e| format!("Failed to list spaces: {}", e))
+}
+
+#[tauri::command]
+async fn create_space(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<iroh::spaces::SpaceInfo, String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    let space = iroh::spaces::create(&iroh, name.trim())
+        .await
+        .map_err(|e| format!("Failed to create space: {}", e))?;
+    let task = iroh::spaces::watch(&iroh, &space.id, app)
+        .await
+        .map_err(|e| format!("Failed to watch space: {}", e))?;
+    state.add_node_task(task).await;
+    Ok(space)
+}
+
+/// Ticket for joining a space; `writable` lets members add files
+#[tauri::command]
+async fn share_space(
+    state: State<'_, AppState>,
+    space_id: String,
+    writable: Option<bool>,
+) -> Result<String, String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    iroh::spaces::share(&iroh, &space_id, writable.unwrap_or(true))
+        .await
+        .map_err(|e| format!("Failed to share space: {}", e))
+}
+
+#[tauri::command]
+async fn join_space(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    ticket: String,
+) -> Result<String, String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    let space_id = iroh::spaces::join(&iroh, &ticket)
+        .await
+        .map_err(|e| format!("Failed to join space: {}", e))?;
+    let task = iroh::spaces::watch(&iroh, &space_id, app)
+        .await
+        .map_err(|e| format!("Failed to watch space: {}", e))?;
+    state.add_node_task(task).await;
+    Ok(space_id)
+}
+
+#[tauri::command]
+async fn leave_space(state: State<'_, AppState>, space_id: String) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    iroh::spaces::leave(&iroh, &space_id)
+        .await
+        .map_err(|e| format!("Failed to leave space: {}", e))
+}
+
+#[tauri::command]
+async fn list_space_files(
+    state: State<'_, AppState>,
+    space_id: String,
+) -> Result<Vec<iroh::spaces::SpaceFile>, String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    iroh::spaces::files(&iroh, &space_id)
+        .await
+        .map_err(|e| format!("Failed to list space files: {}", e))
+}
+
+/// Add a file to a space under `name` (its own name by default)
+#[tauri::command]
+async fn add_space_file(
+    state: State<'_, AppState>,
+    space_id: String,
+    file_path: String,
+    name: Option<String>,
+) -> Result<iroh::spaces::SpaceFile, String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    let path = PathBuf::from(&file_path);
+    let name = match name {
+        Some(name) => name,
+        None => path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| format!("No file name in {}", file_path))?
+            .to_string(),
+    };
+    iroh::spaces::add_file(&iroh, &space_id, &path, &name)
+        .await
+        .map_err(|e| format!("Failed to add file: {}", e))
+}
+
+#[tauri::command]
+async fn remove_space_file(
+    state: State<'_, AppState>,
+    space_id: String,
+    name: String,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    iroh::spaces::remove_file(&iroh, &space_id, &name)
+        .await
+        .map_err(|e| format!("Failed to remove file: {}", e))
+}
+
+/// Save a space file; without a path it goes to the download directory
+#[tauri::command]
+async fn save_space_file(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    space_id: String,
+    name: String,
+    output_path: Option<String>,
+) -> Result<String, String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    let path = match output_path {
+        Some(output_path) => resolve_user_path(&state, &app, &output_path).await?,
+        None => {
+            let dir = downloads::download_dir(&state, &app).await?;
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .map_err(|e| format!("Failed to create download directory: {}", e))?;
+            dir.join(&name)
+        }
+    };
+    iroh::spaces::save_file(&iroh, &space_id, &name, &path)
+        .await
+        .map_err(|e| format!("Failed to save file: {}", e))?;
+    Ok(path.display().to_string())
+}
+
+#[tauri::command]
+async fn list_post_receive_rules(
+    state: State<'_, AppState>,
+) -> Result<Vec<post_receive::PostReceiveRule>, String> {
+    Ok(state.get_settings().await.post_receive)
+}
+
+/// Add a rule run on received files; no extensions matches every file
+#[tauri::command]
+async fn add_post_receive_rule(
+    state: State<'_, AppState>,
+    action: post_receive::PostAction,
+    extensions: Option<Vec<String>>,
+    directory: Option<String>,
+) -> Result<post_receive::PostReceiveRule, String> {
+    state.require_unlocked().await?;
+    if let post_receive::PostAction::Run { program, .. } = &action {
+        if program.trim().is_empty() {
+            return Err("No program to run".to_string());
+        }
+    }
+    let rule = post_receive::PostReceiveRule {
+        id: uuid::Uuid::new_v4().to_string(),
+        extensions: extensions.unwrap_or_default(),
+        directory,
+        action,
+        enabled: true,
+    };
+    info!("Adding post-receive rule {:?}", rule);
+    state
+        .update_settings(|s| s.post_receive.push(rule.clone()))
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(rule)
+}
+
+#[tauri::command]
+async fn remove_post_receive_rule(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let mut found = false;
+    state
+        .update_settings(|s| {
+            let before = s.post_receive.len();
+            s.post_receive.retain(|rule| rule.id != id);
+            found = s.post_receive.len() != before;
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    if !found {
+        return Err("Unknown post-receive rule".to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_post_receive_rule_enabled(
+    state: State<'_, AppState>,
+    id: String,
+    enabled: bool,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let mut found = false;
+    state
+        .update_settings(|s| {
+            if let Some(rule) = s.post_receive.iter_mut().find(|r| r.id == id) {
+                rule.enabled = enabled;
+                found = true;
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    if !found {
+        return Err("Unknown post-receive rule".to_string());
+    }
+    Ok(())
+}
+
+/// Transfer IDs of sends waiting for the battery
+#[tauri::command]
+async fn get_deferred_sends(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.power.deferred_sends().await)
+}
+
+/// Start a send deferred for the battery right away
+#[tauri::command]
+async fn force_send(state: State<'_, AppState>, transfer_id: String) -> Result<(), String> {
+    state.require_unlocked().await?;
+    if !state.power.force_send(&transfer_id).await {
+        return Err("Send is not deferred".to_string());
+    }
+    info!("Force-starting send {}", transfer_id);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_metered_policy(state: State<'_, AppState>) -> Result<metered::MeteredPolicy, String> {
+    Ok(state.get_settings().await.metered)
+}
+
+#[tauri::command]
+async fn set_metered_policy(
+    state: State<'_, AppState>,
+    policy: metered::MeteredPolicy,
+) -> Result<(), String> {
+    info!("Setting metered policy: {:?}", policy);
+    state
+        .update_settings(|s| s.metered = policy)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+/// Report whether the connection is metered where the backend can't tell;
+/// `None` goes back to OS detection
+#[tauri::command]
+async fn set_network_metered(
+    state: State<'_, AppState>,
+    metered: Option<bool>,
+) -> Result<(), String> {
+    state.metered.set_reported(metered).await;
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct MeteredStatus {
+    metered: bool,
+    /// Transfer IDs of deferred downloads
+    deferred: Vec<String>,
+}
+
+#[tauri::command]
+async fn get_metered_status(state: State<'_, AppState>) -> Result<MeteredStatus, String> {
+    Ok(MeteredStatus {
+        metered: state.metered.is_metered().await,
+        deferred: state.metered.deferred().await,
+    })
+}
+
+/// Start a download deferred on a metered connection right away
+#[tauri::command]
+async fn force_start(state: State<'_, AppState>, transfer_id: String) -> Result<(), String> {
+    state.require_unlocked().await?;
+    if !state.metered.force_start(&transfer_id).await {
+        return Err("Transfer is not deferred".to_string());
+    }
+    info!("Force-starting transfer {}", transfer_id);
+    Ok(())
+}
+
+/// Doze and battery optimization state; never restricted off Android
+#[tauri::command]
+async fn get_background_restrictions(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<doze::BackgroundRestrictions, String> {
+    if let Some(restrictions) = doze::detect().await {
+        if state.doze.update(restrictions).await {
+            let _ = AppEvent::BackgroundRestrictionsChanged(restrictions).emit(&app);
+        }
+    }
+    Ok(state.doze.current().await)
+}
+
+/// Open the system list where vegam can be exempted from battery optimization
+#[tauri::command]
+async fn open_battery_optimization_settings() -> Result<(), String> {
+    doze::open_battery_settings().await
+}
+
+#[tauri::command]
+async fn get_transfer_status(
+    state: State<'_, AppState>,
+    transfer_id: String,
+) -> Result<Option<TransferInfo>, String> {
+    state.require_unlocked().await?;
+    Ok(state.get_transfer(&transfer_id).await)
+}
+
+#[tauri::command]
+async fn get_peer_transfers(
+    state: State<'_, AppState>,
+    node_id: String,
+) -> Result<Vec<TransferInfo>, String> {
+    state.require_unlocked().await?;
+    Ok(state.get_peer_transfers(&node_id).await)
+}
+
+#[tauri::command]
+async fn get_transfer_history(
+    state: State<'_, AppState>,
+    limit: Option<u32>,
+) -> Result<Vec<TransferInfo>, String> {
+    state.require_unlocked().await?;
+    Ok(state
+        .get_transfer_history(limit.unwrap_or(DEFAULT_HISTORY_LIMIT))
+        .await)
+}
+
+#[tauri::command]
+async fn list_transfers(
+    state: State<'_, AppState>,
+    filter: Option<TransferFilter>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+) -> Result<TransferPage, String> {
+    state.require_unlocked().await?;
+    state
+        .list_transfers(
+            filter.unwrap_or_default(),
+            page.unwrap_or(0),
+            page_size.unwrap_or(DEFAULT_PAGE_SIZE),
+        )
+        .await
+        .map_err(|e| format!("Failed to list transfers: {}", e))
+}
+
+#[tauri::command]
+async fn search_transfers(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<TransferInfo>, String> {
+    state.require_unlocked().await?;
+    let limit = limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .min(history::MAX_PAGE_SIZE);
+    state
+        .search_transfers(query, limit)
+        .await
+        .map_err(|e| format!("Failed to search transfers: {}", e))
+}
+
+#[tauri::command]
+async fn set_transfer_note(
+    state: State<'_, AppState>,
+    transfer_id: String,
+    note: Option<String>,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    // Treat a blank note as clearing it
+    let note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    let found = state
+        .set_transfer_note(&transfer_id, note)
+        .await
+        .map_err(|e| format!("Failed to save note: {}", e))?;
+    if !found {
+        return Err(format!("Unknown transfer: {}", transfer_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_transfer_pinned(
+    state: State<'_, AppState>,
+    transfer_id: String,
+    pinned: bool,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    info!("Setting transfer {} pinned: {}", transfer_id, pinned);
+    let found = state
+        .set_transfer_pinned(&transfer_id, pinned)
+        .await
+        .map_err(|e| format!("Failed to pin transfer: {}", e))?;
+    if !found {
+        return Err(format!("Unknown transfer: {}", transfer_id));
+    }
+    Ok(())
+}
+
+/// Delete finished, unpinned transfers created before `older_than` (unix seconds), or all of them
+#[tauri::command]
+async fn clear_history(
+    state: State<'_, AppState>,
+    older_than: Option<u64>,
+) -> Result<usize, String> {
+    state.require_unlocked().await?;
+    info!("Clearing transfer history older than {:?}", older_than);
+    state
+        .clear_history(older_than)
+        .await
+        .map_err(|e| format!("Failed to clear history: {}", e))
+}
+
+#[tauri::command]
+async fn get_history_retention(state: State<'_, AppState>) -> Result<HistoryRetention, String> {
+    Ok(state.get_settings().await.history_retention)
+}
+
+/// Set how long history is kept and apply it right away
+#[tauri::command]
+async fn set_history_retention(
+    state: State<'_, AppState>,
+    max_age_days: Option<u32>,
+    max_entries: Option<u32>,
+) -> Result<HistoryRetention, String> {
+    let retention = HistoryRetention {
+        max_age_days,
+        max_entries,
+    };
+    info!("Setting history retention: {:?}", retention);
+
+    state
+        .update_settings(|s| s.history_retention = retention)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    state
+        .enforce_history_retention(retention)
+        .await
+        .map_err(|e| format!("Failed to prune history: {}", e))?;
+
+    Ok(retention)
+}
+
+#[tauri::command]
+async fn list_blobs(state: State<'_, AppState>) -> Result<Vec<BlobRecord>, String> {
+    state
+        .list_blobs()
+        .await
+        .map_err(|e| format!("Failed to list blobs: {}", e))
+}
+
+#[tauri::command]
+async fn delete_blob(state: State<'_, AppState>, hash: String) -> Result<(), String> {
+    state.require_unlocked().await?;
+    info!("Deleting blob: {}", hash);
+    let iroh = state.get_iroh().await.map_err(|e| e.to_string())?;
+    let blob = state
+        .get_blob(&hash)
+        .await
+        .map_err(|e| format!("Failed to read blob: {}", e))?
+        .ok_or_else(|| format!("Unknown blob: {}", hash))?;
+
+    iroh::gc::delete_blob(&iroh, &state, &blob)
+        .await
+        .map_err(|e| format!("Failed to delete blob: {}", e))
+}
+
+/// Overwrite and delete a received file; with `transfer_id`, also drop its
+/// blob from the store and scrub the history entry
+///
+/// With `transfer_id`, `path` must be where that transfer's file was saved;
+/// without one, it must be inside a download directory.
+#[tauri::command]
+async fn shred_file(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    path: String,
+    transfer_id: Option<String>,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let path = resolve_user_path(&state, &app, &path).await?;
+    let transfer = match transfer_id {
+        Some(id) => Some(
+            state
+                .get_transfer(&id)
+                .await
+                .ok_or_else(|| format!("Unknown transfer: {}", id))?,
+        ),
+        None => None,
+    };
+    let canonical = tokio::fs::canonicalize(&path)
+        .await
+        .map_err(|e| format!("Failed to find {}: {}", path.display(), e))?;
+    match &transfer {
+        Some(transfer) => {
+            let saved = transfer
+                .saved_path
+                .as_deref()
+                .ok_or("This transfer has no saved file to shred")?;
+            let saved = tokio::fs::canonicalize(saved)
+                .await
+                .map_err(|e| format!("Failed to find the saved file: {}", e))?;
+            if saved != canonical {
+                return Err("That isn't the file this transfer saved".to_string());
+            }
+        }
+        None => {
+            let mut dirs = vec![downloads::download_dir(&state, &app).await?];
+            for dir in state.get_settings().await.peer_download_dirs.into_values() {
+                if let Ok(dir) = resolve_user_path(&state, &app, &dir).await {
+                    dirs.push(dir);
+                }
+            }
+            let mut inside = false;
+            for dir in dirs {
+                if let Ok(dir) = tokio::fs::canonicalize(&dir).await {
+                    inside |= canonical.starts_with(dir);
+                }
+            }
+            if !inside {
+                return Err("Only files in a download directory can be shredded".to_string());
+            }
+        }
+    }
+    if transfer.as_ref().is_some_and(|t| t.is_active()) {
+        return Err("Transfer is still running".to_string());
+    }
+
+    info!("Shredding {}", path.display());
+    shred::shred_file(&path)
+        .await
+        .map_err(|e| format!("Failed to shred file: {}", e))?;
+
+    let Some(transfer) = transfer else {
+        return Ok(());
+    };
+    if let Some(hash) = &transfer.ticket_hash {
+        let iroh = state.get_iroh().await.map_err(|e| e.to_string())?;
+        iroh::gc::purge_blob(&iroh, &state, hash)
+            .await
+            .map_err(|e| format!("Failed to delete blob: {}", e))?;
+    }
+    state
+        .purge_transfer(&transfer.id)
+        .await
+        .map_err(|e| format!("Failed to delete history entry: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn pin_blob(state: State<'_, AppState>, hash: String, pinned: bool) -> Result<(), String> {
+    info!("Setting blob {} pinned: {}", hash, pinned);
+    let found = state
+        .pin_blob(&hash, pinned)
+        .await
+        .map_err(|e| format!("Failed to pin blob: {}", e))?;
+    if !found {
+        return Err(format!("Unknown blob: {}", hash));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_blob_gc_policy(state: State<'_, AppState>) -> Result<BlobGcPolicy, String> {
+    Ok(state.get_settings().await.blob_gc)
+}
+
+#[tauri::command]
+async fn set_blob_gc_policy(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    max_idle_days: Option<u32>,
+    max_store_bytes: Option<u64>,
+) -> Result<BlobGcPolicy, String> {
+    let policy = BlobGcPolicy {
+        max_idle_days,
+        max_store_bytes,
+    };
+    info!("Setting blob GC policy: {:?}", policy);
+
+    state
+        .update_settings(|s| s.blob_gc = policy)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    // Apply right away when the node is running
+    if let Ok(iroh) = state.get_iroh().await {
+        let removed = iroh::gc::collect(&iroh, &state, policy)
+            .await
+            .map_err(|e| format!("Failed to collect blobs: {}", e))?;
+        iroh::gc::emit_removed(&app, removed);
+    }
+
+    Ok(policy)
+}
+
+/// Encrypt newly shared files in the blob store; needs a system keystore
+///
+/// Blobs shared before enabling stay as they are until collected.
+#[tauri::command]
+async fn set_blob_encryption(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    enabled: bool,
+) -> Result<(), String> {
+    info!("Setting blob store encryption: {}", enabled);
+
+    // Create the store key up front so a missing keystore is reported now
+    if enabled {
+        iroh::at_rest::store_key(&node_data_dir(&app)?)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    state
+        .update_settings(|s| s.encrypt_blob_store = enabled)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+/// Encrypt newly shared files end to end, with a key only the ticket carries
+#[tauri::command]
+async fn set_content_encryption(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    info!("Setting end-to-end content encryption: {}", enabled);
+    state
+        .update_settings(|s| s.encrypt_content = enabled)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_content_encryption(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.get_settings().await.encrypt_content)
+}
+
+#[tauri::command]
+async fn set_save_media_to_gallery(
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    info!("Setting save media to gallery: {}", enabled);
+    state
+        .update_settings(|s| s.save_media_to_gallery = enabled)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_save_media_to_gallery(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.get_settings().await.save_media_to_gallery)
+}
+
+#[tauri::command]
+async fn set_audit_log_enabled(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state.require_unlocked().await?;
+    if state.is_ephemeral().await {
+        return Err("The audit log is off in ephemeral mode".to_string());
+    }
+    info!("Setting audit log: {}", enabled);
+    state
+        .update_settings(|s| s.audit_log = enabled)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    state.audit.set_enabled(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_audit_log_enabled(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.get_settings().await.audit_log)
+}
+
+/// Newest audit log entries first
+#[tauri::command]
+async fn get_audit_log(
+    state: State<'_, AppState>,
+    limit: Option<u32>,
+) -> Result<Vec<audit::AuditEntry>, String> {
+    state.require_unlocked().await?;
+    state
+        .audit
+        .recent(limit.unwrap_or(DEFAULT_AUDIT_LIMIT) as usize)
+        .await
+        .map_err(|e| format!("Failed to read audit log: {}", e))
+}
+
+/// Write the whole audit log as JSONL to `path`; returns the bytes written
+#[tauri::command]
+async fn export_audit_log(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<u64, String> {
+    state.require_unlocked().await?;
+    let path = resolve_user_path(&state, &app, &path).await?;
+    info!("Exporting audit log to {}", path.display());
+    state
+        .audit
+        .export(&path)
+        .await
+        .map_err(|e| format!("Failed to export audit log: {}", e))
+}
+
+/// Set the app passcode and auto-lock timeout, or remove the lock with `None`
+#[tauri::command]
+async fn set_app_lock(
+    state: State<'_, AppState>,
+    passcode: Option<String>,
+    auto_lock_minutes: Option<u32>,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    let app_lock = passcode
+        .map(|passcode| lock::AppLock::new(&passcode, auto_lock_minutes))
+        .transpose()?;
+    info!("Setting app lock: {}", app_lock.is_some());
+    state
+        .update_settings(|s| s.app_lock = app_lock)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    state
+        .app_lock
+        .write()
+        .await
+        .clear(std::time::Instant::now());
+    Ok(())
+}
+
+#[tauri::command]
+async fn lock_app(state: State<'_, AppState>) -> Result<(), String> {
+    if state.get_settings().await.app_lock.is_none() {
+        return Err("No passcode set".to_string());
+    }
+    state.app_lock.write().await.lock();
+    Ok(())
+}
+
+#[tauri::command]
+async fn unlock(state: State<'_, AppState>, passcode: String) -> Result<(), String> {
+    let Some(app_lock) = state.get_settings().await.app_lock else {
+        return Ok(());
+    };
+    state
+        .app_lock
+        .write()
+        .await
+        .try_unlock(&app_lock, &passcode, std::time::Instant::now())
+}
+
+#[tauri::command]
+async fn is_locked(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.is_locked().await)
+}
+
+#[tauri::command]
+async fn get_storage_usage(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<StorageUsage, String> {
+    let local_dir = app
+        .path()
+        .app_local_data_dir()
+        .map_err(|e| format!("Failed to get data directory: {}", e))?;
+    let paths = StoragePaths {
+        blob_store: node_data_dir(&app)?.join(iroh::BLOB_STORE_DIR),
+        history: local_dir.join(history::HISTORY_FILE),
+        logs: app
+            .path()
+            .app_log_dir()
+            .map_err(|e| format!("Failed to get log directory: {}", e))?,
+    };
+
+    // A memory store keeps shared and downloading blobs in RAM, not on disk
+    let (shared_blobs, partial_downloads) = match state.get_settings().await.blob_store {
+        iroh::BlobStoreKind::Filesystem => (
+            state.blobs_size().await.unwrap_or(0),
+            state.partial_download_bytes().await,
+        ),
+        iroh::BlobStoreKind::Memory => (0, 0),
+    };
+
+    StorageUsage::measure(paths, shared_blobs, partial_downloads)
+        .await
+        .map_err(|e| format!("Failed to measure storage: {}", e))
+}
+
+#[tauri::command]
+async fn list_peers(state: State<'_, AppState>) -> Result<Vec<PeerInfo>, String> {
+    Ok(state.get_peers().await)
+}
+
+#[tauri::command]
+async fn get_device_name(state: State<'_, AppState>) -> Result<String, String> {
+    Ok(state.get_settings().await.display_name())
+}
+
+#[tauri::command]
+async fn set_device_name(state: State<'_, AppState>, name: String) -> Result<String, String> {
+    let name = name.trim().to_string();
+    info!("Setting device name: {}", name);
+
+    // An empty name resets to the hostname
+    let settings = state
+        .update_settings(|s| s.device_name = (!name.is_empty()).then_some(name))
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    state.trigger_announcement();
+
+    Ok(settings.display_name())
+}
+
+/// Reject strings that aren't valid node IDs before persisting them
+fn validate_node_id(node_id: &str) -> Result<(), String> {
+    node_id
+        .parse::<iroh_base::EndpointId>()
+        .map(|_| ())
+        .map_err(|e| format!("Invalid node ID: {}", e))
+}
+
+#[tauri::command]
+async fn trust_peer(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+) -> Result<TrustedPeer, String> {
+    state.require_unlocked().await?;
+    validate_node_id(&node_id)?;
+    info!("Trusting peer: {}", node_id);
+
+    let device_name = state
+        .peers
+        .read()
+        .await
+        .get(&node_id)
+        .map(|p| p.device_name.clone())
+        .unwrap_or_else(|| "Unknown Device".to_string());
+
+    let trusted = state
+        .trust_peer(node_id, device_name)
+        .await
+        .map_err(|e| format!("Failed to save trust store: {}", e))?;
+
+    let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(&app);
+
+    Ok(trusted)
+}
+
+#[tauri::command]
+async fn untrust_peer(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+) -> Result<(), String> {
+    info!("Untrusting peer: {}", node_id);
+
+    state
+        .update_settings(|s| {
+            s.trusted_peers.remove(&node_id);
+            s.clipboard_sync.devices.remove(&node_id);
+        })
+        .await
+        .map_err(|e| format!("Failed to save trust store: {}", e))?;
+
+    state.refresh_peers_from_settings().await;
+    let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(&app);
+
+    Ok(())
+}
+
+/// Emoji fingerprint of `node_id`, or of our own node when omitted
+#[tauri::command]
+async fn get_fingerprint(
+    state: State<'_, AppState>,
+    node_id: Option<String>,
+) -> Result<String, String> {
+    let node_id = match node_id {
+        Some(node_id) => {
+            validate_node_id(&node_id)?;
+            node_id
+        }
+        None => state
+            .get_iroh()
+            .await
+            .map_err(|e| format!("Node not initialized: {}", e))?
+            .node_addr
+            .id
+            .to_string(),
+    };
+    Ok(iroh::identity::fingerprint(&node_id))
+}
+
+/// Replace our node key; returns the new node ID, used from the next start
+///
+/// Until then the current identity keeps running. Trusted peers move their
+/// trust to the new ID when they see its first announcements.
+#[tauri::command]
+async fn rotate_identity(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    state.require_unlocked().await?;
+    // The session key is a throwaway; rotating would replace the real key and
+    // sign a link from the anonymous session to it
+    if state.is_ephemeral().await {
+        return Err("Identity can't be rotated in ephemeral mode".to_string());
+    }
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    let transition = iroh::identity::rotate(&node_data_dir(&app)?, iroh.endpoint.secret_key())
+        .await
+        .map_err(|e| format!("Failed to rotate identity: {}", e))?;
+    let new_node_id = transition.new_node_id.clone();
+
+    state
+        .update_settings(|s| s.identity_transition = Some(transition))
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(new_node_id)
+}
+
+/// Mark a trusted peer as verified after comparing fingerprints
+#[tauri::command]
+async fn set_peer_verified(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+    verified: bool,
+) -> Result<(), String> {
+    info!("Setting verified for {}: {}", node_id, verified);
+
+    if !state.is_trusted(&node_id).await {
+        return Err("Only trusted peers can be verified".to_string());
+    }
+
+    state
+        .update_settings(|s| {
+            if let Some(trusted) = s.trusted_peers.get_mut(&node_id) {
+                trusted.verified = verified;
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to save trust store: {}", e))?;
+
+    state.refresh_peers_from_settings().await;
+    let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(&app);
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_trusted_peers(state: State<'_, AppState>) -> Result<Vec<TrustedPeer>, String> {
+    Ok(state
+        .get_settings()
+        .await
+        .trusted_peers
+        .into_values()
+        .collect())
+}
+
+/// Show a one-time pairing code for another device to scan
+#[tauri::command]
+async fn start_pairing(state: State<'_, AppState>) -> Result<String, String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    let pending = iroh::pairing::PendingPairing::generate();
+    let code = iroh::pairing::pairing_code(&iroh.node_addr.id, &pending);
+    *state.pairing.write().await = Some(pending);
+
+    info!("Started pairing");
+    Ok(code)
+}
+
+#[tauri::command]
+async fn cancel_pairing(state: State<'_, AppState>) -> Result<(), String> {
+    state.pairing.write().await.take();
+    Ok(())
+}
+
+/// Pair with the device showing `code`; both sides end up trusting each other
+#[tauri::command]
+async fn pair_with_code(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    code: String,
+) -> Result<TrustedPeer, String> {
+    state.require_unlocked().await?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    iroh::pairing::pair(&iroh.endpoint, &code, &app)
+        .await
+        .map_err(|e| format!("Pairing failed: {}", e))
+}
+
+/// Show a one-time 6-digit PIN for another device to type in
+#[tauri::command]
+async fn start_pin_pairing(state: State<'_, AppState>) -> Result<String, String> {
+    state.require_unlocked().await?;
+    state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    let pending = iroh::pairing::PendingPin::generate();
+    let pin = pending.pin.clone();
+    *state.pin_pairing.write().await = Some(pending);
+
+    info!("Started PIN pairing");
+    Ok(pin)
+}
+
+#[tauri::command]
+async fn cancel_pin_pairing(state: State<'_, AppState>) -> Result<(), String> {
+    state.pin_pairing.write().await.take();
+    Ok(())
+}
+
+/// Pair with `node_id`, which is showing `pin`; both sides end up trusting each other
+#[tauri::command]
+async fn pair_with_pin(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+    pin: String,
+) -> Result<TrustedPeer, String> {
+    state.require_unlocked().await?;
+    let remote = node_id
+        .parse()
+        .map_err(|e| format!("Invalid node ID: {}", e))?;
+    iroh::pairing::validate_pin(pin.trim()).map_err(|e| e.to_string())?;
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    iroh::pairing::pair_with_pin(&iroh.endpoint, remote, pin.trim(), &app)
+        .await
+        .map_err(|e| format!("Pairing failed: {}", e))
+}
+
+#[tauri::command]
+async fn block_peer(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+) -> Result<(), String> {
+    validate_node_id(&node_id)?;
+    info!("Blocking peer: {}", node_id);
+
+    // Blocking overrides any previous trust
+    let blocked = node_id.clone();
+    state
+        .update_settings(|s| {
+            s.trusted_peers.remove(&blocked);
+            s.blocked_peers.insert(blocked);
+        })
+        .await
+        .map_err(|e| format!("Failed to save blocklist: {}", e))?;
+
+    state.sync_access_policy().await;
+    state.remove_peer(&node_id).await;
+    let _ = AppEvent::PeerLost(node_id).emit(&app);
+    let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(&app);
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn unblock_peer(state: State<'_, AppState>, node_id: String) -> Result<(), String> {
+    info!("Unblocking peer: {}", node_id);
+
+    state
+        .update_settings(|s| {
+            s.blocked_peers.remove(&node_id);
+        })
+        .await
+        .map_err(|e| format!("Failed to save blocklist: {}", e))?;
+
+    state.sync_access_policy().await;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_blocked_peers(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state
+        .get_settings()
+        .await
+        .blocked_peers
+        .into_iter()
+        .collect())
+}
+
+#[tauri::command]
+async fn get_capabilities(state: State<'_, AppState>) -> Result<PeerCapabilities, String> {
+    let max_file_size = state.get_settings().await.max_file_size;
+    Ok(iroh::discovery::local_capabilities(max_file_size))
+}
+
+/// Version of the event payloads in `events::AppEvent`
+#[tauri::command]
+fn get_event_version() -> u32 {
+    events::EVENT_VERSION
+}
+
+#[tauri::command]
+async fn set_max_file_size(
+    state: State<'_, AppState>,
+    max_file_size: Option<u64>,
+) -> Result<PeerCapabilities, String> {
+    info!("Setting max accepted file size: {:?}", max_file_size);
+
+    state
+        .update_settings(|s| s.max_file_size = max_file_size)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    state.trigger_announcement();
+
+    Ok(iroh::discovery::local_capabilities(max_file_size))
+}
+
+#[tauri::command]
+async fn get_file_type_policy(state: State<'_, AppState>) -> Result<FileTypePolicy, String> {
+    Ok(state.get_settings().await.file_type_policy)
+}
+
+/// Set the blocked and allowed extension lists; an empty `allowed` allows all
+#[tauri::command]
+async fn set_file_type_policy(
+    state: State<'_, AppState>,
+    blocked: Vec<String>,
+    allowed: Vec<String>,
+) -> Result<FileTypePolicy, String> {
+    state.require_unlocked().await?;
+    let policy = FileTypePolicy { blocked, allowed }.normalized();
+    info!("Setting file type policy: {:?}", policy);
+
+    state
+        .update_settings(|s| s.file_type_policy = policy.clone())
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(policy)
+}
+
+#[tauri::command]
+async fn get_blob_store(state: State<'_, AppState>) -> Result<iroh::BlobStoreKind, String> {
+    Ok(state.get_settings().await.blob_store)
+}
+
+#[tauri::command]
+async fn get_lan_only(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.get_settings().await.network.lan_only)
+}
+
+/// Keep all traffic on the local network; takes effect the next time the node starts
+#[tauri::command]
+async fn set_lan_only(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    info!("Setting LAN-only mode: {}", enabled);
+    state
+        .update_settings(|s| s.network.lan_only = enabled)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_public_discovery(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.get_settings().await.network.public_discovery)
+}
+
+/// Publish our address for lookup by node ID, or keep it private; takes effect
+/// the next time the node starts
+#[tauri::command]
+async fn set_public_discovery(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    info!("Setting public discovery: {}", enabled);
+    state
+        .update_settings(|s| s.network.public_discovery = enabled)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_ipv6_mode(state: State<'_, AppState>) -> Result<iroh::network::Ipv6Mode, String> {
+    Ok(state.get_settings().await.network.ipv6)
+}
+
+/// Choose which direct addresses we advertise; takes effect the next time the node starts
+#[tauri::command]
+async fn set_ipv6_mode(
+    state: State<'_, AppState>,
+    mode: iroh::network::Ipv6Mode,
+) -> Result<(), String> {
+    info!("Setting IPv6 mode: {:?}", mode);
+    state
+        .update_settings(|s| s.network.ipv6 = mode)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+/// Sockets the endpoint is bound to, split into IPv4 and IPv6
+#[tauri::command]
+async fn get_bound_sockets(
+    state: State<'_, AppState>,
+) -> Result<iroh::network::BoundSockets, String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    Ok(iroh::network::BoundSockets::new(
+        iroh.endpoint.bound_sockets(),
+    ))
+}
+
+/// Addresses, relay, NAT guess and per-peer paths for diagnostics
+#[tauri::command]
+async fn get_network_info(
+    state: State<'_, AppState>,
+) -> Result<iroh::network::NetworkInfo, String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    let settings = state.get_settings().await;
+    let peers = state.peers.read().await;
+    Ok(iroh::network::NetworkInfo::collect(
+        &iroh,
+        &settings.network,
+        peers.values(),
+    ))
+}
+
+/// Connect times to the relays the node can use, fastest first
+#[tauri::command]
+async fn probe_relays(
+    state: State<'_, AppState>,
+) -> Result<Vec<iroh::network::RelayProbe>, String> {
+    let candidates = state.get_settings().await.network.relay_candidates();
+    Ok(iroh::network::probe_relays(candidates).await)
+}
+
+#[tauri::command]
+async fn get_preferred_relay(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    Ok(state.get_settings().await.network.preferred_relay)
+}
+
+/// Pin the home relay to one of the relay candidates, or `None` to pick by
+/// latency; takes effect the next time the node starts
+#[tauri::command]
+async fn set_preferred_relay(
+    state: State<'_, AppState>,
+    relay_url: Option<String>,
+) -> Result<(), String> {
+    let network = state.get_settings().await.network;
+    let relay_url = relay_url
+        .filter(|url| !url.trim().is_empty())
+        .map(|url| {
+            let parsed = iroh::network::parse_relay_url(&url)
+                .map_err(|e| format!("Invalid relay URL: {}", e))?;
+            if !network.relay_candidates().contains(&parsed) {
+                return Err(format!("{} is not one of the available relays", url));
+            }
+            Ok(parsed.to_string())
+        })
+        .transpose()?;
+    info!("Setting preferred relay: {:?}", relay_url);
+    state
+        .update_settings(|s| s.network.preferred_relay = relay_url)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+/// Fresh net report, relay probes and peer paths for support tickets
+#[tauri::command]
+async fn run_network_diagnostics(
+    state: State<'_, AppState>,
+) -> Result<iroh::network::NetworkDiagnostics, String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+    info!("Running network diagnostics");
+    let settings = state.get_settings().await;
+    let peers = state.get_peers().await;
+    Ok(iroh::network::run_diagnostics(&iroh, &settings.network, &peers).await)
+}
+
+#[tauri::command]
+async fn get_proxy_url(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    Ok(state.get_settings().await.network.proxy_url)
+}
+
+/// Route relay traffic through a proxy, or connect directly with `None`; takes
+/// effect the next time the node starts
+#[tauri::command]
+async fn set_proxy_url(
+    state: State<'_, AppState>,
+    proxy_url: Option<String>,
+) -> Result<(), String> {
+    let proxy_url = proxy_url
+        .filter(|url| !url.trim().is_empty())
+        .map(|url| {
+            iroh::network::parse_proxy_url(&url)
+                .map(|parsed| parsed.to_string())
+                .map_err(|e| format!("Invalid proxy URL: {}", e))
+        })
+        .transpose()?;
+    info!("Setting proxy: {}", proxy_url.is_some());
+    state
+        .update_settings(|s| s.network.proxy_url = proxy_url)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_relay_urls(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state.get_settings().await.network.relay_urls)
+}
+
+/// Use these relays, preferred first, instead of the public ones (empty to
+/// go back); takes effect the next time the node starts
+#[tauri::command]
+async fn set_relay_urls(state: State<'_, AppState>, relay_urls: Vec<String>) -> Result<(), String> {
+    let relay_urls = relay_urls
+        .iter()
+        .filter(|url| !url.trim().is_empty())
+        .map(|url| {
+            iroh::network::parse_relay_url(url)
+                .map(|parsed| parsed.to_string())
+                .map_err(|e| format!("Invalid relay URL {}: {}", url, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    info!("Setting {} custom relays", relay_urls.len());
+    state
+        .update_settings(|s| s.network.relay_urls = relay_urls)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+/// Choose the blob store backend; takes effect the next time the node starts
+#[tauri::command]
+async fn set_blob_store(
+    state: State<'_, AppState>,
+    blob_store: iroh::BlobStoreKind,
+) -> Result<(), String> {
+    info!("Setting blob store: {:?}", blob_store);
+    state
+        .update_settings(|s| s.blob_store = blob_store)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_discovery_timings(state: State<'_, AppState>) -> Result<DiscoveryTimings, String> {
+    Ok(state.get_settings().await.discovery_timings)
+}
+
+#[tauri::command]
+async fn set_discovery_timings(
+    state: State<'_, AppState>,
+    announcement_interval_secs: u64,
+    peer_timeout_secs: u64,
+) -> Result<DiscoveryTimings, String> {
+    let timings = DiscoveryTimings {
+        announcement_interval_secs,
+        peer_timeout_secs,
+    };
+    timings.validate().map_err(|e| e.to_string())?;
+    info!("Setting discovery timings: {:?}", timings);
+
+    state
+        .update_settings(|s| s.discovery_timings = timings)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(timings)
+}
+
+#[derive(serde::Serialize)]
+struct RoomInfo {
+    room_code: Option<String>,
+    /// Ticket the current topic was joined from, when joined by invite
+    room_invite: Option<String>,
+}
+
+/// Trusted peers are the most likely room members, so use them to find the swarm
+fn room_bootstrap(settings: &settings::Settings) -> Vec<iroh_base::EndpointId> {
+    settings
+        .trusted_peers
+        .keys()
+        .filter_map(|id| id.parse().ok())
+        .collect()
+}
+
+/// Room bootstrap plus the node that issued the invite
+fn invite_bootstrap(
+    settings: &settings::Settings,
+    ticket: &iroh::GossipTicket,
+) -> Vec<iroh_base::EndpointId> {
+    let mut bootstrap = room_bootstrap(settings);
+    bootstrap.push(ticket.node_id);
+    bootstrap
+}
+
+/// Move the main node's primary discovery onto a new gossip topic, sealing
+/// payloads with `room_key` for a room
+async fn switch_discovery_topic(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    topic_id: iroh_gossip::proto::TopicId,
+    room_key: Option<iroh::room::RoomKey>,
+    bootstrap: Vec<iroh_base::EndpointId>,
+) -> Result<(), String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    if iroh.gossip.is_subscribed(&topic_id).await {
+        return Err("Already a member of this room or group".to_string());
+    }
+
+    let old_topic = iroh.gossip.primary_topic().await;
+    state.stop_discovery_task(&old_topic).await;
+
+    iroh.gossip
+        .join(topic_id, bootstrap)
+        .await
+        .map_err(|e| format!("Failed to join topic: {}", e))?;
+    if let Some(room_key) = room_key {
+        iroh.gossip.set_room_key(topic_id, room_key).await;
+    }
+
+    let task = iroh::discovery::start_discovery(
+        &iroh,
+        &topic_id,
+        iroh::discovery::PRIMARY_GROUP.to_string(),
+        app.clone(),
+    )
+    .await
+    .map_err(|e| format!("Failed to start discovery: {}", e))?;
+    state.set_discovery_task(topic_id, task).await;
+
+    // Peers from the old topic are no longer reachable via discovery
+    forget_group_peers(state, app, iroh::discovery::PRIMARY_GROUP).await;
+
+    Ok(())
+}
+
+/// Drop group membership from peers and emit `peer-lost` for those left in no group
+async fn forget_group_peers(state: &AppState, app: &tauri::AppHandle, group_id: &str) {
+    for node_id in state.remove_group_membership(group_id).await {
+        let _ = AppEvent::PeerLost(node_id).emit(app);
+    }
+    let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(app);
+}
+
+#[tauri::command]
+async fn create_room(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<RoomInfo, String> {
+    let room_code = iroh::room::generate_room_code();
+    join_room(state, app, room_code).await
+}
+
+#[tauri::command]
+async fn join_room(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    room_code: String,
+) -> Result<RoomInfo, String> {
+    let topic_id =
+        iroh::room::topic_for_room(&room_code).map_err(|e| format!("Invalid room code: {}", e))?;
+    let room_key =
+        iroh::room::key_for_room(&room_code).map_err(|e| format!("Invalid room code: {}", e))?;
+    info!("Joining private room");
+
+    let bootstrap = room_bootstrap(&state.get_settings().await);
+    switch_discovery_topic(&state, &app, topic_id, Some(room_key), bootstrap).await?;
+
+    state
+        .update_settings(|s| {
+            s.room_code = Some(room_code.clone());
+            s.room_invite = None;
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(RoomInfo {
+        room_code: Some(room_code),
+        room_invite: None,
+    })
+}
+
+#[tauri::command]
+async fn leave_room(state: State<'_, AppState>, app: tauri::AppHandle) -> Result<RoomInfo, String> {
+    info!("Leaving room");
+
+    // Fall back to a fresh topic that nobody else knows
+    let topic_id = iroh_gossip::proto::TopicId::from_bytes(rand::random());
+    let bootstrap = room_bootstrap(&state.get_settings().await);
+    switch_discovery_topic(&state, &app, topic_id, None, bootstrap).await?;
+
+    state
+        .update_settings(|s| {
+            s.room_code = None;
+            s.room_invite = None;
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(RoomInfo {
+        room_code: None,
+        room_invite: None,
+    })
+}
+
+#[tauri::command]
+async fn get_room(state: State<'_, AppState>) -> Result<RoomInfo, String> {
+    let settings = state.get_settings().await;
+    Ok(RoomInfo {
+        room_code: settings.room_code,
+        room_invite: settings.room_invite,
+    })
+}
+
+/// Serialized gossip ticket for the current discovery topic, to share as text
+/// or QR; a private room's invite includes its payload key
+#[tauri::command]
+async fn get_room_invite(state: State<'_, AppState>) -> Result<String, String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    Ok(iroh.gossip.ticket().await.to_string())
+}
+
+/// Join the discovery topic from an invite, bootstrapping via the inviting node
+#[tauri::command]
+async fn join_from_invite(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    ticket: String,
+) -> Result<RoomInfo, String> {
+    let parsed = ticket
+        .parse::<iroh::GossipTicket>()
+        .map_err(|e| format!("Invalid invite: {}", e))?;
+    info!("Joining room from invite issued by {}", parsed.node_id);
+
+    let bootstrap = invite_bootstrap(&state.get_settings().await, &parsed);
+    switch_discovery_topic(&state, &app, parsed.topic_id, parsed.room_key, bootstrap).await?;
+
+    let ticket = ticket.trim().to_string();
+    state
+        .update_settings(|s| {
+            s.room_code = None;
+            s.room_invite = Some(ticket.clone());
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(RoomInfo {
+        room_code: None,
+        room_invite: Some(ticket),
+    })
+}
+
+/// Subscribe to a group's topic and run discovery on it
+async fn start_group_discovery(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    iroh: &crate::iroh::Iroh,
+    group: &PeerGroup,
+) -> Result<(), String> {
+    let topic_id = iroh::room::topic_for_room(&group.room_code)
+        .map_err(|e| format!("Invalid room code: {}", e))?;
+    let room_key = iroh::room::key_for_room(&group.room_code)
+        .map_err(|e| format!("Invalid room code: {}", e))?;
+
+    if iroh.gossip.is_subscribed(&topic_id).await {
+        return Err("Already a member of this room or group".to_string());
+    }
+
+    iroh.gossip
+        .subscribe(topic_id, room_bootstrap(&state.get_settings().await))
+        .await
+        .map_err(|e| format!("Failed to join group: {}", e))?;
+    iroh.gossip.set_room_key(topic_id, room_key).await;
+
+    let task = iroh::discovery::start_discovery(iroh, &topic_id, group.id.clone(), app.clone())
+        .await
+        .map_err(|e| format!("Failed to start discovery: {}", e))?;
+    state.set_discovery_task(topic_id, task).await;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn create_group(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<PeerGroup, String> {
+    let room_code = iroh::room::generate_room_code();
+    join_group(state, app, name, room_code).await
+}
+
+#[tauri::command]
+async fn join_group(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    name: String,
+    room_code: String,
+) -> Result<PeerGroup, String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    let group = PeerGroup {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.trim().to_string(),
+        room_code,
+        announce: true,
+    };
+    info!("Joining group: {}", group.name);
+
+    start_group_discovery(&state, &app, &iroh, &group).await?;
+
+    let saved = group.clone();
+    state
+        .update_settings(|s| {
+            s.groups.insert(saved.id.clone(), saved);
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(group)
+}
+
+#[tauri::command]
+async fn leave_group(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    group_id: String,
+) -> Result<(), String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    let group = state
+        .get_settings()
+        .await
+        .groups
+        .get(&group_id)
+        .cloned()
+        .ok_or_else(|| format!("Unknown group: {}", group_id))?;
+    info!("Leaving group: {}", group.name);
+
+    if let Ok(topic_id) = iroh::room::topic_for_room(&group.room_code) {
+        state.stop_discovery_task(&topic_id).await;
+        iroh.gossip.unsubscribe(&topic_id).await;
+    }
+
+    state
+        .update_settings(|s| {
+            s.groups.remove(&group_id);
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    forget_group_peers(&state, &app, &group_id).await;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_groups(state: State<'_, AppState>) -> Result<Vec<PeerGroup>, String> {
+    Ok(state.get_settings().await.groups.into_values().collect())
+}
+
+#[tauri::command]
+async fn set_group_announce(
+    state: State<'_, AppState>,
+    group_id: String,
+    announce: bool,
+) -> Result<PeerGroup, String> {
+    let settings = state
+        .update_settings(|s| {
+            if let Some(group) = s.groups.get_mut(&group_id) {
+                group.announce = announce;
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    settings
+        .groups
+        .get(&group_id)
+        .cloned()
+        .ok_or_else(|| format!("Unknown group: {}", group_id))
+}
+
+#[tauri::command]
+async fn list_group_peers(
+    state: State<'_, AppState>,
+    group_id: String,
+) -> Result<Vec<PeerInfo>, String> {
+    Ok(state.get_group_peers(&group_id).await)
+}
+
+#[tauri::command]
+async fn set_discoverable(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    discoverable: bool,
+    send_goodbye: bool,
+) -> Result<bool, String> {
+    apply_discoverable(&state, &app, discoverable, send_goodbye).await?;
+    Ok(discoverable)
+}
+
+/// Save discoverability, announce or say goodbye, and emit `discoverable-changed`
+async fn apply_discoverable(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    discoverable: bool,
+    send_goodbye: bool,
+) -> Result<(), String> {
+    info!("Setting discoverable: {}", discoverable);
+
+    state
+        .update_settings(|s| s.discoverable = discoverable)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    if discoverable {
+        state.trigger_announcement();
+    } else if send_goodbye {
+        if let Ok(iroh) = state.get_iroh().await {
+            iroh::discovery::broadcast_goodbye(&iroh).await;
+        }
+    }
+
+    let _ = AppEvent::DiscoverableChanged(discoverable).emit(app);
+    Ok(())
+}
+
+/// Flip discoverability; meant for tiles and widgets (see `quick.rs`)
+#[tauri::command]
+async fn toggle_discoverable(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<bool, String> {
+    quick::toggle_discoverable(&state, &app).await
+}
+
+/// Compact status for tiles and widgets
+#[tauri::command]
+async fn get_quick_status(state: State<'_, AppState>) -> Result<quick::QuickStatus, String> {
+    Ok(quick::status(&state).await)
+}
+
+/// Serve blobs only to trusted peers; others are refused when they connect
+#[tauri::command]
+async fn set_trusted_only_provider(
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<bool, String> {
+    state.require_unlocked().await?;
+    info!("Setting trusted-only provider: {}", enabled);
+
+    state
+        .update_settings(|s| s.trusted_only_provider = enabled)
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    state.sync_access_policy().await;
+
+    Ok(enabled)
+}
+
+#[tauri::command]
+async fn get_trusted_only_provider(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.get_settings().await.trusted_only_provider)
+}
+
+#[tauri::command]
+async fn get_discoverable(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.get_settings().await.discoverable)
+}
+
+#[derive(serde::Serialize)]
+struct PingResult {
+    node_id: String,
+    latency_ms: u32,
+    /// Rolling average, if the peer is in the discovered list
+    average_latency_ms: Option<u32>,
+}
+
+/// Text a paired peer; returns the message as stored
+#[tauri::command]
+async fn send_message(
+    state: State<'_, AppState>,
+    node_id: String,
+    text: String,
+) -> Result<chat::ChatMessage, String> {
+    state.require_unlocked().await?;
+    chat::send(&state, &node_id, &text).await
+}
+
+/// Conversation with a peer, oldest first; `before` (unix seconds) pages back
+#[tauri::command]
+async fn get_messages(
+    state: State<'_, AppState>,
+    node_id: String,
+    before: Option<u64>,
+    limit: Option<u32>,
+) -> Result<Vec<chat::ChatMessage>, String> {
+    state.require_unlocked().await?;
+    let limit = limit.unwrap_or(100).min(history::MAX_PAGE_SIZE);
+    state
+        .messages(&node_id, before, limit)
+        .await
+        .map_err(|e| format!("Failed to read messages: {}", e))
+}
+
+#[tauri::command]
+async fn clear_messages(state: State<'_, AppState>, node_id: String) -> Result<usize, String> {
+    state.require_unlocked().await?;
+    state
+        .clear_messages(&node_id)
+        .await
+        .map_err(|e| format!("Failed to clear messages: {}", e))
+}
+
+/// Send a transfer signal straight to one peer over the control channel
+#[tauri::command]
+async fn send_control_message(
+    state: State<'_, AppState>,
+    node_id: String,
+    message: iroh::control::ControlMessage,
+) -> Result<(), String> {
+    state.require_unlocked().await?;
+    deliver_control_message(&state, &node_id, &message).await
+}
+
+async fn deliver_control_message(
+    state: &AppState,
+    node_id: &str,
+    message: &iroh::control::ControlMessage,
+) -> Result<(), String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    let endpoint_id = node_id
+        .parse::<iroh_base::EndpointId>()
+        .map_err(|e| format!("Invalid node ID: {}", e))?;
+    if state.is_blocked(node_id).await {
+        return Err(format!("Peer {} is blocked", node_id));
+    }
+
+    iroh::control::send(&iroh.endpoint, endpoint_id, message)
+        .await
+        .map_err(|e| format!("Failed to send control message: {}", e))
+}
+
+#[tauri::command]
+async fn ping_peer(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+) -> Result<PingResult, String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    let endpoint_id = node_id
+        .parse::<iroh_base::EndpointId>()
+        .map_err(|e| format!("Invalid node ID: {}", e))?;
+
+    let rtt = iroh::ping::ping(&iroh.endpoint, endpoint_id)
+        .await
+        .map_err(|e| format!("Ping failed: {}", e))?;
+    let latency_ms = rtt.as_millis().min(u32::MAX as u128) as u32;
+    info!("Ping {}: {} ms", node_id, latency_ms);
+
+    let average_latency_ms = state.record_peer_latency(&node_id, latency_ms).await;
+    if average_latency_ms.is_some() {
+        let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(&app);
+    }
+
+    Ok(PingResult {
+        node_id,
+        latency_ms,
+        average_latency_ms,
+    })
+}
+
+#[tauri::command]
+async fn set_peer_alias(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+    alias: Option<String>,
+) -> Result<(), String> {
+    validate_node_id(&node_id)?;
+
+    // Blank aliases clear the override
+    let alias = alias
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty());
+    info!("Setting alias for {}: {:?}", node_id, alias);
+
+    state
+        .update_settings(|s| match alias {
+            Some(alias) => {
+                s.peer_aliases.insert(node_id, alias);
+            }
+            None => {
+                s.peer_aliases.remove(&node_id);
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    state.refresh_peers_from_settings().await;
+    let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(&app);
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_peer_favorite(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+    favorite: bool,
+) -> Result<(), String> {
+    validate_node_id(&node_id)?;
+    info!("Setting favorite for {}: {}", node_id, favorite);
+
+    let peer = state.peers.read().await.get(&node_id).cloned();
+    state
+        .update_settings(|s| {
+            if !favorite {
+                s.favorite_peers.remove(&node_id);
+                return;
+            }
+            // Keep the last known name/color for showing the peer offline
+            let existing = s.favorite_peers.get(&node_id).cloned();
+            let entry = settings::FavoritePeer {
+                node_id: node_id.clone(),
+                device_name: peer
+                    .as_ref()
+                    .map(|p| p.device_name.clone())
+                    .or_else(|| existing.as_ref().map(|f| f.device_name.clone()))
+                    .unwrap_or_else(|| "Unknown Device".to_string()),
+                color: peer
+                    .as_ref()
+                    .map(|p| p.color.clone())
+                    .or_else(|| existing.map(|f| f.color))
+                    .unwrap_or_else(|| iroh::discovery::default_color(&node_id)),
+            };
+            s.favorite_peers.insert(node_id.clone(), entry);
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    state.refresh_peers_from_settings().await;
+    let _ = AppEvent::PeerListUpdated(state.get_peers().await).emit(&app);
+
+    Ok(())
+}
+
+/// Route files from a peer to `dir` when no explicit path is given; `None` resets to Downloads
+#[tauri::command]
+async fn set_peer_download_dir(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    node_id: String,
+    dir: Option<String>,
+) -> Result<(), String> {
+    validate_node_id(&node_id)?;
+
+    let dir = dir.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+    if let Some(dir) = &dir {
+        resolve_user_path(&state, &app, dir).await?;
+    }
+    info!("Setting download directory for {}: {:?}", node_id, dir);
+
+    state
+        .update_settings(|s| match dir {
+            Some(dir) => {
+                s.peer_download_dirs.insert(node_id, dir);
+            }
+            None => {
+                s.peer_download_dirs.remove(&node_id);
+            }
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_peer_download_dirs(
+    state: State<'_, AppState>,
+) -> Result<std::collections::BTreeMap<String, String>, String> {
+    Ok(state.get_settings().await.peer_download_dirs)
+}
+
+#[tauri::command]
+async fn start_ble_discovery(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut slot = state.ble_task.write().await;
+    if slot.as_ref().is_some_and(|task| !task.is_finished()) {
+        return Ok(());
+    }
+
+    let task = iroh::ble::start(app)
+        .await
+        .map_err(|e| format!("Failed to start BLE discovery: {}", e))?;
+    *slot = Some(task);
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_ble_discovery(state: State<'_, AppState>) -> Result<(), String> {
+    if let Some(task) = state.ble_task.write().await.take() {
+        info!("Stopping BLE discovery");
+        task.abort();
+    }
+    iroh::ble::stop();
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct PeerIdentity {
+    device_name: String,
+    color: String,
+    avatar: Option<u8>,
+}
+
+#[tauri::command]
+async fn get_identity(state: State<'_, AppState>) -> Result<PeerIdentity, String> {
+    let iroh = state
+        .get_iroh()
+        .await
+        .map_err(|e| format!("Node not initialized: {}", e))?;
+
+    let settings = state.get_settings().await;
+    Ok(PeerIdentity {
+        device_name: settings.display_name(),
+        color: settings.color_for(&iroh.node_addr.id.to_string()),
+        avatar: settings.avatar,
+    })
+}
+
+#[tauri::command]
+async fn set_identity(
+    state: State<'_, AppState>,
+    color: Option<String>,
+    avatar: Option<u8>,
+) -> Result<PeerIdentity, String> {
+    if let Some(color) = &color {
+        if !iroh::discovery::is_valid_color(color) {
+            return Err(format!("Invalid color: {} (expected #rrggbb)", color));
+        }
+    }
+
+    // Passing no color resets to the one derived from the node ID
+    state
+        .update_settings(|s| {
+            s.color = color.map(|c| c.to_lowercase());
+            s.avatar = avatar;
+        })
+        .await
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    state.trigger_announcement();
+
+    get_identity(state).await
+}
+
+#[derive(serde::Serialize)]
+struct TicketMetadata {
+    filename: String,
     size: u64,
+    /// Set when the file type policy refuses this file; receiving it then
+    /// needs `allow_blocked_type`
+    blocked_reason: Option<String>,
 }
 
 #[tauri::command]
@@ -366,9 +3835,20 @@ async fn parse_ticket_metadata(
         .map_err(|e| format!("Node not initialized: {}", e))?;
 
     let node_id = iroh.node_addr.id.to_string();
-    let (filename, size, _) = iroh::transfer::parse_enhanced_ticket(&ticket, &node_id)
+    let (filename, size, _, _) = iroh::transfer::parse_enhanced_ticket(&ticket, &node_id)
         .map_err(|e| format!("Failed to parse ticket: {}", e))?;
-    Ok(TicketMetadata { filename, size })
+    let blocked_reason = state
+        .get_settings()
+        .await
+        .file_type_policy
+        .check(&filename)
+        .err()
+        .map(|e| e.to_string());
+    Ok(TicketMetadata {
+        filename,
+        size,
+        blocked_reason,
+    })
 }
 
 #[derive(serde::Serialize)]
@@ -412,6 +3892,7 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_android_fs::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(
             tauri_plugin_log::Builder::new()
                 .level(log::LevelFilter::Debug)
@@ -428,11 +3909,19 @@ pub fn run() {
         );
 
     #[cfg(not(target_os = "android"))]
-    let builder = tauri::Builder::default()
+    let builder = tauri::Builder::default();
+    // First, so a second launch hands its arguments over before anything else starts
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+        launch::handle_args(app, argv, std::path::Path::new(&cwd));
+    }));
+    #[cfg(not(target_os = "android"))]
+    let builder = builder
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(
             tauri_plugin_log::Builder::new()
                 .level(log::LevelFilter::Info)
@@ -449,17 +3938,331 @@ pub fn run() {
 
     builder
         .manage(app_state)
+        .setup(|app| {
+            let data_dir = app.path().app_local_data_dir()?;
+            std::fs::create_dir_all(&data_dir)?;
+            migrations::run(&data_dir)?;
+            let state = app.state::<AppState>();
+            tauri::async_runtime::block_on(async {
+                state
+                    .load_settings(data_dir.join(settings::SETTINGS_FILE))
+                    .await;
+                state
+                    .open_history(data_dir.join(history::HISTORY_FILE))
+                    .await;
+                state.audit.set_path(data_dir.join(audit::AUDIT_FILE)).await;
+            });
+            history::spawn_retention_task(app.handle().clone());
+            iroh::gc::spawn_gc_task(app.handle().clone());
+            share::init(app.handle().clone());
+            quick::init(app.handle().clone());
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let state = handle.state::<AppState>();
+                let settings = state.get_settings().await.local_api;
+                if let Err(e) = state.local_api.apply(&handle, &settings).await {
+                    tracing::warn!("Failed to start the local API: {}", e);
+                }
+                let folders = state.get_settings().await.watch_folders;
+                state.watch_folders.sync(&handle, &folders).await;
+            });
+            power::spawn_power_monitor(app.handle().clone());
+            clipboard::spawn_clipboard_sync(app.handle().clone());
+            doze::spawn_doze_monitor(app.handle().clone());
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            if let Ok(cwd) = std::env::current_dir() {
+                launch::handle_args(app.handle(), std::env::args().collect(), &cwd);
+            }
+            #[cfg(target_os = "macos")]
+            launch::init_services(app.handle().clone());
+            #[cfg(target_os = "linux")]
+            tauri::async_runtime::spawn_blocking(|| {
+                if let Err(e) = launch::register_link_handler() {
+                    tracing::warn!("Failed to register vegam:// links: {}", e);
+                }
+            });
+            #[cfg(target_os = "android")]
+            foreground::spawn_transfer_service(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             init_node,
+            restart_node,
             get_node_id,
+            is_ephemeral,
             send_file,
+            send_to_peers,
+            get_transfer_groups,
+            get_transfer_group,
+            take_shared_files,
+            take_dropped_files,
+            take_opened_tickets,
+            set_drop_target,
+            list_http_links,
+            get_http_share_bind,
+            set_http_share_bind,
+            revoke_http_link,
             receive_file,
+            list_resumable_receives,
+            resume_receive,
             get_transfer_status,
+            get_bandwidth_limits,
+            set_bandwidth_limits,
+            get_notification_policy,
+            set_notification_policy,
+            get_low_power_policy,
+            set_low_power_policy,
+            set_power_status,
+            is_low_power,
+            get_battery_defer_policy,
+            set_battery_defer_policy,
+            get_deferred_sends,
+            force_send,
+            get_clipboard_sync,
+            set_clipboard_sync,
+            set_clipboard_sync_device,
+            get_webhook,
+            set_webhook,
+            test_webhook,
+            get_local_api,
+            set_local_api,
+            regenerate_local_api_token,
+            list_watch_folders,
+            add_watch_folder,
+            remove_watch_folder,
+            set_watch_folder_enabled,
+            get_running_watches,
+            list_post_receive_rules,
+            add_post_receive_rule,
+            remove_post_receive_rule,
+            set_post_receive_rule_enabled,
+            list_spaces,
+            create_space,
+            share_space,
+            join_space,
+            leave_space,
+            list_space_files,
+            add_space_file,
+            remove_space_file,
+            save_space_file,
+            get_metered_policy,
+            set_metered_policy,
+            set_network_metered,
+            get_metered_status,
+            force_start,
+            get_background_restrictions,
+            open_battery_optimization_settings,
+            get_peer_transfers,
+            get_transfer_history,
+            list_transfers,
+            search_transfers,
+            set_transfer_note,
+            set_transfer_pinned,
+            clear_history,
+            get_history_retention,
+            set_history_retention,
+            list_blobs,
+            delete_blob,
+            shred_file,
+            pin_blob,
+            get_blob_gc_policy,
+            set_blob_gc_policy,
+            set_blob_encryption,
+            set_content_encryption,
+            get_content_encryption,
+            set_save_media_to_gallery,
+            get_save_media_to_gallery,
+            get_storage_usage,
+            set_audit_log_enabled,
+            get_audit_log_enabled,
+            get_audit_log,
+            export_audit_log,
+            set_app_lock,
+            lock_app,
+            unlock,
+            is_locked,
             list_peers,
             get_device_name,
+            set_device_name,
+            get_identity,
+            set_identity,
+            trust_peer,
+            untrust_peer,
+            list_trusted_peers,
+            get_fingerprint,
+            rotate_identity,
+            set_peer_verified,
+            start_pairing,
+            cancel_pairing,
+            start_pin_pairing,
+            cancel_pin_pairing,
+            pair_with_pin,
+            pair_with_code,
+            block_peer,
+            unblock_peer,
+            list_blocked_peers,
+            get_capabilities,
+            get_event_version,
+            set_max_file_size,
+            get_file_type_policy,
+            set_file_type_policy,
+            get_blob_store,
+            set_blob_store,
+            get_lan_only,
+            set_lan_only,
+            get_public_discovery,
+            set_public_discovery,
+            get_ipv6_mode,
+            set_ipv6_mode,
+            get_bound_sockets,
+            get_network_info,
+            run_network_diagnostics,
+            get_proxy_url,
+            set_proxy_url,
+            get_relay_urls,
+            set_relay_urls,
+            probe_relays,
+            get_preferred_relay,
+            set_preferred_relay,
+            get_discovery_timings,
+            set_discovery_timings,
+            create_room,
+            join_room,
+            leave_room,
+            get_room,
+            get_room_invite,
+            join_from_invite,
+            create_group,
+            join_group,
+            leave_group,
+            list_groups,
+            set_group_announce,
+            list_group_peers,
+            set_discoverable,
+            get_discoverable,
+            toggle_discoverable,
+            get_quick_status,
+            set_trusted_only_provider,
+            get_trusted_only_provider,
+            ping_peer,
+            send_control_message,
+            send_message,
+            get_messages,
+            clear_messages,
+            set_peer_alias,
+            set_peer_favorite,
+            set_peer_download_dir,
+            get_download_dir,
+            set_download_dir,
+            get_sandbox,
+            portal_pick_files,
+            portal_pick_download_dir,
+            list_peer_download_dirs,
+            start_ble_discovery,
+            stop_ble_discovery,
             parse_ticket_metadata,
             get_relay_status,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(handle_run_event);
+}
+
+/// Shut the node down cleanly when the app exits
+///
+/// Peers get a goodbye so they drop us immediately, running transfers are
+/// recorded as interrupted with their last progress, and buffered state is
+/// written to history before the router closes its connections.
+fn handle_run_event(app: &tauri::AppHandle, event: tauri::RunEvent) {
+    match event {
+        tauri::RunEvent::WindowEvent {
+            event: tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }),
+            ..
+        } => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(handle_dropped_files(app, paths));
+        }
+        // Links and ticket files (see `launch.rs`); elsewhere they arrive as arguments
+        #[cfg(target_os = "macos")]
+        tauri::RunEvent::Opened { urls } => {
+            let opened: Vec<_> = urls.iter().filter_map(launch::opened_url).collect();
+            if !opened.is_empty() {
+                launch::raise(app);
+                tauri::async_runtime::spawn(launch::handle_opened(app.clone(), opened));
+            }
+        }
+        tauri::RunEvent::Exit => {
+            let state = app.state::<AppState>();
+            tauri::async_runtime::block_on(async {
+                info!("Shutting down Iroh node");
+                state.interrupt_running_transfers().await;
+                if let Err(e) = state.flush_blobs_served().await {
+                    tracing::warn!("Failed to flush blob download times: {}", e);
+                }
+                shutdown_node(&state).await;
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Check files dropped on the window, then send them to the drop target or
+/// stage them for the frontend, and emit `files-dropped`
+async fn handle_dropped_files(app: tauri::AppHandle, paths: Vec<std::path::PathBuf>) {
+    let state = app.state::<AppState>();
+    let mut dropped = drag_drop::FilesDropped::default();
+    for path in &paths {
+        match drag_drop::check(path).await {
+            Ok(file) => dropped.files.push(file),
+            Err(rejected) => dropped.rejected.push(rejected),
+        }
+    }
+    info!(
+        "{} file(s) dropped, {} refused",
+        dropped.files.len(),
+        dropped.rejected.len()
+    );
+
+    // A locked app only stages; importing waits until it is unlocked
+    if !state.is_locked().await {
+        dropped.target = state.drop_zone.target().await;
+    }
+    match &dropped.target {
+        Some(node_id) => {
+            let mut sent = Vec::new();
+            for mut file in std::mem::take(&mut dropped.files) {
+                match offer_dropped_file(&state, &app, node_id, &mut file).await {
+                    Ok(()) => sent.push(file),
+                    Err(reason) => dropped.rejected.push(drag_drop::RejectedDrop {
+                        path: file.path,
+                        reason,
+                    }),
+                }
+            }
+            dropped.files = sent;
+        }
+        None => state.drop_zone.stage(&dropped.files).await,
+    }
+
+    let _ = AppEvent::FilesDropped(dropped).emit(&app);
+}
+
+/// Import a dropped file and offer it to `node_id` over the control channel
+async fn offer_dropped_file(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    node_id: &str,
+    file: &mut drag_drop::DroppedFile,
+) -> Result<(), String> {
+    let info = share_file(state, app.clone(), file.path.clone(), false).await?;
+    file.transfer_id = Some(info.transfer_id.clone());
+    file.ticket = Some(info.ticket.clone());
+
+    let offer = iroh::control::ControlMessage::Offer {
+        transfer_id: info.transfer_id,
+        ticket: info.ticket,
+        file_name: info.file_name,
+        file_size: info.file_size,
+    };
+    deliver_control_message(state, node_id, &offer).await
 }
@@ -0,0 +1,394 @@
+// Local control API
+//
+// With `local_api.enabled`, a small HTTP server on 127.0.0.1 lets scripts and
+// other apps drive vegam while it runs: list peers, send, receive and query
+// transfers, the same operations as the commands and through the same code
+// (`share_file`, `start_receive`). Every request needs
+// `Authorization: Bearer <token>`; the token is generated when the API is
+// first enabled and shown in the settings. Bodies and responses are JSON,
+// errors are `{ "error": "..." }`. Sending, receiving and history fail with
+// 423 while the app is locked.
+//
+//   GET  /v1/peers
+//   GET  /v1/transfers[?limit=N]
+//   GET  /v1/transfers/<id>
+//   POST /v1/send      { "path", "http_link"? }
+//   POST /v1/receive   { "ticket", "output_path"?, "allow_blocked_type"? }
+
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn};
+
+use crate::state::AppState;
+
+/// Largest request head we read before giving up
+const MAX_HEAD_SIZE: usize = 8 * 1024;
+/// Largest JSON body accepted
+const MAX_BODY_SIZE: usize = 64 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Transfers returned by `GET /v1/transfers` without a limit
+const DEFAULT_LIMIT: u32 = 50;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalApiSettings {
+    pub enabled: bool,
+    /// Port on 127.0.0.1
+    pub port: u16,
+    /// Bearer token; generated when the API is first enabled
+    pub token: String,
+}
+
+impl Default for LocalApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 47470,
+            token: String::new(),
+        }
+    }
+}
+
+/// Payload of `get_local_api`
+#[derive(Clone, Debug, Serialize)]
+pub struct LocalApiInfo {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+    /// Base URL while the server runs
+    pub url: Option<String>,
+}
+
+/// A new random bearer token
+pub fn generate_token() -> String {
+    URL_SAFE_NO_PAD.encode(crate::iroh::identity::random_key())
+}
+
+/// The running server, if any
+#[derive(Clone, Default)]
+pub struct LocalApi {
+    server: Arc<Mutex<Option<(u16, JoinHandle<()>)>>>,
+}
+
+impl LocalApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Port the server is listening on
+    pub async fn port(&self) -> Option<u16> {
+        self.server.lock().await.as_ref().map(|(port, _)| *port)
+    }
+
+    /// Stop the server and start it again if `settings` enable it
+    pub async fn apply(&self, handle: &AppHandle, settings: &LocalApiSettings) -> Result<()> {
+        let mut server = self.server.lock().await;
+        if let Some((_, task)) = server.take() {
+            task.abort();
+            info!("Local API stopped");
+        }
+        if !settings.enabled || settings.token.is_empty() {
+            return Ok(());
+        }
+
+        let listener = TcpListener::bind(("127.0.0.1", settings.port)).await?;
+        let port = listener.local_addr()?.port();
+        info!("Local API listening on 127.0.0.1:{}", port);
+
+        let handle = handle.clone();
+        let token: Arc<str> = settings.token.clone().into();
+        let task = tokio::spawn(async move {
+            loop {
+                let (stream, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        warn!("Local API accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let handle = handle.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, &handle, &token).await {
+                        warn!("Local API request failed: {}", e);
+                    }
+                });
+            }
+        });
+        *server = Some((port, task));
+        Ok(())
+    }
+}
+
+/// A parsed request
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    query: Option<String>,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Failed request: status line and message
+struct ApiError(&'static str, String);
+
+impl ApiError {
+    fn bad_request(message: impl ToString) -> Self {
+        Self("400 Bad Request", message.to_string())
+    }
+
+    fn not_found() -> Self {
+        Self("404 Not Found", "Not found".to_string())
+    }
+
+    /// Errors from commands; a locked app gets its own status
+    fn command(message: String) -> Self {
+        if message == crate::lock::LOCKED {
+            Self("423 Locked", message)
+        } else {
+            Self("500 Internal Server Error", message)
+        }
+    }
+}
+
+async fn serve(mut stream: TcpStream, handle: &AppHandle, token: &str) -> Result<()> {
+    let request = match timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(Some(request))) => request,
+        Ok(Ok(None)) => {
+            let body = json!({ "error": "Not an HTTP request" });
+            return respond(&mut stream, "400 Bad Request", &body).await;
+        }
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return Err(anyhow::anyhow!("Timed out reading request")),
+    };
+
+    let result = if authorized(request.authorization.as_deref(), token) {
+        handle_request(handle, &request).await
+    } else {
+        Err(ApiError(
+            "401 Unauthorized",
+            "Missing or wrong token".to_string(),
+        ))
+    };
+    match result {
+        Ok(body) => respond(&mut stream, "200 OK", &body).await,
+        Err(ApiError(status, message)) => {
+            respond(&mut stream, status, &json!({ "error": message })).await
+        }
+    }
+}
+
+async fn handle_request(handle: &AppHandle, request: &Request) -> Result<Value, ApiError> {
+    let state = handle.state::<AppState>();
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["v1", "peers"]) => Ok(json!(state.get_peers().await)),
+        ("GET", ["v1", "transfers"]) => {
+            state.require_unlocked().await.map_err(ApiError::command)?;
+            let limit = query_param(request.query.as_deref(), "limit")
+                .map(|limit| limit.parse().map_err(ApiError::bad_request))
+                .transpose()?
+                .unwrap_or(DEFAULT_LIMIT);
+            Ok(json!(state.get_transfer_history(limit).await))
+        }
+        ("GET", ["v1", "transfers", id]) => {
+            state.require_unlocked().await.map_err(ApiError::command)?;
+            let transfer = state
+                .get_transfer(id)
+                .await
+                .ok_or_else(ApiError::not_found)?;
+            Ok(json!(transfer))
+        }
+        ("POST", ["v1", "send"]) => {
+            #[derive(Deserialize)]
+            struct Send {
+                path: String,
+                #[serde(default)]
+                http_link: bool,
+            }
+            let send: Send = parse_body(&request.body)?;
+            state.require_unlocked().await.map_err(ApiError::command)?;
+            info!("Sending {} for the local API", send.path);
+            let ticket = crate::share_file(&state, handle.clone(), send.path, send.http_link)
+                .await
+                .map_err(ApiError::command)?;
+            Ok(json!(ticket))
+        }
+        ("POST", ["v1", "receive"]) => {
+            #[derive(Deserialize)]
+            struct Receive {
+                ticket: String,
+                output_path: Option<String>,
+                #[serde(default)]
+                allow_blocked_type: bool,
+            }
+            let receive: Receive = parse_body(&request.body)?;
+            state.require_unlocked().await.map_err(ApiError::command)?;
+            let transfer = crate::start_receive(
+                &state,
+                handle.clone(),
+                receive.ticket,
+                receive.output_path,
+                receive.allow_blocked_type,
+                None,
+            )
+            .await
+            .map_err(ApiError::command)?;
+            Ok(json!(transfer))
+        }
+        (_, ["v1", "peers" | "transfers" | "send" | "receive"]) | (_, ["v1", "transfers", _]) => {
+            Err(ApiError(
+                "405 Method Not Allowed",
+                "Method not allowed".to_string(),
+            ))
+        }
+        _ => Err(ApiError::not_found()),
+    }
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body).map_err(|e| ApiError::bad_request(format!("Invalid body: {}", e)))
+}
+
+/// Whether the `Authorization` header carries `token`, compared in constant
+/// time
+fn authorized(authorization: Option<&str>, token: &str) -> bool {
+    let Some(given) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    bool::from(given.trim().as_bytes().ct_eq(token.as_bytes()))
+}
+
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Read a request head and its body; `None` if it isn't HTTP/1.x
+async fn read_request(stream: &mut TcpStream) -> Result<Option<Request>> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let head_end = loop {
+        if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if data.len() > MAX_HEAD_SIZE {
+            return Err(anyhow::anyhow!("Request head too large"));
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        data.extend_from_slice(&buf[..n]);
+    };
+
+    let Some(mut request) = parse_head(&String::from_utf8_lossy(&data[..head_end])) else {
+        return Ok(None);
+    };
+    let length = content_length(&String::from_utf8_lossy(&data[..head_end])).unwrap_or(0);
+    if length > MAX_BODY_SIZE {
+        return Err(anyhow::anyhow!("Request body too large"));
+    }
+    let mut body = data.split_off(head_end);
+    while body.len() < length {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buf[..n]);
+    }
+    body.truncate(length);
+    request.body = body;
+    Ok(Some(request))
+}
+
+/// Method, path, query and `Authorization` of a request head
+fn parse_head(head: &str) -> Option<Request> {
+    let mut lines = head.lines();
+    let mut parts = lines.next()?.split(' ');
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    parts
+        .next()
+        .filter(|version| version.starts_with("HTTP/1."))?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+    Some(Request {
+        method,
+        path,
+        query,
+        authorization: header(head, "authorization").map(str::to_string),
+        body: Vec::new(),
+    })
+}
+
+fn content_length(head: &str) -> Option<usize> {
+    header(head, "content-length")?.parse().ok()
+}
+
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &Value) -> Result<()> {
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Cache-Control: no-store\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_parsing_and_auth() {
+        let head = "GET /v1/transfers?limit=5&x=1 HTTP/1.1\r\n\
+                    Host: 127.0.0.1\r\n\
+                    authorization: Bearer s3cret\r\n\
+                    Content-Length: 12\r\n\r\n";
+        let request = parse_head(head).unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/v1/transfers");
+        assert_eq!(query_param(request.query.as_deref(), "limit"), Some("5"));
+        assert_eq!(query_param(request.query.as_deref(), "y"), None);
+        assert_eq!(content_length(head), Some(12));
+        assert!(parse_head("GET /v1/peers\r\n\r\n").is_none());
+
+        assert!(authorized(request.authorization.as_deref(), "s3cret"));
+        assert!(!authorized(request.authorization.as_deref(), "s3cre"));
+        assert!(!authorized(Some("Basic s3cret"), "s3cret"));
+        assert!(!authorized(None, "s3cret"));
+    }
+}
@@ -0,0 +1,181 @@
+// App lock
+//
+// With a passcode set, sensitive commands (sending, receiving, transfer
+// history, the audit log) fail with a `Locked` error until `unlock` is called
+// with the right passcode. The app starts locked and locks itself again after
+// `auto_lock_minutes` without a sensitive command. Only a salted PBKDF2 hash of
+// the passcode is stored. Biometric prompts are up to the frontend, which then
+// unlocks with a passcode it keeps in the platform's secure storage.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::{Duration, Instant};
+
+/// Error returned by gated commands while the app is locked
+pub const LOCKED: &str = "Locked";
+
+const MIN_PASSCODE_LENGTH: usize = 4;
+const PBKDF2_ROUNDS: u32 = 100_000;
+/// Wrong passcodes allowed before unlocking is refused for `LOCKOUT`
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const LOCKOUT: Duration = Duration::from_secs(60);
+
+/// Persisted passcode hash and auto-lock timeout
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AppLock {
+    /// Base64url random salt
+    salt: String,
+    /// Base64url PBKDF2-HMAC-SHA256 of the passcode
+    hash: String,
+    /// Lock after this many minutes without a sensitive command; never when unset
+    pub auto_lock_minutes: Option<u32>,
+}
+
+impl AppLock {
+    pub fn new(passcode: &str, auto_lock_minutes: Option<u32>) -> Result<Self, String> {
+        if passcode.chars().count() < MIN_PASSCODE_LENGTH {
+            return Err(format!(
+                "Passcode must be at least {} characters",
+                MIN_PASSCODE_LENGTH
+            ));
+        }
+        if auto_lock_minutes == Some(0) {
+            return Err("Auto-lock timeout must be at least one minute".to_string());
+        }
+        let mut salt = [0u8; 16];
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut salt);
+        Ok(Self {
+            salt: URL_SAFE_NO_PAD.encode(salt),
+            hash: URL_SAFE_NO_PAD.encode(hash_passcode(passcode, &salt)),
+            auto_lock_minutes,
+        })
+    }
+
+    pub fn verify(&self, passcode: &str) -> bool {
+        let Ok(salt) = URL_SAFE_NO_PAD.decode(self.salt.as_bytes()) else {
+            return false;
+        };
+        let Ok(expected) = URL_SAFE_NO_PAD.decode(self.hash.as_bytes()) else {
+            return false;
+        };
+        let actual = hash_passcode(passcode, &salt);
+        // Compare without bailing at the first differing byte
+        expected.len() == actual.len()
+            && expected
+                .iter()
+                .zip(actual.iter())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+
+    pub fn auto_lock(&self) -> Option<Duration> {
+        self.auto_lock_minutes
+            .map(|minutes| Duration::from_secs(minutes as u64 * 60))
+    }
+}
+
+fn hash_passcode(passcode: &str, salt: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passcode.as_bytes(), salt, PBKDF2_ROUNDS, &mut out);
+    out
+}
+
+/// Whether the app is locked right now, plus unlock throttling
+#[derive(Debug)]
+pub struct LockState {
+    locked: bool,
+    last_activity: Instant,
+    failed_attempts: u32,
+    lockout_until: Option<Instant>,
+}
+
+impl LockState {
+    pub fn new(locked: bool) -> Self {
+        Self {
+            locked,
+            last_activity: Instant::now(),
+            failed_attempts: 0,
+            lockout_until: None,
+        }
+    }
+
+    /// Lock if idle for longer than `auto_lock`, then report whether locked
+    pub fn check(&mut self, auto_lock: Option<Duration>, now: Instant) -> bool {
+        if let Some(timeout) = auto_lock {
+            if now.duration_since(self.last_activity) >= timeout {
+                self.locked = true;
+            }
+        }
+        self.locked
+    }
+
+    /// Record activity, postponing the auto-lock
+    pub fn touch(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    /// Drop the lock entirely, e.g. when the passcode is removed
+    pub fn clear(&mut self, now: Instant) {
+        *self = Self::new(false);
+        self.last_activity = now;
+    }
+
+    pub fn try_unlock(
+        &mut self,
+        lock: &AppLock,
+        passcode: &str,
+        now: Instant,
+    ) -> Result<(), String> {
+        if let Some(until) = self.lockout_until {
+            if now < until {
+                return Err(format!(
+                    "Too many wrong passcodes, try again in {}s",
+                    (until - now).as_secs().max(1)
+                ));
+            }
+            self.lockout_until = None;
+        }
+        if !lock.verify(passcode) {
+            self.failed_attempts += 1;
+            if self.failed_attempts >= MAX_FAILED_ATTEMPTS {
+                self.failed_attempts = 0;
+                self.lockout_until = Some(now + LOCKOUT);
+            }
+            return Err("Wrong passcode".to_string());
+        }
+        self.clear(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlock_and_lockout() {
+        let lock = AppLock::new("1234", Some(5)).unwrap();
+        assert!(AppLock::new("12", None).is_err());
+
+        let start = Instant::now();
+        let mut state = LockState::new(true);
+        assert!(state.check(lock.auto_lock(), start));
+        assert!(state.try_unlock(&lock, "1234", start).is_ok());
+        assert!(!state.check(lock.auto_lock(), start));
+
+        // Idle past the timeout locks again
+        assert!(state.check(lock.auto_lock(), start + Duration::from_secs(5 * 60)));
+
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            assert!(state.try_unlock(&lock, "0000", start).is_err());
+        }
+        // Even the right passcode is refused during the lockout
+        assert!(state.try_unlock(&lock, "1234", start).is_err());
+        assert!(state.try_unlock(&lock, "1234", start + LOCKOUT).is_ok());
+    }
+}
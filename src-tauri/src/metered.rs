@@ -0,0 +1,192 @@
+// Metered connection awareness
+//
+// With `defer_large` on, downloads bigger than `threshold_bytes` wait while
+// the connection is metered and start by themselves once it isn't, or right
+// away with `force_start`. Windows reports the connection cost of the internet
+// profile and Linux asks NetworkManager. Android, iOS and macOS have no API we
+// can reach without native code, so the frontend reports what it knows (e.g.
+// `navigator.connection.type` in the Android WebView); a reported value always
+// wins over detection. Sends aren't deferred: the receiver pulls the data, so
+// holding it back is up to the receiving device.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Notify, RwLock};
+use tokio::time::Duration;
+
+/// How often a deferred download re-checks the connection
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeteredPolicy {
+    /// Hold back large downloads while on a metered connection
+    pub defer_large: bool,
+    /// Downloads larger than this wait for an unmetered connection
+    pub threshold_bytes: u64,
+}
+
+impl Default for MeteredPolicy {
+    fn default() -> Self {
+        Self {
+            defer_large: false,
+            threshold_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+/// Metered state and the downloads waiting on it
+#[derive(Clone, Default)]
+pub struct MeteredGate {
+    /// Set by the frontend; overrides detection
+    reported: Arc<RwLock<Option<bool>>>,
+    /// Deferred downloads by transfer ID
+    deferred: Arc<RwLock<HashMap<String, Arc<Notify>>>>,
+}
+
+impl MeteredGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the current connection is metered; unknown counts as not
+    pub async fn is_metered(&self) -> bool {
+        match *self.reported.read().await {
+            Some(metered) => metered,
+            None => detect().await.unwrap_or(false),
+        }
+    }
+
+    pub async fn set_reported(&self, metered: Option<bool>) {
+        *self.reported.write().await = metered;
+    }
+
+    /// IDs of downloads waiting for an unmetered connection
+    pub async fn deferred(&self) -> Vec<String> {
+        self.deferred.read().await.keys().cloned().collect()
+    }
+
+    /// Wait until a download of `size` bytes may start under `policy`
+    ///
+    /// Calls `on_deferred` once if the download has to wait.
+    pub async fn wait(
+        &self,
+        transfer_id: &str,
+        size: u64,
+        policy: MeteredPolicy,
+        on_deferred: impl FnOnce(),
+    ) {
+        if !policy.defer_large || size <= policy.threshold_bytes || !self.is_metered().await {
+            return;
+        }
+
+        let release = Arc::new(Notify::new());
+        self.deferred
+            .write()
+            .await
+            .insert(transfer_id.to_string(), release.clone());
+        on_deferred();
+
+        loop {
+            tokio::select! {
+                _ = release.notified() => break,
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {
+                    if !self.is_metered().await {
+                        break;
+                    }
+                }
+            }
+        }
+        self.deferred.write().await.remove(transfer_id);
+    }
+
+    /// Start a deferred download now; false if it isn't waiting
+    pub async fn force_start(&self, transfer_id: &str) -> bool {
+        match self.deferred.read().await.get(transfer_id) {
+            Some(release) => {
+                release.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Ask the OS whether the active connection is metered
+#[cfg(target_os = "windows")]
+pub async fn detect() -> Option<bool> {
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let output = tokio::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "[Windows.Networking.Connectivity.NetworkInformation,Windows.Networking.Connectivity,ContentType=WindowsRuntime]::GetInternetConnectionProfile().GetConnectionCost().NetworkCostType",
+        ])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .await
+        .ok()?;
+    parse_windows_cost(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Ask the OS whether the active connection is metered
+#[cfg(target_os = "linux")]
+pub async fn detect() -> Option<bool> {
+    let output = tokio::process::Command::new("busctl")
+        .args([
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .output()
+        .await
+        .ok()?;
+    parse_nm_metered(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// No OS query here; the frontend reports the state instead
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub async fn detect() -> Option<bool> {
+    None
+}
+
+/// `NetworkCostType` of the internet connection profile
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn parse_windows_cost(output: &str) -> Option<bool> {
+    match output.trim() {
+        "Unrestricted" => Some(false),
+        "Fixed" | "Variable" => Some(true),
+        _ => None,
+    }
+}
+
+/// NetworkManager's `NMMetered` as printed by busctl, e.g. `u 1`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_nm_metered(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("u ")? {
+        // Yes, guessed yes
+        "1" | "3" => Some(true),
+        // No, guessed no
+        "2" | "4" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metered_detection_parsing() {
+        assert_eq!(parse_nm_metered("u 1\n"), Some(true));
+        assert_eq!(parse_nm_metered("u 4"), Some(false));
+        assert_eq!(parse_nm_metered("u 0"), None);
+        assert_eq!(parse_windows_cost("Variable\r\n"), Some(true));
+        assert_eq!(parse_windows_cost("Unrestricted"), Some(false));
+        assert_eq!(parse_windows_cost(""), None);
+    }
+}
@@ -0,0 +1,109 @@
+// Data directory migrations
+//
+// The app local data dir holds settings, the history database and the Iroh
+// node's blob store. Its layout version is kept in a `data_version` file and
+// upgraded by running the migrations below in order at startup, before
+// anything else opens those files. The history schema has its own
+// `MIGRATIONS` in history.rs; this list is for changes across files, such as
+// moving or rewriting them.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::{info, warn};
+
+pub const DATA_VERSION_FILE: &str = "data_version";
+
+type Migration = fn(&Path) -> Result<()>;
+
+/// Ordered migrations; entry `i` upgrades the data dir from version `i` to `i + 1`
+const MIGRATIONS: &[(&str, Migration)] = &[("baseline layout", baseline)];
+
+/// Layout version this build writes
+pub const CURRENT_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Upgrade `data_dir` to `CURRENT_VERSION`
+///
+/// Each step is recorded as soon as it succeeds, so a failed or interrupted
+/// upgrade resumes where it stopped. A data dir written by a newer build is
+/// left untouched.
+pub fn run(data_dir: &Path) -> Result<()> {
+    let version = read_version(data_dir)?;
+    if version > CURRENT_VERSION {
+        warn!(
+            "Data dir version {} is newer than this build ({}); not migrating",
+            version, CURRENT_VERSION
+        );
+        return Ok(());
+    }
+
+    for (i, (name, migrate)) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let to = i as u32 + 1;
+        info!("Migrating data dir to version {}: {}", to, name);
+        migrate(data_dir).with_context(|| format!("Data dir migration {} failed", to))?;
+        write_version(data_dir, to)?;
+    }
+    Ok(())
+}
+
+/// Installs from before versioning have no version file and count as version 0
+fn read_version(data_dir: &Path) -> Result<u32> {
+    match std::fs::read_to_string(data_dir.join(DATA_VERSION_FILE)) {
+        Ok(contents) => contents
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid {} file: {:?}", DATA_VERSION_FILE, contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_version(data_dir: &Path, version: u32) -> Result<()> {
+    // Write then rename so a crash never leaves a truncated version file
+    let path = data_dir.join(DATA_VERSION_FILE);
+    let temp = data_dir.join(format!("{}.tmp", DATA_VERSION_FILE));
+    std::fs::write(&temp, version.to_string())?;
+    std::fs::rename(&temp, &path)?;
+    Ok(())
+}
+
+/// Version 1: the layout when versioning was introduced
+///
+/// `settings.json` and `history.db` in the data dir, node data (including the
+/// `blobs` store) under `iroh/`. Settings and history already upgrade
+/// themselves, so there is nothing to change.
+fn baseline(_data_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("vegam-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_migrates_to_current_version() {
+        let dir = temp_dir("migrate");
+        run(&dir).unwrap();
+        assert_eq!(read_version(&dir).unwrap(), CURRENT_VERSION);
+
+        // Running again is a no-op
+        run(&dir).unwrap();
+        assert_eq!(read_version(&dir).unwrap(), CURRENT_VERSION);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_leaves_newer_data_dir_alone() {
+        let dir = temp_dir("newer");
+        write_version(&dir, CURRENT_VERSION + 1).unwrap();
+        run(&dir).unwrap();
+        assert_eq!(read_version(&dir).unwrap(), CURRENT_VERSION + 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+// Native notifications
+//
+// Finished and failed receives, incoming offers and chat messages raise an OS notification
+// through tauri-plugin-notification, sent from here rather than the frontend
+// so they still appear while the window is closed, minimized or (on mobile)
+// the webview is suspended. `NotificationPolicy` picks which of these notify
+// and whether to stay quiet while the window has focus, since the UI shows
+// them already.
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tracing::warn;
+
+use crate::state::AppState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationPolicy {
+    pub transfer_completed: bool,
+    pub transfer_failed: bool,
+    pub incoming_offer: bool,
+    pub message_received: bool,
+    /// Skip notifications while the main window is focused
+    pub only_when_unfocused: bool,
+}
+
+impl Default for NotificationPolicy {
+    fn default() -> Self {
+        Self {
+            transfer_completed: true,
+            transfer_failed: true,
+            incoming_offer: true,
+            message_received: true,
+            only_when_unfocused: true,
+        }
+    }
+}
+
+/// Something worth a notification
+#[derive(Clone, Debug)]
+pub enum Notice {
+    Completed { file_name: String },
+    Failed { file_name: String, error: String },
+    Offer { from: String, file_name: String },
+    Message { from: String, text: Option<String> },
+}
+
+impl Notice {
+    pub fn title(&self) -> String {
+        match self {
+            Self::Completed { .. } => "File received".to_string(),
+            Self::Failed { .. } => "Transfer failed".to_string(),
+            Self::Offer { from, .. } => format!("{} wants to send you a file", from),
+            // Locked: say who it's from, not what it says
+            Self::Message { from, text: None } => format!("New message from {}", from),
+            Self::Message { from, .. } => format!("Message from {}", from),
+        }
+    }
+
+    pub fn body(&self) -> String {
+        match self {
+            Self::Completed { file_name } => file_name.clone(),
+            Self::Failed { file_name, error } => format!("{}: {}", file_name, error),
+            Self::Offer { file_name, .. } => file_name.clone(),
+            Self::Message { text, .. } => text.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl NotificationPolicy {
+    pub fn allows(&self, notice: &Notice) -> bool {
+        match notice {
+            Notice::Completed { .. } => self.transfer_completed,
+            Notice::Failed { .. } => self.transfer_failed,
+            Notice::Offer { .. } => self.incoming_offer,
+            Notice::Message { .. } => self.message_received,
+        }
+    }
+}
+
+/// Show `notice` if the policy allows it
+pub async fn notify(handle: &AppHandle, notice: Notice) {
+    let policy = handle
+        .state::<AppState>()
+        .get_settings()
+        .await
+        .notifications;
+    if !policy.allows(&notice) {
+        return;
+    }
+    if policy.only_when_unfocused && window_focused(handle) {
+        return;
+    }
+
+    if let Err(e) = handle
+        .notification()
+        .builder()
+        .title(notice.title())
+        .body(notice.body())
+        .show()
+    {
+        warn!("Failed to show notification: {}", e);
+    }
+}
+
+fn window_focused(handle: &AppHandle) -> bool {
+    handle
+        .get_webview_window("main")
+        .and_then(|window| window.is_focused().ok())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_filters_notices() {
+        let policy = NotificationPolicy {
+            transfer_completed: false,
+            ..NotificationPolicy::default()
+        };
+        let done = Notice::Completed {
+            file_name: "a.jpg".to_string(),
+        };
+        let failed = Notice::Failed {
+            file_name: "a.jpg".to_string(),
+            error: "Peer went away".to_string(),
+        };
+        assert!(!policy.allows(&done));
+        assert!(policy.allows(&failed));
+        assert_eq!(failed.body(), "a.jpg: Peer went away");
+
+        let offer = Notice::Offer {
+            from: "Pixel".to_string(),
+            file_name: "b.mp4".to_string(),
+        };
+        assert!(policy.allows(&offer));
+        assert_eq!(offer.title(), "Pixel wants to send you a file");
+
+        let hidden = Notice::Message {
+            from: "Pixel".to_string(),
+            text: None,
+        };
+        assert_eq!(hidden.title(), "New message from Pixel");
+        assert_eq!(hidden.body(), "");
+    }
+}
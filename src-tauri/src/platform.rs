@@ -1,21 +1,89 @@
+use serde::{Deserialize, Serialize};
 use std::io;
+use std::path::Path;
 
-/// Read file with platform-specific handling
+/// Android content:// URI, e.g. a picked file or a SAF folder
+pub fn is_content_uri(path: &str) -> bool {
+    path.starts_with("content://")
+}
+
+/// What's known about a file without reading it
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FileMetadata {
+    pub name: Option<String>,
+    pub size: Option<u64>,
+    pub mime_type: Option<String>,
+}
+
+/// Name, size and MIME type of a file without opening it
+/// On Android, content:// URIs are asked about through the content resolver
+/// (`ContentInfo.kt`); plain paths come from the file system and have no MIME
+/// type
+pub async fn file_metadata(path: &str) -> io::Result<FileMetadata> {
+    #[cfg(target_os = "android")]
+    if is_content_uri(path) {
+        return content_uri_metadata(path).await;
+    }
+
+    let metadata = tokio::fs::metadata(path).await?;
+    Ok(FileMetadata {
+        name: Path::new(path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(String::from),
+        size: Some(metadata.len()),
+        mime_type: None,
+    })
+}
+
+#[cfg(target_os = "android")]
+async fn content_uri_metadata(uri: &str) -> io::Result<FileMetadata> {
+    use jni::objects::{JString, JValue};
+
+    let uri = uri.to_string();
+    let json = tokio::task::spawn_blocking(move || {
+        with_java_class("codes.imran.vegam.ContentInfo", |env, class, context| {
+            let uri = env.new_string(&uri)?;
+            let json = env
+                .call_static_method(
+                    class,
+                    "query",
+                    "(Landroid/content/Context;Ljava/lang/String;)Ljava/lang/String;",
+                    &[JValue::Object(context), JValue::Object(&uri)],
+                )?
+                .l()?;
+            let json: String = env.get_string(&JString::from(json))?.into();
+            Ok(json)
+        })
+    })
+    .await
+    .map_err(io::Error::other)?
+    .map_err(|e| io::Error::other(format!("Content resolver query failed: {}", e)))?;
+
+    serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Open a file for reading with platform-specific handling
 /// On Android, handles content:// URIs through tauri-plugin-android-fs
 /// On desktop, uses standard file system
+///
+/// The file is read incrementally by the caller, so large videos from the
+/// photo picker don't have to fit in memory. Nothing relies on the reported
+/// length, which some providers get wrong for files over 2 GB or don't know
+/// at all (pipes); reading stops at end of stream.
 #[cfg(target_os = "android")]
-pub async fn read_file(app: &tauri::AppHandle, path: &str) -> io::Result<Vec<u8>> {
-    use std::io::Read;
+pub async fn open_file(app: &tauri::AppHandle, path: &str) -> io::Result<tokio::fs::File> {
     use tauri_plugin_android_fs::AndroidFsExt;
     use tauri_plugin_fs::FilePath;
 
-    log::info!("Android: reading file: {}", path);
+    log::info!("Android: opening file: {}", path);
 
     let api = app.android_fs_async();
 
     // Convert path string to FileUri
     // For content URIs, parse as URL
-    let file_path = if path.starts_with("content://") {
+    let file_path = if is_content_uri(path) {
         let url = url::Url::parse(path)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
         FilePath::Url(url)
@@ -27,21 +95,228 @@ pub async fn read_file(app: &tauri::AppHandle, path: &str) -> io::Result<Vec<u8>
     let uri: tauri_plugin_android_fs::FileUri = file_path.into();
 
     // Open file for reading
-    let mut file = api
+    let file = api
         .open_file_readable(&uri)
         .await
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
 
-    // Read to bytes
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+    Ok(tokio::fs::File::from_std(file))
+}
+
+#[cfg(not(target_os = "android"))]
+pub async fn open_file(_app: &tauri::AppHandle, path: &str) -> io::Result<tokio::fs::File> {
+    log::info!("Desktop: opening file: {}", path);
+
+    tokio::fs::File::open(path)
+        .await
+        .map_err(|e| outside_sandbox(path, e))
+}
+
+/// Sandbox the desktop app runs in
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sandbox {
+    Flatpak,
+    Snap,
+}
+
+/// Flatpak and Snap confine the app to its own files plus what the user
+/// hands it through the document portal (see `portal_pick`)
+#[cfg(target_os = "linux")]
+pub fn sandbox() -> Option<Sandbox> {
+    if Path::new("/.flatpak-info").exists() {
+        Some(Sandbox::Flatpak)
+    } else if std::env::var_os("SNAP").is_some() {
+        Some(Sandbox::Snap)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sandbox() -> Option<Sandbox> {
+    None
+}
+
+/// Say so when a file can't be opened because the sandbox hides it
+#[cfg_attr(target_os = "android", allow(dead_code))]
+fn outside_sandbox(path: &str, e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied if sandbox().is_some() => {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "{} isn't visible inside the sandbox, choose it with the file picker ({})",
+                    path, e
+                ),
+            )
+        }
+        _ => e,
+    }
+}
+
+/// Let the user pick files, or a folder, through the FileChooser portal
+/// On Linux the portal exports each pick through the document portal, so the
+/// returned paths (under /run/user/<uid>/doc) can be read and written inside
+/// a Flatpak or Snap sandbox, much like the content:// URIs Android's picker
+/// hands out. Nothing is returned if the user cancels.
+#[cfg(target_os = "linux")]
+pub async fn portal_pick(directory: bool, multiple: bool) -> io::Result<Vec<String>> {
+    use ashpd::desktop::file_chooser::SelectedFiles;
+    use ashpd::desktop::ResponseError;
+
+    let title = if directory {
+        "Choose a download folder"
+    } else {
+        "Choose files to send"
+    };
+    let request = SelectedFiles::open_file()
+        .title(title)
+        .modal(true)
+        .directory(directory)
+        .multiple(multiple)
+        .send()
+        .await
+        .map_err(io::Error::other)?;
+    let selected = match request.response() {
+        Ok(selected) => selected,
+        Err(ashpd::Error::Response(ResponseError::Cancelled)) => return Ok(Vec::new()),
+        Err(e) => return Err(io::Error::other(e)),
+    };
+    selected
+        .uris()
+        .iter()
+        .map(|uri| {
+            uri.to_file_path()
+                .ok()
+                .and_then(|path| path.to_str().map(String::from))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Not a local file: {}", uri),
+                    )
+                })
+        })
+        .collect()
+}
 
-    Ok(buffer)
+#[cfg(not(target_os = "linux"))]
+pub async fn portal_pick(_directory: bool, _multiple: bool) -> io::Result<Vec<String>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "The file chooser portal exists only on Linux",
+    ))
+}
+
+/// Copy a received file into a SAF folder (tree URI) the user picked
+/// On Android, creates the file through tauri-plugin-android-fs, since
+/// locations outside app storage (Downloads, SD card) can't be written with
+/// `std::fs`
+#[cfg(target_os = "android")]
+pub async fn save_to_tree(app: &tauri::AppHandle, tree_uri: &str, source: &Path) -> io::Result<()> {
+    use tauri_plugin_android_fs::AndroidFsExt;
+    use tauri_plugin_fs::FilePath;
+
+    log::info!("Android: saving {} to {}", source.display(), tree_uri);
+
+    let name = source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "File has no name"))?;
+    let url = url::Url::parse(tree_uri)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let dir: tauri_plugin_android_fs::FileUri = FilePath::Url(url).into();
+
+    let api = app.android_fs_async();
+    let uri = api
+        .create_new_file(&dir, name, None)
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    let mut target = api
+        .open_file_writable(&uri)
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+
+    let source = source.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(source)?;
+        std::io::copy(&mut file, &mut target)?;
+        target.sync_all()
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
 #[cfg(not(target_os = "android"))]
-pub async fn read_file(_app: &tauri::AppHandle, path: &str) -> io::Result<Vec<u8>> {
-    log::info!("Desktop: reading file: {}", path);
+pub async fn save_to_tree(
+    _app: &tauri::AppHandle,
+    tree_uri: &str,
+    _source: &Path,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "Can't save to {}: content:// folders exist only on Android",
+            tree_uri
+        ),
+    ))
+}
+
+/// Run `f` with a JNI env, one of the app's classes and the app's Context
+///
+/// FindClass on a native thread only sees system classes, so app classes are
+/// loaded through the Context's class loader. A Java exception thrown by `f`
+/// is cleared and returned as the error.
+#[cfg(target_os = "android")]
+pub fn with_java_class<T>(
+    class_name: &str,
+    f: impl FnOnce(&mut jni::JNIEnv, &jni::objects::JClass, &jni::objects::JObject) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    use jni::objects::{JClass, JObject, JValue};
+
+    let ctx = ndk_context::android_context();
+    let vm = unsafe { jni::JavaVM::from_raw(ctx.vm().cast()) }?;
+    let mut env = vm.attach_current_thread()?;
+    // Owned by ndk-context, never deleted here
+    let context = unsafe { JObject::from_raw(ctx.context().cast()) };
+
+    let loader = env
+        .call_method(&context, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])?
+        .l()?;
+    let name = env.new_string(class_name)?;
+    let class: JClass = env
+        .call_method(
+            &loader,
+            "loadClass",
+            "(Ljava/lang/String;)Ljava/lang/Class;",
+            &[JValue::Object(&name)],
+        )?
+        .l()?
+        .into();
+
+    let res = f(&mut env, &class, &context);
+    if env.exception_check()? {
+        env.exception_describe()?;
+        env.exception_clear()?;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_metadata() {
+        let path = std::env::temp_dir().join(format!("vegam-meta-{}.txt", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, b"hello").await.unwrap();
+
+        let metadata = file_metadata(path.to_str().unwrap()).await.unwrap();
+        assert_eq!(metadata.name.as_deref(), path.file_name().unwrap().to_str());
+        assert_eq!(metadata.size, Some(5));
+        assert_eq!(metadata.mime_type, None);
 
-    tokio::fs::read(path).await
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(file_metadata(path.to_str().unwrap()).await.is_err());
+    }
 }
@@ -0,0 +1,497 @@
+// Post-receive actions
+//
+// Rules in `post_receive` run on a received file once it has been verified
+// and moved into place. A rule matches by extension (`zip`, `tar.gz`; any if
+// none are listed) and optionally by the directory the file was saved under,
+// and does one thing: extract an archive (zip, tar, tar.gz) into a folder next
+// to it, move the file to another folder, or run a program with `{path}` in
+// its arguments replaced by the file's path (no shell involved). Matching
+// rules run in order on the file's current location, so a move is seen by the
+// rules after it. Each rule's outcome is an `ActionResult`, stored with the
+// transfer in history and sent with a `transfer-update`. Files saved to a SAF
+// folder or the gallery have no path here, so no rules run for them.
+//
+// Archives come from peers, so extraction holds each entry to the receive
+// `FileTypePolicy` (blocked entries are skipped and listed in the result) and
+// gives up, removing what it wrote, past `MAX_EXTRACT_BYTES` or
+// `MAX_EXTRACT_ENTRIES`. Only regular files and directories are unpacked.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::settings::FileTypePolicy;
+use crate::state::AppState;
+
+/// Programs still running after this are killed
+const RUN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Output kept in a `run` result
+const MAX_OUTPUT_CHARS: usize = 1000;
+/// Archive suffixes `extract` understands, longest first
+const ARCHIVE_SUFFIXES: [&str; 4] = [".tar.gz", ".tgz", ".tar", ".zip"];
+/// Most one archive may unpack to
+const MAX_EXTRACT_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+/// Most files and directories one archive may hold
+const MAX_EXTRACT_ENTRIES: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PostReceiveRule {
+    pub id: String,
+    /// Extensions without the dot, case-insensitive; any file if empty
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// Only files saved under this directory
+    #[serde(default)]
+    pub directory: Option<String>,
+    pub action: PostAction,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PostAction {
+    /// Unpack into a folder named after the archive
+    Extract {
+        #[serde(default)]
+        delete_archive: bool,
+    },
+    Move {
+        to: String,
+    },
+    /// `{path}` in `args` becomes the file's path
+    Run {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl PostAction {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Extract { .. } => "extract",
+            Self::Move { .. } => "move",
+            Self::Run { .. } => "run",
+        }
+    }
+}
+
+/// Outcome of one rule, kept with the transfer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActionResult {
+    pub rule_id: String,
+    /// `extract`, `move` or `run`
+    pub action: String,
+    pub ok: bool,
+    /// Where the file or its contents went, the program's output, or the error
+    pub detail: String,
+}
+
+impl PostReceiveRule {
+    /// Whether the rule applies to `path`; `directory` is the rule's resolved directory
+    pub fn matches(&self, path: &Path, directory: Option<&Path>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if directory.is_some_and(|dir| !path.starts_with(dir)) {
+            return false;
+        }
+        if self.extensions.is_empty() {
+            return true;
+        }
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        let name = name.to_lowercase();
+        self.extensions.iter().any(|ext| {
+            let ext = ext.trim_start_matches('.').to_lowercase();
+            name.ends_with(&format!(".{}", ext))
+        })
+    }
+}
+
+/// Run the matching rules on a received file, returning their results and where the file is now
+pub async fn run(
+    state: &AppState,
+    app: &AppHandle,
+    mut path: PathBuf,
+) -> (Vec<ActionResult>, PathBuf) {
+    let settings = state.get_settings().await;
+    let policy = settings.file_type_policy;
+    let mut results = Vec::new();
+    for rule in settings.post_receive {
+        let directory = match &rule.directory {
+            Some(dir) => match crate::resolve_user_path(state, app, dir).await {
+                Ok(dir) => Some(dir),
+                Err(e) => {
+                    warn!("Skipping post-receive rule {}: {}", rule.id, e);
+                    continue;
+                }
+            },
+            None => None,
+        };
+        if !rule.matches(&path, directory.as_deref()) {
+            continue;
+        }
+        if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            break;
+        }
+
+        info!(
+            "Running post-receive {} on {}",
+            rule.action.name(),
+            path.display()
+        );
+        let outcome = match &rule.action {
+            PostAction::Extract { delete_archive } => {
+                extract(&path, *delete_archive, &policy).await
+            }
+            PostAction::Move { to } => match crate::resolve_user_path(state, app, to).await {
+                Ok(dir) => move_to(&path, &dir).await.map(|moved| {
+                    path = moved;
+                    path.display().to_string()
+                }),
+                Err(e) => Err(anyhow!(e)),
+            },
+            PostAction::Run { program, args } => run_program(program, args, &path).await,
+        };
+        let (ok, detail) = match outcome {
+            Ok(detail) => (true, detail),
+            Err(e) => {
+                warn!("Post-receive rule {} failed: {:#}", rule.id, e);
+                (false, format!("{:#}", e))
+            }
+        };
+        results.push(ActionResult {
+            rule_id: rule.id.clone(),
+            action: rule.action.name().to_string(),
+            ok,
+            detail,
+        });
+    }
+    (results, path)
+}
+
+/// `name` with ` (n)` before its extension
+fn numbered(name: &str, n: u32) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{} ({}).{}", stem, n, ext),
+        _ => format!("{} ({})", name, n),
+    }
+}
+
+/// `dir/name`, numbered if something already has that name
+async fn unused_path(dir: &Path, name: &str) -> PathBuf {
+    let mut path = dir.join(name);
+    let mut n = 1;
+    while tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        path = dir.join(numbered(name, n));
+        n += 1;
+    }
+    path
+}
+
+async fn extract(archive: &Path, delete_archive: bool, policy: &FileTypePolicy) -> Result<String> {
+    let name = archive
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow!("Archive has no usable name"))?;
+    let lower = name.to_lowercase();
+    let suffix = ARCHIVE_SUFFIXES
+        .iter()
+        .find(|suffix| lower.ends_with(*suffix))
+        .ok_or_else(|| anyhow!("{} isn't a zip or tar archive", name))?;
+    let stem = name.get(..name.len() - suffix.len()).unwrap_or("");
+    let parent = archive.parent().unwrap_or(Path::new("."));
+    let dest = unused_path(parent, if stem.is_empty() { "archive" } else { stem }).await;
+
+    let source = archive.to_path_buf();
+    let target = dest.clone();
+    let suffix = *suffix;
+    let policy = policy.clone();
+    let skipped = tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+        let limits = Limits {
+            bytes: MAX_EXTRACT_BYTES,
+            entries: MAX_EXTRACT_ENTRIES,
+        };
+        let result = unpack(&source, &target, suffix, &policy, limits);
+        if result.is_err() {
+            let _ = std::fs::remove_dir_all(&target);
+        }
+        result
+    })
+    .await?
+    .with_context(|| format!("Failed to extract {}", name))?;
+
+    if delete_archive {
+        tokio::fs::remove_file(archive).await?;
+    }
+    if skipped.is_empty() {
+        Ok(dest.display().to_string())
+    } else {
+        Ok(format!(
+            "{} (skipped blocked {})",
+            dest.display(),
+            skipped.join(", ")
+        ))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Limits {
+    bytes: u64,
+    entries: usize,
+}
+
+/// Extraction into one directory, counted against `Limits`
+struct Unpacker<'a> {
+    target: &'a Path,
+    policy: &'a FileTypePolicy,
+    limits: Limits,
+    bytes: u64,
+    entries: usize,
+    /// Entries the policy refused
+    skipped: Vec<String>,
+}
+
+impl Unpacker<'_> {
+    fn count_entry(&mut self) -> Result<()> {
+        self.entries += 1;
+        if self.entries > self.limits.entries {
+            bail!("Archive has more than {} entries", self.limits.entries);
+        }
+        Ok(())
+    }
+
+    fn dir(&mut self, relative: &Path) -> Result<()> {
+        self.count_entry()?;
+        std::fs::create_dir_all(self.target.join(relative))?;
+        Ok(())
+    }
+
+    /// Write a file entry unless the policy blocks it
+    fn file(&mut self, relative: &Path, contents: &mut impl Read) -> Result<()> {
+        self.count_entry()?;
+        let name = relative.display().to_string();
+        if let Err(e) = self.policy.check(&name) {
+            warn!("Not extracting {}: {}", name, e);
+            self.skipped.push(name);
+            return Ok(());
+        }
+
+        let path = self.target.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::File::create(&path)?;
+        // One byte past the cap is enough to know it was crossed
+        let remaining = self.limits.bytes - self.bytes;
+        let written = std::io::copy(&mut contents.take(remaining + 1), &mut file)?;
+        self.bytes += written;
+        if self.bytes > self.limits.bytes {
+            bail!("Archive unpacks to more than {} bytes", self.limits.bytes);
+        }
+        Ok(())
+    }
+}
+
+/// Unpack `source` into `target`, returning the entries the policy skipped
+fn unpack(
+    source: &Path,
+    target: &Path,
+    suffix: &str,
+    policy: &FileTypePolicy,
+    limits: Limits,
+) -> Result<Vec<String>> {
+    std::fs::create_dir_all(target)?;
+    let file = std::fs::File::open(source)?;
+    let mut unpacker = Unpacker {
+        target,
+        policy,
+        limits,
+        bytes: 0,
+        entries: 0,
+        skipped: Vec::new(),
+    };
+    match suffix {
+        ".zip" => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i)?;
+                // Refuses names that would land outside `target`
+                let Some(relative) = entry.enclosed_name() else {
+                    bail!("Unsafe path in archive: {}", entry.name());
+                };
+                if entry.is_dir() {
+                    unpacker.dir(&relative)?;
+                } else {
+                    unpacker.file(&relative, &mut entry)?;
+                }
+            }
+        }
+        ".tar" => unpack_tar(tar::Archive::new(file), &mut unpacker)?,
+        _ => unpack_tar(
+            tar::Archive::new(flate2::read::GzDecoder::new(file)),
+            &mut unpacker,
+        )?,
+    }
+    Ok(unpacker.skipped)
+}
+
+fn unpack_tar(mut archive: tar::Archive<impl Read>, unpacker: &mut Unpacker) -> Result<()> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path.components().any(|c| {
+            !matches!(
+                c,
+                std::path::Component::Normal(_) | std::path::Component::CurDir
+            )
+        }) {
+            bail!("Unsafe path in archive: {}", path.display());
+        }
+        match entry.header().entry_type() {
+            tar::EntryType::Directory => unpacker.dir(&path)?,
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                unpacker.file(&path, &mut entry)?
+            }
+            // Links and special files
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Move `path` into `dir`, returning where it ended up
+async fn move_to(path: &Path, dir: &Path) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow!("File has no usable name"))?;
+    tokio::fs::create_dir_all(dir).await?;
+    let dest = unused_path(dir, name).await;
+    if tokio::fs::rename(path, &dest).await.is_err() {
+        // Different filesystem
+        tokio::fs::copy(path, &dest).await?;
+        tokio::fs::remove_file(path).await?;
+    }
+    Ok(dest)
+}
+
+async fn run_program(program: &str, args: &[String], path: &Path) -> Result<String> {
+    let file = path.display().to_string();
+    let args: Vec<String> = args
+        .iter()
+        .map(|arg| arg.replace("{path}", &file))
+        .collect();
+    let child = tokio::process::Command::new(program)
+        .args(&args)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(RUN_TIMEOUT, child)
+        .await
+        .map_err(|_| anyhow!("{} timed out", program))?
+        .with_context(|| format!("Failed to start {}", program))?;
+
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    let text = text.trim();
+    let skip = text.chars().count().saturating_sub(MAX_OUTPUT_CHARS);
+    let tail: String = text.chars().skip(skip).collect();
+    if !output.status.success() {
+        bail!("{} exited with {}: {}", program, output.status, tail);
+    }
+    Ok(tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_matching() {
+        let rule = PostReceiveRule {
+            id: "r1".to_string(),
+            extensions: vec!["zip".to_string(), ".TAR.GZ".to_string()],
+            directory: Some("/nas/incoming".to_string()),
+            action: PostAction::Extract {
+                delete_archive: false,
+            },
+            enabled: true,
+        };
+        let dir = Path::new("/nas/incoming");
+        assert!(rule.matches(Path::new("/nas/incoming/photos.ZIP"), Some(dir)));
+        assert!(rule.matches(Path::new("/nas/incoming/src.tar.gz"), Some(dir)));
+        assert!(!rule.matches(Path::new("/nas/incoming/notes.txt"), Some(dir)));
+        assert!(!rule.matches(Path::new("/home/me/photos.zip"), Some(dir)));
+        assert!(!rule.matches(Path::new("/nas/incoming-old/photos.zip"), Some(dir)));
+
+        let any = PostReceiveRule {
+            extensions: Vec::new(),
+            directory: None,
+            ..rule.clone()
+        };
+        assert!(any.matches(Path::new("/anywhere/file"), None));
+        let disabled = PostReceiveRule {
+            enabled: false,
+            ..any
+        };
+        assert!(!disabled.matches(Path::new("/anywhere/file"), None));
+
+        assert_eq!(numbered("photos.zip", 2), "photos (2).zip");
+        assert_eq!(numbered("photos", 1), "photos (1)");
+        assert_eq!(numbered(".bashrc", 1), ".bashrc (1)");
+    }
+
+    #[test]
+    fn test_extract_policy_and_caps() {
+        let dir = std::env::temp_dir().join(format!("vegam-extract-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, size) in [("docs/notes.txt", 100), ("setup.exe", 10)] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(size as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, vec![0u8; size].as_slice())
+                .unwrap();
+        }
+        let archive = dir.join("bundle.tar");
+        std::fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+        let policy = FileTypePolicy::default();
+        let limits = Limits {
+            bytes: 1000,
+            entries: 10,
+        };
+
+        let out = dir.join("ok");
+        let skipped = unpack(&archive, &out, ".tar", &policy, limits).unwrap();
+        assert_eq!(skipped, vec!["setup.exe".to_string()]);
+        assert!(out.join("docs/notes.txt").exists());
+        assert!(!out.join("setup.exe").exists());
+
+        let small = Limits {
+            bytes: 50,
+            entries: 10,
+        };
+        assert!(unpack(&archive, &dir.join("big"), ".tar", &policy, small).is_err());
+        let few = Limits {
+            bytes: 1000,
+            entries: 1,
+        };
+        assert!(unpack(&archive, &dir.join("many"), ".tar", &policy, few).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,287 @@
+// Low-power networking
+//
+// With `low_power.enabled`, the node saves battery while the device runs on
+// battery below `battery_threshold` percent: announcements go out less often,
+// the connection monitor and blob GC skip their rounds, and a node started in
+// this state uses longer QUIC keepalives. Everything returns to normal once
+// the device charges or the level recovers. Linux reads the battery from
+// sysfs; elsewhere the frontend reports it (the WebView's Battery Status API),
+// and a reported status always wins.
+//
+// Separately, `battery_defer` holds back sends bigger than `threshold_bytes`
+// while the device is on battery below its own threshold: importing and
+// serving a large file costs more than the battery can spare. The send stays
+// pending, `send-deferred` says why, and it goes ahead once the device charges
+// or recovers, or right away with `force_send`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration};
+use tracing::info;
+
+use crate::events::AppEvent;
+use crate::state::{AppState, TransferInfo};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Keepalive for connections of a node started in low-power mode
+pub const LOW_POWER_KEEP_ALIVE: Duration = Duration::from_secs(25);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LowPowerPolicy {
+    pub enabled: bool,
+    /// Battery percentage below which low-power mode kicks in
+    pub battery_threshold: u8,
+}
+
+impl Default for LowPowerPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            battery_threshold: 20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PowerStatus {
+    pub on_battery: bool,
+    /// Percent; unknown on some devices
+    pub level: Option<u8>,
+}
+
+impl LowPowerPolicy {
+    pub fn applies(&self, status: PowerStatus) -> bool {
+        self.enabled
+            && status.on_battery
+            && status
+                .level
+                .is_some_and(|level| level < self.battery_threshold)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryDeferPolicy {
+    /// Hold back large sends while the battery is low
+    pub enabled: bool,
+    /// Battery percentage below which large sends wait
+    pub battery_threshold: u8,
+    /// Sends larger than this wait for the battery
+    pub threshold_bytes: u64,
+}
+
+impl Default for BatteryDeferPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            battery_threshold: 30,
+            threshold_bytes: 100 * 1024 * 1024,
+        }
+    }
+}
+
+impl BatteryDeferPolicy {
+    /// Whether a send of `size` bytes has to wait
+    pub fn defers(&self, size: u64, status: PowerStatus) -> bool {
+        self.enabled
+            && size > self.threshold_bytes
+            && status.on_battery
+            && status
+                .level
+                .is_some_and(|level| level < self.battery_threshold)
+    }
+}
+
+/// Payload of `send-deferred`
+#[derive(Clone, Debug, Serialize)]
+pub struct DeferredSend {
+    pub transfer: TransferInfo,
+    /// Battery percentage when the send was held back
+    pub battery_level: Option<u8>,
+    pub battery_threshold: u8,
+}
+
+/// Announcement interval while in low-power mode: three times as long, but
+/// short enough that peers with our peer timeout don't drop us
+pub fn stretched_interval(interval: Duration, peer_timeout: Duration) -> Duration {
+    (interval * 3).min(peer_timeout * 2 / 3).max(interval)
+}
+
+/// Power status, whether low-power mode is active and the sends waiting for
+/// the battery
+#[derive(Clone, Default)]
+pub struct PowerState {
+    reported: Arc<RwLock<Option<PowerStatus>>>,
+    low_power: Arc<AtomicBool>,
+    /// Deferred sends by transfer ID
+    deferred: Arc<RwLock<HashMap<String, Arc<Notify>>>>,
+}
+
+impl PowerState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_low_power(&self) -> bool {
+        self.low_power.load(Ordering::Relaxed)
+    }
+
+    pub async fn set_reported(&self, status: Option<PowerStatus>) {
+        *self.reported.write().await = status;
+    }
+
+    pub async fn status(&self) -> Option<PowerStatus> {
+        match *self.reported.read().await {
+            Some(status) => Some(status),
+            None => detect().await,
+        }
+    }
+
+    /// Re-evaluate the mode, returning the new value if it changed
+    pub async fn refresh(&self, policy: LowPowerPolicy) -> Option<bool> {
+        let low_power = self.status().await.is_some_and(|s| policy.applies(s));
+        let was = self.low_power.swap(low_power, Ordering::Relaxed);
+        (was != low_power).then_some(low_power)
+    }
+
+    /// IDs of sends waiting for the battery
+    pub async fn deferred_sends(&self) -> Vec<String> {
+        self.deferred.read().await.keys().cloned().collect()
+    }
+
+    /// Wait until a send of `size` bytes may start under `policy`
+    ///
+    /// Calls `on_deferred` with the battery status once if the send has to
+    /// wait.
+    pub async fn wait_for_battery(
+        &self,
+        transfer_id: &str,
+        size: u64,
+        policy: BatteryDeferPolicy,
+        on_deferred: impl FnOnce(PowerStatus),
+    ) {
+        let Some(status) = self.status().await.filter(|s| policy.defers(size, *s)) else {
+            return;
+        };
+
+        let release = Arc::new(Notify::new());
+        self.deferred
+            .write()
+            .await
+            .insert(transfer_id.to_string(), release.clone());
+        on_deferred(status);
+
+        loop {
+            tokio::select! {
+                _ = release.notified() => break,
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {
+                    if !self.status().await.is_some_and(|s| policy.defers(size, s)) {
+                        break;
+                    }
+                }
+            }
+        }
+        self.deferred.write().await.remove(transfer_id);
+    }
+
+    /// Start a deferred send now; false if it isn't waiting
+    pub async fn force_send(&self, transfer_id: &str) -> bool {
+        match self.deferred.read().await.get(transfer_id) {
+            Some(release) => {
+                release.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Spawn a task that switches low-power mode on and off, emitting
+/// `low-power-changed`
+pub fn spawn_power_monitor(handle: AppHandle) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut timer = interval(CHECK_INTERVAL);
+        loop {
+            timer.tick().await;
+            let state = handle.state::<AppState>();
+            let policy = state.get_settings().await.low_power;
+            if let Some(low_power) = state.power.refresh(policy).await {
+                info!("Low-power mode {}", if low_power { "on" } else { "off" });
+                let _ = AppEvent::LowPowerChanged(low_power).emit(&handle);
+            }
+        }
+    })
+}
+
+#[cfg(target_os = "linux")]
+async fn detect() -> Option<PowerStatus> {
+    let mut entries = tokio::fs::read_dir("/sys/class/power_supply").await.ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let Ok(kind) = tokio::fs::read_to_string(path.join("type")).await else {
+            continue;
+        };
+        if kind.trim() != "Battery" {
+            continue;
+        }
+        let Ok(status) = tokio::fs::read_to_string(path.join("status")).await else {
+            continue;
+        };
+        let capacity = tokio::fs::read_to_string(path.join("capacity")).await.ok();
+        return Some(parse_sysfs(&status, capacity.as_deref()));
+    }
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn detect() -> Option<PowerStatus> {
+    None
+}
+
+/// A sysfs battery's `status` and `capacity`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_sysfs(status: &str, capacity: Option<&str>) -> PowerStatus {
+    PowerStatus {
+        on_battery: status.trim() == "Discharging",
+        level: capacity.and_then(|c| c.trim().parse().ok()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_low_power_policy() {
+        let policy = LowPowerPolicy {
+            enabled: true,
+            battery_threshold: 20,
+        };
+        assert!(policy.applies(parse_sysfs("Discharging\n", Some("15\n"))));
+        assert!(!policy.applies(parse_sysfs("Charging", Some("15"))));
+        assert!(!policy.applies(parse_sysfs("Discharging", Some("80"))));
+        assert!(!policy.applies(parse_sysfs("Discharging", None)));
+
+        let defer = BatteryDeferPolicy {
+            enabled: true,
+            ..BatteryDeferPolicy::default()
+        };
+        let large = 200 * 1024 * 1024;
+        assert!(defer.defers(large, parse_sysfs("Discharging", Some("25"))));
+        assert!(!defer.defers(large, parse_sysfs("Charging", Some("25"))));
+        assert!(!defer.defers(1024, parse_sysfs("Discharging", Some("25"))));
+        assert!(!defer.defers(large, parse_sysfs("Discharging", Some("60"))));
+
+        let secs = Duration::from_secs;
+        assert_eq!(stretched_interval(secs(30), secs(90)), secs(60));
+        assert_eq!(stretched_interval(secs(10), secs(90)), secs(30));
+        // Never shorter than the normal interval
+        assert_eq!(stretched_interval(secs(60), secs(60)), secs(60));
+    }
+}
@@ -0,0 +1,146 @@
+// Quick Settings tile and widget hooks
+//
+// A tile or widget has to show something without the webview running, so
+// everything here works straight off `AppState`: `get_quick_status` is a
+// compact summary (discoverable, peers online, running transfers and their
+// overall progress) and `toggle_discoverable` flips discoverability the way
+// `set_discoverable` does. On Android `QuickTile.kt` calls the same functions
+// over JNI; when the tile wakes a process the app isn't set up in, the status
+// says `running: false` and tapping the tile opens the app instead.
+
+use serde::Serialize;
+use std::sync::OnceLock;
+use tauri::AppHandle;
+
+use crate::foreground::TransferSummary;
+use crate::state::AppState;
+
+/// Set once the app is up
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+/// Payload of `get_quick_status` and the tile's JNI calls
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct QuickStatus {
+    /// App is set up; everything else is empty when it isn't
+    pub running: bool,
+    pub discoverable: bool,
+    pub peers_online: usize,
+    pub active_transfers: usize,
+    /// Overall percent of the running transfers, when their sizes are known
+    pub progress: Option<u8>,
+}
+
+/// Make the app reachable from the tile
+pub fn init(handle: AppHandle) {
+    let _ = APP.set(handle);
+}
+
+pub async fn status(state: &AppState) -> QuickStatus {
+    let summary = TransferSummary::of(state.transfers.read().await.values(), 0);
+    QuickStatus {
+        running: true,
+        discoverable: state.get_settings().await.discoverable,
+        peers_online: state.get_peers().await.len(),
+        active_transfers: summary.as_ref().map_or(0, |s| s.running),
+        progress: summary.and_then(|s| u8::try_from(s.percent()).ok()),
+    }
+}
+
+/// Flip discoverability, saying goodbye when turning it off
+pub async fn toggle_discoverable(state: &AppState, app: &AppHandle) -> Result<bool, String> {
+    let discoverable = !state.get_settings().await.discoverable;
+    crate::apply_discoverable(state, app, discoverable, true).await?;
+    Ok(discoverable)
+}
+
+/// Run `f` against the app if it's up, for the JNI entry points
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+fn with_app<T>(f: impl FnOnce(&AppHandle, &AppState) -> T) -> Option<T> {
+    use tauri::Manager;
+
+    let app = APP.get()?;
+    Some(f(app, &app.state::<AppState>()))
+}
+
+/// `QuickTile.status(): String`, a `QuickStatus` as JSON
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_codes_imran_vegam_QuickTile_status(
+    env: jni::JNIEnv,
+    _class: jni::objects::JClass,
+) -> jni::sys::jstring {
+    let status = with_app(|_, state| tauri::async_runtime::block_on(status(state)));
+    to_java_json(env, &status.unwrap_or_default())
+}
+
+/// `QuickTile.toggleDiscoverable(): String`, the new `QuickStatus` as JSON
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_codes_imran_vegam_QuickTile_toggleDiscoverable(
+    env: jni::JNIEnv,
+    _class: jni::objects::JClass,
+) -> jni::sys::jstring {
+    let status = with_app(|app, state| {
+        tauri::async_runtime::block_on(async {
+            if let Err(e) = toggle_discoverable(state, app).await {
+                log::warn!("Failed to toggle discoverability from the tile: {}", e);
+            }
+            status(state).await
+        })
+    });
+    to_java_json(env, &status.unwrap_or_default())
+}
+
+#[cfg(target_os = "android")]
+fn to_java_json(env: jni::JNIEnv, status: &QuickStatus) -> jni::sys::jstring {
+    let json = serde_json::to_string(status).unwrap_or_default();
+    env.new_string(json)
+        .map(|s| s.into_raw())
+        .unwrap_or(std::ptr::null_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{TransferDirection, TransferInfo, TransferStatus};
+
+    #[tokio::test]
+    async fn test_quick_status() {
+        let state = AppState::new();
+        assert_eq!(
+            status(&state).await,
+            QuickStatus {
+                running: true,
+                discoverable: state.get_settings().await.discoverable,
+                ..QuickStatus::default()
+            }
+        );
+
+        let transfer = TransferInfo {
+            id: "t1".to_string(),
+            file_name: "a.mp4".to_string(),
+            file_size: 200,
+            bytes_transferred: 50,
+            status: TransferStatus::InProgress,
+            error: None,
+            direction: TransferDirection::Receive,
+            speed_bps: 0,
+            peer_node_id: None,
+            started_at: None,
+            finished_at: None,
+            ticket_hash: None,
+            note: None,
+            pinned: false,
+            actions: Vec::new(),
+            saved_path: None,
+        };
+        state
+            .transfers
+            .write()
+            .await
+            .insert(transfer.id.clone(), transfer);
+        let quick = status(&state).await;
+        assert_eq!(quick.active_transfers, 1);
+        assert_eq!(quick.progress, Some(25));
+    }
+}
@@ -0,0 +1,428 @@
+// Persisted user settings
+//
+// Settings live in a small JSON file in the app's local data directory and are
+// loaded once at startup. Missing or unreadable files fall back to defaults.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::bandwidth::BandwidthLimits;
+use crate::clipboard::ClipboardSyncSettings;
+use crate::history::HistoryRetention;
+use crate::iroh::{
+    discovery, gc::BlobGcPolicy, identity::IdentityTransition, network::NetworkOptions,
+    BlobStoreKind,
+};
+use crate::local_api::LocalApiSettings;
+use crate::lock::AppLock;
+use crate::metered::MeteredPolicy;
+use crate::notify::NotificationPolicy;
+use crate::post_receive::PostReceiveRule;
+use crate::power::{BatteryDeferPolicy, LowPowerPolicy};
+use crate::state::{PeerInfo, TrustLevel};
+use crate::watch::WatchFolder;
+use crate::webhook::WebhookSettings;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// User-chosen display name; falls back to the hostname when unset
+    pub device_name: Option<String>,
+    /// Color override (`#rrggbb`); derived from the node ID when unset
+    pub color: Option<String>,
+    /// Avatar/emoji index shown next to the name
+    pub avatar: Option<u8>,
+    /// Peers the user has explicitly trusted, keyed by node ID
+    pub trusted_peers: BTreeMap<String, TrustedPeer>,
+    /// Peers whose announcements, connections and tickets are ignored
+    pub blocked_peers: BTreeSet<String>,
+    /// Largest incoming file we accept, advertised to peers
+    pub max_file_size: Option<u64>,
+    /// Private room to discover peers in, rejoined on startup
+    pub room_code: Option<String>,
+    /// Gossip ticket the primary topic was joined from, used when not in a room
+    pub room_invite: Option<String>,
+    /// Additional discovery groups, keyed by group ID
+    pub groups: BTreeMap<String, PeerGroup>,
+    /// When false we stop announcing but keep listening for peers
+    pub discoverable: bool,
+    /// Local nicknames for peers, keyed by node ID
+    pub peer_aliases: BTreeMap<String, String>,
+    /// Peers pinned to the top of the list and kept while offline, keyed by node ID
+    pub favorite_peers: BTreeMap<String, FavoritePeer>,
+    /// Where files from a peer are saved when no path is chosen, keyed by node ID
+    pub peer_download_dirs: BTreeMap<String, String>,
+    /// Blob store backend, applied when the node starts
+    pub blob_store: BlobStoreKind,
+    /// Relay, discovery and socket options, applied when the node starts
+    pub network: NetworkOptions,
+    /// When shared blobs nobody downloads any more are deleted
+    pub blob_gc: BlobGcPolicy,
+    /// Encrypt shared files before they enter the blob store
+    pub encrypt_blob_store: bool,
+    /// Encrypt shared files end to end with a key carried only in the ticket
+    pub encrypt_content: bool,
+    /// File extensions refused on receive unless the user overrides
+    pub file_type_policy: FileTypePolicy,
+    /// Refuse blob downloads from peers that aren't trusted, even with a ticket
+    pub trusted_only_provider: bool,
+    /// Address the browser download server listens on; all interfaces if unset
+    pub http_share_bind: Option<IpAddr>,
+    /// Keep the append-only connection and transfer audit log
+    pub audit_log: bool,
+    /// Last node key rotation, announced so peers can move their trust over
+    pub identity_transition: Option<IdentityTransition>,
+    /// Passcode gating sensitive commands, when set
+    pub app_lock: Option<AppLock>,
+    /// How long finished transfers are kept in history
+    pub history_retention: HistoryRetention,
+    /// How often we announce and how long silent peers are kept
+    pub discovery_timings: DiscoveryTimings,
+    /// Whether large downloads wait for an unmetered connection
+    pub metered: MeteredPolicy,
+    /// Global upload and download caps
+    pub bandwidth: BandwidthLimits,
+    /// When to cut back on network activity to save battery
+    pub low_power: LowPowerPolicy,
+    /// Whether large sends wait while the battery is low
+    pub battery_defer: BatteryDeferPolicy,
+    /// Put received photos and videos in the phone's gallery
+    pub save_media_to_gallery: bool,
+    /// Where received files go when no path is given; platform default if unset
+    pub download_dir: Option<String>,
+    /// Which events raise OS notifications
+    pub notifications: NotificationPolicy,
+    /// Token-protected HTTP API on localhost
+    pub local_api: LocalApiSettings,
+    /// Directories whose new files are sent automatically
+    pub watch_folders: Vec<WatchFolder>,
+    /// Clipboard sharing with the user's own devices
+    pub clipboard_sync: ClipboardSyncSettings,
+    /// HTTP callback on transfer events
+    pub webhook: WebhookSettings,
+    /// Actions run on received files, in order
+    pub post_receive: Vec<PostReceiveRule>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            device_name: None,
+            color: None,
+            avatar: None,
+            trusted_peers: BTreeMap::new(),
+            blocked_peers: BTreeSet::new(),
+            max_file_size: None,
+            room_code: None,
+            room_invite: None,
+            groups: BTreeMap::new(),
+            discoverable: true,
+            peer_aliases: BTreeMap::new(),
+            favorite_peers: BTreeMap::new(),
+            peer_download_dirs: BTreeMap::new(),
+            blob_store: BlobStoreKind::default(),
+            network: NetworkOptions::default(),
+            blob_gc: BlobGcPolicy::default(),
+            encrypt_blob_store: false,
+            encrypt_content: false,
+            file_type_policy: FileTypePolicy::default(),
+            trusted_only_provider: false,
+            http_share_bind: None,
+            audit_log: false,
+            identity_transition: None,
+            app_lock: None,
+            history_retention: HistoryRetention::default(),
+            discovery_timings: DiscoveryTimings::default(),
+            metered: MeteredPolicy::default(),
+            bandwidth: BandwidthLimits::default(),
+            low_power: LowPowerPolicy::default(),
+            battery_defer: BatteryDeferPolicy::default(),
+            save_media_to_gallery: false,
+            download_dir: None,
+            notifications: NotificationPolicy::default(),
+            local_api: LocalApiSettings::default(),
+            watch_folders: Vec::new(),
+            clipboard_sync: ClipboardSyncSettings::default(),
+            webhook: WebhookSettings::default(),
+            post_receive: Vec::new(),
+        }
+    }
+}
+
+/// Announcement interval and peer timeout, in seconds
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryTimings {
+    pub announcement_interval_secs: u64,
+    pub peer_timeout_secs: u64,
+}
+
+impl Default for DiscoveryTimings {
+    fn default() -> Self {
+        Self {
+            announcement_interval_secs: discovery::DEFAULT_ANNOUNCEMENT_INTERVAL.as_secs(),
+            peer_timeout_secs: discovery::DEFAULT_PEER_TIMEOUT.as_secs(),
+        }
+    }
+}
+
+impl DiscoveryTimings {
+    /// Check the timings are within bounds and consistent with each other
+    pub fn validate(&self) -> Result<()> {
+        let interval = Duration::from_secs(self.announcement_interval_secs);
+        if !(discovery::MIN_ANNOUNCEMENT_INTERVAL..=discovery::MAX_ANNOUNCEMENT_INTERVAL)
+            .contains(&interval)
+        {
+            return Err(anyhow::anyhow!(
+                "Announcement interval must be between {} and {} seconds",
+                discovery::MIN_ANNOUNCEMENT_INTERVAL.as_secs(),
+                discovery::MAX_ANNOUNCEMENT_INTERVAL.as_secs()
+            ));
+        }
+
+        let min_timeout = interval * discovery::MIN_TIMEOUT_INTERVALS;
+        let timeout = Duration::from_secs(self.peer_timeout_secs);
+        if !(min_timeout..=discovery::MAX_PEER_TIMEOUT).contains(&timeout) {
+            return Err(anyhow::anyhow!(
+                "Peer timeout must be between {} and {} seconds",
+                min_timeout.as_secs(),
+                discovery::MAX_PEER_TIMEOUT.as_secs()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Interval between announcements, falling back to the default if the
+    /// persisted value is out of bounds
+    pub fn announcement_interval(&self) -> Duration {
+        let timings = self.checked();
+        Duration::from_secs(timings.announcement_interval_secs)
+    }
+
+    pub fn peer_timeout(&self) -> Duration {
+        let timings = self.checked();
+        Duration::from_secs(timings.peer_timeout_secs)
+    }
+
+    fn checked(&self) -> Self {
+        if self.validate().is_ok() {
+            *self
+        } else {
+            Self::default()
+        }
+    }
+}
+
+/// Executable and installer types refused by default
+const DEFAULT_BLOCKED_EXTENSIONS: &[&str] = &[
+    "apk", "app", "bat", "cmd", "com", "deb", "dmg", "exe", "ipa", "jar", "msi", "pkg", "ps1",
+    "rpm", "scr", "sh", "vbs",
+];
+
+/// Which file extensions may be received
+///
+/// Extensions are compared lowercase and without the dot. A non-empty
+/// `allowed` list turns the policy into an allowlist; `blocked` always wins.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileTypePolicy {
+    pub blocked: Vec<String>,
+    pub allowed: Vec<String>,
+}
+
+impl Default for FileTypePolicy {
+    fn default() -> Self {
+        Self {
+            blocked: DEFAULT_BLOCKED_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            allowed: Vec::new(),
+        }
+    }
+}
+
+impl FileTypePolicy {
+    /// Lowercase, dotless, deduplicated extensions
+    pub fn normalized(self) -> Self {
+        let normalize = |list: Vec<String>| {
+            let mut list: Vec<String> = list
+                .into_iter()
+                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect();
+            list.sort();
+            list.dedup();
+            list
+        };
+        Self {
+            blocked: normalize(self.blocked),
+            allowed: normalize(self.allowed),
+        }
+    }
+
+    /// Why `file_name` may not be received, if it may not
+    pub fn check(&self, file_name: &str) -> Result<()> {
+        // Windows ignores trailing dots and spaces, so `x.exe.` is still an `.exe`
+        let name = file_name.trim_end_matches(['.', ' ']);
+        let extension = Path::new(name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        match extension {
+            Some(ext) if self.blocked.contains(&ext) => {
+                Err(anyhow::anyhow!("Blocked file type: .{}", ext))
+            }
+            Some(ext) if !self.allowed.is_empty() && !self.allowed.contains(&ext) => Err(
+                anyhow::anyhow!("File type .{} is not in the allowed list", ext),
+            ),
+            None if !self.allowed.is_empty() => Err(anyhow::anyhow!(
+                "Files without an extension are not allowed"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A discovery group backed by its own room code/topic
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PeerGroup {
+    pub id: String,
+    pub name: String,
+    pub room_code: String,
+    /// Whether we announce ourselves in this group; we always listen
+    #[serde(default = "default_true")]
+    pub announce: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrustedPeer {
+    pub node_id: String,
+    /// Name the peer had when it was trusted
+    pub device_name: String,
+    pub trusted_at: u64,
+    /// The users compared fingerprints and confirmed this is the right device
+    #[serde(default)]
+    pub verified: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FavoritePeer {
+    pub node_id: String,
+    /// Last known name and color, shown while the peer is offline
+    pub device_name: String,
+    pub color: String,
+}
+
+impl Settings {
+    /// Load settings from disk, returning defaults if the file is missing or invalid
+    pub async fn load(path: &Path) -> Self {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Failed to parse settings, using defaults: {}", e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Write settings to disk, creating the parent directory if needed
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let bytes = serde_json::to_vec_pretty(self)?;
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+
+    /// Name announced to other peers
+    pub fn display_name(&self) -> String {
+        self.device_name
+            .clone()
+            .unwrap_or_else(discovery::get_device_name)
+    }
+
+    /// Apply locally stored per-peer settings to a discovered peer
+    pub fn apply_to_peer(&self, peer: &mut PeerInfo) {
+        let trusted = self.trusted_peers.get(&peer.node_id);
+        peer.trust_level = if trusted.is_some() {
+            TrustLevel::Trusted
+        } else {
+            TrustLevel::Unknown
+        };
+        peer.verified = trusted.is_some_and(|t| t.verified);
+        if peer.fingerprint.is_empty() {
+            peer.fingerprint = crate::iroh::identity::fingerprint(&peer.node_id);
+        }
+        peer.alias = self.peer_aliases.get(&peer.node_id).cloned();
+        peer.favorite = self.favorite_peers.contains_key(&peer.node_id);
+    }
+
+    /// Color announced to other peers
+    pub fn color_for(&self, node_id: &str) -> String {
+        self.color
+            .clone()
+            .unwrap_or_else(|| discovery::default_color(node_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_timings_bounds() {
+        assert!(DiscoveryTimings::default().validate().is_ok());
+
+        let too_fast = DiscoveryTimings {
+            announcement_interval_secs: 1,
+            peer_timeout_secs: 30,
+        };
+        assert!(too_fast.validate().is_err());
+        assert_eq!(
+            too_fast.announcement_interval(),
+            discovery::DEFAULT_ANNOUNCEMENT_INTERVAL
+        );
+
+        let timeout_too_short = DiscoveryTimings {
+            announcement_interval_secs: 60,
+            peer_timeout_secs: 90,
+        };
+        assert!(timeout_too_short.validate().is_err());
+    }
+
+    #[test]
+    fn test_file_type_policy() {
+        let policy = FileTypePolicy::default();
+        assert!(policy.check("photo.JPG").is_ok());
+        assert!(policy.check("README").is_ok());
+        assert!(policy.check("setup.EXE").is_err());
+        assert!(policy.check("setup.exe. ").is_err());
+        assert!(policy.check("app.apk").is_err());
+
+        let allowlist = FileTypePolicy {
+            blocked: vec!["pdf".to_string()],
+            allowed: vec![".JPG".to_string(), "pdf".to_string()],
+        }
+        .normalized();
+        assert_eq!(allowlist.allowed, vec!["jpg", "pdf"]);
+        assert!(allowlist.check("photo.jpg").is_ok());
+        assert!(allowlist.check("doc.pdf").is_err());
+        assert!(allowlist.check("notes.txt").is_err());
+        assert!(allowlist.check("README").is_err());
+    }
+}
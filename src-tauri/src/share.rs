@@ -0,0 +1,99 @@
+// Receiving shares from other apps
+//
+// On Android vegam is a share target: "Share → vegam" in Gallery or Files
+// starts `MainActivity` with an ACTION_SEND(_MULTIPLE) intent, and the activity
+// hands the content URIs with their display names and sizes to
+// `onShareReceived` below. A share that launches the app arrives before the
+// webview listens, so files are staged here until the frontend takes them
+// with `take_shared_files`; once the app is up `share-received` is emitted
+// too. The URIs go to `send_file` as they are, since `platform::open_file`
+// reads content URIs.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+use tauri::AppHandle;
+
+use crate::events::AppEvent;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SharedFile {
+    /// content:// URI, readable by `send_file`
+    pub uri: String,
+    pub name: String,
+    /// Unknown for some providers
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+/// Shared files the frontend hasn't taken yet
+static STAGED: Mutex<Vec<SharedFile>> = Mutex::new(Vec::new());
+/// Set once the app is up, so later shares can be announced
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+/// Start announcing shares; ones that launched the app stay staged
+pub fn init(handle: AppHandle) {
+    let _ = APP.set(handle);
+}
+
+/// Take the staged files, leaving none
+pub fn take() -> Vec<SharedFile> {
+    std::mem::take(&mut *STAGED.lock().unwrap())
+}
+
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+fn stage(files: Vec<SharedFile>) {
+    if files.is_empty() {
+        return;
+    }
+    log::info!("Received {} shared file(s)", files.len());
+    STAGED.lock().unwrap().extend(files.iter().cloned());
+    if let Some(handle) = APP.get() {
+        let _ = AppEvent::ShareReceived(files).emit(handle);
+    }
+}
+
+/// Files as sent by `MainActivity`: a JSON array of `SharedFile`
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+fn parse_shared(json: &str) -> serde_json::Result<Vec<SharedFile>> {
+    serde_json::from_str(json)
+}
+
+/// `MainActivity.onShareReceived(filesJson: String)`
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_codes_imran_vegam_MainActivity_onShareReceived(
+    mut env: jni::JNIEnv,
+    _activity: jni::objects::JObject,
+    files: jni::objects::JString,
+) {
+    let json: String = match env.get_string(&files) {
+        Ok(json) => json.into(),
+        Err(e) => {
+            log::warn!("Failed to read shared files: {}", e);
+            return;
+        }
+    };
+    match parse_shared(&json) {
+        Ok(files) => stage(files),
+        Err(e) => log::warn!("Invalid shared files {}: {}", json, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shared_files() {
+        let files = parse_shared(
+            r#"[{"uri":"content://media/1","name":"a.jpg","size":12},
+                {"uri":"content://media/2","name":"b.mp4"}]"#,
+        )
+        .unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].size, Some(12));
+        assert_eq!(files[1].name, "b.mp4");
+        assert_eq!(files[1].size, None);
+        assert!(parse_shared("not json").is_err());
+    }
+}
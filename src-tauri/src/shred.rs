@@ -0,0 +1,58 @@
+// Secure delete of received files
+//
+// The file is overwritten with random bytes and synced before it is removed,
+// so its contents don't linger in the freed blocks. SSD wear levelling,
+// copy-on-write filesystems and snapshots can still keep old blocks around;
+// this is best effort, not a forensic guarantee.
+
+use anyhow::Result;
+use std::io::Write;
+use std::path::Path;
+
+/// Overwrite in chunks so large files don't need a matching buffer
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Overwrite and delete a regular file; returns its size
+pub async fn shred_file(path: &Path) -> Result<u64> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || shred_blocking(&path)).await?
+}
+
+fn shred_blocking(path: &Path) -> Result<u64> {
+    // Don't follow symlinks: only the link itself would be ours to delete
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_file() {
+        anyhow::bail!("Not a regular file: {}", path.display());
+    }
+    let len = metadata.len();
+
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(CHUNK_SIZE as u64) as usize;
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut chunk[..n]);
+        file.write_all(&chunk[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::remove_file(path)?;
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shred_removes_file() {
+        let path = std::env::temp_dir().join(format!("vegam-shred-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, vec![7u8; CHUNK_SIZE + 10]).unwrap();
+
+        assert_eq!(shred_blocking(&path).unwrap(), (CHUNK_SIZE + 10) as u64);
+        assert!(!path.exists());
+        assert!(shred_blocking(&path).is_err());
+    }
+}
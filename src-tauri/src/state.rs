@@ -1,11 +1,45 @@
 use anyhow::Result;
-use iroh_blobs::{api::tags::TagInfo, Hash};
+use iroh_base::EndpointAddr;
+use iroh_blobs::Hash;
+use iroh_gossip::proto::TopicId;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Instant;
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinHandle;
+use tracing::warn;
 
+use crate::audit::AuditLog;
+use crate::bandwidth::Bandwidth;
+use crate::chat::ChatMessage;
+use crate::clipboard::ClipboardSync;
+use crate::doze::DozeGate;
+use crate::drag_drop::DropZone;
+use crate::fanout::TransferGroups;
+use crate::history::{
+    now_secs, BlobRecord, HistoryRetention, HistoryStore, JournalEntry, ResumableReceive,
+    TransferFilter, TransferPage,
+};
+use crate::http_share::HttpShare;
+use crate::iroh::access::AccessPolicy;
+use crate::iroh::connection::PeerConnectionType;
+use crate::iroh::identity::IdentityTransition;
+use crate::iroh::pairing::{PendingPairing, PendingPin};
 use crate::iroh::Iroh;
+use crate::local_api::LocalApi;
+use crate::lock::{LockState, LOCKED};
+use crate::metered::MeteredGate;
+use crate::post_receive::ActionResult;
+use crate::power::PowerState;
+use crate::settings::{Settings, TrustedPeer};
+use crate::watch::WatchFolders;
+use crate::webhook::Webhook;
+
+/// How long a transfer peer's last address is kept for reconnecting
+const PEER_ADDR_TTL_SECS: u64 = 30 * 24 * 60 * 60;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TransferInfo {
@@ -18,6 +52,40 @@ pub struct TransferInfo {
     pub direction: TransferDirection,
     #[serde(default)]
     pub speed_bps: u64, // bytes per second
+    /// Remote node, when known (always for receives)
+    #[serde(default)]
+    pub peer_node_id: Option<String>,
+    /// Unix seconds when the transfer was created
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    /// Unix seconds when the transfer completed, failed or was cancelled
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    /// Hash of the blob the ticket points to
+    #[serde(default)]
+    pub ticket_hash: Option<String>,
+    /// User's note, set with `set_transfer_note`
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Kept by `clear_history` and retention, set with `set_transfer_pinned`
+    #[serde(default)]
+    pub pinned: bool,
+    /// Results of post-receive actions run on the file
+    #[serde(default)]
+    pub actions: Vec<ActionResult>,
+    /// Where a received file was saved on disk (not for SAF folders or the gallery)
+    #[serde(default)]
+    pub saved_path: Option<String>,
+}
+
+impl TransferInfo {
+    /// Still pending or running
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.status,
+            TransferStatus::Pending | TransferStatus::InProgress
+        )
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -28,6 +96,8 @@ pub enum TransferStatus {
     Completed,
     Failed,
     Cancelled,
+    /// Still running when the app last exited
+    Interrupted,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -37,33 +107,346 @@ pub enum TransferDirection {
     Receive,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PeerInfo {
     pub node_id: String,
     pub device_name: String,
     pub last_seen: u64,
+    pub color: String,
+    pub avatar: Option<u8>,
+    #[serde(default)]
+    pub trust_level: TrustLevel,
+    #[serde(default)]
+    pub capabilities: PeerCapabilities,
+    /// Groups this peer has been seen in (`default` for the primary room)
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Rolling average round-trip time from `ping_peer`
+    #[serde(default)]
+    pub latency_ms: Option<u32>,
+    /// How the endpoint currently reaches this peer
+    #[serde(default)]
+    pub connection_type: PeerConnectionType,
+    /// Local nickname overriding the announced device name in the UI
+    #[serde(default)]
+    pub alias: Option<String>,
+    /// Announced OS (android, ios, macos, windows, linux); empty if unknown
+    #[serde(default)]
+    pub platform: String,
+    #[serde(default)]
+    pub app_version: String,
+    /// True when the peer runs a different major/minor app version
+    #[serde(default)]
+    pub version_mismatch: bool,
+    #[serde(default)]
+    pub favorite: bool,
+    /// Short emoji sequence derived from the node ID, for comparing out loud
+    #[serde(default)]
+    pub fingerprint: String,
+    /// Trusted peer whose fingerprint the user has confirmed
+    #[serde(default)]
+    pub verified: bool,
+    /// Favorite that isn't currently discovered, listed from settings
+    #[serde(default)]
+    pub offline: bool,
+}
+
+/// Announced protocol level and features, used to degrade gracefully
+/// against older peers
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PeerCapabilities {
+    /// 0 for peers that predate capability advertisement
+    pub protocol_version: u32,
+    pub features: Vec<Feature>,
+    /// Largest file the peer will accept, if limited
+    pub max_file_size: Option<u64>,
+}
+
+impl PeerCapabilities {
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// Whether the peer will accept a file of the given size
+    pub fn accepts_size(&self, size: u64) -> bool {
+        self.max_file_size.is_none_or(|max| size <= max)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    EncryptedTickets,
+    Collections,
+    Compression,
+    PushOffers,
+    /// Accepts the `vegam/control/0` ALPN for transfer signalling
+    ControlChannel,
+    /// Features announced by newer peers that we don't know about
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustLevel {
+    #[default]
+    Unknown,
+    Trusted,
 }
 
 pub struct AppState {
     pub iroh: Arc<RwLock<Option<Iroh>>>,
     #[cfg(debug_assertions)]
     pub iroh_debug: Arc<RwLock<Option<Iroh>>>,
-    // Keep tags alive to prevent MemStore GC of blobs during transfer
-    pub blob_tags: Arc<RwLock<HashMap<Hash, Arc<TagInfo>>>>,
+    // Last time (unix seconds) each blob was requested, not yet written to history
+    pub blobs_served: Arc<RwLock<HashMap<Hash, u64>>>,
+    // Running transfers; finished ones live in `history`
     pub transfers: Arc<RwLock<HashMap<String, TransferInfo>>>,
+    history: Arc<RwLock<Option<HistoryStore>>>,
     pub peers: Arc<RwLock<HashMap<String, PeerInfo>>>,
+    pub settings: Arc<RwLock<Settings>>,
+    settings_path: Arc<RwLock<Option<PathBuf>>>,
+    // Shared with the blob provider to refuse connections from blocked peers
+    pub access_policy: Arc<RwLock<AccessPolicy>>,
+    // Wakes discovery tasks to broadcast an announcement immediately
+    pub announce_now: Arc<Notify>,
+    // Discovery tasks for the main node, one per subscribed topic
+    discovery_tasks: Arc<RwLock<HashMap<TopicId, JoinHandle<()>>>>,
+    // Other background tasks bound to the current node, stopped on restart
+    node_tasks: Arc<RwLock<Vec<JoinHandle<()>>>>,
+    // BLE proximity scanner, when running
+    pub ble_task: Arc<RwLock<Option<JoinHandle<()>>>>,
+    // Pairing code currently on display, if any
+    pub pairing: Arc<RwLock<Option<PendingPairing>>>,
+    // Pairing PIN currently on display, if any
+    pub pin_pairing: Arc<RwLock<Option<PendingPin>>>,
+    // Append-only record of connections and transfers, when enabled
+    pub audit: AuditLog,
+    // Session with a throwaway identity that persists nothing
+    pub ephemeral: Arc<RwLock<bool>>,
+    // App lock state; only consulted while a passcode is set
+    pub app_lock: Arc<RwLock<LockState>>,
+    // Browser download links and their HTTP server
+    pub http_share: HttpShare,
+    // Token-protected HTTP API on localhost, while enabled
+    pub local_api: LocalApi,
+    // Client for webhook calls
+    pub webhook: Webhook,
+    // Fan-out sends and where each recipient is
+    pub transfer_groups: TransferGroups,
+    // Watchers of the enabled watch folders
+    pub watch_folders: WatchFolders,
+    // Last clipboard content seen or synced in
+    pub clipboard: ClipboardSync,
+    // Metered connection state and downloads deferred until it clears
+    pub metered: MeteredGate,
+    // Doze and related restrictions, and transfers waiting for them to lift
+    pub doze: DozeGate,
+    // Global upload and download caps shared by all transfers
+    pub bandwidth: Bandwidth,
+    // Battery status and whether low-power mode is on
+    pub power: PowerState,
+    // Blob connections open to peers downloading from us
+    pub serving: Arc<AtomicUsize>,
+    // Files dropped on the window and where new drops go
+    pub drop_zone: DropZone,
 }
 
 impl AppState {
     pub fn new() -> Self {
+        let history = Arc::new(RwLock::new(None));
         Self {
             iroh: Arc::new(RwLock::new(None)),
             #[cfg(debug_assertions)]
             iroh_debug: Arc::new(RwLock::new(None)),
-            blob_tags: Arc::new(RwLock::new(HashMap::new())),
+            blobs_served: Arc::new(RwLock::new(HashMap::new())),
             transfers: Arc::new(RwLock::new(HashMap::new())),
+            audit: AuditLog::new(history.clone()),
+            history,
             peers: Arc::new(RwLock::new(HashMap::new())),
+            settings: Arc::new(RwLock::new(Settings::default())),
+            settings_path: Arc::new(RwLock::new(None)),
+            access_policy: Arc::new(RwLock::new(AccessPolicy::default())),
+            announce_now: Arc::new(Notify::new()),
+            discovery_tasks: Arc::new(RwLock::new(HashMap::new())),
+            node_tasks: Arc::new(RwLock::new(Vec::new())),
+            ble_task: Arc::new(RwLock::new(None)),
+            pairing: Arc::new(RwLock::new(None)),
+            pin_pairing: Arc::new(RwLock::new(None)),
+            ephemeral: Arc::new(RwLock::new(false)),
+            app_lock: Arc::new(RwLock::new(LockState::new(false))),
+            http_share: HttpShare::new(),
+            local_api: LocalApi::new(),
+            webhook: Webhook::new(),
+            transfer_groups: TransferGroups::new(),
+            watch_folders: WatchFolders::new(),
+            clipboard: ClipboardSync::new(),
+            metered: MeteredGate::new(),
+            doze: DozeGate::new(),
+            bandwidth: Bandwidth::new(),
+            power: PowerState::new(),
+            serving: Arc::new(AtomicUsize::new(0)),
+            drop_zone: DropZone::new(),
+        }
+    }
+
+    /// Load persisted settings and remember where to save future changes
+    pub async fn load_settings(&self, path: PathBuf) {
+        let loaded = Settings::load(&path).await;
+        self.audit.set_enabled(loaded.audit_log);
+        self.bandwidth.apply(loaded.bandwidth);
+        // With a passcode set, every launch starts locked
+        *self.app_lock.write().await = LockState::new(loaded.app_lock.is_some());
+        *self.settings.write().await = loaded;
+        *self.settings_path.write().await = Some(path);
+        self.sync_access_policy().await;
+    }
+
+    /// Stop persisting anything for the rest of the session
+    ///
+    /// Settings changes stay in memory, history is neither read nor written and
+    /// the device announces itself under a random name. The audit log is switched
+    /// off and can't be turned back on. Must run before the node starts, which
+    /// then gets a throwaway key and an in-memory blob store.
+    pub async fn enter_ephemeral_mode(&self) {
+        *self.settings_path.write().await = None;
+        *self.history.write().await = None;
+        self.audit.set_enabled(false);
+        self.audit.clear_path().await;
+        {
+            let mut settings = self.settings.write().await;
+            settings.device_name = Some(format!(
+                "Guest {:04}",
+                rand::Rng::gen_range(&mut rand::thread_rng(), 0..10_000)
+            ));
+            settings.color = None;
+            settings.avatar = None;
+            // Nothing may link the throwaway identity to the real one
+            settings.identity_transition = None;
+        }
+        *self.ephemeral.write().await = true;
+    }
+
+    pub async fn is_ephemeral(&self) -> bool {
+        *self.ephemeral.read().await
+    }
+
+    /// Whether the app is locked, applying the auto-lock timeout first
+    pub async fn is_locked(&self) -> bool {
+        let auto_lock = match &self.settings.read().await.app_lock {
+            Some(lock) => lock.auto_lock(),
+            None => return false,
+        };
+        self.app_lock.write().await.check(auto_lock, Instant::now())
+    }
+
+    /// Gate for sensitive commands: fails with `LOCKED` while locked, otherwise
+    /// counts as activity for the auto-lock
+    pub async fn require_unlocked(&self) -> Result<(), String> {
+        if self.is_locked().await {
+            return Err(LOCKED.to_string());
+        }
+        self.app_lock.write().await.touch(Instant::now());
+        Ok(())
+    }
+
+    /// Rebuild the provider access policy from persisted settings (blocklist,
+    /// trust store, trusted-only mode)
+    pub async fn sync_access_policy(&self) {
+        let settings = self.settings.read().await;
+        *self.access_policy.write().await = AccessPolicy::from_settings(&settings);
+    }
+
+    pub async fn get_settings(&self) -> Settings {
+        self.settings.read().await.clone()
+    }
+
+    /// Apply a change to settings and persist it
+    pub async fn update_settings<F>(&self, f: F) -> Result<Settings>
+    where
+        F: FnOnce(&mut Settings),
+    {
+        let mut settings = self.settings.write().await;
+        f(&mut settings);
+        if let Some(path) = self.settings_path.read().await.as_ref() {
+            settings.save(path).await?;
+        }
+        Ok(settings.clone())
+    }
+
+    /// Persist a peer as trusted and refresh the live peer list
+    pub async fn trust_peer(&self, node_id: String, device_name: String) -> Result<TrustedPeer> {
+        // Re-trusting (e.g. pairing again) keeps an earlier verification
+        let verified = self
+            .settings
+            .read()
+            .await
+            .trusted_peers
+            .get(&node_id)
+            .is_some_and(|t| t.verified);
+        let trusted = TrustedPeer {
+            node_id: node_id.clone(),
+            device_name,
+            trusted_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            verified,
+        };
+
+        let entry = trusted.clone();
+        self.update_settings(|s| {
+            s.trusted_peers.insert(node_id, entry);
+        })
+        .await?;
+        self.refresh_peers_from_settings().await;
+
+        Ok(trusted)
+    }
+
+    /// Move trust (and alias) from a peer's old node ID to the one it rotated to
+    ///
+    /// Only an old ID we trust and haven't blocked is migrated. The new key has a
+    /// different fingerprint, so the peer needs verifying again.
+    pub async fn migrate_trust(
+        &self,
+        transition: &IdentityTransition,
+    ) -> Result<Option<TrustedPeer>> {
+        {
+            let settings = self.settings.read().await;
+            if !settings.trusted_peers.contains_key(&transition.old_node_id)
+                || settings.trusted_peers.contains_key(&transition.new_node_id)
+                || settings.blocked_peers.contains(&transition.old_node_id)
+            {
+                return Ok(None);
+            }
         }
+
+        let mut migrated = None;
+        self.update_settings(|s| {
+            let old = &transition.old_node_id;
+            let new = &transition.new_node_id;
+            if let Some(mut trusted) = s.trusted_peers.remove(old) {
+                trusted.node_id = new.clone();
+                trusted.verified = false;
+                s.trusted_peers.insert(new.clone(), trusted.clone());
+                migrated = Some(trusted);
+            }
+            if let Some(alias) = s.peer_aliases.remove(old) {
+                s.peer_aliases.insert(new.clone(), alias);
+            }
+        })
+        .await?;
+        self.refresh_peers_from_settings().await;
+
+        Ok(migrated)
+    }
+
+    /// Ask running discovery tasks to re-announce right away
+    pub fn trigger_announcement(&self) {
+        self.announce_now.notify_waiters();
     }
 
     pub async fn set_iroh(&self, iroh: Iroh) {
@@ -77,12 +460,73 @@ impl AppState {
         *i = Some(iroh);
     }
 
+    /// Track a topic's discovery task, stopping any it replaces
+    pub async fn set_discovery_task(&self, topic_id: TopicId, task: JoinHandle<()>) {
+        let mut tasks = self.discovery_tasks.write().await;
+        if let Some(old) = tasks.insert(topic_id, task) {
+            old.abort();
+        }
+    }
+
+    pub async fn stop_discovery_task(&self, topic_id: &TopicId) {
+        if let Some(task) = self.discovery_tasks.write().await.remove(topic_id) {
+            task.abort();
+        }
+    }
+
+    pub async fn add_node_task(&self, task: JoinHandle<()>) {
+        self.node_tasks.write().await.push(task);
+    }
+
+    /// Stop discovery and every other task bound to the current node
+    pub async fn stop_node_tasks(&self) {
+        for (_, task) in self.discovery_tasks.write().await.drain() {
+            task.abort();
+        }
+        for task in self.node_tasks.write().await.drain(..) {
+            task.abort();
+        }
+    }
+
+    /// Peers seen in a group
+    pub async fn get_group_peers(&self, group_id: &str) -> Vec<PeerInfo> {
+        let peers = self.peers.read().await;
+        peers
+            .values()
+            .filter(|p| p.groups.iter().any(|g| g == group_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Forget group membership after leaving it, returning peers that are
+    /// no longer in any group
+    pub async fn remove_group_membership(&self, group_id: &str) -> Vec<String> {
+        let mut peers = self.peers.write().await;
+        for peer in peers.values_mut() {
+            peer.groups.retain(|g| g != group_id);
+        }
+        let orphaned: Vec<String> = peers
+            .values()
+            .filter(|p| p.groups.is_empty())
+            .map(|p| p.node_id.clone())
+            .collect();
+        for node_id in &orphaned {
+            peers.remove(node_id);
+        }
+        orphaned
+    }
+
     pub async fn get_iroh(&self) -> Result<Iroh> {
         let iroh = self.iroh.read().await;
         iroh.clone()
             .ok_or_else(|| anyhow::anyhow!("Iroh node not initialized"))
     }
 
+    /// Remove the node from state, e.g. to shut it down
+    pub async fn take_iroh(&self) -> Option<Iroh> {
+        self.iroh.write().await.take()
+    }
+
     #[cfg(debug_assertions)]
     pub async fn get_iroh_debug(&self) -> Result<Iroh> {
         let iroh = self.iroh_debug.read().await;
@@ -90,26 +534,63 @@ impl AppState {
             .ok_or_else(|| anyhow::anyhow!("Iroh debug node not initialized"))
     }
 
-    /// Store tag to keep blob alive in MemStore
-    pub async fn add_blob_tag(&self, hash: Hash, tag: Arc<TagInfo>) {
-        let mut tags = self.blob_tags.write().await;
-        tags.insert(hash, tag);
+    /// Open the persistent transfer history
+    pub async fn open_history(&self, path: PathBuf) {
+        match HistoryStore::open(&path) {
+            Ok(store) => *self.history.write().await = Some(store),
+            Err(e) => warn!("Failed to open transfer history {:?}: {}", path, e),
+        }
+    }
+
+    async fn history(&self) -> Option<HistoryStore> {
+        self.history.read().await.clone()
     }
 
-    /// Remove tag to allow MemStore GC of blob
-    #[allow(dead_code)]
-    pub async fn remove_blob_tag(&self, hash: &Hash) {
-        let mut tags = self.blob_tags.write().await;
-        tags.remove(hash);
+    async fn require_history(&self) -> Result<HistoryStore> {
+        self.history()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Transfer history is not available"))
     }
 
-    pub async fn add_transfer(&self, transfer: TransferInfo) {
+    /// Record a transfer update, persisting it to history
+    pub async fn add_transfer(&self, mut transfer: TransferInfo) {
+        if !transfer.is_active() && transfer.finished_at.is_none() {
+            transfer.finished_at = Some(now_secs());
+        }
+        // Notes and pins are set separately; don't drop ones added while running
+        if let Some(cached) = self.transfers.read().await.get(&transfer.id) {
+            if transfer.note.is_none() {
+                transfer.note = cached.note.clone();
+            }
+            transfer.pinned |= cached.pinned;
+        }
+
+        let persisted = match self.history().await {
+            Some(history) => match history.upsert(transfer.clone()).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Failed to record transfer {}: {}", transfer.id, e);
+                    false
+                }
+            },
+            None => false,
+        };
+
+        if !transfer.is_active() {
+            self.audit.transfer_finished(&transfer).await;
+            let webhook = self.settings.read().await.webhook.clone();
+            self.webhook.transfer_finished(&webhook, &transfer);
+        }
+
+        // Only running transfers stay cached; finished ones are read from history
         let mut transfers = self.transfers.write().await;
-        transfers.insert(transfer.id.clone(), transfer);
+        if persisted && !transfer.is_active() {
+            transfers.remove(&transfer.id);
+        } else {
+            transfers.insert(transfer.id.clone(), transfer);
+        }
     }
 
-    // Reserved for future transfer progress tracking
-    #[allow(dead_code)]
     pub async fn update_transfer_progress(&self, id: &str, bytes_transferred: u64) {
         let mut transfers = self.transfers.write().await;
         if let Some(transfer) = transfers.get_mut(id) {
@@ -136,13 +617,515 @@ impl AppState {
     }
 
     pub async fn get_transfer(&self, id: &str) -> Option<TransferInfo> {
-        let transfers = self.transfers.read().await;
-        transfers.get(id).cloned()
+        if let Some(transfer) = self.transfers.read().await.get(id).cloned() {
+            return Some(transfer);
+        }
+        let history = self.history().await?;
+        history.get(id.to_string()).await.unwrap_or_else(|e| {
+            warn!("Failed to read transfer history: {}", e);
+            None
+        })
+    }
+
+    /// Transfers exchanged with a given peer, most recent first
+    pub async fn get_peer_transfers(&self, node_id: &str) -> Vec<TransferInfo> {
+        let node_id = node_id.to_string();
+        let stored = match self.history().await {
+            Some(history) => history.for_peer(node_id.clone()).await,
+            None => Ok(Vec::new()),
+        };
+        let active: Vec<TransferInfo> = self
+            .transfers
+            .read()
+            .await
+            .values()
+            .filter(|t| t.peer_node_id.as_deref() == Some(node_id.as_str()))
+            .cloned()
+            .collect();
+        merge_active(stored, active)
+    }
+
+    /// Delete finished transfers created before `before` (unix seconds), or all of them
+    pub async fn clear_history(&self, before: Option<u64>) -> Result<usize> {
+        let history = self.require_history().await?;
+        history.clear(before).await
+    }
+
+    /// Filtered, paginated view of all transfers
+    pub async fn list_transfers(
+        &self,
+        filter: TransferFilter,
+        page: u32,
+        page_size: u32,
+    ) -> Result<TransferPage> {
+        let history = self.require_history().await?;
+        let mut result = history.list(filter, page, page_size).await?;
+
+        // Stored rows lag behind running transfers' progress
+        let active = self.transfers.read().await;
+        for transfer in result.transfers.iter_mut() {
+            if let Some(current) = active.get(&transfer.id) {
+                *transfer = current.clone();
+            }
+        }
+        Ok(result)
+    }
+
+    /// Journal a received file about to be written to `temp_path`
+    pub async fn begin_file_write(
+        &self,
+        transfer_id: &str,
+        temp_path: &Path,
+        final_path: &Path,
+    ) -> Result<()> {
+        self.require_history()
+            .await?
+            .journal_begin(JournalEntry {
+                transfer_id: transfer_id.to_string(),
+                temp_path: temp_path.to_string_lossy().into_owned(),
+                final_path: final_path.to_string_lossy().into_owned(),
+                written: false,
+            })
+            .await
+    }
+
+    /// Move a complete, synced temporary file into place
+    pub async fn commit_file_write(
+        &self,
+        transfer_id: &str,
+        temp_path: &Path,
+        final_path: &Path,
+    ) -> Result<()> {
+        // Without history (e.g. ephemeral mode) there's no journal to update
+        if let Some(history) = self.history().await {
+            history.journal_written(transfer_id.to_string()).await?;
+        }
+        tokio::fs::rename(temp_path, final_path).await?;
+        Ok(())
+    }
+
+    /// Drop the journal entry once history has the transfer's final state
+    pub async fn end_file_write(&self, transfer_id: &str) {
+        if let Some(history) = self.history().await {
+            if let Err(e) = history.journal_end(transfer_id.to_string()).await {
+                warn!("Failed to clear journal entry for {}: {}", transfer_id, e);
+            }
+        }
+    }
+
+    /// Remember how a receive was started, so it can be restarted if the
+    /// process dies before it ends
+    pub async fn remember_receive(&self, receive: ResumableReceive) -> Result<()> {
+        self.require_history().await?.resumable_put(receive).await
     }
 
+    pub async fn record_message(&self, message: ChatMessage) -> Result<()> {
+        self.require_history().await?.save_message(message).await
+    }
+
+    /// Messages with a peer before `before` (unix seconds), oldest first
+    pub async fn messages(
+        &self,
+        node_id: &str,
+        before: Option<u64>,
+        limit: u32,
+    ) -> Result<Vec<ChatMessage>> {
+        self.require_history()
+            .await?
+            .messages(node_id.to_string(), before, limit)
+            .await
+    }
+
+    pub async fn clear_messages(&self, node_id: &str) -> Result<usize> {
+        self.require_history()
+            .await?
+            .clear_messages(node_id.to_string())
+            .await
+    }
+
+    /// The receive ended, one way or another
+    pub async fn forget_receive(&self, transfer_id: &str) {
+        if let Some(history) = self.history().await {
+            if let Err(e) = history.resumable_remove(transfer_id.to_string()).await {
+                warn!("Failed to forget receive {}: {}", transfer_id, e);
+            }
+        }
+    }
+
+    /// Receives cut off by an exit or a killed process, with what's needed
+    /// to start them again; records of ones that ended otherwise are dropped
+    pub async fn resumable_receives(&self) -> Result<Vec<(TransferInfo, ResumableReceive)>> {
+        let history = self.require_history().await?;
+        let mut resumable = Vec::new();
+        for receive in history.resumable_receives().await? {
+            if self
+                .transfers
+                .read()
+                .await
+                .contains_key(&receive.transfer_id)
+            {
+                // Still running, or already restarted
+                continue;
+            }
+            match history.get(receive.transfer_id.clone()).await? {
+                Some(transfer) if transfer.status == TransferStatus::Interrupted => {
+                    resumable.push((transfer, receive));
+                }
+                _ => history.resumable_remove(receive.transfer_id).await?,
+            }
+        }
+        Ok(resumable)
+    }
+
+    /// Repair state left behind by a previous session that was killed
+    ///
+    /// Journaled file writes are finished or rolled back first, then any
+    /// transfer still marked as running is interrupted. Returns the updated
+    /// transfers so they can be re-emitted to the frontend.
+    pub async fn recover_transfers(&self) -> Result<Vec<TransferInfo>> {
+        let mut recovered = self.replay_file_journal().await?;
+        recovered.extend(self.interrupt_stale_transfers().await?);
+        Ok(recovered)
+    }
+
+    async fn replay_file_journal(&self) -> Result<Vec<TransferInfo>> {
+        let history = self.require_history().await?;
+        let active = self
+            .transfers
+            .read()
+            .await
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut completed = Vec::new();
+        for entry in history.journal_entries().await? {
+            if active.contains(&entry.transfer_id) {
+                continue;
+            }
+            let temp_path = Path::new(&entry.temp_path);
+            let final_path = Path::new(&entry.final_path);
+            let temp_exists = tokio::fs::try_exists(temp_path).await.unwrap_or(false);
+
+            // Only a fully written file may be moved into place; anything
+            // else is a partial download and is removed
+            let in_place = if entry.written && temp_exists {
+                tokio::fs::rename(temp_path, final_path).await.is_ok()
+            } else {
+                if temp_exists {
+                    if let Err(e) = tokio::fs::remove_file(temp_path).await {
+                        warn!("Failed to remove partial file {:?}: {}", temp_path, e);
+                    }
+                }
+                entry.written
+            };
+
+            if in_place {
+                if let Some(mut transfer) = history.get(entry.transfer_id.clone()).await? {
+                    if transfer.status != TransferStatus::Completed {
+                        if let Ok(metadata) = tokio::fs::metadata(final_path).await {
+                            transfer.file_size = metadata.len();
+                            transfer.bytes_transferred = metadata.len();
+                        }
+                        transfer.status = TransferStatus::Completed;
+                        transfer.error = None;
+                        self.add_transfer(transfer.clone()).await;
+                        completed.push(transfer);
+                    }
+                }
+            }
+            history.journal_end(entry.transfer_id).await?;
+        }
+        Ok(completed)
+    }
+
+    /// Mark transfers left running by a previous session as interrupted
+    async fn interrupt_stale_transfers(&self) -> Result<Vec<TransferInfo>> {
+        let history = self.require_history().await?;
+        let stale: Vec<_> = {
+            let active = self.transfers.read().await;
+            history
+                .unfinished()
+                .await?
+                .into_iter()
+                .filter(|t| !active.contains_key(&t.id))
+                .collect()
+        };
+
+        let mut interrupted = Vec::with_capacity(stale.len());
+        for mut transfer in stale {
+            transfer.status = TransferStatus::Interrupted;
+            transfer.error = Some("Interrupted when the app closed".to_string());
+            self.add_transfer(transfer.clone()).await;
+            interrupted.push(transfer);
+        }
+        Ok(interrupted)
+    }
+
+    /// Record transfers still running as interrupted, for when the app exits
+    pub async fn interrupt_running_transfers(&self) {
+        let running: Vec<_> = self
+            .transfers
+            .read()
+            .await
+            .values()
+            .filter(|t| t.is_active())
+            .cloned()
+            .collect();
+        for mut transfer in running {
+            transfer.status = TransferStatus::Interrupted;
+            transfer.error = Some("Interrupted when the app closed".to_string());
+            self.add_transfer(transfer).await;
+        }
+    }
+
+    /// Pin a finished transfer so cleanup keeps it, along with its shared blob
+    ///
+    /// Returns false if the transfer isn't in history.
+    pub async fn set_transfer_pinned(&self, id: &str, pinned: bool) -> Result<bool> {
+        let history = self.require_history().await?;
+        if !history.set_pinned(id.to_string(), pinned).await? {
+            return Ok(false);
+        }
+
+        // Keep the blob too, so the file can still be reshared
+        let transfer = history.get(id.to_string()).await?;
+        if let Some(hash) = transfer.and_then(|t| t.ticket_hash) {
+            history.set_blob_pinned(hash, pinned).await?;
+        }
+
+        if let Some(transfer) = self.transfers.write().await.get_mut(id) {
+            transfer.pinned = pinned;
+        }
+        Ok(true)
+    }
+
+    /// Set or clear a transfer's note; returns false if the transfer is unknown
+    pub async fn set_transfer_note(&self, id: &str, note: Option<String>) -> Result<bool> {
+        let stored = self
+            .require_history()
+            .await?
+            .set_note(id.to_string(), note.clone())
+            .await?;
+
+        let mut transfers = self.transfers.write().await;
+        let cached = match transfers.get_mut(id) {
+            Some(transfer) => {
+                transfer.note = note;
+                true
+            }
+            None => false,
+        };
+        Ok(stored || cached)
+    }
+
+    /// Attach post-receive action results to a finished transfer
+    pub async fn set_transfer_actions(&self, id: &str, actions: Vec<ActionResult>) -> Result<bool> {
+        self.require_history()
+            .await?
+            .set_actions(id.to_string(), actions)
+            .await
+    }
+
+    /// Record where a received file now is, after a post-receive move
+    pub async fn set_transfer_saved_path(&self, id: &str, path: &str) -> Result<bool> {
+        let stored = match self.history().await {
+            Some(history) => {
+                history
+                    .set_saved_path(id.to_string(), path.to_string())
+                    .await?
+            }
+            None => false,
+        };
+        let mut transfers = self.transfers.write().await;
+        let cached = match transfers.get_mut(id) {
+            Some(transfer) => {
+                transfer.saved_path = Some(path.to_string());
+                true
+            }
+            None => false,
+        };
+        Ok(stored || cached)
+    }
+
+    /// Remove a transfer for good, scrubbing it from the history database
+    pub async fn purge_transfer(&self, id: &str) -> Result<bool> {
+        let cached = self.transfers.write().await.remove(id).is_some();
+        let stored = match self.history().await {
+            Some(history) => history.purge(id.to_string()).await?,
+            None => false,
+        };
+        Ok(stored || cached)
+    }
+
+    /// Ranked search over history by file name, note and peer name
+    pub async fn search_transfers(&self, query: String, limit: u32) -> Result<Vec<TransferInfo>> {
+        let mut peer_names = HashMap::new();
+        {
+            let settings = self.settings.read().await;
+            for (id, peer) in &settings.trusted_peers {
+                peer_names.insert(id.clone(), peer.device_name.clone());
+            }
+            for (id, peer) in &settings.favorite_peers {
+                peer_names.insert(id.clone(), peer.device_name.clone());
+            }
+            for (id, peer) in self.peers.read().await.iter() {
+                peer_names.insert(id.clone(), peer.device_name.clone());
+            }
+            // Aliases are what the user sees, so they win
+            for (id, alias) in &settings.peer_aliases {
+                peer_names.insert(id.clone(), alias.clone());
+            }
+        }
+
+        let mut results = self
+            .require_history()
+            .await?
+            .search(query, peer_names, limit)
+            .await?;
+
+        // Stored rows lag behind running transfers' progress
+        let active = self.transfers.read().await;
+        for transfer in results.iter_mut() {
+            if let Some(current) = active.get(&transfer.id) {
+                *transfer = current.clone();
+            }
+        }
+        Ok(results)
+    }
+
+    /// Track a shared blob so it can be garbage collected later
+    pub async fn record_blob(&self, blob: BlobRecord) -> Result<()> {
+        self.require_history().await?.record_blob(blob).await
+    }
+
+    pub async fn list_blobs(&self) -> Result<Vec<BlobRecord>> {
+        self.flush_blobs_served().await?;
+        self.require_history().await?.list_blobs().await
+    }
+
+    pub async fn get_blob(&self, hash: &str) -> Result<Option<BlobRecord>> {
+        self.require_history()
+            .await?
+            .get_blob(hash.to_string())
+            .await
+    }
+
+    /// Returns false if the blob isn't tracked
+    pub async fn pin_blob(&self, hash: &str, pinned: bool) -> Result<bool> {
+        self.require_history()
+            .await?
+            .set_blob_pinned(hash.to_string(), pinned)
+            .await
+    }
+
+    pub async fn forget_blob(&self, hash: &str) -> Result<()> {
+        self.require_history()
+            .await?
+            .delete_blob(hash.to_string())
+            .await
+    }
+
+    pub async fn idle_blobs(&self, cutoff: u64) -> Result<Vec<BlobRecord>> {
+        self.require_history().await?.idle_blobs(cutoff).await
+    }
+
+    pub async fn evictable_blobs(&self) -> Result<Vec<BlobRecord>> {
+        self.require_history().await?.evictable_blobs().await
+    }
+
+    pub async fn blobs_size(&self) -> Result<u64> {
+        self.require_history().await?.blobs_size().await
+    }
+
+    /// Write buffered download times to history
+    pub async fn flush_blobs_served(&self) -> Result<()> {
+        let served: Vec<_> = self
+            .blobs_served
+            .write()
+            .await
+            .drain()
+            .map(|(hash, at)| (hash.to_string(), at))
+            .collect();
+        if served.is_empty() {
+            return Ok(());
+        }
+        self.require_history()
+            .await?
+            .mark_blobs_served(served)
+            .await
+    }
+
+    /// Remember how a transfer peer was reached, for dialing it next session
+    pub async fn remember_peer_addr(&self, addr: &EndpointAddr) {
+        let Some(history) = self.history().await else {
+            return;
+        };
+        let saved = match serde_json::to_string(addr) {
+            Ok(json) => history.save_peer_addr(addr.id.to_string(), json).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = saved {
+            warn!("Failed to save address of {}: {}", addr.id, e);
+        }
+    }
+
+    /// Last known addresses of recent transfer peers
+    pub async fn recent_peer_addrs(&self) -> Result<Vec<EndpointAddr>> {
+        let Some(history) = self.history().await else {
+            return Ok(Vec::new());
+        };
+        let cutoff = now_secs().saturating_sub(PEER_ADDR_TTL_SECS);
+        Ok(history
+            .peer_addrs(cutoff)
+            .await?
+            .iter()
+            .filter_map(|json| serde_json::from_str(json).ok())
+            .collect())
+    }
+
+    pub async fn enforce_history_retention(&self, retention: HistoryRetention) -> Result<usize> {
+        match self.history().await {
+            Some(history) => history.enforce(retention).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Most recent transfers, including ones from previous sessions
+    pub async fn get_transfer_history(&self, limit: u32) -> Vec<TransferInfo> {
+        let stored = match self.history().await {
+            Some(history) => history.recent(limit).await,
+            None => Ok(Vec::new()),
+        };
+        let active: Vec<TransferInfo> = self.transfers.read().await.values().cloned().collect();
+        let mut merged = merge_active(stored, active);
+        merged.truncate(limit as usize);
+        merged
+    }
+
+    /// Discovered peers plus offline favorites, favorites first
     pub async fn get_peers(&self) -> Vec<PeerInfo> {
+        let settings = self.settings.read().await;
         let peers = self.peers.read().await;
-        peers.values().cloned().collect()
+        let mut list: Vec<PeerInfo> = peers.values().cloned().collect();
+
+        for favorite in settings.favorite_peers.values() {
+            if peers.contains_key(&favorite.node_id) {
+                continue;
+            }
+            let mut peer = PeerInfo {
+                node_id: favorite.node_id.clone(),
+                device_name: favorite.device_name.clone(),
+                color: favorite.color.clone(),
+                offline: true,
+                ..Default::default()
+            };
+            settings.apply_to_peer(&mut peer);
+            list.push(peer);
+        }
+
+        list.sort_by_key(|p| !p.favorite);
+        list
     }
 
     pub async fn add_peer(&self, peer: PeerInfo) {
@@ -150,14 +1133,130 @@ impl AppState {
         peers.insert(peer.node_id.clone(), peer);
     }
 
+    /// Trust level for a peer, based on the persisted trust store
+    pub async fn trust_level(&self, node_id: &str) -> TrustLevel {
+        if self.settings.read().await.trusted_peers.contains_key(node_id) {
+            TrustLevel::Trusted
+        } else {
+            TrustLevel::Unknown
+        }
+    }
+
+    pub async fn is_blocked(&self, node_id: &str) -> bool {
+        self.settings.read().await.blocked_peers.contains(node_id)
+    }
+
+    pub async fn is_trusted(&self, node_id: &str) -> bool {
+        self.trust_level(node_id).await == TrustLevel::Trusted
+    }
+
+    /// Re-apply local per-peer settings (trust, alias) after they change
+    pub async fn refresh_peers_from_settings(&self) {
+        {
+            let settings = self.settings.read().await;
+            let mut peers = self.peers.write().await;
+            for peer in peers.values_mut() {
+                settings.apply_to_peer(peer);
+            }
+        }
+        // Trust changes affect who the provider serves
+        self.sync_access_policy().await;
+    }
+
+    /// Fold a new ping sample into the peer's rolling average latency
+    pub async fn record_peer_latency(&self, node_id: &str, sample_ms: u32) -> Option<u32> {
+        let mut peers = self.peers.write().await;
+        let peer = peers.get_mut(node_id)?;
+        let average = match peer.latency_ms {
+            // Exponential moving average weighting recent samples at 1/4
+            Some(prev) => (prev * 3 + sample_ms) / 4,
+            None => sample_ms,
+        };
+        peer.latency_ms = Some(average);
+        Some(average)
+    }
+
+    /// Mark a known peer as seen now, e.g. on transfer or connection activity
+    pub async fn touch_peer(&self, node_id: &str) {
+        touch_peer(&self.peers, node_id).await;
+    }
+
+    /// Bytes received so far by downloads that are still running
+    pub async fn partial_download_bytes(&self) -> u64 {
+        let transfers = self.transfers.read().await;
+        transfers
+            .values()
+            .filter(|t| matches!(t.direction, TransferDirection::Receive) && t.is_active())
+            .map(|t| t.bytes_transferred)
+            .sum()
+    }
+
+    /// Whether a transfer with this peer is still pending or running
+    pub async fn has_active_transfer(&self, node_id: &str) -> bool {
+        let transfers = self.transfers.read().await;
+        transfers
+            .values()
+            .any(|t| t.peer_node_id.as_deref() == Some(node_id) && t.is_active())
+    }
+
     pub async fn remove_peer(&self, node_id: &str) {
         let mut peers = self.peers.write().await;
         peers.remove(node_id);
     }
 }
 
+/// Prefer the cached copy of running transfers (fresher progress) over the
+/// stored one, keeping active transfers first
+fn merge_active(stored: Result<Vec<TransferInfo>>, active: Vec<TransferInfo>) -> Vec<TransferInfo> {
+    let stored = stored.unwrap_or_else(|e| {
+        warn!("Failed to read transfer history: {}", e);
+        Vec::new()
+    });
+    let active_ids: std::collections::HashSet<String> =
+        active.iter().map(|t| t.id.clone()).collect();
+    let mut merged = active;
+    merged.extend(stored.into_iter().filter(|t| !active_ids.contains(&t.id)));
+    merged
+}
+
+/// Refresh `last_seen` on a peer if we know it
+pub async fn touch_peer(peers: &RwLock<HashMap<String, PeerInfo>>, node_id: &str) {
+    if let Some(peer) = peers.write().await.get_mut(node_id) {
+        peer.last_seen = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+    }
+}
+
 impl Default for AppState {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{AuditEntry, AuditKind};
+
+    #[tokio::test]
+    async fn test_ephemeral_mode_writes_no_audit_log() {
+        let dir = std::env::temp_dir().join(format!("vegam-audit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(crate::audit::AUDIT_FILE);
+
+        let entry = AuditEntry::new(AuditKind::Connected, Some("peer".to_string()));
+        let state = AppState::new();
+        state.audit.set_path(path.clone()).await;
+        state.audit.set_enabled(true);
+        state.enter_ephemeral_mode().await;
+        state.audit.record(entry.clone()).await;
+        // Re-enabling the flag alone must not bring the file back
+        state.audit.set_enabled(true);
+        state.audit.record(entry).await;
+
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+// Disk usage breakdown for the storage screen
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Bytes used per category; the categories don't overlap
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StorageUsage {
+    /// Files we're sharing, as tracked for garbage collection
+    pub shared_blobs: u64,
+    /// Data of downloads still running
+    pub partial_downloads: u64,
+    /// Everything else in the blob store: received blobs not yet collected, metadata
+    pub blob_store_other: u64,
+    /// Transfer history database
+    pub history: u64,
+    pub logs: u64,
+    pub total: u64,
+}
+
+/// Where the measured categories live on disk
+pub struct StoragePaths {
+    pub blob_store: PathBuf,
+    pub history: PathBuf,
+    pub logs: PathBuf,
+}
+
+impl StorageUsage {
+    /// Measure disk usage; `shared_blobs` and `partial_downloads` come from app state
+    pub async fn measure(
+        paths: StoragePaths,
+        shared_blobs: u64,
+        partial_downloads: u64,
+    ) -> Result<Self> {
+        tokio::task::spawn_blocking(move || {
+            let blob_store = dir_size(&paths.blob_store)?;
+            let history = db_size(&paths.history)?;
+            let logs = dir_size(&paths.logs)?;
+            let blob_store_other = blob_store.saturating_sub(shared_blobs + partial_downloads);
+            Ok(Self {
+                shared_blobs,
+                partial_downloads,
+                blob_store_other,
+                history,
+                logs,
+                total: shared_blobs + partial_downloads + blob_store_other + history + logs,
+            })
+        })
+        .await?
+    }
+}
+
+/// Total size of the files under `path`; missing directories count as empty
+fn dir_size(path: &Path) -> Result<u64> {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut size = 0;
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// SQLite database size including its journal files
+fn db_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for suffix in ["", "-wal", "-shm", "-journal"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        match std::fs::metadata(&file) {
+            Ok(metadata) => size += metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_size_counts_nested_files() {
+        let root = std::env::temp_dir().join(format!("vegam-storage-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        std::fs::write(root.join("a"), [0u8; 10]).unwrap();
+        std::fs::write(root.join("nested/b"), [0u8; 5]).unwrap();
+
+        assert_eq!(dir_size(&root).unwrap(), 15);
+        assert_eq!(dir_size(&root.join("missing")).unwrap(), 0);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
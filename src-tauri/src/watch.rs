@@ -0,0 +1,289 @@
+// Watch folders
+//
+// Each watch in `watch_folders` names a directory and where new files in it
+// go: one peer, or every peer currently in a group (`default` is the primary
+// room). The `notify` crate reports file events; a path is sent once it has
+// been quiet for `debounce_secs`, so files still being written (screenshots,
+// downloads) go out complete. Names matching an `ignore` pattern (`*` and `?`
+// wildcards) are skipped. A file is imported once and offered to every
+// recipient over the control channel, like a drop on a peer, and
+// `watch-file-sent` reports the result. While the app is locked files wait.
+// Only the top level of a directory is watched, and only files that appear
+// or change while the app runs are sent.
+
+use anyhow::Result;
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::events::AppEvent;
+use crate::iroh::control::ControlMessage;
+use crate::state::AppState;
+
+/// How often pending paths are checked for having settled
+const SETTLE_TICK: Duration = Duration::from_millis(500);
+
+/// Where a watch sends its files
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum WatchTarget {
+    Peer {
+        node_id: String,
+    },
+    /// Every peer seen in the group
+    Group {
+        group_id: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WatchFolder {
+    pub id: String,
+    pub path: String,
+    pub target: WatchTarget,
+    /// File name patterns never sent
+    #[serde(default = "default_ignore")]
+    pub ignore: Vec<String>,
+    /// Quiet time before a new or changed file is sent
+    #[serde(default = "default_debounce_secs")]
+    pub debounce_secs: u64,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// Hidden files and partial downloads
+pub fn default_ignore() -> Vec<String> {
+    [".*", "*.part", "*.crdownload", "*.tmp", "*~"]
+        .map(String::from)
+        .to_vec()
+}
+
+pub fn default_debounce_secs() -> u64 {
+    2
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WatchFolder {
+    pub fn ignores(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return true;
+        };
+        self.ignore
+            .iter()
+            .any(|pattern| wildcard_match(pattern, name))
+    }
+}
+
+/// Payload of `watch-file-sent`
+#[derive(Clone, Debug, Serialize)]
+pub struct WatchFileSent {
+    pub watch_id: String,
+    pub path: String,
+    pub transfer_id: Option<String>,
+    /// Peers the file was offered to
+    pub recipients: Vec<String>,
+    /// Why the file or some offers weren't sent
+    pub error: Option<String>,
+}
+
+/// Running watchers by watch ID
+#[derive(Clone, Default)]
+pub struct WatchFolders {
+    running: Arc<Mutex<HashMap<String, notify::RecommendedWatcher>>>,
+}
+
+impl WatchFolders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop all watchers and start one per enabled watch
+    pub async fn sync(&self, handle: &AppHandle, folders: &[WatchFolder]) {
+        let mut running = self.running.lock().await;
+        running.clear();
+        for folder in folders.iter().filter(|f| f.enabled) {
+            match start(handle.clone(), folder.clone()) {
+                Ok(watcher) => {
+                    info!("Watching {} ({})", folder.path, folder.id);
+                    running.insert(folder.id.clone(), watcher);
+                }
+                Err(e) => warn!("Failed to watch {}: {}", folder.path, e),
+            }
+        }
+    }
+
+    /// IDs of watches currently running
+    pub async fn running(&self) -> Vec<String> {
+        self.running.lock().await.keys().cloned().collect()
+    }
+}
+
+/// Watch `folder`, sending settled paths until the watcher is dropped
+fn start(handle: AppHandle, folder: WatchFolder) -> Result<notify::RecommendedWatcher> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Watch error: {}", e),
+        })?;
+    watcher.watch(Path::new(&folder.path), RecursiveMode::NonRecursive)?;
+
+    // Ends when the watcher, and with it the sender, is dropped
+    tauri::async_runtime::spawn(async move {
+        let debounce = Duration::from_secs(folder.debounce_secs);
+        let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+        let mut tick = tokio::time::interval(SETTLE_TICK);
+        loop {
+            tokio::select! {
+                path = rx.recv() => match path {
+                    Some(path) if !folder.ignores(&path) => {
+                        pending.insert(path, Instant::now());
+                    }
+                    Some(_) => {}
+                    None => break,
+                },
+                _ = tick.tick() => {
+                    let state = handle.state::<AppState>();
+                    if pending.is_empty() || state.is_locked().await {
+                        continue;
+                    }
+                    let now = Instant::now();
+                    let settled: Vec<PathBuf> = pending
+                        .iter()
+                        .filter(|(_, seen)| now.duration_since(**seen) >= debounce)
+                        .map(|(path, _)| path.clone())
+                        .collect();
+                    for path in settled {
+                        pending.remove(&path);
+                        if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+                            send(&handle, &folder, path).await;
+                        }
+                    }
+                }
+            }
+        }
+    });
+    Ok(watcher)
+}
+
+/// Import `path` once and offer it to the watch's recipients
+async fn send(handle: &AppHandle, folder: &WatchFolder, path: PathBuf) {
+    let state = handle.state::<AppState>();
+    let recipients = match &folder.target {
+        WatchTarget::Peer { node_id } => vec![node_id.clone()],
+        WatchTarget::Group { group_id } => state
+            .get_group_peers(group_id)
+            .await
+            .into_iter()
+            .map(|peer| peer.node_id)
+            .collect(),
+    };
+    let mut sent = WatchFileSent {
+        watch_id: folder.id.clone(),
+        path: path.display().to_string(),
+        transfer_id: None,
+        recipients: Vec::new(),
+        error: None,
+    };
+
+    if recipients.is_empty() {
+        sent.error = Some("No peers to send to".to_string());
+    } else {
+        info!("Sending watched file {}", sent.path);
+        match crate::share_file(&state, handle.clone(), sent.path.clone(), false).await {
+            Ok(info) => {
+                sent.transfer_id = Some(info.transfer_id.clone());
+                let offer = ControlMessage::Offer {
+                    transfer_id: info.transfer_id,
+                    ticket: info.ticket,
+                    file_name: info.file_name,
+                    file_size: info.file_size,
+                };
+                let mut failed = Vec::new();
+                for node_id in recipients {
+                    match crate::deliver_control_message(&state, &node_id, &offer).await {
+                        Ok(()) => sent.recipients.push(node_id),
+                        Err(e) => failed.push(e),
+                    }
+                }
+                if !failed.is_empty() {
+                    sent.error = Some(failed.join("; "));
+                }
+            }
+            Err(e) => sent.error = Some(e),
+        }
+    }
+
+    if let Some(e) = &sent.error {
+        warn!("Watched file {} not fully sent: {}", sent.path, e);
+    }
+    let _ = AppEvent::WatchFileSent(sent).emit(handle);
+}
+
+/// Glob-style match of a whole file name: `*` is any run, `?` one character
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Last `*` seen and the name position it is currently matching up to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_patterns() {
+        assert!(wildcard_match("*.png", "Screenshot 1.png"));
+        assert!(!wildcard_match("*.png", "notes.png.txt"));
+        assert!(wildcard_match("IMG_????.jpg", "IMG_0042.jpg"));
+        assert!(!wildcard_match("IMG_????.jpg", "IMG_42.jpg"));
+        assert!(wildcard_match("a*b*c", "aXXbYYc"));
+        assert!(wildcard_match("*", ""));
+
+        let folder = WatchFolder {
+            id: "w1".to_string(),
+            path: "/home/me/Screenshots".to_string(),
+            target: WatchTarget::Group {
+                group_id: "default".to_string(),
+            },
+            ignore: default_ignore(),
+            debounce_secs: default_debounce_secs(),
+            enabled: true,
+        };
+        assert!(folder.ignores(Path::new("/home/me/Screenshots/.DS_Store")));
+        assert!(folder.ignores(Path::new("/home/me/Screenshots/video.mp4.part")));
+        assert!(!folder.ignores(Path::new("/home/me/Screenshots/shot.png")));
+    }
+}
@@ -6,16 +6,107 @@ export interface TransferInfo {
 	file_name: string;
 	file_size: number;
 	bytes_transferred: number;
-	status: "pending" | "inprogress" | "completed" | "failed" | "cancelled";
+	status:
+		| "pending"
+		| "inprogress"
+		| "completed"
+		| "failed"
+		| "cancelled"
+		| "interrupted";
 	error: string | null;
 	direction: "send" | "receive";
 	speed_bps: number;
+	peer_node_id: string | null;
+	/** Unix seconds */
+	started_at: number | null;
+	/** Unix seconds; null while running */
+	finished_at: number | null;
+	/** Hash of the blob the ticket points to */
+	ticket_hash: string | null;
+	note: string | null;
+	pinned: boolean;
+	/** Results of post-receive actions run on the file */
+	actions: ActionResult[];
+	/** Where a received file was saved on disk; null for SAF folders and the gallery */
+	saved_path: string | null;
+}
+
+export interface ActionResult {
+	rule_id: string;
+	action: "extract" | "move" | "run";
+	ok: boolean;
+	/** Where the file or its contents went, the program's output, or the error */
+	detail: string;
 }
 
 export interface PeerInfo {
 	node_id: string;
 	device_name: string;
 	last_seen: number;
+	color: string;
+	avatar: number | null;
+	trust_level: "unknown" | "trusted";
+	capabilities: PeerCapabilities;
+	groups: string[];
+	latency_ms: number | null;
+	connection_type: PeerConnectionType;
+	alias: string | null;
+	platform: string;
+	app_version: string;
+	version_mismatch: boolean;
+	favorite: boolean;
+	fingerprint: string;
+	verified: boolean;
+	offline: boolean;
+}
+
+export type PeerConnectionType = "none" | "direct" | "relay" | "mixed";
+
+export interface ConnectionUpgrade {
+	node_id: string;
+	from: PeerConnectionType;
+}
+
+export interface ConnectionChange {
+	node_id: string;
+	from: PeerConnectionType;
+	to: PeerConnectionType;
+	/** Running transfers with this peer */
+	transfer_ids: string[];
+}
+
+export interface PeerGroup {
+	id: string;
+	name: string;
+	room_code: string;
+	announce: boolean;
+}
+
+export type Feature =
+	| "encrypted_tickets"
+	| "collections"
+	| "compression"
+	| "push_offers"
+	| "control_channel"
+	| "unknown";
+
+export interface PeerCapabilities {
+	protocol_version: number;
+	features: Feature[];
+	max_file_size: number | null;
+}
+
+export interface TrustedPeer {
+	node_id: string;
+	device_name: string;
+	trusted_at: number;
+	verified: boolean;
+}
+
+export interface PeerIdentity {
+	device_name: string;
+	color: string;
+	avatar: number | null;
 }
 
 export interface BlobTicketInfo {
@@ -23,11 +114,39 @@ export interface BlobTicketInfo {
 	file_name: string;
 	file_size: number;
 	transfer_id: string;
+	/** Browser download link, when requested with `httpLink` */
+	http_url: string | null;
+}
+
+export interface HttpLinkInfo {
+	token: string;
+	url: string;
+	file_name: string;
+	file_size: number;
+	expires_at: number;
+}
+
+export interface RoomInfo {
+	room_code: string | null;
+	room_invite: string | null;
+}
+
+export interface PingResult {
+	node_id: string;
+	latency_ms: number;
+	average_latency_ms: number | null;
 }
 
 export interface TicketMetadata {
 	filename: string;
 	size: number;
+	/** Set when the file type policy refuses this file */
+	blocked_reason: string | null;
+}
+
+export interface FileTypePolicy {
+	blocked: string[];
+	allowed: string[];
 }
 
 export interface RelayStatus {
@@ -35,23 +154,580 @@ export interface RelayStatus {
 	relay_url: string | null;
 }
 
-export async function initNode(): Promise<string> {
-	return await invoke<string>("init_node");
+/** With `ephemeral` the session gets a throwaway identity and persists nothing */
+export async function initNode(ephemeral?: boolean): Promise<string> {
+	return await invoke<string>("init_node", { ephemeral: ephemeral ?? null });
+}
+
+export interface NetworkOptions {
+	lan_only: boolean;
+	public_discovery: boolean;
+	ipv6: Ipv6Mode;
+	proxy_url: string | null;
+	/** Custom relays, preferred first; public relays when empty */
+	relay_urls: string[];
+	/** Home relay pinned to one of the relay candidates; by latency when null */
+	preferred_relay: string | null;
+}
+
+/** Shut the node down and start it again, saving `network` first if given. Fails while transfers are running. */
+export async function restartNode(network?: NetworkOptions): Promise<string> {
+	return await invoke<string>("restart_node", { network: network ?? null });
+}
+
+export async function isEphemeral(): Promise<boolean> {
+	return await invoke<boolean>("is_ephemeral");
 }
 
 export async function getNodeId(): Promise<string> {
 	return await invoke<string>("get_node_id");
 }
 
-export async function sendFile(filePath: string): Promise<BlobTicketInfo> {
-	return await invoke<BlobTicketInfo>("send_file", { filePath });
+/** With `httpLink` the file can also be downloaded from a browser on the same network via `http_url`. */
+export async function sendFile(
+	filePath: string,
+	httpLink?: boolean,
+): Promise<BlobTicketInfo> {
+	return await invoke<BlobTicketInfo>("send_file", {
+		filePath,
+		httpLink: httpLink ?? null,
+	});
+}
+
+export type RecipientStatus =
+	| "offered"
+	| "accepted"
+	| "rejected"
+	| "cancelled"
+	| "completed"
+	| "failed";
+
+export interface Recipient {
+	node_id: string;
+	status: RecipientStatus;
+	/** Why the offer failed or the recipient rejected it */
+	error: string | null;
+	bytes_received: number | null;
+}
+
+export interface TransferGroup {
+	id: string;
+	/** The shared send every recipient downloads */
+	transfer_id: string;
+	file_name: string;
+	file_size: number;
+	created_at: number;
+	recipients: Recipient[];
+}
+
+/** Import a file once and offer it to each peer; resolves once every offer was tried. */
+export async function sendToPeers(nodeIds: string[], filePath: string): Promise<TransferGroup> {
+	return await invoke<TransferGroup>("send_to_peers", { nodeIds, filePath });
+}
+
+/** Fan-out sends this session, newest first */
+export async function getTransferGroups(): Promise<TransferGroup[]> {
+	return await invoke<TransferGroup[]>("get_transfer_groups");
+}
+
+export async function getTransferGroup(groupId: string): Promise<TransferGroup | null> {
+	return await invoke<TransferGroup | null>("get_transfer_group", { groupId });
+}
+
+export interface SharedFile {
+	/** content:// URI, pass to `sendFile` as is */
+	uri: string;
+	name: string;
+	size: number | null;
+}
+
+/** Files shared into the app ("Share → vegam" on Android); each is returned once. */
+export async function takeSharedFiles(): Promise<SharedFile[]> {
+	return await invoke<SharedFile[]>("take_shared_files");
+}
+
+export interface DroppedFile {
+	path: string;
+	name: string;
+	size: number;
+	/** Set when imported for the drop target */
+	transfer_id: string | null;
+	ticket: string | null;
+}
+
+export interface RejectedDrop {
+	path: string;
+	reason: string;
+}
+
+/** Payload of `files-dropped` */
+export interface FilesDropped {
+	files: DroppedFile[];
+	rejected: RejectedDrop[];
+	/** Peer the files were offered to; null when staged for `takeDroppedFiles` */
+	target: string | null;
+}
+
+/** Files dragged onto the window and staged; each is returned once. */
+export async function takeDroppedFiles(): Promise<DroppedFile[]> {
+	return await invoke<DroppedFile[]>("take_dropped_files");
+}
+
+export interface OpenedTicket {
+	/** Ticket for `receiveFile`; null when the link or file couldn't be read */
+	ticket: string | null;
+	/** `.vegamticket` file it came from */
+	path: string | null;
+	filename: string | null;
+	size: number | null;
+	error: string | null;
+}
+
+/** Tickets opened from `vegam://` links or `.vegamticket` files; each is returned once. */
+export async function takeOpenedTickets(): Promise<OpenedTicket[]> {
+	return await invoke<OpenedTicket[]>("take_opened_tickets");
+}
+
+/** Offer files dropped from now on to this peer, or stage them (`null`) */
+export async function setDropTarget(nodeId: string | null): Promise<void> {
+	return await invoke<void>("set_drop_target", { nodeId });
+}
+
+export async function listHttpLinks(): Promise<HttpLinkInfo[]> {
+	return await invoke<HttpLinkInfo[]>("list_http_links");
+}
+
+export async function revokeHttpLink(token: string): Promise<void> {
+	return await invoke<void>("revoke_http_link", { token });
+}
+
+/** IP address the download link server listens on; null for all interfaces */
+export async function getHttpShareBind(): Promise<string | null> {
+	return await invoke<string | null>("get_http_share_bind");
+}
+
+/** Listen on one of this device's addresses (e.g. the LAN one), or all with null */
+export async function setHttpShareBind(address: string | null): Promise<void> {
+	return await invoke<void>("set_http_share_bind", { address });
 }
 
+/**
+ * Without an output path the file goes to the sender's download directory.
+ * Pass `allowBlockedType` once the user has confirmed a file the file type
+ * policy refuses.
+ */
 export async function receiveFile(
 	ticket: string,
-	outputPath: string,
+	outputPath?: string,
+	allowBlockedType?: boolean,
 ): Promise<TransferInfo> {
-	return await invoke<TransferInfo>("receive_file", { ticket, outputPath });
+	return await invoke<TransferInfo>("receive_file", {
+		ticket,
+		outputPath: outputPath ?? null,
+		allowBlockedType: allowBlockedType ?? null,
+	});
+}
+
+/** Receives cut short by an exit or the OS killing the app */
+export async function listResumableReceives(): Promise<TransferInfo[]> {
+	return await invoke<TransferInfo[]>("list_resumable_receives");
+}
+
+/** Restart an interrupted receive; keeps its transfer ID */
+export async function resumeReceive(transferId: string): Promise<TransferInfo> {
+	return await invoke<TransferInfo>("resume_receive", { transferId });
+}
+
+/** Bytes per second; null for unlimited */
+export interface BandwidthLimits {
+	upload_bps: number | null;
+	download_bps: number | null;
+}
+
+export async function getBandwidthLimits(): Promise<BandwidthLimits> {
+	return await invoke<BandwidthLimits>("get_bandwidth_limits");
+}
+
+/** Caps the total rate across all transfers; applies to running transfers immediately. */
+export async function setBandwidthLimits(limits: BandwidthLimits): Promise<void> {
+	return await invoke<void>("set_bandwidth_limits", { limits });
+}
+
+export interface MeteredPolicy {
+	defer_large: boolean;
+	threshold_bytes: number;
+}
+
+export interface MeteredStatus {
+	metered: boolean;
+	/** Transfer IDs of deferred downloads */
+	deferred: string[];
+}
+
+export async function getMeteredPolicy(): Promise<MeteredPolicy> {
+	return await invoke<MeteredPolicy>("get_metered_policy");
+}
+
+export async function setMeteredPolicy(policy: MeteredPolicy): Promise<void> {
+	return await invoke<void>("set_metered_policy", { policy });
+}
+
+/** Report the connection as metered (e.g. from `navigator.connection`); `null` goes back to OS detection. */
+export async function setNetworkMetered(metered: boolean | null): Promise<void> {
+	return await invoke<void>("set_network_metered", { metered });
+}
+
+export async function getMeteredStatus(): Promise<MeteredStatus> {
+	return await invoke<MeteredStatus>("get_metered_status");
+}
+
+/** Start a download deferred on a metered connection right away. */
+export async function forceStart(transferId: string): Promise<void> {
+	return await invoke<void>("force_start", { transferId });
+}
+
+export interface BackgroundRestrictions {
+	/** Device is in Doze */
+	device_idle: boolean;
+	/** Battery saver is on */
+	power_save: boolean;
+	ignoring_battery_optimizations: boolean;
+	background_restricted: boolean;
+	/** App standby bucket (10 active up to 45 restricted) */
+	standby_bucket: number | null;
+}
+
+/** Doze and battery optimization state (Android; never restricted elsewhere). */
+export async function getBackgroundRestrictions(): Promise<BackgroundRestrictions> {
+	return await invoke<BackgroundRestrictions>("get_background_restrictions");
+}
+
+/** Open Android's battery optimization settings to exempt the app. */
+export async function openBatteryOptimizationSettings(): Promise<void> {
+	return await invoke<void>("open_battery_optimization_settings");
+}
+
+export interface NotificationPolicy {
+	transfer_completed: boolean;
+	transfer_failed: boolean;
+	incoming_offer: boolean;
+	message_received: boolean;
+	/** Stay quiet while the main window has focus */
+	only_when_unfocused: boolean;
+}
+
+export async function getNotificationPolicy(): Promise<NotificationPolicy> {
+	return await invoke<NotificationPolicy>("get_notification_policy");
+}
+
+/** Also asks for notification permission where the OS needs it */
+export async function setNotificationPolicy(policy: NotificationPolicy): Promise<void> {
+	return await invoke<void>("set_notification_policy", { policy });
+}
+
+export interface LowPowerPolicy {
+	enabled: boolean;
+	/** Battery percentage below which low-power mode kicks in */
+	battery_threshold: number;
+}
+
+export interface PowerStatus {
+	on_battery: boolean;
+	level: number | null;
+}
+
+export async function getLowPowerPolicy(): Promise<LowPowerPolicy> {
+	return await invoke<LowPowerPolicy>("get_low_power_policy");
+}
+
+export async function setLowPowerPolicy(policy: LowPowerPolicy): Promise<void> {
+	return await invoke<void>("set_low_power_policy", { policy });
+}
+
+/** Report battery status (e.g. from `navigator.getBattery()`); `null` goes back to OS detection. */
+export async function setPowerStatus(status: PowerStatus | null): Promise<void> {
+	return await invoke<void>("set_power_status", { status });
+}
+
+export async function isLowPower(): Promise<boolean> {
+	return await invoke<boolean>("is_low_power");
+}
+
+export interface BatteryDeferPolicy {
+	enabled: boolean;
+	/** Battery percentage below which large sends wait */
+	battery_threshold: number;
+	/** Sends larger than this wait for the battery */
+	threshold_bytes: number;
+}
+
+export interface DeferredSend {
+	transfer: TransferInfo;
+	battery_level: number | null;
+	battery_threshold: number;
+}
+
+export async function getBatteryDeferPolicy(): Promise<BatteryDeferPolicy> {
+	return await invoke<BatteryDeferPolicy>("get_battery_defer_policy");
+}
+
+export async function setBatteryDeferPolicy(policy: BatteryDeferPolicy): Promise<void> {
+	return await invoke<void>("set_battery_defer_policy", { policy });
+}
+
+export interface LocalApiInfo {
+	enabled: boolean;
+	/** Port on 127.0.0.1 */
+	port: number;
+	/** Bearer token for `Authorization` */
+	token: string;
+	/** Base URL while the server runs */
+	url: string | null;
+}
+
+export async function getLocalApi(): Promise<LocalApiInfo> {
+	return await invoke<LocalApiInfo>("get_local_api");
+}
+
+/** Turn the localhost API on or off; the token is generated the first time */
+export async function setLocalApi(enabled: boolean, port?: number): Promise<LocalApiInfo> {
+	return await invoke<LocalApiInfo>("set_local_api", { enabled, port: port ?? null });
+}
+
+export async function regenerateLocalApiToken(): Promise<LocalApiInfo> {
+	return await invoke<LocalApiInfo>("regenerate_local_api_token");
+}
+
+export type WatchTarget =
+	| { kind: "peer"; node_id: string }
+	/** Every peer seen in the group (`default` for the primary room) */
+	| { kind: "group"; group_id: string };
+
+export interface WatchFolder {
+	id: string;
+	path: string;
+	target: WatchTarget;
+	/** File name patterns (`*`, `?`) never sent */
+	ignore: string[];
+	/** Quiet time before a new or changed file is sent */
+	debounce_secs: number;
+	enabled: boolean;
+}
+
+export interface WatchFileSent {
+	watch_id: string;
+	path: string;
+	transfer_id: string | null;
+	/** Peers the file was offered to */
+	recipients: string[];
+	error: string | null;
+}
+
+export async function listWatchFolders(): Promise<WatchFolder[]> {
+	return await invoke<WatchFolder[]>("list_watch_folders");
+}
+
+/** Send new files in `path` automatically; `ignore` defaults to hidden files and partial downloads */
+export async function addWatchFolder(
+	path: string,
+	target: WatchTarget,
+	ignore?: string[],
+	debounceSecs?: number,
+): Promise<WatchFolder> {
+	return await invoke<WatchFolder>("add_watch_folder", {
+		path,
+		target,
+		ignore: ignore ?? null,
+		debounceSecs: debounceSecs ?? null,
+	});
+}
+
+export async function removeWatchFolder(id: string): Promise<void> {
+	return await invoke<void>("remove_watch_folder", { id });
+}
+
+export async function setWatchFolderEnabled(id: string, enabled: boolean): Promise<void> {
+	return await invoke<void>("set_watch_folder_enabled", { id, enabled });
+}
+
+/** IDs of watch folders being watched right now */
+export async function getRunningWatches(): Promise<string[]> {
+	return await invoke<string[]>("get_running_watches");
+}
+
+export interface WebhookSettings {
+	enabled: boolean;
+	/** http(s) URL that receives the JSON events */
+	url: string;
+	/** HMAC-SHA256 key for the `X-Vegam-Signature` header */
+	secret: string;
+	transfer_completed: boolean;
+	transfer_failed: boolean;
+	offer_received: boolean;
+}
+
+export async function getWebhook(): Promise<WebhookSettings> {
+	return await invoke<WebhookSettings>("get_webhook");
+}
+
+/** Save the webhook; an enabled one without a secret gets a random secret, returned here */
+export async function setWebhook(settings: WebhookSettings): Promise<WebhookSettings> {
+	return await invoke<WebhookSettings>("set_webhook", { settings });
+}
+
+/** Send a `ping` event to the saved webhook; rejects with the reason it failed */
+export async function testWebhook(): Promise<void> {
+	return await invoke<void>("test_webhook");
+}
+
+export interface ClipboardSyncSettings {
+	enabled: boolean;
+	/** Node IDs of trusted peers to sync with */
+	devices: string[];
+	/** Also sync images */
+	images: boolean;
+	/** Largest text synced, in bytes */
+	max_text_bytes: number;
+	/** Largest image synced, in bytes of RGBA pixels */
+	max_image_bytes: number;
+}
+
+export async function getClipboardSync(): Promise<ClipboardSyncSettings> {
+	return await invoke<ClipboardSyncSettings>("get_clipboard_sync");
+}
+
+export async function setClipboardSync(settings: ClipboardSyncSettings): Promise<void> {
+	return await invoke<void>("set_clipboard_sync", { settings });
+}
+
+/** Sync the clipboard with one trusted peer, or stop; it must turn us on too */
+export async function setClipboardSyncDevice(nodeId: string, enabled: boolean): Promise<void> {
+	return await invoke<void>("set_clipboard_sync_device", { nodeId, enabled });
+}
+
+export type PostAction =
+	/** Unpack a zip, tar or tar.gz into a folder next to it */
+	| { kind: "extract"; delete_archive: boolean }
+	| { kind: "move"; to: string }
+	/** `{path}` in `args` becomes the received file's path; no shell */
+	| { kind: "run"; program: string; args: string[] };
+
+export interface PostReceiveRule {
+	id: string;
+	/** Extensions without the dot (`zip`, `tar.gz`); every file if empty */
+	extensions: string[];
+	/** Only files saved under this directory */
+	directory: string | null;
+	action: PostAction;
+	enabled: boolean;
+}
+
+export async function listPostReceiveRules(): Promise<PostReceiveRule[]> {
+	return await invoke<PostReceiveRule[]>("list_post_receive_rules");
+}
+
+/** Rules run in the order they were added */
+export async function addPostReceiveRule(
+	action: PostAction,
+	extensions?: string[],
+	directory?: string,
+): Promise<PostReceiveRule> {
+	return await invoke<PostReceiveRule>("add_post_receive_rule", {
+		action,
+		extensions: extensions ?? null,
+		directory: directory ?? null,
+	});
+}
+
+export async function removePostReceiveRule(id: string): Promise<void> {
+	return await invoke<void>("remove_post_receive_rule", { id });
+}
+
+export async function setPostReceiveRuleEnabled(id: string, enabled: boolean): Promise<void> {
+	return await invoke<void>("set_post_receive_rule_enabled", { id, enabled });
+}
+
+export interface SpaceInfo {
+	id: string;
+	name: string;
+	/** Whether we can add files */
+	writable: boolean;
+}
+
+export interface SpaceFile {
+	name: string;
+	hash: string;
+	size: number;
+	author: string;
+	/** Unix seconds of the last write */
+	updated_at: number;
+	/** Content has synced, so it can be saved */
+	local: boolean;
+}
+
+export interface SpaceUpdated {
+	space_id: string;
+}
+
+export async function listSpaces(): Promise<SpaceInfo[]> {
+	return await invoke<SpaceInfo[]>("list_spaces");
+}
+
+export async function createSpace(name: string): Promise<SpaceInfo> {
+	return await invoke<SpaceInfo>("create_space", { name });
+}
+
+/** Ticket for joining a space; `writable` (default true) lets members add files */
+export async function shareSpace(spaceId: string, writable?: boolean): Promise<string> {
+	return await invoke<string>("share_space", { spaceId, writable: writable ?? null });
+}
+
+/** Join a space from a ticket, returning its ID */
+export async function joinSpace(ticket: string): Promise<string> {
+	return await invoke<string>("join_space", { ticket });
+}
+
+export async function leaveSpace(spaceId: string): Promise<void> {
+	return await invoke<void>("leave_space", { spaceId });
+}
+
+export async function listSpaceFiles(spaceId: string): Promise<SpaceFile[]> {
+	return await invoke<SpaceFile[]>("list_space_files", { spaceId });
+}
+
+export async function addSpaceFile(
+	spaceId: string,
+	filePath: string,
+	name?: string,
+): Promise<SpaceFile> {
+	return await invoke<SpaceFile>("add_space_file", { spaceId, filePath, name: name ?? null });
+}
+
+export async function removeSpaceFile(spaceId: string, name: string): Promise<void> {
+	return await invoke<void>("remove_space_file", { spaceId, name });
+}
+
+/** Save a synced space file, by default to the download directory; returns the path */
+export async function saveSpaceFile(
+	spaceId: string,
+	name: string,
+	outputPath?: string,
+): Promise<string> {
+	return await invoke<string>("save_space_file", {
+		spaceId,
+		name,
+		outputPath: outputPath ?? null,
+	});
+}
+
+/** Transfer IDs of sends waiting for the battery */
+export async function getDeferredSends(): Promise<string[]> {
+	return await invoke<string[]>("get_deferred_sends");
+}
+
+/** Start a send deferred for the battery right away. */
+export async function forceSend(transferId: string): Promise<void> {
+	return await invoke<void>("force_send", { transferId });
 }
 
 export async function getTransferStatus(
@@ -62,36 +738,984 @@ export async function getTransferStatus(
 	});
 }
 
-export async function listPeers(): Promise<PeerInfo[]> {
-	return await invoke<PeerInfo[]>("list_peers");
+export async function getTransferHistory(
+	limit?: number,
+): Promise<TransferInfo[]> {
+	return await invoke<TransferInfo[]>("get_transfer_history", {
+		limit: limit ?? null,
+	});
 }
 
-export async function getDeviceName(): Promise<string> {
-	return await invoke<string>("get_device_name");
+export type TransferSort = "newest" | "oldest" | "largest" | "smallest" | "name";
+
+export interface TransferFilter {
+	status?: TransferInfo["status"][];
+	direction?: TransferInfo["direction"];
+	peer_node_id?: string;
+	/** Created at or after (unix seconds) */
+	from?: number;
+	/** Created before (unix seconds) */
+	to?: number;
+	sort?: TransferSort;
 }
 
-export async function listenToTransferUpdates(
-	callback: (transfer: TransferInfo) => void,
-): Promise<UnlistenFn> {
-	return await listen<TransferInfo>("transfer-update", (event) => {
-		callback(event.payload);
-	});
+export interface TransferPage {
+	transfers: TransferInfo[];
+	total: number;
+	page: number;
+	page_size: number;
 }
 
-export async function listenToTransferProgress(
-	callback: (transfer: TransferInfo) => void,
-): Promise<UnlistenFn> {
-	return await listen<TransferInfo>("transfer-progress", (event) => {
-		callback(event.payload);
+export async function listTransfers(
+	filter: TransferFilter = {},
+	page = 0,
+	pageSize = 50,
+): Promise<TransferPage> {
+	return await invoke<TransferPage>("list_transfers", {
+		filter,
+		page,
+		pageSize,
 	});
 }
 
-export async function parseTicketMetadata(
-	ticket: string,
-): Promise<TicketMetadata> {
-	return await invoke<TicketMetadata>("parse_ticket_metadata", { ticket });
+/** Best matches first, by file name, peer name and note (case-insensitive). */
+export async function searchTransfers(
+	query: string,
+	limit?: number,
+): Promise<TransferInfo[]> {
+	return await invoke<TransferInfo[]>("search_transfers", { query, limit });
 }
 
-export async function getRelayStatus(): Promise<RelayStatus> {
-	return await invoke<RelayStatus>("get_relay_status");
+/** Pass null (or an empty string) to clear the note. */
+export async function setTransferNote(
+	transferId: string,
+	note: string | null,
+): Promise<void> {
+	await invoke("set_transfer_note", { transferId, note });
+}
+
+/** Pinned transfers (and their shared blobs) are kept by cleanup. */
+export async function setTransferPinned(
+	transferId: string,
+	pinned: boolean,
+): Promise<void> {
+	await invoke("set_transfer_pinned", { transferId, pinned });
+}
+
+/** Deletes finished, unpinned transfers created before `olderThan` (unix seconds), or all of them. */
+export async function clearHistory(olderThan?: number): Promise<number> {
+	return await invoke<number>("clear_history", { olderThan: olderThan ?? null });
+}
+
+export interface HistoryRetention {
+	max_age_days: number | null;
+	max_entries: number | null;
+}
+
+export async function getHistoryRetention(): Promise<HistoryRetention> {
+	return await invoke<HistoryRetention>("get_history_retention");
+}
+
+export async function setHistoryRetention(
+	maxAgeDays: number | null,
+	maxEntries: number | null,
+): Promise<HistoryRetention> {
+	return await invoke<HistoryRetention>("set_history_retention", {
+		maxAgeDays,
+		maxEntries,
+	});
+}
+
+export interface BlobRecord {
+	hash: string;
+	tag: string;
+	file_name: string;
+	size: number;
+	created_at: number;
+	last_served_at: number | null;
+	pinned: boolean;
+}
+
+export interface BlobGcPolicy {
+	max_idle_days: number | null;
+	max_store_bytes: number | null;
+}
+
+export async function listBlobs(): Promise<BlobRecord[]> {
+	return await invoke<BlobRecord[]>("list_blobs");
+}
+
+export async function deleteBlob(hash: string): Promise<void> {
+	await invoke("delete_blob", { hash });
+}
+
+/** Overwrite and delete a received file; with `transferId`, also its blob and history entry */
+export async function shredFile(
+	path: string,
+	transferId?: string,
+): Promise<void> {
+	await invoke("shred_file", { path, transferId: transferId ?? null });
+}
+
+export async function pinBlob(hash: string, pinned: boolean): Promise<void> {
+	await invoke("pin_blob", { hash, pinned });
+}
+
+export async function getBlobGcPolicy(): Promise<BlobGcPolicy> {
+	return await invoke<BlobGcPolicy>("get_blob_gc_policy");
+}
+
+export async function setBlobGcPolicy(
+	maxIdleDays: number | null,
+	maxStoreBytes: number | null,
+): Promise<BlobGcPolicy> {
+	return await invoke<BlobGcPolicy>("set_blob_gc_policy", {
+		maxIdleDays,
+		maxStoreBytes,
+	});
+}
+
+export async function setBlobEncryption(enabled: boolean): Promise<void> {
+	return await invoke<void>("set_blob_encryption", { enabled });
+}
+
+/** Encrypt shared files with a key only the ticket carries */
+export async function setContentEncryption(enabled: boolean): Promise<void> {
+	return await invoke<void>("set_content_encryption", { enabled });
+}
+
+export async function getContentEncryption(): Promise<boolean> {
+	return await invoke<boolean>("get_content_encryption");
+}
+
+/** Put received photos and videos in the phone's gallery (no effect on desktop) */
+export async function setSaveMediaToGallery(enabled: boolean): Promise<void> {
+	return await invoke<void>("set_save_media_to_gallery", { enabled });
+}
+
+export async function getSaveMediaToGallery(): Promise<boolean> {
+	return await invoke<boolean>("get_save_media_to_gallery");
+}
+
+/** Bytes per category; categories don't overlap */
+export interface StorageUsage {
+	shared_blobs: number;
+	partial_downloads: number;
+	blob_store_other: number;
+	history: number;
+	logs: number;
+	total: number;
+}
+
+export async function getStorageUsage(): Promise<StorageUsage> {
+	return await invoke<StorageUsage>("get_storage_usage");
+}
+
+/** One line of the append-only audit log */
+export interface AuditEntry {
+	/** Unix seconds */
+	timestamp: number;
+	kind: "connected" | "refused" | "blob_requested" | "transfer_finished";
+	node_id: string | null;
+	hash: string | null;
+	file_name: string | null;
+	/** Requested blob size, or bytes moved by a finished transfer */
+	bytes: number | null;
+	transfer_id: string | null;
+	direction: TransferInfo["direction"] | null;
+	status: TransferInfo["status"] | null;
+}
+
+export async function setAuditLogEnabled(enabled: boolean): Promise<void> {
+	return await invoke<void>("set_audit_log_enabled", { enabled });
+}
+
+export async function getAuditLogEnabled(): Promise<boolean> {
+	return await invoke<boolean>("get_audit_log_enabled");
+}
+
+/** Newest entries first */
+export async function getAuditLog(limit?: number): Promise<AuditEntry[]> {
+	return await invoke<AuditEntry[]>("get_audit_log", { limit: limit ?? null });
+}
+
+/** Write the log as JSONL; returns bytes written */
+export async function exportAuditLog(path: string): Promise<number> {
+	return await invoke<number>("export_audit_log", { path });
+}
+
+/** Error message of commands refused while the app is locked */
+export const LOCKED_ERROR = "Locked";
+
+/** Set the app passcode, or remove it with `null` */
+export async function setAppLock(
+	passcode: string | null,
+	autoLockMinutes?: number,
+): Promise<void> {
+	await invoke("set_app_lock", {
+		passcode,
+		autoLockMinutes: autoLockMinutes ?? null,
+	});
+}
+
+export async function lockApp(): Promise<void> {
+	await invoke("lock_app");
+}
+
+export async function unlock(passcode: string): Promise<void> {
+	await invoke("unlock", { passcode });
+}
+
+export async function isLocked(): Promise<boolean> {
+	return await invoke<boolean>("is_locked");
+}
+
+export async function listenToBlobsRemoved(
+	callback: (blobs: BlobRecord[]) => void,
+): Promise<UnlistenFn> {
+	return await listen<BlobRecord[]>("blobs-removed", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function getPeerTransfers(
+	nodeId: string,
+): Promise<TransferInfo[]> {
+	return await invoke<TransferInfo[]>("get_peer_transfers", { nodeId });
+}
+
+export async function listPeers(): Promise<PeerInfo[]> {
+	return await invoke<PeerInfo[]>("list_peers");
+}
+
+export async function getDeviceName(): Promise<string> {
+	return await invoke<string>("get_device_name");
+}
+
+export async function setDeviceName(name: string): Promise<string> {
+	return await invoke<string>("set_device_name", { name });
+}
+
+export async function getIdentity(): Promise<PeerIdentity> {
+	return await invoke<PeerIdentity>("get_identity");
+}
+
+export async function setIdentity(
+	color: string | null,
+	avatar: number | null,
+): Promise<PeerIdentity> {
+	return await invoke<PeerIdentity>("set_identity", { color, avatar });
+}
+
+export async function trustPeer(nodeId: string): Promise<TrustedPeer> {
+	return await invoke<TrustedPeer>("trust_peer", { nodeId });
+}
+
+export async function untrustPeer(nodeId: string): Promise<void> {
+	return await invoke<void>("untrust_peer", { nodeId });
+}
+
+export async function listTrustedPeers(): Promise<TrustedPeer[]> {
+	return await invoke<TrustedPeer[]>("list_trusted_peers");
+}
+
+/** Replace the node key; returns the node ID used from the next start */
+export async function rotateIdentity(): Promise<string> {
+	return await invoke<string>("rotate_identity");
+}
+
+export async function getFingerprint(nodeId?: string): Promise<string> {
+	return await invoke<string>("get_fingerprint", { nodeId });
+}
+
+export async function setPeerVerified(
+	nodeId: string,
+	verified: boolean,
+): Promise<void> {
+	return await invoke<void>("set_peer_verified", { nodeId, verified });
+}
+
+export async function startPairing(): Promise<string> {
+	return await invoke<string>("start_pairing");
+}
+
+export async function cancelPairing(): Promise<void> {
+	return await invoke<void>("cancel_pairing");
+}
+
+export async function pairWithCode(code: string): Promise<TrustedPeer> {
+	return await invoke<TrustedPeer>("pair_with_code", { code });
+}
+
+export async function startPinPairing(): Promise<string> {
+	return await invoke<string>("start_pin_pairing");
+}
+
+export async function cancelPinPairing(): Promise<void> {
+	return await invoke<void>("cancel_pin_pairing");
+}
+
+export async function pairWithPin(
+	nodeId: string,
+	pin: string,
+): Promise<TrustedPeer> {
+	return await invoke<TrustedPeer>("pair_with_pin", { nodeId, pin });
+}
+
+export async function listenToPairing(
+	callback: (peer: TrustedPeer) => void,
+): Promise<UnlistenFn> {
+	return await listen<TrustedPeer>("peer-paired", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function blockPeer(nodeId: string): Promise<void> {
+	return await invoke<void>("block_peer", { nodeId });
+}
+
+export async function unblockPeer(nodeId: string): Promise<void> {
+	return await invoke<void>("unblock_peer", { nodeId });
+}
+
+export async function listBlockedPeers(): Promise<string[]> {
+	return await invoke<string[]>("list_blocked_peers");
+}
+
+export async function getCapabilities(): Promise<PeerCapabilities> {
+	return await invoke<PeerCapabilities>("get_capabilities");
+}
+
+export async function setMaxFileSize(
+	maxFileSize: number | null,
+): Promise<PeerCapabilities> {
+	return await invoke<PeerCapabilities>("set_max_file_size", { maxFileSize });
+}
+
+export type BlobStoreKind = "memory" | "filesystem";
+
+export async function getEventVersion(): Promise<number> {
+	return await invoke<number>("get_event_version");
+}
+
+export async function getFileTypePolicy(): Promise<FileTypePolicy> {
+	return await invoke<FileTypePolicy>("get_file_type_policy");
+}
+
+export async function setFileTypePolicy(
+	blocked: string[],
+	allowed: string[],
+): Promise<FileTypePolicy> {
+	return await invoke<FileTypePolicy>("set_file_type_policy", {
+		blocked,
+		allowed,
+	});
+}
+
+export async function getBlobStore(): Promise<BlobStoreKind> {
+	return await invoke<BlobStoreKind>("get_blob_store");
+}
+
+/** Takes effect the next time the node starts. */
+export async function setBlobStore(blobStore: BlobStoreKind): Promise<void> {
+	return await invoke<void>("set_blob_store", { blobStore });
+}
+
+export async function getLanOnly(): Promise<boolean> {
+	return await invoke<boolean>("get_lan_only");
+}
+
+/** No relays or public discovery; takes effect the next time the node starts. */
+export async function setLanOnly(enabled: boolean): Promise<void> {
+	return await invoke<void>("set_lan_only", { enabled });
+}
+
+export type Ipv6Mode = "enabled" | "preferred" | "disabled";
+
+export async function getPublicDiscovery(): Promise<boolean> {
+	return await invoke<boolean>("get_public_discovery");
+}
+
+/** Publish our address so peers can dial us by node ID alone. Takes effect the next time the node starts. */
+export async function setPublicDiscovery(enabled: boolean): Promise<void> {
+	return await invoke<void>("set_public_discovery", { enabled });
+}
+
+export async function getIpv6Mode(): Promise<Ipv6Mode> {
+	return await invoke<Ipv6Mode>("get_ipv6_mode");
+}
+
+/** Takes effect the next time the node starts. */
+export async function setIpv6Mode(mode: Ipv6Mode): Promise<void> {
+	return await invoke<void>("set_ipv6_mode", { mode });
+}
+
+export interface BoundSockets {
+	ipv4: string[];
+	ipv6: string[];
+}
+
+export async function getBoundSockets(): Promise<BoundSockets> {
+	return await invoke<BoundSockets>("get_bound_sockets");
+}
+
+export type NatType =
+	| "unknown"
+	| "udp_blocked"
+	| "open"
+	| "endpoint_independent"
+	| "symmetric";
+
+export interface PeerPath {
+	node_id: string;
+	device_name: string;
+	connection_type: PeerConnectionType;
+	latency_ms: number | null;
+}
+
+export interface NetworkInfo {
+	node_id: string;
+	direct_addresses: string[];
+	home_relay: string | null;
+	bound_sockets: BoundSockets;
+	public_ipv4: string | null;
+	public_ipv6: string | null;
+	nat_type: NatType;
+	captive_portal: boolean | null;
+	lan_only: boolean;
+	peers: PeerPath[];
+}
+
+export async function getNetworkInfo(): Promise<NetworkInfo> {
+	return await invoke<NetworkInfo>("get_network_info");
+}
+
+export async function getRelayUrls(): Promise<string[]> {
+	return await invoke<string[]>("get_relay_urls");
+}
+
+/** Custom relays, preferred first; an empty list goes back to the public relays. Takes effect the next time the node starts. */
+export async function setRelayUrls(relayUrls: string[]): Promise<void> {
+	return await invoke<void>("set_relay_urls", { relayUrls });
+}
+
+export interface RelayProbe {
+	url: string;
+	/** TCP connect time; null when the relay didn't answer */
+	latency_ms: number | null;
+}
+
+/** Connect times to the relays the node can use, fastest first. */
+export async function probeRelays(): Promise<RelayProbe[]> {
+	return await invoke<RelayProbe[]>("probe_relays");
+}
+
+export async function getPreferredRelay(): Promise<string | null> {
+	return await invoke<string | null>("get_preferred_relay");
+}
+
+/** Pin the home relay (one of `probeRelays()`), or `null` to pick by latency. Takes effect the next time the node starts. */
+export async function setPreferredRelay(relayUrl: string | null): Promise<void> {
+	return await invoke<void>("set_preferred_relay", { relayUrl });
+}
+
+export interface RelayChange {
+	from: string | null;
+	to: string | null;
+}
+
+export async function listenToRelayChanged(
+	callback: (change: RelayChange) => void,
+): Promise<UnlistenFn> {
+	return await listen<RelayChange>("relay-changed", (event) => {
+		callback(event.payload);
+	});
+}
+
+export interface NetworkDiagnostics {
+	/** Unix seconds */
+	generated_at: number;
+	/** False when no new net report arrived in time */
+	report_fresh: boolean;
+	udp_v4: boolean | null;
+	udp_v6: boolean | null;
+	/** Port-restricted (symmetric) NAT */
+	mapping_varies_by_dest_ipv4: boolean | null;
+	mapping_varies_by_dest_ipv6: boolean | null;
+	preferred_relay: string | null;
+	relays: RelayProbe[];
+	network: NetworkInfo;
+}
+
+/** Takes up to ~15 s: re-runs the net report and probes every relay. */
+export async function runNetworkDiagnostics(): Promise<NetworkDiagnostics> {
+	return await invoke<NetworkDiagnostics>("run_network_diagnostics");
+}
+
+export async function getProxyUrl(): Promise<string | null> {
+	return await invoke<string | null>("get_proxy_url");
+}
+
+/** `socks5://host:port` or `http://host:port`; `null` to connect directly. Takes effect the next time the node starts. */
+export async function setProxyUrl(proxyUrl: string | null): Promise<void> {
+	return await invoke<void>("set_proxy_url", { proxyUrl });
+}
+
+export interface DiscoveryTimings {
+	announcement_interval_secs: number;
+	peer_timeout_secs: number;
+}
+
+export async function getDiscoveryTimings(): Promise<DiscoveryTimings> {
+	return await invoke<DiscoveryTimings>("get_discovery_timings");
+}
+
+export async function setDiscoveryTimings(
+	announcementIntervalSecs: number,
+	peerTimeoutSecs: number,
+): Promise<DiscoveryTimings> {
+	return await invoke<DiscoveryTimings>("set_discovery_timings", {
+		announcementIntervalSecs,
+		peerTimeoutSecs,
+	});
+}
+
+export async function createRoom(): Promise<RoomInfo> {
+	return await invoke<RoomInfo>("create_room");
+}
+
+export async function joinRoom(roomCode: string): Promise<RoomInfo> {
+	return await invoke<RoomInfo>("join_room", { roomCode });
+}
+
+export async function leaveRoom(): Promise<RoomInfo> {
+	return await invoke<RoomInfo>("leave_room");
+}
+
+export async function getRoom(): Promise<RoomInfo> {
+	return await invoke<RoomInfo>("get_room");
+}
+
+export async function getRoomInvite(): Promise<string> {
+	return await invoke<string>("get_room_invite");
+}
+
+export async function joinFromInvite(ticket: string): Promise<RoomInfo> {
+	return await invoke<RoomInfo>("join_from_invite", { ticket });
+}
+
+export async function createGroup(name: string): Promise<PeerGroup> {
+	return await invoke<PeerGroup>("create_group", { name });
+}
+
+export async function joinGroup(
+	name: string,
+	roomCode: string,
+): Promise<PeerGroup> {
+	return await invoke<PeerGroup>("join_group", { name, roomCode });
+}
+
+export async function leaveGroup(groupId: string): Promise<void> {
+	return await invoke<void>("leave_group", { groupId });
+}
+
+export async function listGroups(): Promise<PeerGroup[]> {
+	return await invoke<PeerGroup[]>("list_groups");
+}
+
+export async function setGroupAnnounce(
+	groupId: string,
+	announce: boolean,
+): Promise<PeerGroup> {
+	return await invoke<PeerGroup>("set_group_announce", { groupId, announce });
+}
+
+export async function listGroupPeers(groupId: string): Promise<PeerInfo[]> {
+	return await invoke<PeerInfo[]>("list_group_peers", { groupId });
+}
+
+export async function setDiscoverable(
+	discoverable: boolean,
+	sendGoodbye: boolean,
+): Promise<boolean> {
+	return await invoke<boolean>("set_discoverable", {
+		discoverable,
+		sendGoodbye,
+	});
+}
+
+export async function getDiscoverable(): Promise<boolean> {
+	return await invoke<boolean>("get_discoverable");
+}
+
+/** Flip discoverability (saying goodbye when turning it off); returns the new value */
+export async function toggleDiscoverable(): Promise<boolean> {
+	return await invoke<boolean>("toggle_discoverable");
+}
+
+export interface QuickStatus {
+	running: boolean;
+	discoverable: boolean;
+	peers_online: number;
+	active_transfers: number;
+	/** Overall percent of running transfers, null when sizes are unknown */
+	progress: number | null;
+}
+
+/** Compact status, as shown by the Android Quick Settings tile */
+export async function getQuickStatus(): Promise<QuickStatus> {
+	return await invoke<QuickStatus>("get_quick_status");
+}
+
+export async function setTrustedOnlyProvider(
+	enabled: boolean,
+): Promise<boolean> {
+	return await invoke<boolean>("set_trusted_only_provider", { enabled });
+}
+
+export async function getTrustedOnlyProvider(): Promise<boolean> {
+	return await invoke<boolean>("get_trusted_only_provider");
+}
+
+export async function pingPeer(nodeId: string): Promise<PingResult> {
+	return await invoke<PingResult>("ping_peer", { nodeId });
+}
+
+export type ControlMessage =
+	| {
+			type: "offer";
+			transfer_id: string;
+			ticket: string;
+			file_name: string;
+			file_size: number;
+	  }
+	| { type: "accept"; transfer_id: string }
+	| { type: "reject"; transfer_id: string; reason: string | null }
+	| { type: "cancel"; transfer_id: string }
+	| { type: "receipt"; transfer_id: string; bytes_received: number }
+	| { type: "chat"; id: string; text: string; sent_at: number }
+	| { type: "clipboard"; content: ClipboardContent };
+
+export type ClipboardContent =
+	| { kind: "text"; text: string }
+	/** `rgba` is base64 RGBA pixels */
+	| { kind: "image"; width: number; height: number; rgba: string };
+
+export interface ControlReceived {
+	/** Node ID of the sender */
+	from: string;
+	message: ControlMessage;
+}
+
+/** Resolves once the peer has received the message. */
+export async function sendControlMessage(
+	nodeId: string,
+	message: ControlMessage,
+): Promise<void> {
+	return await invoke<void>("send_control_message", { nodeId, message });
+}
+
+export interface ChatMessage {
+	id: string;
+	/** The other device */
+	peer_node_id: string;
+	/** We sent it */
+	outgoing: boolean;
+	text: string;
+	/** Unix seconds, by the sender's clock */
+	sent_at: number;
+}
+
+/** Text a paired peer; resolves with the stored message once delivered. */
+export async function sendMessage(nodeId: string, text: string): Promise<ChatMessage> {
+	return await invoke<ChatMessage>("send_message", { nodeId, text });
+}
+
+/** Conversation with a peer, oldest first; pass the oldest `sent_at` as `before` for earlier ones */
+export async function getMessages(
+	nodeId: string,
+	before?: number,
+	limit?: number,
+): Promise<ChatMessage[]> {
+	return await invoke<ChatMessage[]>("get_messages", {
+		nodeId,
+		before: before ?? null,
+		limit: limit ?? null,
+	});
+}
+
+export async function clearMessages(nodeId: string): Promise<number> {
+	return await invoke<number>("clear_messages", { nodeId });
+}
+
+export async function setPeerAlias(
+	nodeId: string,
+	alias: string | null,
+): Promise<void> {
+	return await invoke<void>("set_peer_alias", { nodeId, alias });
+}
+
+export async function setPeerDownloadDir(
+	nodeId: string,
+	dir: string | null,
+): Promise<void> {
+	return await invoke<void>("set_peer_download_dir", { nodeId, dir });
+}
+
+export async function listPeerDownloadDirs(): Promise<Record<string, string>> {
+	return await invoke<Record<string, string>>("list_peer_download_dirs");
+}
+
+/** Directory received files go to when no path is given */
+export async function getDownloadDir(): Promise<string> {
+	return await invoke<string>("get_download_dir");
+}
+
+/** Absolute, `~/` or SAF folder; null restores the platform default */
+export async function setDownloadDir(dir: string | null): Promise<void> {
+	return await invoke<void>("set_download_dir", { dir });
+}
+
+/** Flatpak or Snap sandbox the desktop app runs in, if any */
+export async function getSandbox(): Promise<"flatpak" | "snap" | null> {
+	return await invoke<"flatpak" | "snap" | null>("get_sandbox");
+}
+
+/** Pick files through the desktop portal (Linux); paths stay readable in a sandbox. Empty if cancelled. */
+export async function portalPickFiles(multiple?: boolean): Promise<string[]> {
+	return await invoke<string[]>("portal_pick_files", {
+		multiple: multiple ?? null,
+	});
+}
+
+/** Pick and save the download directory through the desktop portal (Linux); null if cancelled */
+export async function portalPickDownloadDir(): Promise<string | null> {
+	return await invoke<string | null>("portal_pick_download_dir");
+}
+
+export async function setPeerFavorite(
+	nodeId: string,
+	favorite: boolean,
+): Promise<void> {
+	return await invoke<void>("set_peer_favorite", { nodeId, favorite });
+}
+
+export async function startBleDiscovery(): Promise<void> {
+	return await invoke<void>("start_ble_discovery");
+}
+
+export async function stopBleDiscovery(): Promise<void> {
+	return await invoke<void>("stop_ble_discovery");
+}
+
+export async function listenToTransferUpdates(
+	callback: (transfer: TransferInfo) => void,
+): Promise<UnlistenFn> {
+	return await listen<TransferInfo>("transfer-update", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToTransferProgress(
+	callback: (transfer: TransferInfo) => void,
+): Promise<UnlistenFn> {
+	return await listen<TransferInfo>("transfer-progress", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToTransferDeferred(
+	callback: (transfer: TransferInfo) => void,
+): Promise<UnlistenFn> {
+	return await listen<TransferInfo>("transfer-deferred", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function parseTicketMetadata(
+	ticket: string,
+): Promise<TicketMetadata> {
+	return await invoke<TicketMetadata>("parse_ticket_metadata", { ticket });
+}
+
+export async function getRelayStatus(): Promise<RelayStatus> {
+	return await invoke<RelayStatus>("get_relay_status");
+}
+
+export async function listenToConnectionUpgrades(
+	callback: (upgrade: ConnectionUpgrade) => void,
+): Promise<UnlistenFn> {
+	return await listen<ConnectionUpgrade>("peer-connection-upgraded", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToConnectionChanges(
+	callback: (change: ConnectionChange) => void,
+): Promise<UnlistenFn> {
+	return await listen<ConnectionChange>("peer-connection-changed", (event) => {
+		callback(event.payload);
+	});
+}
+
+export interface NeighborEvent {
+	group_id: string;
+	node_id: string;
+	neighbors: number;
+}
+
+export async function listenToNeighborChanges(
+	callback: (event: NeighborEvent, up: boolean) => void,
+): Promise<UnlistenFn> {
+	const unlistenUp = await listen<NeighborEvent>("gossip-neighbor-up", (event) => {
+		callback(event.payload, true);
+	});
+	const unlistenDown = await listen<NeighborEvent>(
+		"gossip-neighbor-down",
+		(event) => {
+			callback(event.payload, false);
+		},
+	);
+	return () => {
+		unlistenUp();
+		unlistenDown();
+	};
+}
+
+export async function listenToDiscoveryRestarts(
+	callback: (groupId: string) => void,
+): Promise<UnlistenFn> {
+	return await listen<string>("discovery-restarted", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToNetworkChanges(
+	callback: (relayUrls: string[]) => void,
+): Promise<UnlistenFn> {
+	return await listen<string[]>("network-changed", (event) => {
+		callback(event.payload);
+	});
+}
+
+/** Fires with the relay URL once the node reaches its home relay */
+export async function listenToRelayConnected(
+	callback: (relayUrl: string) => void,
+): Promise<UnlistenFn> {
+	return await listen<string>("relay-connected", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToLowPowerChanged(
+	callback: (lowPower: boolean) => void,
+): Promise<UnlistenFn> {
+	return await listen<boolean>("low-power-changed", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToControlMessages(
+	callback: (received: ControlReceived) => void,
+): Promise<UnlistenFn> {
+	return await listen<ControlReceived>("control-message", (event) => {
+		callback(event.payload);
+	});
+}
+
+/** Fires when files are shared into the running app; call `takeSharedFiles` on startup too */
+export async function listenToShareReceived(
+	callback: (files: SharedFile[]) => void,
+): Promise<UnlistenFn> {
+	return await listen<SharedFile[]>("share-received", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToFilesDropped(
+	callback: (dropped: FilesDropped) => void,
+): Promise<UnlistenFn> {
+	return await listen<FilesDropped>("files-dropped", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToBackgroundRestrictionsChanged(
+	callback: (restrictions: BackgroundRestrictions) => void,
+): Promise<UnlistenFn> {
+	return await listen<BackgroundRestrictions>(
+		"background-restrictions-changed",
+		(event) => {
+			callback(event.payload);
+		},
+	);
+}
+
+/** Fires when a link or ticket file is opened; call `takeOpenedTickets` on startup too */
+export async function listenToTicketOpened(
+	callback: (opened: OpenedTicket) => void,
+): Promise<UnlistenFn> {
+	return await listen<OpenedTicket>("ticket-opened", (event) => {
+		callback(event.payload);
+	});
+}
+
+/** Fires when discoverability changes, including from the Quick Settings tile */
+export async function listenToDiscoverableChanged(
+	callback: (discoverable: boolean) => void,
+): Promise<UnlistenFn> {
+	return await listen<boolean>("discoverable-changed", (event) => {
+		callback(event.payload);
+	});
+}
+
+/** Fires on startup when receives from the last run can be resumed */
+export async function listenToReceivesResumable(
+	callback: (transfers: TransferInfo[]) => void,
+): Promise<UnlistenFn> {
+	return await listen<TransferInfo[]>("receives-resumable", (event) => {
+		callback(event.payload);
+	});
+}
+
+/** Fires when a large send waits for the battery; `forceSend` starts it anyway */
+export async function listenToSendDeferred(
+	callback: (deferred: DeferredSend) => void,
+): Promise<UnlistenFn> {
+	return await listen<DeferredSend>("send-deferred", (event) => {
+		callback(event.payload);
+	});
+}
+
+/** Fires when a file from a watch folder was imported and offered */
+export async function listenToWatchFileSent(
+	callback: (sent: WatchFileSent) => void,
+): Promise<UnlistenFn> {
+	return await listen<WatchFileSent>("watch-file-sent", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToSpaceUpdated(
+	callback: (updated: SpaceUpdated) => void,
+): Promise<UnlistenFn> {
+	return await listen<SpaceUpdated>("space-updated", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToMessageReceived(
+	callback: (message: ChatMessage) => void,
+): Promise<UnlistenFn> {
+	return await listen<ChatMessage>("message-received", (event) => {
+		callback(event.payload);
+	});
+}
+
+export async function listenToTransferGroupUpdated(
+	callback: (group: TransferGroup) => void,
+): Promise<UnlistenFn> {
+	return await listen<TransferGroup>("transfer-group-updated", (event) => {
+		callback(event.payload);
+	});
 }